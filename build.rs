@@ -8,23 +8,34 @@ use zip::{CompressionMethod, ZipWriter};
 const DIR_ENV_KEY: &str = "JVM_CLASSPATH_DIR";
 
 fn main() {
-    // only applicable to miri builds
-    if var("CARGO_FEATURE_MIRI").is_err() {
+    println!("cargo:rerun-if-env-changed={}", DIR_ENV_KEY);
+
+    // only applicable to builds with an embedded classpath
+    if var("CARGO_FEATURE_EMBEDDED_CLASSPATH").is_err() {
         return;
     }
 
     let dir = PathBuf::from(
         var(DIR_ENV_KEY).unwrap_or_else(|_| panic!("missing env var {}", DIR_ENV_KEY)),
     );
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let out_file_path = PathBuf::from(var("OUT_DIR").unwrap()).join("classpath.zip");
+
+    // an archive like glibj.zip is embedded as is
+    if dir.is_file() {
+        std::fs::copy(&dir, &out_file_path).expect("failed to copy classpath archive");
+        return;
+    }
 
     if !dir.is_dir() {
-        panic!("not a dir")
+        panic!("not a dir or archive")
     }
 
-    let out_file_path = PathBuf::from(var("OUT_DIR").unwrap()).join("classpath.zip");
     let out_file = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(out_file_path)
         .expect("failed to create out file");
 
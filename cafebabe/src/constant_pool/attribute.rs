@@ -1,5 +1,5 @@
 use crate::buffer::Buffer;
-use crate::constant_pool::ConstantPool;
+use crate::constant_pool::{ClassRefEntry, ConstantPool, Item};
 use crate::{constant_pool, ClassError, ClassResult, RawAttribute};
 use mutf8::MString;
 use std::fmt::{Debug, Formatter};
@@ -14,18 +14,68 @@ pub trait Attribute: Sized {
 pub enum OwnedAttribute {
     SourceFile(SourceFile),
     Code(Code),
+    ConstantValue(ConstantValue),
     Other { name: MString, info: Box<[u8]> },
 }
 
 #[derive(Debug)]
 pub struct SourceFile(pub MString);
 
+/// Name of the class hosting the nest this class is a member of
+#[derive(Debug)]
+pub struct NestHost(pub MString);
+
+/// Value of a static field
+#[derive(Debug, Clone)]
+pub enum ConstantValue {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(MString),
+}
+
 pub struct Code {
     pub max_stack: u16,
     pub max_locals: u16,
     pub code: Arc<[u8]>,
-    // TODO exception handlers
-    // TODO attributes
+    /// In the order they should be searched
+    pub exception_handlers: Box<[ExceptionHandler]>,
+    pub line_numbers: LineNumberTable,
+    // TODO other attributes
+}
+
+#[derive(Debug, Clone)]
+pub struct ExceptionHandler {
+    /// Inclusive
+    pub start_pc: u16,
+    /// Exclusive
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    /// None catches all exceptions, used for finally blocks
+    pub catch_type: Option<MString>,
+}
+
+/// Source line numbers by code offset, possibly spread across multiple attributes
+#[derive(Debug, Default)]
+pub struct LineNumberTable(Vec<LineNumber>);
+
+#[derive(Debug, Copy, Clone)]
+pub struct LineNumber {
+    pub start_pc: u16,
+    pub line: u16,
+}
+
+/// Bootstrap methods of a class, indexed by invokedynamic and dynamic constant entries
+#[derive(Debug, Default)]
+pub struct BootstrapMethods(pub Box<[BootstrapMethod]>);
+
+#[derive(Debug)]
+pub struct BootstrapMethod {
+    /// Method handle entry of the bootstrap method
+    pub method_handle: constant_pool::Index,
+    /// Loadable entries passed as static arguments
+    pub arguments: Box<[constant_pool::Index]>,
 }
 
 impl Attribute for SourceFile {
@@ -43,10 +93,55 @@ impl Attribute for SourceFile {
     }
 }
 
+impl Attribute for NestHost {
+    const NAME: &'static str = "NestHost";
+
+    fn parse(bytes: &[u8], constant_pool: &ConstantPool) -> ClassResult<Self> {
+        if bytes.len() != 2 {
+            return Err(ClassError::AttributeFormat("attr length should be 2"));
+        }
+
+        let index = constant_pool::Index::from_be_bytes([bytes[0], bytes[1]]);
+        constant_pool
+            .entry::<ClassRefEntry>(index)
+            .map(|class| NestHost(class.name.to_owned()))
+    }
+}
+
+impl Attribute for ConstantValue {
+    const NAME: &'static str = "ConstantValue";
+
+    fn parse(bytes: &[u8], constant_pool: &ConstantPool) -> ClassResult<Self> {
+        if bytes.len() != 2 {
+            return Err(ClassError::AttributeFormat("attr length should be 2"));
+        }
+
+        let index = constant_pool::Index::from_be_bytes([bytes[0], bytes[1]]);
+        let item = constant_pool
+            .item(index)
+            .ok_or(ClassError::CpIndex(index))?;
+
+        Ok(match item {
+            Item::Integer { int } => ConstantValue::Int(*int),
+            Item::Float { float } => ConstantValue::Float(*float),
+            Item::Long { long } => ConstantValue::Long(*long),
+            Item::Double { double } => ConstantValue::Double(*double),
+            Item::String { string } => {
+                ConstantValue::String(constant_pool.string_entry(*string)?.to_owned())
+            }
+            _ => {
+                return Err(ClassError::AttributeFormat(
+                    "constant value should be a primitive or string",
+                ))
+            }
+        })
+    }
+}
+
 impl Attribute for Code {
     const NAME: &'static str = "Code";
 
-    fn parse(bytes: &[u8], _: &ConstantPool) -> ClassResult<Self> {
+    fn parse(bytes: &[u8], constant_pool: &ConstantPool) -> ClassResult<Self> {
         let mut buf = Buffer::new(bytes);
         let max_stack = buf.read()?;
         let max_locals = buf.read()?;
@@ -58,20 +153,119 @@ impl Attribute for Code {
                 .into_boxed_slice(),
         );
 
+        let handler_count: u16 = buf.read()?;
+        let mut exception_handlers = Vec::with_capacity(handler_count as usize);
+        for _ in 0..handler_count {
+            let start_pc = buf.read()?;
+            let end_pc = buf.read()?;
+            let handler_pc = buf.read()?;
+            let catch_type: constant_pool::Index = buf.read()?;
+
+            if start_pc >= end_pc || end_pc as u32 > code_len || handler_pc as u32 >= code_len {
+                return Err(ClassError::AttributeFormat(
+                    "invalid exception handler range",
+                ));
+            }
+
+            let catch_type = if catch_type == 0 {
+                None
+            } else {
+                let class = constant_pool.entry::<ClassRefEntry>(catch_type)?;
+                Some(class.name.to_owned())
+            };
+
+            exception_handlers.push(ExceptionHandler {
+                start_pc,
+                end_pc,
+                handler_pc,
+                catch_type,
+            });
+        }
+
+        let mut line_numbers = LineNumberTable::default();
+        let attr_count: u16 = buf.read()?;
+        for _ in 0..attr_count {
+            let name = constant_pool.string_entry(buf.read()?)?;
+            let length: u32 = buf.read()?;
+            let info = buf.read_slice(length as usize)?;
+
+            if name.to_utf8() == LineNumberTable::NAME {
+                let table = LineNumberTable::parse(info, constant_pool)?;
+                line_numbers.0.extend(table.0);
+            }
+        }
+
         Ok(Code {
             max_stack,
             max_locals,
             code,
+            exception_handlers: exception_handlers.into_boxed_slice(),
+            line_numbers,
         })
     }
 }
 
+impl Attribute for LineNumberTable {
+    const NAME: &'static str = "LineNumberTable";
+
+    fn parse(bytes: &[u8], _: &ConstantPool) -> ClassResult<Self> {
+        let mut buf = Buffer::new(bytes);
+        let count: u16 = buf.read()?;
+        let mut lines = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            lines.push(LineNumber {
+                start_pc: buf.read()?,
+                line: buf.read()?,
+            });
+        }
+
+        Ok(LineNumberTable(lines))
+    }
+}
+
+impl Attribute for BootstrapMethods {
+    const NAME: &'static str = "BootstrapMethods";
+
+    fn parse(bytes: &[u8], _: &ConstantPool) -> ClassResult<Self> {
+        let mut buf = Buffer::new(bytes);
+        let count: u16 = buf.read()?;
+        let mut methods = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let method_handle = buf.read()?;
+            let arg_count: u16 = buf.read()?;
+            let arguments = (0..arg_count)
+                .map(|_| buf.read())
+                .collect::<ClassResult<_>>()?;
+
+            methods.push(BootstrapMethod {
+                method_handle,
+                arguments,
+            });
+        }
+
+        Ok(BootstrapMethods(methods.into_boxed_slice()))
+    }
+}
+
+impl LineNumberTable {
+    /// Line of the instruction at the given code offset, if known
+    pub fn line_for_pc(&self, pc: usize) -> Option<u16> {
+        // entries aren't necessarily sorted or unique, the closest preceding one wins
+        self.0
+            .iter()
+            .filter(|entry| entry.start_pc as usize <= pc)
+            .max_by_key(|entry| entry.start_pc)
+            .map(|entry| entry.line)
+    }
+}
+
 impl Debug for Code {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Code")
             .field("max_stack", &self.max_stack)
             .field("max_locals", &self.max_locals)
             .field("code length", &self.code.len())
+            .field("exception handlers", &self.exception_handlers)
             .finish()
     }
 }
@@ -83,6 +277,9 @@ impl<'c> RawAttribute<'c> {
             SourceFile::NAME => {
                 OwnedAttribute::SourceFile(SourceFile::parse(self.info, constant_pool)?)
             }
+            ConstantValue::NAME => {
+                OwnedAttribute::ConstantValue(ConstantValue::parse(self.info, constant_pool)?)
+            }
             _ => OwnedAttribute::Other {
                 name: self.name.to_owned(),
                 info: self.info.to_vec().into_boxed_slice(),
@@ -96,7 +293,80 @@ impl Debug for OwnedAttribute {
         match self {
             OwnedAttribute::SourceFile(a) => write!(f, "{:?}", a),
             OwnedAttribute::Code(a) => write!(f, "{:?}", a),
+            OwnedAttribute::ConstantValue(a) => write!(f, "{:?}", a),
             OwnedAttribute::Other { name, .. } => write!(f, "{:?}", name),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constant_pool::tests::pool;
+
+    #[test]
+    fn code_exception_handlers() {
+        let pool = pool();
+        let bytes = [
+            0x00, 0x02, // max stack
+            0x00, 0x01, // max locals
+            0x00, 0x00, 0x00, 0x04, // code length
+            0x2a, 0x4c, 0x2b, 0xb1, // code
+            0x00, 0x02, // handler count
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x03, 0x00, 0x05, // catch StringBuilder
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00, // catch any
+            0x00, 0x01, // attribute count
+            0x00, 0x1d, // LineNumberTable
+            0x00, 0x00, 0x00, 0x0a, // length
+            0x00, 0x02, // line count
+            0x00, 0x02, 0x00, 0x07, // pc 2 is line 7
+            0x00, 0x00, 0x00, 0x05, // pc 0 is line 5
+        ];
+
+        let code = Code::parse(&bytes, &pool).expect("should succeed");
+        assert_eq!(code.exception_handlers.len(), 2);
+
+        let handler = &code.exception_handlers[0];
+        assert_eq!(
+            (handler.start_pc, handler.end_pc, handler.handler_pc),
+            (0, 2, 3)
+        );
+        assert_eq!(
+            handler
+                .catch_type
+                .as_deref()
+                .map(|s| s.to_utf8())
+                .as_deref(),
+            Some("java/lang/StringBuilder")
+        );
+        assert!(code.exception_handlers[1].catch_type.is_none());
+
+        assert_eq!(code.line_numbers.line_for_pc(0), Some(5));
+        assert_eq!(code.line_numbers.line_for_pc(1), Some(5));
+        assert_eq!(code.line_numbers.line_for_pc(3), Some(7));
+
+        // handler outside of the code
+        let mut bad = bytes;
+        bad[19] = 0x08;
+        assert!(Code::parse(&bad, &pool).is_err());
+    }
+
+    #[test]
+    fn bootstrap_methods() {
+        let pool = pool();
+        let bytes = [
+            0x00, 0x02, // method count
+            0x00, 0x20, 0x00, 0x02, 0x00, 0x21, 0x00, 0x22, // 2 args
+            0x00, 0x23, 0x00, 0x00, // no args
+        ];
+
+        let methods = BootstrapMethods::parse(&bytes, &pool).expect("should succeed");
+        assert_eq!(methods.0.len(), 2);
+        assert_eq!(methods.0[0].method_handle, 0x20);
+        assert_eq!(&*methods.0[0].arguments, &[0x21, 0x22]);
+        assert!(methods.0[1].arguments.is_empty());
+
+        // truncated args
+        assert!(BootstrapMethods::parse(&bytes[..8], &pool).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::constant_pool::item::Item;
-use crate::constant_pool::Tag;
+use crate::constant_pool::{Index, Tag};
 use crate::{ClassError, ClassResult, ConstantPool};
 
 pub trait Entry<'c>: Sized {
@@ -49,6 +49,38 @@ pub struct InterfaceMethodRefEntry<'c> {
 #[derive(Debug)]
 pub struct FloatEntry(pub f32);
 
+/// Field or method referenced by a method handle
+#[derive(Debug)]
+pub struct MethodHandleEntry<'c> {
+    /// Reference kind from 1 (getField) to 9 (invokeInterface)
+    pub kind: u8,
+    pub class: &'c mutf8::mstr,
+    pub name: &'c mutf8::mstr,
+    pub desc: &'c mutf8::mstr,
+}
+
+#[derive(Debug)]
+pub struct MethodTypeEntry<'c> {
+    pub desc: &'c mutf8::mstr,
+}
+
+#[derive(Debug)]
+pub struct InvokeDynamicEntry<'c> {
+    /// Index into the BootstrapMethods attribute
+    pub bootstrap_method: Index,
+    pub name: &'c mutf8::mstr,
+    pub desc: &'c mutf8::mstr,
+}
+
+/// Dynamically computed constant, with a field descriptor
+#[derive(Debug)]
+pub struct DynamicEntry<'c> {
+    /// Index into the BootstrapMethods attribute
+    pub bootstrap_method: Index,
+    pub name: &'c mutf8::mstr,
+    pub desc: &'c mutf8::mstr,
+}
+
 impl<'c> Entry<'c> for Utf8Entry<'c> {
     const TAG: Tag = Tag::Utf8;
 
@@ -185,3 +217,115 @@ impl<'c> Entry<'c> for FloatEntry {
         }
     }
 }
+
+impl<'c> Entry<'c> for MethodHandleEntry<'c> {
+    const TAG: Tag = Tag::MethodHandle;
+
+    fn from_item(item: &Item<'c>, pool: &ConstantPool<'c>) -> ClassResult<Self> {
+        match item {
+            Item::MethodHandle {
+                reference_kind,
+                reference,
+            } => {
+                let (class, name, desc) = match reference_kind {
+                    1..=4 => {
+                        let field: FieldRefEntry = pool.entry(*reference)?;
+                        (field.class, field.name, field.desc)
+                    }
+                    5 | 8 => {
+                        let method: MethodRefEntry = pool.entry(*reference)?;
+                        (method.class, method.name, method.desc)
+                    }
+                    6 | 7 => match pool.entry::<MethodRefEntry>(*reference) {
+                        Ok(method) => (method.class, method.name, method.desc),
+                        Err(_) => {
+                            let method: InterfaceMethodRefEntry = pool.entry(*reference)?;
+                            (method.class, method.name, method.desc)
+                        }
+                    },
+                    9 => {
+                        let method: InterfaceMethodRefEntry = pool.entry(*reference)?;
+                        (method.class, method.name, method.desc)
+                    }
+                    _ => return Err(ClassError::ReferenceKind(*reference_kind)),
+                };
+
+                Ok(MethodHandleEntry {
+                    kind: *reference_kind,
+                    class,
+                    name,
+                    desc,
+                })
+            }
+            _ => Err(ClassError::WrongTag {
+                expected: Self::TAG,
+                actual: item.tag(),
+            }),
+        }
+    }
+}
+
+impl<'c> Entry<'c> for MethodTypeEntry<'c> {
+    const TAG: Tag = Tag::MethodType;
+
+    fn from_item(item: &Item<'c>, pool: &ConstantPool<'c>) -> ClassResult<Self> {
+        match item {
+            Item::MethodType { descriptor } => {
+                let desc = pool.string_entry(*descriptor)?;
+                Ok(MethodTypeEntry { desc })
+            }
+            _ => Err(ClassError::WrongTag {
+                expected: Self::TAG,
+                actual: item.tag(),
+            }),
+        }
+    }
+}
+
+impl<'c> Entry<'c> for DynamicEntry<'c> {
+    const TAG: Tag = Tag::Dynamic;
+
+    fn from_item(item: &Item<'c>, pool: &ConstantPool<'c>) -> ClassResult<Self> {
+        match item {
+            Item::Dynamic {
+                bootstrap_method_attr,
+                name_and_type,
+            } => {
+                let name_and_type: NameAndTypeEntry = pool.entry(*name_and_type)?;
+                Ok(DynamicEntry {
+                    bootstrap_method: *bootstrap_method_attr,
+                    name: name_and_type.name,
+                    desc: name_and_type.desc,
+                })
+            }
+            _ => Err(ClassError::WrongTag {
+                expected: Self::TAG,
+                actual: item.tag(),
+            }),
+        }
+    }
+}
+
+impl<'c> Entry<'c> for InvokeDynamicEntry<'c> {
+    const TAG: Tag = Tag::InvokeDynamic;
+
+    fn from_item(item: &Item<'c>, pool: &ConstantPool<'c>) -> ClassResult<Self> {
+        match item {
+            Item::InvokeDynamic {
+                bootstrap_method_attr,
+                name_and_type,
+            } => {
+                let name_and_type: NameAndTypeEntry = pool.entry(*name_and_type)?;
+                Ok(InvokeDynamicEntry {
+                    bootstrap_method: *bootstrap_method_attr,
+                    name: name_and_type.name,
+                    desc: name_and_type.desc,
+                })
+            }
+            _ => Err(ClassError::WrongTag {
+                expected: Self::TAG,
+                actual: item.tag(),
+            }),
+        }
+    }
+}
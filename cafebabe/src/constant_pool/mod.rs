@@ -85,14 +85,14 @@ impl Debug for ConstantPool<'_> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use crate::buffer::Buffer;
     use crate::constant_pool::entry::{ClassRefEntry, MethodRefEntry, Utf8Entry};
     use crate::constant_pool::item::Item;
     use crate::constant_pool::Tag;
     use crate::ConstantPool;
 
-    fn pool() -> ConstantPool<'static> {
+    pub(crate) fn pool() -> ConstantPool<'static> {
         const POOL: [u8; 636] = [
             0x00, 0x45, 0x0a, 0x00, 0x15, 0x00, 0x26, 0x0a, 0x00, 0x27, 0x00, 0x28, 0x09, 0x00,
             0x0d, 0x00, 0x29, 0x09, 0x00, 0x2a, 0x00, 0x2b, 0x07, 0x00, 0x2c, 0x0a, 0x00, 0x05,
@@ -46,6 +46,9 @@ pub enum ClassError {
     #[error("Expected {expected:?} item but found {actual:?}")]
     WrongTag { expected: Tag, actual: Tag },
 
+    #[error("Invalid method handle reference kind {0}")]
+    ReferenceKind(u8),
+
     #[error("Invalid type descriptor {0:?}")]
     TypeDescriptor(MString),
 
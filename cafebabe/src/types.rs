@@ -1,7 +1,9 @@
 use crate::buffer::Buffer;
+use crate::constant_pool::attribute::Attribute;
 use crate::constant_pool::ConstantPool;
 use crate::{ClassError, ClassResult};
 use bitflags::bitflags;
+use mutf8::StrExt;
 use std::fmt::{Debug, Display, Formatter};
 
 #[derive(Debug)]
@@ -90,6 +92,10 @@ bitflags! {
 bitflags! {
     pub struct CommonAccessFlags: u16 {
         const PUBLIC = 0x0001;
+        /// Only meaningful for fields and methods
+        const PRIVATE = 0x0002;
+        /// Only meaningful for fields and methods
+        const PROTECTED = 0x0004;
         const FINAL = 0x0010;
         const STATIC = 0x0008;
         const SYNTHETIC = 0x1000;
@@ -102,6 +108,12 @@ pub trait AccessFlags: Copy {
     fn is_public(&self) -> bool {
         self.common().contains(CommonAccessFlags::PUBLIC)
     }
+    fn is_private(&self) -> bool {
+        self.common().contains(CommonAccessFlags::PRIVATE)
+    }
+    fn is_protected(&self) -> bool {
+        self.common().contains(CommonAccessFlags::PROTECTED)
+    }
     fn is_static(&self) -> bool {
         self.common().contains(CommonAccessFlags::STATIC)
     }
@@ -134,6 +146,20 @@ pub struct RawAttribute<'c> {
     pub info: &'c [u8],
 }
 
+impl<'c> FieldInfo<'c> {
+    pub fn attribute<A: Attribute>(&self, constant_pool: &ConstantPool) -> ClassResult<A> {
+        let attr_name = A::NAME.to_mstr();
+        let bytes = self
+            .attributes
+            .iter()
+            .find(|a| a.name == attr_name.as_ref())
+            .map(|attr| attr.info)
+            .ok_or(ClassError::Attribute(A::NAME))?;
+
+        A::parse(bytes, constant_pool)
+    }
+}
+
 impl ClassVersion {
     pub fn new(major: u16, minor: u16) -> Self {
         Self { major, minor }
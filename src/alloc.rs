@@ -1,19 +1,103 @@
 use crate::class::Object;
 use crate::error::{Throwable, Throwables, VmResult};
 
-use cafebabe::mutf8::MString;
+use cafebabe::mutf8::{mstr, MString};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
 use std::mem::{ManuallyDrop, MaybeUninit};
+use std::ops::Deref;
 use std::sync::{Arc, Weak};
 
 // TODO gc arena
 pub type VmRef<T> = Arc<T>;
 pub type WeakVmRef<T> = Weak<T>;
 
-// TODO actually intern strings
-
 pub type NativeString = MString;
 
-pub type InternedString = MString;
+/// Handle to a string in the global symbol table, used for class, method and field names and
+/// method descriptors. Symbols are deliberately immortal: each is leaked as a `&'static mstr` so
+/// handles are `Copy` and can be compared and hashed by pointer only, without refcounting. The
+/// table only grows, bounded by the distinct names of all classes ever loaded, so unloading
+/// classes does not free their symbols
+#[derive(Copy, Clone)]
+pub struct InternedString(&'static mstr);
+
+lazy_static! {
+    static ref SYMBOLS: RwLock<HashMap<&'static [u8], InternedString>> =
+        RwLock::new(HashMap::with_capacity(8192));
+}
+
+impl InternedString {
+    pub fn intern(string: &mstr) -> Self {
+        if let Some(sym) = Self::lookup(string) {
+            return sym;
+        }
+
+        let mut symbols = SYMBOLS.write();
+
+        // might have been interned while the lock was released
+        if let Some(sym) = symbols.get(string.as_bytes()) {
+            return *sym;
+        }
+
+        let bytes: &'static [u8] = Box::leak(Box::from(string.as_bytes()));
+        let sym = InternedString(mstr::from_mutf8(bytes));
+        symbols.insert(bytes, sym);
+        sym
+    }
+
+    /// Does not intern if missing
+    pub fn lookup(string: &mstr) -> Option<Self> {
+        SYMBOLS.read().get(string.as_bytes()).copied()
+    }
+
+    pub fn as_mstr(self) -> &'static mstr {
+        self.0
+    }
+}
+
+impl Deref for InternedString {
+    type Target = mstr;
+
+    fn deref(&self) -> &mstr {
+        self.0
+    }
+}
+
+impl AsRef<mstr> for InternedString {
+    fn as_ref(&self) -> &mstr {
+        self.0
+    }
+}
+
+impl PartialEq for InternedString {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0.as_bytes(), other.0.as_bytes())
+    }
+}
+
+impl Eq for InternedString {}
+
+impl Hash for InternedString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_bytes().as_ptr().hash(state)
+    }
+}
+
+impl Debug for InternedString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.0, f)
+    }
+}
+
+impl Display for InternedString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self.0, f)
+    }
+}
 
 // TODO methods on VmRef newtype
 pub fn vmref_is_null(vmref: &VmRef<Object>) -> bool {
@@ -53,8 +137,7 @@ pub fn vmref_alloc_object(f: impl FnOnce() -> VmResult<Object>) -> VmResult<VmRe
 }
 
 pub fn vmref_alloc_exception(throwable: Throwables) -> VmRef<Throwable> {
-    let class_name = throwable.symbol();
-    VmRef::new(Throwable { class_name })
+    Throwable::new(throwable)
 }
 
 pub fn vmref_to_weak<T>(vmref: &VmRef<T>) -> WeakVmRef<T> {
@@ -63,8 +146,9 @@ pub fn vmref_to_weak<T>(vmref: &VmRef<T>) -> WeakVmRef<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::alloc::{vmref_eq, vmref_is_null};
+    use crate::alloc::{vmref_eq, vmref_is_null, InternedString};
     use crate::class::null;
+    use cafebabe::mutf8::StrExt;
 
     #[test]
     fn null_is_null() {
@@ -75,4 +159,22 @@ mod tests {
     fn null_singleton() {
         assert!(vmref_eq(&null(), &null()));
     }
+
+    #[test]
+    fn interning() {
+        let a = InternedString::intern(&"java/lang/Object".as_mstr());
+        let b = InternedString::intern(&"java/lang/Object".to_mstr());
+        let c = InternedString::intern(&"java/lang/String".as_mstr());
+
+        assert_eq!(a, b);
+        assert!(std::ptr::eq(a.as_mstr(), b.as_mstr()));
+        assert_ne!(a, c);
+        assert_eq!(a.as_mstr(), "java/lang/Object".as_mstr().as_ref());
+
+        assert_eq!(
+            InternedString::lookup(&"java/lang/String".as_mstr()),
+            Some(c)
+        );
+        assert_eq!(InternedString::lookup(&"not/interned/Yet".as_mstr()), None);
+    }
 }
@@ -1,6 +1,6 @@
 //! Initialisation of bootstrap classes
 
-use cafebabe::mutf8::StrExt;
+use cafebabe::mutf8::{mstr, StrExt};
 use cafebabe::MethodAccessFlags;
 
 use crate::class::{Class, ClassLoader, NativeInternalFn, WhichLoader};
@@ -29,7 +29,51 @@ impl Preload {
     }
 }
 
+/// Bootstrap methods implemented in Java by the class library that are replaced with internal
+/// natives when linked: (class, method, descriptor, function).
+///
+/// GNU Classpath implements `VMClassLoader.getPackage` and `getPackages` in Java, from a static
+/// map of unversioned, unsealed packages built from the boot package names at class
+/// initialisation, without asking the VM. Packages are tracked per loader by the VM with their
+/// manifest info and sealing, so these two are replaced to return those instead. Natives can
+/// only be bound to methods declared native, hence the separate table
+const INTRINSICS: &[(&str, &str, &str, NativeInternalFn)] = &[
+    (
+        "java/lang/VMClassLoader",
+        "getPackage",
+        "(Ljava/lang/String;)Ljava/lang/Package;",
+        java_lang_vmclassloader::get_package,
+    ),
+    (
+        "java/lang/VMClassLoader",
+        "getPackages",
+        "()[Ljava/lang/Package;",
+        java_lang_vmclassloader::get_packages,
+    ),
+];
+
+/// Internal native replacing the given Java method of a bootstrap class, if any
+pub fn intrinsic(class: &mstr, method: &mstr, desc: &mstr) -> Option<NativeInternalFn> {
+    INTRINSICS
+        .iter()
+        .find(|(c, m, d, _)| {
+            c.as_bytes() == class.as_bytes()
+                && m.as_bytes() == method.as_bytes()
+                && d.as_bytes() == desc.as_bytes()
+        })
+        .map(|(_, _, _, f)| *f)
+}
+
 pub fn init_bootstrap_classes(classloader: &ClassLoader) -> VmResult<()> {
+    // TODO remove class name from start of native methods
+    let preload = include!("preload.txt");
+
+    // read and parse everything up front in parallel, linking below stays in order
+    let classes = ["java/lang/Object", "java/lang/Class"]
+        .into_iter()
+        .chain(preload.iter().map(|preload| preload.class));
+    classloader.prefetch_boot_classes(classes);
+
     // our lord and saviours first
     Preload::new("java/lang/Object").load(classloader)?;
     Preload::new("java/lang/Class").load(classloader)?;
@@ -39,9 +83,6 @@ pub fn init_bootstrap_classes(classloader: &ClassLoader) -> VmResult<()> {
 
     init_primitives(classloader)?;
 
-    // TODO remove class name from start of native methods
-    let preload = include!("preload.txt");
-
     for preload in preload.iter() {
         preload.load(classloader)?;
     }
@@ -1,12 +1,14 @@
 use std::cell::UnsafeCell;
+use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::mem::MaybeUninit;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::thread::ThreadId;
+use std::time::Instant;
 
 use itertools::Itertools;
 use log::*;
-use parking_lot::Mutex;
+use parking_lot::{MappedRwLockReadGuard, Mutex, MutexGuard, RwLock, RwLockReadGuard};
 
 use cafebabe::mutf8::{mstr, StrExt};
 use cafebabe::{
@@ -14,12 +16,14 @@ use cafebabe::{
 };
 
 use crate::alloc::{vmref_eq, InternedString, NativeString, VmRef, WeakVmRef};
+use crate::class::dispatch::{self, MethodTables};
 use crate::class::loader::current_thread;
 use crate::class::object::Object;
-use crate::class::{ClassLoader, WhichLoader};
+use crate::class::{ClassLoader, RuntimePackage, WhichLoader};
 use crate::constant_pool::RuntimeConstantPool;
+use crate::debug::{ClassEvent, ClassEventKind};
 use crate::error::{Throwable, Throwables, VmResult};
-use crate::interpreter::{Frame, InterpreterError, NativeThunkHandle};
+use crate::interpreter::{DecodedCode, Frame, InterpreterError, NativeThunkHandle, NativeThunks};
 use crate::jni::NativeLibraries;
 use crate::storage::{
     FieldDataType, FieldId, FieldStorage, FieldStorageLayout, FieldStorageLayoutBuilder,
@@ -28,12 +32,14 @@ use crate::thread;
 use crate::types::{DataType, DataValue, MethodSignature, PrimitiveDataType, ReturnType};
 use std::ffi::{CStr, CString};
 
-// TODO when a ClassLoader is dropped, ensure all native libraries associated with it are freed too
-
 pub struct Class {
     name: InternedString,
+    /// Name of the runtime package, which is identified with the defining loader
+    package: InternedString,
     class_type: ClassType,
     source_file: Option<NativeString>,
+    /// From the NestHost attribute, None if this class hosts its own nest
+    nest_host: Option<InternedString>,
     state: LockedClassState,
     loader: WhichLoader,
 
@@ -51,9 +57,12 @@ pub struct Class {
 
     interfaces: Vec<VmRef<Class>>,
     fields: Vec<Field>,
-    methods: Vec<VmRef<Method>>,
+    /// Behind a lock so unloading can take them, as methods reference the class back
+    methods: RwLock<ClassMethods>,
 
     constant_pool: RuntimeConstantPool,
+    /// Method handles, method types and dynamically computed constants, by constant pool index
+    resolved_constants: Mutex<HashMap<u16, DataValue>>,
 
     static_fields_layout: FieldStorageLayout,
     static_fields_values: FieldStorage,
@@ -61,10 +70,17 @@ pub struct Class {
     instance_fields_layout: FieldStorageLayout,
 }
 
+#[derive(Default)]
+struct ClassMethods {
+    methods: Vec<VmRef<Method>>,
+    /// vtable and itables, built during linking
+    tables: MethodTables,
+}
+
 #[derive(Debug, Clone)]
 pub enum ClassType {
-    // TODO store dimensions
-    Array(VmRef<Class>),
+    /// Component class and number of dimensions
+    Array(VmRef<Class>, u8),
     Primitive(PrimitiveDataType),
     Normal,
 }
@@ -85,9 +101,11 @@ struct LockedClassState(UnsafeCell<ClassState>);
 
 #[derive(Debug)]
 pub struct Field {
-    name: NativeString,
+    name: InternedString,
     desc: DataType<'static>,
     flags: FieldAccessFlags,
+    /// From the ConstantValue attribute of a static field
+    constant_value: Option<attribute::ConstantValue>,
 }
 
 #[derive(Copy, Clone)]
@@ -147,8 +165,8 @@ pub enum NativeFunction {
 
 #[derive(Debug)]
 pub struct Method {
-    name: NativeString,
-    desc: NativeString,
+    name: InternedString,
+    desc: InternedString,
     flags: MethodAccessFlags,
     /// Always initialised during linking
     class: MaybeUninit<VmRef<Class>>,
@@ -159,11 +177,47 @@ pub struct Method {
 
     code: MethodCode,
     attributes: Vec<attribute::OwnedAttribute>,
+
+    /// Index into the vtable for methods of classes, or into the itable of the declaring
+    /// interface. Only for instance methods that can be overridden
+    vtable_index: Option<u32>,
+
+    /// Java code translated for the interpreter on first invocation
+    decoded: OnceLock<Arc<DecodedCode>>,
 }
 
 unsafe impl Sync for Method {}
 unsafe impl Send for Method {}
 
+/// Contents of a class file in owned form, checked against the class file format but not yet
+/// linked. Doesn't need the class loader, so classes can be parsed off the loading thread
+pub(in crate::class) struct ClassDefinition {
+    name: InternedString,
+    source_file: Option<NativeString>,
+    nest_host: Option<InternedString>,
+    /// Only None for java/lang/Object
+    super_class: Option<InternedString>,
+    interfaces: Vec<InternedString>,
+    methods: Vec<MethodDefinition>,
+    fields: Vec<Field>,
+    access_flags: ClassAccessFlags,
+    constant_pool: RuntimeConstantPool,
+}
+
+/// A method before its class exists
+struct MethodDefinition {
+    name: InternedString,
+    desc: InternedString,
+    flags: MethodAccessFlags,
+    args: Vec<DataType<'static>>,
+    return_type: ReturnType<'static>,
+    code: MethodCode,
+    attributes: Vec<attribute::OwnedAttribute>,
+}
+
+// safety: native methods are only bound after linking, so there are no native thunks to share
+unsafe impl Send for MethodDefinition {}
+
 pub enum MethodLookupResult {
     Found(VmRef<Method>),
     FoundMultiple,
@@ -183,17 +237,15 @@ struct MangledMethodName(CString);
 /// Long mangled name e.g. Java_com_me_Class_methodName__IL
 struct MangledMethodNameLong(CString);
 
-// TODO get classloader reference from tls instead of parameter
-
-impl Class {
-    pub fn link(
+impl ClassDefinition {
+    /// Checks the parsed class file is the expected class and converts it to owned form. The
+    /// loader only decides whether methods are replaced with intrinsics
+    pub(in crate::class) fn parse(
         expected_name: &mstr,
         loaded: cafebabe::ClassFile,
-        loader: WhichLoader,
-        classloader: &ClassLoader,
-    ) -> VmResult<VmRef<Self>> {
-        debug!("linking class {:?}", expected_name);
-        // TODO this crashes in release builds, oops
+        loader: &WhichLoader,
+    ) -> VmResult<Self> {
+        trace!("parsing class {:?}", expected_name);
 
         // check this is indeed the class we expected
         // TODO verify constant pool offsets so we can raise a single classformaterror then trust it
@@ -205,10 +257,13 @@ impl Class {
                 "expected to load class {:?} but actually loaded {:?}",
                 expected_name, defined_class_name
             );
-            return Err(Throwables::NoClassDefFoundError);
+            return Err(Throwables::NoClassDefFoundError(format!(
+                "{} (wrong name: {})",
+                expected_name, defined_class_name
+            )));
         }
 
-        let name = defined_class_name.to_owned();
+        let name = InternedString::intern(defined_class_name);
         let source_file = match loaded.attribute::<attribute::SourceFile>() {
             Ok(src) => {
                 trace!("source file: {:?}", src.0);
@@ -221,20 +276,22 @@ impl Class {
             }
         };
 
-        // TODO preparation? https://docs.oracle.com/javase/specs/jvms/se11/html/jvms-5.html#jvms-5.4.2
+        let nest_host = match loaded.attribute::<attribute::NestHost>() {
+            Ok(host) => Some(InternedString::intern(&host.0)),
+            Err(ClassError::Attribute(_)) => None,
+            Err(e) => {
+                warn!("failed to get nest host: {}", e);
+                return Err(Throwables::ClassFormatError);
+            }
+        };
 
-        // resolve superclass and interfaces
         let super_class = match loaded.super_class() {
             Ok(super_name) => {
-                // ensure loaded
                 trace!("super class: {:?}", super_name);
-                let super_class =
-                    classloader.load_class_caused_by(super_name, loader.clone(), &name)?;
-                Some(super_class)
+                Some(InternedString::intern(super_name))
             }
             Err(ClassError::NoSuper) if name.as_bytes() == b"java/lang/Object" => {
                 // the one exception, no super class expected
-                trace!("no super class expected for java.lang.Object");
                 None
             }
             Err(e) => {
@@ -246,23 +303,15 @@ impl Class {
         let interfaces = {
             let mut vec = Vec::with_capacity(loaded.interface_count());
             for interface in loaded.interfaces() {
-                let interface_name = match interface {
-                    Ok(iface) => iface,
+                match interface {
+                    Ok(iface) => vec.push(InternedString::intern(iface)),
                     Err(e) => {
                         warn!("failed to get interface: {}", e);
                         return Err(Throwables::ClassFormatError);
                     }
-                };
-
-                let interface =
-                    classloader.load_class_caused_by(interface_name, loader.clone(), &name)?;
-                vec.push(interface);
+                }
             }
 
-            trace!(
-                "interfaces: {:?}",
-                vec.iter().map(|iface| iface.name()).collect_vec()
-            );
             vec
         };
 
@@ -318,7 +367,22 @@ impl Class {
                                 _ => unreachable!(),
                             };
 
-                            MethodCode::Java(code)
+                            match loader {
+                                WhichLoader::Bootstrap => match crate::bootstrap::intrinsic(
+                                    &name,
+                                    method.name,
+                                    method.descriptor,
+                                ) {
+                                    Some(f) => {
+                                        trace!("replacing {:?} with intrinsic", method.name);
+                                        MethodCode::Native(Mutex::new(NativeCode::Bound(
+                                            NativeFunction::Internal(f),
+                                        )))
+                                    }
+                                    None => MethodCode::Java(code),
+                                },
+                                WhichLoader::User(_) => MethodCode::Java(code),
+                            }
                         }
 
                         None => {
@@ -346,16 +410,15 @@ impl Class {
                     code
                 );
 
-                vec.push(VmRef::new(Method {
-                    name: method.name.to_owned(),
-                    desc: method.descriptor.to_owned(),
+                vec.push(MethodDefinition {
+                    name: InternedString::intern(method.name),
+                    desc: InternedString::intern(method.descriptor),
                     flags: method.access_flags,
-                    class: MaybeUninit::zeroed(), // populated at the end
                     args,
                     return_type: signature.return_type().to_owned(),
                     code,
                     attributes,
-                }))
+                })
             }
             vec
         };
@@ -373,16 +436,151 @@ impl Class {
                     Throwables::ClassFormatError
                 })?;
 
+                // only static fields are assigned a constant value
+                let constant_value = if field.access_flags.is_static() {
+                    match field.attribute::<attribute::ConstantValue>(loaded.constant_pool()) {
+                        Ok(value) => {
+                            if !is_constant_assignable(&value, &desc) {
+                                warn!(
+                                    "constant value {:?} can't be assigned to field {:?} of type {:?}",
+                                    value, field.name, desc
+                                );
+                                return Err(Throwables::ClassFormatError);
+                            }
+                            Some(value)
+                        }
+                        Err(ClassError::Attribute(_)) => None,
+                        Err(e) => {
+                            warn!("invalid constant value for field {:?}: {}", field.name, e);
+                            return Err(Throwables::ClassFormatError);
+                        }
+                    }
+                } else {
+                    None
+                };
+
                 vec.push(Field {
-                    name: field.name.to_owned(),
+                    name: InternedString::intern(field.name),
                     desc: desc.to_owned(),
                     flags: field.access_flags,
+                    constant_value,
                 })
             }
 
             vec
         };
 
+        let bootstrap_methods = match loaded.attribute::<attribute::BootstrapMethods>() {
+            Ok(methods) => methods,
+            Err(ClassError::Attribute(_)) => attribute::BootstrapMethods::default(),
+            Err(e) => {
+                warn!("failed to get bootstrap methods: {}", e);
+                return Err(Throwables::ClassFormatError);
+            }
+        };
+
+        let constant_pool =
+            RuntimeConstantPool::from_cafebabe(loaded.constant_pool(), &bootstrap_methods)
+                .map_err(|e| {
+                    warn!("invalid constant pool: {}", e);
+                    Throwables::ClassFormatError
+                })?;
+
+        Ok(ClassDefinition {
+            name,
+            source_file,
+            nest_host,
+            super_class,
+            interfaces,
+            methods,
+            fields,
+            access_flags: loaded.access_flags(),
+            constant_pool,
+        })
+    }
+
+    /// Superclass and superinterfaces, which are loaded during linking
+    pub(in crate::class) fn dependencies(&self) -> impl Iterator<Item = &mstr> {
+        self.super_class
+            .iter()
+            .chain(self.interfaces.iter())
+            .map(|name| name.as_mstr())
+    }
+}
+
+// TODO get classloader reference from tls instead of parameter
+
+impl Class {
+    /// Loads the superclass and superinterfaces of a parsed class and creates it
+    pub(in crate::class) fn link(
+        definition: ClassDefinition,
+        loader: WhichLoader,
+        classloader: &ClassLoader,
+    ) -> VmResult<VmRef<Self>> {
+        let ClassDefinition {
+            name,
+            source_file,
+            nest_host,
+            super_class,
+            interfaces,
+            methods,
+            fields,
+            access_flags,
+            constant_pool,
+        } = definition;
+        debug!("linking class {:?}", name);
+        // TODO this crashes in release builds, oops
+
+        // TODO preparation? https://docs.oracle.com/javase/specs/jvms/se11/html/jvms-5.html#jvms-5.4.2
+
+        // resolve superclass and interfaces
+        let super_class = match super_class {
+            Some(super_name) => {
+                // ensure loaded
+                let super_class =
+                    classloader.load_class_caused_by(&super_name, loader.clone(), &name)?;
+                Some(super_class)
+            }
+            None => {
+                trace!("no super class expected for java.lang.Object");
+                None
+            }
+        };
+
+        let interfaces = {
+            let mut vec = Vec::with_capacity(interfaces.len());
+            for interface_name in interfaces {
+                let interface =
+                    classloader.load_class_caused_by(&interface_name, loader.clone(), &name)?;
+                vec.push(interface);
+            }
+
+            trace!(
+                "interfaces: {:?}",
+                vec.iter().map(|iface| iface.name()).collect_vec()
+            );
+            vec
+        };
+
+        // nothing can fail from here, so methods are never dropped before their class is set
+        let methods = methods
+            .into_iter()
+            .map(|method| {
+                VmRef::new(Method {
+                    name: method.name,
+                    desc: method.desc,
+                    flags: method.flags,
+                    class: MaybeUninit::zeroed(), // populated at the end
+                    args: method.args,
+                    return_type: method.return_type,
+                    code: method.code,
+                    attributes: method.attributes,
+                    vtable_index: None, // assigned when the class is created
+                    decoded: OnceLock::new(),
+                })
+            })
+            .collect();
+
         // initialise field layout
         let (static_fields_layout, instance_fields_layout) = {
             let mut static_builder = FieldStorageLayoutBuilder::empty();
@@ -441,43 +639,36 @@ impl Class {
         // preparation step - initialise static fields
         // TODO do verification first to throw ClassFormatErrors, then this should not throw any classformaterrors
         let static_fields_values = static_fields_layout.new_storage();
-
-        let constant_pool =
-            RuntimeConstantPool::from_cafebabe(loaded.constant_pool()).map_err(|e| {
-                warn!("invalid constant pool: {}", e);
-                Throwables::ClassFormatError
-            })?;
-
-        let access = loaded.access_flags();
+        for (i, field) in fields.iter().filter(|f| f.flags.is_static()).enumerate() {
+            // strings are set during initialisation, once they can be instantiated
+            if let Some(value) = field
+                .constant_value
+                .as_ref()
+                .and_then(|v| primitive_constant(v, &field.desc))
+            {
+                let field_id = static_fields_layout.get_self_id(i).unwrap();
+                static_fields_values.ensure_set(field_id, value);
+            }
+        }
 
         let class = Self::new(
             classloader,
             name,
             ClassType::Normal,
             source_file,
+            nest_host,
             loader,
             super_class,
             interfaces,
             fields,
             methods,
-            access,
+            access_flags,
             constant_pool,
             instance_fields_layout,
             static_fields_layout,
             static_fields_values,
         );
 
-        // fix up method class refs
-        // safety: this is probably mostly safe, this is the only reference to the class but we need to
-        // clone the Arc for each method, so it has to be immutable
-        unsafe {
-            let class_mut = &mut *(Arc::as_ptr(&class) as *mut Class);
-            class_mut.methods.iter_mut().for_each(|m| {
-                let m = Arc::get_mut(m).unwrap();
-                m.class = MaybeUninit::new(class.clone());
-            })
-        }
-
         Ok(class)
     }
 
@@ -489,8 +680,19 @@ impl Class {
     ) -> VmResult<VmRef<Self>> {
         let super_class = classloader.get_bootstrap_class("java/lang/Object");
 
-        // TODO Every array type implements the interfaces Cloneable and java.io.Serializable.
-        let interfaces = Vec::new();
+        // every array type implements Cloneable and Serializable (JLS 4.10.3)
+        let interfaces = vec![
+            classloader.load_class("java/lang/Cloneable".as_mstr(), WhichLoader::Bootstrap)?,
+            classloader.load_class("java/io/Serializable".as_mstr(), WhichLoader::Bootstrap)?,
+        ];
+
+        let dimensions = match elem_cls.class_type() {
+            ClassType::Array(_, dims) => dims.checked_add(1).ok_or_else(|| {
+                warn!("array class {:?} has more than 255 dimensions", name);
+                Throwables::NoClassDefFoundError(name.to_string())
+            })?,
+            _ => 1,
+        };
 
         let access_flags = {
             let flags = if let ClassType::Normal = elem_cls.class_type {
@@ -506,8 +708,9 @@ impl Class {
 
         let cls = Self::new(
             classloader,
-            name.to_owned(),
-            ClassType::Array(elem_cls),
+            InternedString::intern(name),
+            ClassType::Array(elem_cls, dimensions),
+            None,
             None,
             loader,
             Some(super_class),
@@ -535,9 +738,10 @@ impl Class {
 
         let cls = Self::new(
             classloader,
-            name.to_owned(),
+            InternedString::intern(name),
             ClassType::Primitive(primitive),
             None,
+            None,
             WhichLoader::Bootstrap,
             Some(super_class),
             Vec::new(),
@@ -559,11 +763,12 @@ impl Class {
         name: InternedString,
         class_type: ClassType,
         source_file: Option<NativeString>,
+        nest_host: Option<InternedString>,
         loader: WhichLoader,
         super_class: Option<VmRef<Class>>,
         interfaces: Vec<VmRef<Class>>,
         fields: Vec<Field>,
-        methods: Vec<VmRef<Method>>,
+        mut methods: Vec<VmRef<Method>>,
         access_flags: ClassAccessFlags,
         constant_pool: RuntimeConstantPool,
         instance_fields_layout: FieldStorageLayout,
@@ -572,11 +777,30 @@ impl Class {
     ) -> VmRef<Class> {
         assert!(super_class.is_none() == (name.as_bytes() == b"java/lang/Object"));
 
+        let package = match &class_type {
+            ClassType::Array(elem, _) => elem.package,
+            ClassType::Primitive(_) | ClassType::Normal => {
+                InternedString::intern(RuntimePackage::name_of_class(&name))
+            }
+        };
+
+        let indices = dispatch::method_indices(
+            super_class.as_ref(),
+            access_flags.contains(ClassAccessFlags::INTERFACE),
+            (&package, &loader),
+            &methods,
+        );
+        for (method, idx) in methods.iter_mut().zip(indices) {
+            Arc::get_mut(method).unwrap().vtable_index = idx;
+        }
+
         let mut vm_class = VmRef::new(Self {
             name,
+            package,
             class_type,
             access_flags,
             source_file,
+            nest_host,
             state: LockedClassState::default(),
             loader,
             class_object: MaybeUninit::uninit(),
@@ -584,8 +808,12 @@ impl Class {
             class_object_init: false,
             super_class,
             interfaces,
-            methods,
+            methods: RwLock::new(ClassMethods {
+                methods,
+                tables: MethodTables::default(),
+            }),
             constant_pool,
+            resolved_constants: Mutex::default(),
             instance_fields_layout,
             static_fields_layout,
             static_fields_values,
@@ -594,6 +822,17 @@ impl Class {
 
         // alloc java/lang/Class if possible
         classloader.populate_class_vmdata(&mut vm_class);
+
+        // fix up method class refs, nothing else references the methods yet
+        vm_class.methods.write().methods.iter_mut().for_each(|m| {
+            let m = Arc::get_mut(m).unwrap();
+            m.class = MaybeUninit::new(vm_class.clone());
+        });
+
+        // methods are shared by the tables so are immutable from here
+        let method_tables = MethodTables::build(&vm_class);
+        vm_class.methods.write().tables = method_tables;
+
         vm_class
     }
 
@@ -603,9 +842,15 @@ impl Class {
         desc: &mstr,
         flags: MethodAccessFlags,
     ) -> MethodLookupResult {
-        let mut matching = self.methods.iter().filter(|m| {
-            m.flags.contains(flags) && m.name.as_mstr() == name && m.desc.as_mstr() == desc
-        });
+        let (name, desc) = match lookup_symbols(name, desc) {
+            Some(symbols) => symbols,
+            None => return MethodLookupResult::NotFound,
+        };
+
+        let methods = self.methods();
+        let mut matching = methods
+            .iter()
+            .filter(|m| m.flags.contains(flags) && m.name == name && m.desc == desc);
 
         let first = matching.next();
         let next = matching.next();
@@ -637,7 +882,7 @@ impl Class {
     }
 
     pub fn find_method_by_id(&self, id: i32) -> Option<VmRef<Method>> {
-        self.methods.get(id as usize).cloned()
+        self.methods().get(id as usize).cloned()
     }
 
     pub fn find_callable_method(
@@ -647,7 +892,12 @@ impl Class {
         flags: MethodAccessFlags,
     ) -> VmResult<VmRef<Method>> {
         self.find_method_in_this_only(name, descriptor, flags, MethodAccessFlags::ABSTRACT)
-            .ok_or(Throwables::Other("java/lang/NoSuchMethodError"))
+            .ok_or_else(|| {
+                Throwables::WithMessage(
+                    "java/lang/NoSuchMethodError",
+                    format!("{}.{}{}", self.name, name, descriptor),
+                )
+            })
     }
 
     pub fn find_method_in_this_only(
@@ -662,13 +912,14 @@ impl Class {
             "invalid method descriptor {:?}",
             desc
         );
-        self.methods
+        let (name, desc) = lookup_symbols(name, desc)?;
+        self.methods()
             .iter()
             .find(|m| {
                 m.flags.contains(flags)
                     && (m.flags - antiflags) == m.flags
-                    && m.name.as_mstr() == name
-                    && m.desc.as_mstr() == desc
+                    && m.name == name
+                    && m.desc == desc
             })
             .cloned()
     }
@@ -704,12 +955,18 @@ impl Class {
     ) -> impl Iterator<Item = (usize, VmRef<Method>)> + '_ {
         // TODO search in super classes too?
 
-        self.methods.iter().enumerate().filter_map(move |(i, m)| {
-            (m.flags.contains(flags)
-                && (m.flags - antiflags) == m.flags
-                && m.is_instance_initializer())
-            .then(|| (i, m.clone()))
-        })
+        let constructors = self
+            .methods()
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, m)| {
+                (m.flags.contains(flags)
+                    && (m.flags - antiflags) == m.flags
+                    && m.is_instance_initializer())
+                .then(|| (i, m.clone()))
+            })
+            .collect::<Vec<_>>();
+        constructors.into_iter()
     }
 
     /// Looks in superinterfaces only
@@ -737,47 +994,16 @@ impl Class {
         found
     }
 
-    pub fn find_overriding_method(self: VmRef<Class>, method: &Method) -> Option<VmRef<Method>> {
-        let can_override = |m: &Method| {
-            let flags = m.flags;
-            // TODO also this check, wtf does it mean:
-            // mA is marked neither ACC_PUBLIC nor ACC_PROTECTED nor ACC_PRIVATE, and either (a)
-            // the declaration of mA appears in the same run-time package as the declaration of mC,
-            // or (b) if mA is declared in a class A and mC is declared in a class C, then there
-            // exists a method mB declared in a class B such that C is a subclass of B and B is a
-            // subclass of A and mC can override mB and mB can override mA.
-            !flags.contains(MethodAccessFlags::PRIVATE)
-                && !flags.contains(MethodAccessFlags::ABSTRACT)
-                && flags.intersects(MethodAccessFlags::PUBLIC | MethodAccessFlags::PROTECTED)
-                && m.name() == method.name()
-                && m.descriptor() == method.descriptor()
-        };
-
-        // resolve in this class first
-        if let Some(found) = self.find_method_in_this_only(
-            method.name(),
-            method.descriptor(),
-            MethodAccessFlags::empty(),
-            MethodAccessFlags::ABSTRACT,
-        ) {
-            return Some(found);
-        }
-
-        // recurse to find overridable
-        let mut ret = None;
-
-        self.with_supers(|cls| {
-            for method in &cls.methods {
-                if can_override(&method) {
-                    ret = Some(method.to_owned());
-                    return SuperIteration::Stop;
-                }
-            }
-
-            SuperIteration::KeepGoing
-        });
+    /// Selects the method to invoke on a receiver of this class for the given resolved method
+    /// (JVMS 5.4.6), from the vtable or itable
+    pub fn select_method(&self, resolved: &VmRef<Method>) -> VmResult<VmRef<Method>> {
+        let tables = self.method_tables();
+        let selected = tables.select(resolved)?;
+        Ok(selected.unwrap_or(resolved).clone())
+    }
 
-        ret
+    pub(in crate::class) fn method_tables(&self) -> MappedRwLockReadGuard<'_, MethodTables> {
+        RwLockReadGuard::map(self.methods.read_recursive(), |m| &m.tables)
     }
 
     fn find_field_index_with(
@@ -786,10 +1012,12 @@ impl Class {
         desc: &DataType,
         search: FieldSearchType,
     ) -> Option<usize> {
+        // no field can have a name that was never interned
+        let name = InternedString::lookup(name)?;
         fields
             .iter()
             .filter(|f| search.matches(f.flags)) // index should skip non-instance/static fields
-            .position(|f| f.desc == *desc && f.name.as_mstr() == name)
+            .position(|f| f.name == name && f.desc == *desc)
     }
 
     pub(in crate::class) fn find_field_index(
@@ -878,6 +1106,31 @@ impl Class {
         }
     }
 
+    /// The class declaring the field found by [find_instance_field_recursive] or
+    /// [find_static_field_recursive], and its flags
+    ///
+    /// [find_instance_field_recursive]: Self::find_instance_field_recursive
+    /// [find_static_field_recursive]: Self::find_static_field_recursive
+    pub fn find_field_declaration(
+        self: &VmRef<Class>,
+        name: &mstr,
+        desc: &DataType,
+        ty: FieldSearchType,
+    ) -> Option<(VmRef<Class>, FieldAccessFlags)> {
+        let mut found = None;
+        self.field_resolution_order(|cls, fields| {
+            match Self::find_field_index_with(fields, name, desc, ty) {
+                Some(idx) => {
+                    found = cls.map(|cls| (cls.clone(), fields[idx].flags));
+                    SuperIteration::Stop
+                }
+                None => SuperIteration::KeepGoing,
+            }
+        });
+
+        found
+    }
+
     pub fn find_static_field_recursive(
         self: &VmRef<Class>,
         name: &mstr,
@@ -918,8 +1171,7 @@ impl Class {
 
         match self.class_type() {
             ClassType::Normal => {
-                debug_assert!(!self.is_interface());
-
+                // supers of an interface are java/lang/Object and its superinterfaces
                 let mut found = false;
                 self.with_supers(|super_cls| {
                     if vmref_eq(super_cls, other) {
@@ -931,7 +1183,7 @@ impl Class {
                 });
                 found
             }
-            ClassType::Array(elem_cls) => {
+            ClassType::Array(elem_cls, _) => {
                 /*If S is an array type SC[], that is, an array of components of type SC, then:
                     If T is a class type, then T must be Object.
                     If T is an interface type, then T must be one of the interfaces implemented by arrays (JLS §4.10.3).
@@ -941,33 +1193,30 @@ impl Class {
                 */
 
                 match other.class_type() {
-                    ClassType::Normal if other.is_interface() => {
-                        todo!("instanceof(array, interface type)")
-                    }
-                    ClassType::Normal => other.name() == "Ljava/lang/Object;".as_mstr(),
-                    ClassType::Array(other_elem) => {
-                        if let Some(arr_prim) = elem_cls.class_type().as_primitive() {
-                            if let Some(other_prim) = other_elem.class_type().as_primitive() {
-                                return arr_prim == other_prim;
-                            }
-
-                            return false;
-                        } else {
-                            assert!(!elem_cls.class_type.is_array());
-                            elem_cls.is_instance_of(other_elem)
+                    ClassType::Normal => self
+                        .super_class
+                        .iter()
+                        .chain(self.interfaces.iter())
+                        .any(|super_cls| vmref_eq(super_cls, other)),
+                    ClassType::Array(other_elem, _) => {
+                        match (elem_cls.class_type(), other_elem.class_type()) {
+                            (ClassType::Primitive(a), ClassType::Primitive(b)) => a == b,
+                            (ClassType::Primitive(_), _) | (_, ClassType::Primitive(_)) => false,
+                            _ => elem_cls.is_instance_of(other_elem),
                         }
                     }
                     ClassType::Primitive(_) => false,
                 }
             }
 
-            ClassType::Primitive(_) => unreachable!(),
+            // only the same primitive, checked above
+            ClassType::Primitive(_) => false,
         }
     }
 
     /// Self is an array class (dest), other is the source array to copy from
     pub fn can_array_be_copied_to(&self, other: &VmRef<Class>) -> bool {
-        let (src_arr_elem, dst_arr_elem) = match self
+        let (dst_component, src_component) = match self
             .class_type()
             .array_class()
             .zip(other.class_type().array_class())
@@ -976,12 +1225,12 @@ impl Class {
             None => return false,
         };
 
-        dst_arr_elem.can_array_elem_be_assigned_to(src_arr_elem)
+        src_component.can_array_elem_be_assigned_to(dst_component)
     }
 
     /// Self is an array class, other is the element to assign (can be null)
     pub fn can_array_be_assigned_to(&self, other: &VmRef<Object>) -> bool {
-        let (src_arr_elem, dst_elem) = match (self.class_type().array_class(), other.class()) {
+        let (component, value_cls) = match (self.class_type().array_class(), other.class()) {
             (Some(arr), None) => {
                 // null only for reference types?
                 if arr.class_type().as_primitive().is_some() {
@@ -995,43 +1244,19 @@ impl Class {
             _ => return false,
         };
 
-        dst_elem.can_array_elem_be_assigned_to(src_arr_elem)
+        value_cls.can_array_elem_be_assigned_to(component)
     }
 
-    /// Self is an array element class (dest), other is the source array element class to copy from
-    fn can_array_elem_be_assigned_to(self: &VmRef<Class>, src_arr_elem: &VmRef<Class>) -> bool {
-        let dst_arr_elem = self;
-        if vmref_eq(src_arr_elem, dst_arr_elem) {
-            true
-        } else {
-            trace!(
-                "can array of {:?} be assigned to by {:?}",
-                src_arr_elem.class_type(),
-                dst_arr_elem.class_type()
-            );
-            match (src_arr_elem.class_type(), dst_arr_elem.class_type()) {
-                (ClassType::Array(_), _) | (_, ClassType::Array(_)) => {
-                    unreachable!("nested arrays?")
-                }
+    /// Self is the class of a value (or of the source array's elements) to be stored in an array
+    /// with the given component class
+    fn can_array_elem_be_assigned_to(self: &VmRef<Class>, component: &VmRef<Class>) -> bool {
+        trace!(
+            "can array of {:?} be assigned to by {:?}",
+            component.class_type(),
+            self.class_type()
+        );
 
-                (ClassType::Primitive(a), ClassType::Primitive(b)) => a == b,
-                (ClassType::Normal, ClassType::Normal) => {
-                    // succeed if one is object
-                    if src_arr_elem.name() == "java/lang/Object".as_mstr()
-                        || dst_arr_elem.name() == "java/lang/Object".as_mstr()
-                    {
-                        true
-                    } else {
-                        todo!(
-                            "check superclasses for assignment of {} = {}",
-                            dst_arr_elem.name(),
-                            src_arr_elem.name()
-                        )
-                    }
-                }
-                _ => false,
-            }
-        }
+        self.is_instance_of(component)
     }
 
     /*    fn implements(self: &VmRef<Class>, iface: &VmRef<Class>) -> bool {
@@ -1041,20 +1266,21 @@ impl Class {
                     .iter()
                     .any(|implemented_iface| vmref_eq(implemented_iface, iface))
         }
+    */
 
-        pub fn extends(self: &VmRef<Class>, cls: &VmRef<Class>) -> bool {
-            let mut current = Some(self);
-            while let Some(this_cls) = current {
-                if vmref_eq(this_cls, cls) {
-                    return true;
-                }
-
-                current = this_cls.super_class();
+    pub fn extends(self: &VmRef<Class>, cls: &VmRef<Class>) -> bool {
+        let mut current = Some(self);
+        while let Some(this_cls) = current {
+            if vmref_eq(this_cls, cls) {
+                return true;
             }
 
-            false
+            current = this_cls.super_class();
         }
-    */
+
+        false
+    }
+
     /// Gross
     pub fn extends_by_name(self: &VmRef<Class>, cls: &mstr) -> bool {
         let mut current = Some(self);
@@ -1073,40 +1299,127 @@ impl Class {
         &self.name
     }
 
+    /// Binary name as returned by `Class.getName()`, e.g. `java.lang.String` or `[I`
+    pub fn java_name(&self) -> String {
+        self.name.to_utf8().replace('/', ".")
+    }
+
     pub const fn constant_pool(&self) -> &RuntimeConstantPool {
         &self.constant_pool
     }
 
-    pub const fn loader(&self) -> &WhichLoader {
-        &self.loader
+    /// Value of the method handle, method type or dynamically computed constant at the given
+    /// index, resolved on first load. If multiple threads race to resolve it, the first to finish
+    /// wins
+    pub fn resolved_constant<E>(
+        &self,
+        idx: u16,
+        resolve: impl FnOnce() -> Result<DataValue, E>,
+    ) -> Result<DataValue, E> {
+        if let Some(value) = self.resolved_constants.lock().get(&idx) {
+            return Ok(value.clone());
+        }
+
+        // resolve without holding the lock, as it may run Java code
+        let value = resolve()?;
+        Ok(self
+            .resolved_constants
+            .lock()
+            .entry(idx)
+            .or_insert(value)
+            .clone())
     }
 
-    pub fn super_class(&self) -> Option<&VmRef<Class>> {
-        self.super_class.as_ref()
+    pub(in crate::class) fn resolved_constants(&self) -> MutexGuard<'_, HashMap<u16, DataValue>> {
+        self.resolved_constants.lock()
     }
 
-    pub fn class_type(&self) -> &ClassType {
-        &self.class_type
+    /// From the SourceFile attribute, if present
+    pub fn source_file(&self) -> Option<&mstr> {
+        self.source_file.as_deref()
     }
 
-    pub fn class_object(&self) -> &VmRef<Object> {
-        // safety: java/lang/Class is loaded and this is populated before any class is initialised
-        #[cfg(debug_assertions)]
-        {
-            assert!(self.class_object_init);
-        }
-        unsafe { self.class_object.assume_init_ref() }
+    /// Slash separated e.g. java/lang, empty for the unnamed package
+    pub fn package_name(&self) -> &mstr {
+        &self.package
     }
 
-    /// class_cls: java/lang/Class class to be instantiated in this method
-    pub(in crate::class) fn init_class_object(self: &mut VmRef<Class>, class_cls: VmRef<Class>) {
-        // allocate class instance
-        let cls_object = VmRef::new(Object::new(class_cls));
+    /// Runtime packages are identified by package name and defining loader
+    pub fn is_same_runtime_package(&self, other: &Class) -> bool {
+        self.package == other.package && self.loader == other.loader
+    }
 
-        // set vmdata field on new class instance
-        let (_, field_id) = cls_object.vmdata();
-        let fields = cls_object.fields().unwrap();
-        fields.ensure_set(field_id, DataValue::VmDataClass(self.clone()));
+    /// Name of the class hosting this class's nest, which is this class itself unless it has a
+    /// NestHost attribute
+    pub fn nest_host_name(&self) -> &mstr {
+        self.nest_host.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Nestmates can access each other's private members (JVMS 5.4.4). The host's NestMembers
+    /// attribute isn't checked, only that both claim the same host within the same runtime package
+    pub fn is_nestmate_of(&self, other: &Class) -> bool {
+        self.is_same_runtime_package(other) && self.nest_host_name() == other.nest_host_name()
+    }
+
+    /// Class accessibility (JVMS 5.4.4)
+    pub fn is_accessible_to(&self, accessor: &Class) -> bool {
+        let cls = match &self.class_type {
+            ClassType::Array(elem, _) => elem,
+            _ => return self.access_flags.is_public() || self.is_same_runtime_package(accessor),
+        };
+
+        cls.is_accessible_to(accessor)
+    }
+
+    /// Accessibility of a field or method declared in this class with the given flags
+    /// (JVMS 5.4.4)
+    pub fn is_member_accessible_to(
+        self: &VmRef<Class>,
+        flags: impl AccessFlags,
+        accessor: &VmRef<Class>,
+    ) -> bool {
+        if flags.is_public() {
+            true
+        } else if flags.is_private() {
+            vmref_eq(self, accessor) || self.is_nestmate_of(accessor)
+        } else if flags.is_protected() {
+            self.is_same_runtime_package(accessor) || accessor.extends(self)
+        } else {
+            // package private
+            self.is_same_runtime_package(accessor)
+        }
+    }
+
+    pub const fn loader(&self) -> &WhichLoader {
+        &self.loader
+    }
+
+    pub fn super_class(&self) -> Option<&VmRef<Class>> {
+        self.super_class.as_ref()
+    }
+
+    pub fn class_type(&self) -> &ClassType {
+        &self.class_type
+    }
+
+    pub fn class_object(&self) -> &VmRef<Object> {
+        // safety: java/lang/Class is loaded and this is populated before any class is initialised
+        #[cfg(debug_assertions)]
+        {
+            assert!(self.class_object_init);
+        }
+        unsafe { self.class_object.assume_init_ref() }
+    }
+
+    /// class_cls: java/lang/Class class to be instantiated in this method
+    pub(in crate::class) fn init_class_object(self: &mut VmRef<Class>, class_cls: VmRef<Class>) {
+        // allocate class instance
+        let cls_object = VmRef::new(Object::new(class_cls));
+
+        // set vmdata field on new class instance
+        let (_, field_id) = cls_object.vmdata();
+        let fields = cls_object.fields().unwrap();
+        fields.ensure_set(field_id, DataValue::VmDataClass(self.clone()));
 
         // point class_object field to class instance
         // TODO use Arc::get_mut_unchecked instead when stable
@@ -1141,12 +1454,23 @@ impl Class {
         matches!(self.get_state(), ClassState::Uninitialised)
     }
 
+    pub fn is_initialised(&self) -> bool {
+        let _monitor = self.class_object().enter_monitor();
+        matches!(self.get_state(), ClassState::Initialised)
+    }
+
     pub fn ensure_init(self: &Arc<Class>) -> VmResult<()> {
+        self.ensure_init_caused_by(None)
+    }
+
+    /// Initiator is the class being initialised when this is its superclass or superinterface,
+    /// otherwise the class of the current frame
+    fn ensure_init_caused_by(self: &Arc<Class>, initiator: Option<&mstr>) -> VmResult<()> {
         // synchronise on initialisation lock
         let mut monitor = self.class_object().enter_monitor();
 
         match self.get_state() {
-            ClassState::Error => Err(Throwables::NoClassDefFoundError),
+            ClassState::Error => Err(self.init_error()),
             ClassState::Initialised => Ok(()),
             ClassState::Initialising(thread) => {
                 if thread == current_thread() {
@@ -1166,7 +1490,7 @@ impl Class {
                     debug!("thread {:?} unblocked", current_thread());
 
                     match self.get_state() {
-                        ClassState::Error => Err(Throwables::NoClassDefFoundError),
+                        ClassState::Error => Err(self.init_error()),
                         ClassState::Initialised => Ok(()),
                         _ => unreachable!(),
                     }
@@ -1178,11 +1502,13 @@ impl Class {
 
                 // release monitor
                 drop(monitor);
+                let start = Instant::now();
 
-                // TODO initialise final static fields from ConstantValue attrs
+                // primitive ConstantValues were set during preparation
+                let mut result = self.init_string_constants();
 
-                // recursively initialise super classes only if this is a class
-                let mut result = Ok(());
+                // recursively initialise super classes only if this is a class. failures leave
+                // this class erroneous too and are propagated as is
                 if !self.is_interface() {
                     if let Some(super_class) = self.super_class() {
                         trace!(
@@ -1191,9 +1517,13 @@ impl Class {
                             super_class.name()
                         );
 
-                        result = super_class.ensure_init().map_err(|e| {
-                            debug!("super class initialisation failed: {:?}", e);
-                            e
+                        result = result.and_then(|_| {
+                            super_class
+                                .ensure_init_caused_by(Some(self.name()))
+                                .map_err(|e| {
+                                    debug!("super class initialisation failed: {:?}", e);
+                                    e
+                                })
                         });
                     }
 
@@ -1203,11 +1533,12 @@ impl Class {
                     result = result.and_then(|_| {
                         let mut result = Ok(());
                         self.with_superinterfaces(|iface| {
-                            let should_init = iface.methods.iter().any(|m| {
-                                let antiflags =
-                                    MethodAccessFlags::STATIC | MethodAccessFlags::ABSTRACT;
-                                (m.flags - antiflags) == m.flags
-                            });
+                            let should_init = iface.is_interface()
+                                && iface.methods().iter().any(|m| {
+                                    let antiflags =
+                                        MethodAccessFlags::STATIC | MethodAccessFlags::ABSTRACT;
+                                    (m.flags - antiflags) == m.flags
+                                });
 
                             let mut iter_result = SuperIteration::KeepGoing;
                             if should_init {
@@ -1217,7 +1548,7 @@ impl Class {
                                     iface.name()
                                 );
 
-                                if let Err(e) = iface.ensure_init() {
+                                if let Err(e) = iface.ensure_init_caused_by(Some(self.name())) {
                                     debug!("super interface initialisation failed: {:?}", e);
                                     result = Err(e);
                                     iter_result = SuperIteration::Stop;
@@ -1241,22 +1572,15 @@ impl Class {
                         MethodLookupResult::Found(m) => {
                             debug!("running static constructor for {:?}", self.name);
 
-                            let thread = thread::get();
-                            let interpreter = thread.interpreter();
-                            let result = Frame::new_no_args(m).and_then(|frame| {
-                                interpreter.execute_frame(frame).map_err(|exc| {
-                                    // TODO wrap exception here and return the proper type
-                                    warn!("exception raised in static constructor: {:?}", exc);
-
-                                    thread::get().set_exception(exc);
-                                    InterpreterError::ExceptionRaised(Throwables::ClassFormatError)
-                                })
-                            });
+                            let frame = Frame::new_no_args(m).map_err(|err| {
+                                warn!("failed to create static constructor frame: {}", err);
+                                Throwables::Other("java/lang/InternalError")
+                            })?;
 
-                            if let Err(err) = result {
-                                warn!("static constructor failed: {}", err);
-                                // TODO proper exception type here
-                                return Err(Throwables::ClassFormatError);
+                            let thread = thread::get();
+                            let result = thread.interpreter().execute_frame(frame);
+                            if let Err(exc) = result {
+                                return Err(Self::wrap_initializer_exception(exc));
                             }
 
                             trace!("initialized class: {:?}", ClassStaticFieldPrinter(&*self))
@@ -1266,6 +1590,24 @@ impl Class {
                     Ok(())
                 });
 
+                let thread = thread::get();
+                let class_loader = thread.global().class_loader();
+                let caller = match initiator {
+                    None if class_loader.is_class_log_enabled() => {
+                        thread.interpreter().current_class()
+                    }
+                    _ => None,
+                };
+                class_loader.record_class_event(ClassEvent {
+                    kind: ClassEventKind::Init,
+                    class: self.name(),
+                    loader: self.loader(),
+                    source: None,
+                    initiator: initiator.or_else(|| caller.as_ref().map(|cls| cls.name())),
+                    duration: start.elapsed(),
+                    failed: result.is_err(),
+                });
+
                 // obtain monitor for updating state
                 let monitor = self.class_object().enter_monitor();
                 match result {
@@ -1278,7 +1620,6 @@ impl Class {
                         // notify all threads
                         monitor.notify_all();
 
-                        // TODO specific exception type e.g. ExceptionInInitializerError
                         Err(e)
                     }
                     Ok(()) => {
@@ -1291,8 +1632,53 @@ impl Class {
         }
     }
 
+    /// Sets static String fields from their ConstantValue attrs to interned instances
+    fn init_string_constants(&self) -> VmResult<()> {
+        let thread = thread::get();
+        let statics = self.fields.iter().filter(|f| f.flags.is_static());
+        for (i, field) in statics.enumerate() {
+            if let Some(attribute::ConstantValue::String(s)) = &field.constant_value {
+                let string = thread.global().intern_string(s)?;
+                let field_id = self.static_fields_layout.get_self_id(i).unwrap();
+                self.static_fields_values
+                    .ensure_set(field_id, DataValue::Reference(string));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Exceptions thrown by a static constructor are rethrown as is if they are Errors, otherwise
+    /// wrapped in an ExceptionInInitializerError (JVMS 5.5 step 11)
+    fn wrap_initializer_exception(exc: VmRef<Throwable>) -> Throwables {
+        let exc_class = match &exc.object {
+            Some(obj) => obj.class(),
+            None => thread::get()
+                .global()
+                .class_loader()
+                .load_class(exc.class_name.as_mstr(), WhichLoader::Bootstrap)
+                .ok(),
+        };
+        let is_error = exc_class
+            .map(|cls| cls.extends_by_name("java/lang/Error".as_mstr()))
+            .unwrap_or(false);
+
+        if is_error {
+            debug!("error raised in static constructor: {:?}", exc);
+            Throwables::Thrown(exc)
+        } else {
+            warn!("exception raised in static constructor: {:?}", exc);
+            Throwables::ExceptionInInitializerError(exc)
+        }
+    }
+
+    /// Raised when initialising a class that previously failed to initialise
+    fn init_error(&self) -> Throwables {
+        Throwables::NoClassDefFoundError(format!("Could not initialize class {}", self.name))
+    }
+
     /// Recurses superclass then all superinterfaces
-    fn with_supers(&self, mut f: impl FnMut(&VmRef<Class>) -> SuperIteration) {
+    pub(in crate::class) fn with_supers(&self, mut f: impl FnMut(&VmRef<Class>) -> SuperIteration) {
         self.__with_supers_recurse(&mut f);
     }
 
@@ -1424,6 +1810,33 @@ impl Class {
         keep_going
     }
 
+    pub fn interfaces(&self) -> &[VmRef<Class>] {
+        &self.interfaces
+    }
+
+    pub fn methods(&self) -> MappedRwLockReadGuard<'_, [VmRef<Method>]> {
+        RwLockReadGuard::map(self.methods.read_recursive(), |m| m.methods.as_slice())
+    }
+
+    /// Drops all methods, the method tables and resolved constant pool references to break their
+    /// cyclic references back to this class, and releases any native thunks generated for the
+    /// methods. Only used by unloading once the class is unreachable
+    pub(in crate::class) fn release_methods(&self, thunks: &mut NativeThunks) {
+        let released = std::mem::take(&mut *self.methods.write());
+        self.constant_pool.clear_resolutions();
+        for method in released.methods {
+            if let MethodCode::Native(native) = &method.code {
+                if let NativeCode::Bound(NativeFunction::Jni { trampoline, .. }) =
+                    &mut *native.lock()
+                {
+                    if let Some(thunk) = trampoline.take() {
+                        thunks.free(thunk);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn static_fields(&self) -> &FieldStorage {
         &self.static_fields_values
     }
@@ -1471,8 +1884,8 @@ impl Class {
             .or_else(|| native_libs.resolve_symbol(name.into_long(method.descriptor()).as_ref()))
             .ok_or_else(|| InterpreterError::NativeMethodNotFound {
                 class: method.class().clone(),
-                name: method.name.clone(),
-                desc: method.desc.clone(),
+                name: method.name,
+                desc: method.desc.as_mstr().to_owned(),
             })?;
 
         debug!("found native method at {:?}", ptr);
@@ -1565,6 +1978,26 @@ impl Method {
         &self.code
     }
 
+    /// Java code pre-decoded for the interpreter, translated once on the first call
+    pub fn decoded_code(&self) -> Option<&Arc<DecodedCode>> {
+        match &self.code {
+            MethodCode::Java(code) => Some(self.decoded.get_or_init(|| {
+                trace!("translating code of {}", self);
+                Arc::new(DecodedCode::translate(code.code.clone()))
+            })),
+            _ => None,
+        }
+    }
+
+    pub fn vtable_index(&self) -> Option<usize> {
+        self.vtable_index.map(|idx| idx as usize)
+    }
+
+    /// Decoded code if it has already been translated
+    pub(in crate::class) fn translated_code(&self) -> Option<&DecodedCode> {
+        self.decoded.get().map(|code| &**code)
+    }
+
     pub fn name(&self) -> &mstr {
         &self.name
     }
@@ -1598,6 +2031,27 @@ impl Method {
         unsafe { &*self.class.as_ptr() }
     }
 
+    /// As in Java exception messages, e.g. `java.lang.String.indexOf(String, int)`
+    pub fn java_signature(&self) -> String {
+        let arg_name = |arg: &DataType| {
+            // HotSpot abbreviates only these
+            let name = arg.java_name();
+            match name.strip_prefix("java.lang.") {
+                Some(short) if matches!(short.trim_end_matches("[]"), "Object" | "String") => {
+                    short.to_owned()
+                }
+                _ => name,
+            }
+        };
+
+        format!(
+            "{}.{}({})",
+            self.class().java_name(),
+            self.name(),
+            self.args().iter().map(arg_name).join(", ")
+        )
+    }
+
     fn mangled_native_name(&self) -> MangledMethodName {
         // TODO cache mangled name in the method
         MangledMethodName::new(self.class().name(), self.name())
@@ -1638,6 +2092,43 @@ impl Field {
     }
 }
 
+/// Value for a static field from its ConstantValue attr, None if it's a String or doesn't match
+/// the field type
+fn primitive_constant(value: &attribute::ConstantValue, ty: &DataType) -> Option<DataValue> {
+    use attribute::ConstantValue as Constant;
+    match (value, ty) {
+        (Constant::Int(i), DataType::Primitive(PrimitiveDataType::Int)) => Some(DataValue::Int(*i)),
+        (
+            Constant::Int(i),
+            DataType::Primitive(
+                prim @ (PrimitiveDataType::Boolean
+                | PrimitiveDataType::Byte
+                | PrimitiveDataType::Short
+                | PrimitiveDataType::Char),
+            ),
+        ) => DataValue::Int(*i).narrow_primitive_to(*prim),
+        (Constant::Long(l), DataType::Primitive(PrimitiveDataType::Long)) => {
+            Some(DataValue::Long(*l))
+        }
+        (Constant::Float(f), DataType::Primitive(PrimitiveDataType::Float)) => {
+            Some(DataValue::Float(*f))
+        }
+        (Constant::Double(d), DataType::Primitive(PrimitiveDataType::Double)) => {
+            Some(DataValue::Double(*d))
+        }
+        _ => None,
+    }
+}
+
+fn is_constant_assignable(value: &attribute::ConstantValue, ty: &DataType) -> bool {
+    match (value, ty) {
+        (attribute::ConstantValue::String(_), DataType::Reference(cls)) => {
+            cls.as_bytes() == b"java/lang/String"
+        }
+        _ => primitive_constant(value, ty).is_some(),
+    }
+}
+
 impl MethodLookupResult {
     fn ok(self) -> Option<VmRef<Method>> {
         if let MethodLookupResult::Found(m) = self {
@@ -1650,7 +2141,14 @@ impl MethodLookupResult {
 
 impl ClassType {
     pub fn is_array(&self) -> bool {
-        matches!(self, Self::Array(_))
+        matches!(self, Self::Array(..))
+    }
+
+    pub fn array_dimensions(&self) -> Option<u8> {
+        match self {
+            Self::Array(_, dims) => Some(*dims),
+            _ => None,
+        }
     }
 
     pub fn as_primitive(&self) -> Option<PrimitiveDataType> {
@@ -1661,7 +2159,7 @@ impl ClassType {
     }
     pub fn array_class(&self) -> Option<&VmRef<Class>> {
         match self {
-            Self::Array(cls) => Some(cls),
+            Self::Array(cls, _) => Some(cls),
             _ => None,
         }
     }
@@ -1735,7 +2233,7 @@ impl<'a> FunctionArgs<'a> {
     }
 
     /// Reverse order
-    pub fn take_all(self) -> impl Iterator<Item = DataValue> + 'a {
+    pub fn take_all(self) -> impl DoubleEndedIterator<Item = DataValue> + 'a {
         self.0
             .iter_mut()
             .map(|val| std::mem::replace(val, DataValue::Boolean(false)))
@@ -1842,11 +2340,16 @@ impl AsRef<CStr> for MangledMethodNameLong {
     }
 }
 
+/// Symbols of a member name and descriptor, None if either was never interned so no member can
+/// have them
+fn lookup_symbols(name: &mstr, desc: &mstr) -> Option<(InternedString, InternedString)> {
+    InternedString::lookup(name).zip(InternedString::lookup(desc))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{Jvm, JvmArgs};
-    use cafebabe::mutf8::MString;
     use std::path::PathBuf;
 
     fn test_jvm() -> Jvm {
@@ -1920,6 +2423,22 @@ mod tests {
             .unwrap_or_else(|err| panic!("failed to load class {:?}: {}", name, err.symbol()))
     }
 
+    /// Defines a compiled test case class with the given loader
+    fn define_test_class(name: &'static str, loader: WhichLoader) -> VmRef<Class> {
+        let bytes = {
+            let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            path.push("test-cases");
+            path.push(format!("{}.class", name));
+            std::fs::read(path).expect("missing test class")
+        };
+
+        thread::get()
+            .global()
+            .class_loader()
+            .define_class(mstr::from_literal(name), &bytes, loader, None)
+            .unwrap_or_else(|err| panic!("failed to define class {:?}: {}", name, err.symbol()))
+    }
+
     #[test]
     fn static_field_inheritance_get() {
         test_logging();
@@ -1959,7 +2478,7 @@ mod tests {
 
             let mut vec = vec![];
             cls.iter_static_fields(|field, val| {
-                vec.push((field.name.clone(), field.desc.clone(), val));
+                vec.push((field.name, field.desc.clone(), val));
                 SuperIteration::KeepGoing
             });
 
@@ -1967,7 +2486,7 @@ mod tests {
                 .into_iter()
                 .map(|(name, ty, val)| {
                     (
-                        NativeString::from_utf8(name.as_bytes()),
+                        InternedString::intern(mstr::from_literal(name)),
                         DataType::from_descriptor(mstr::from_literal(ty)).expect("bad descriptor"),
                         val,
                     )
@@ -2052,7 +2571,7 @@ mod tests {
     #[test]
     fn wtf() {
         let super_class: Option<VmRef<Class>> = None;
-        let name: InternedString = MString::from_utf8(b"java/lang/Object");
+        let name = InternedString::intern(mstr::from_literal("java/lang/Object"));
 
         if super_class.is_none() != (name.as_bytes() == b"java/lang/Object") {
             panic!(
@@ -2061,4 +2580,1371 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn class_init_failure() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        // NPE is wrapped
+        let failing = get_class("ClassInit$NullDeref");
+        let err = failing.ensure_init().expect_err("init should fail");
+        assert!(matches!(
+            &err,
+            Throwables::ExceptionInInitializerError(cause) if cause.class_name == "java/lang/NullPointerException"
+        ));
+
+        // and instantiated with its cause
+        let exc = VmRef::<Throwable>::from(err);
+        assert!(exc.object.is_some());
+        assert_eq!(
+            exc.cause().map(|cause| cause.class_name),
+            Some("java/lang/NullPointerException")
+        );
+
+        // then erroneous
+        let err = failing.ensure_init().expect_err("init should fail");
+        assert!(matches!(err, Throwables::NoClassDefFoundError(_)));
+        assert_eq!(
+            err.message().as_deref(),
+            Some("Could not initialize class ClassInit$NullDeref")
+        );
+
+        // subclass fails with super class
+        let sub = get_class("ClassInit$ExtendsNullDeref");
+        let err = sub.ensure_init().expect_err("init should fail");
+        assert!(matches!(err, Throwables::NoClassDefFoundError(_)));
+    }
+
+    #[test]
+    fn class_init_recursive() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("ClassInit$Recursive");
+        assert_eq!(get_static_field(&cls, "RECURSIVE", "I"), DataValue::Int(11));
+    }
+
+    #[test]
+    fn constant_value_statics() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Constants");
+        let iface = get_class("Constants$Iface");
+
+        assert_eq!(get_static_field(&cls, "BYTE", "B"), DataValue::Byte(-5));
+        assert_eq!(
+            get_static_field(&cls, "CHAR", "C"),
+            DataValue::Char('x' as u16)
+        );
+        assert_eq!(
+            get_static_field(&cls, "BOOL", "Z"),
+            DataValue::Boolean(true)
+        );
+        assert_eq!(
+            get_static_field(&cls, "LONG", "J"),
+            DataValue::Long(1234567890123)
+        );
+        assert_eq!(
+            get_static_field(&cls, "DOUBLE", "D"),
+            DataValue::Double(2.5)
+        );
+        assert_eq!(get_static_field(&iface, "INT", "I"), DataValue::Int(42));
+
+        // same interned instance
+        let string = get_static_field(&cls, "STRING", "Ljava/lang/String;");
+        let iface_string = get_static_field(&iface, "STRING", "Ljava/lang/String;");
+        assert_eq!(string, iface_string);
+        assert_eq!(
+            string
+                .as_reference()
+                .and_then(|s| s.string_value_utf8())
+                .as_deref(),
+            Some("hello")
+        );
+
+        // String.intern shares the same table
+        for name in ["RUNTIME_INTERNED", "FIRST_INTERNED"] {
+            assert_eq!(
+                get_static_field(&cls, name, "Z"),
+                DataValue::Boolean(true),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn array_instance_of() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let strings = get_class("[Ljava/lang/String;");
+        let strings_2d = get_class("[[Ljava/lang/String;");
+        let objects = get_class("[Ljava/lang/Object;");
+        let objects_2d = get_class("[[Ljava/lang/Object;");
+        let comparables = get_class("[Ljava/lang/Comparable;");
+        let ints = get_class("[I");
+        let longs = get_class("[J");
+
+        assert_eq!(strings.class_type().array_dimensions(), Some(1));
+        assert_eq!(strings_2d.class_type().array_dimensions(), Some(2));
+
+        // covariance
+        assert!(strings.is_instance_of(&objects));
+        assert!(!objects.is_instance_of(&strings));
+        assert!(strings.is_instance_of(&comparables));
+        assert!(strings_2d.is_instance_of(&objects_2d));
+        assert!(strings_2d.is_instance_of(&objects));
+        assert!(!objects.is_instance_of(&objects_2d));
+
+        // primitives are invariant
+        assert!(!ints.is_instance_of(&objects));
+        assert!(!ints.is_instance_of(&longs));
+
+        let supers = [
+            get_class("java/lang/Object"),
+            get_class("java/lang/Cloneable"),
+            get_class("java/io/Serializable"),
+        ];
+        for array in [&strings, &strings_2d, &ints] {
+            assert!(supers.iter().all(|cls| array.is_instance_of(cls)));
+        }
+
+        assert!(!strings.is_instance_of(&get_class("java/lang/Comparable")));
+    }
+
+    #[test]
+    fn array_instructions() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("ArrayTypes");
+        assert_eq!(
+            get_static_field(&cls, "MULTI_LENGTHS", "I"),
+            DataValue::Int(34)
+        );
+
+        for name in [
+            "INNERMOST_NULL",
+            "CLONEABLE",
+            "COVARIANT",
+            "PRIMITIVE_NOT_COVARIANT",
+            "CLONE_IS_SHALLOW",
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "I"),
+                DataValue::Int(1),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn exception_handlers() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Exceptions");
+        for (name, expected) in [
+            ("CAUGHT", 5),
+            ("CAUGHT_BY_SUPERCLASS", 6),
+            ("FINALLY", 111),
+            ("FROM_CALLEE", 7),
+            ("NULL_POINTER", 8),
+            ("THROW_NULL", 9),
+            ("RETHROWN", 11),
+            ("NESTED", 12),
+            ("INIT_CAUSE", 1),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "I"),
+                DataValue::Int(expected),
+                "{}",
+                name
+            );
+        }
+
+        // message of a VM-raised exception
+        let message = get_static_field(&cls, "VM_MESSAGE", "Ljava/lang/String;");
+        assert_eq!(
+            message.as_reference().and_then(|s| s.string_value_utf8()),
+            Some("/ by zero".to_owned())
+        );
+    }
+
+    #[test]
+    fn exception_handler_unresolved_catch_type() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        // the resolution error replaces the exception being dispatched
+        let loader = thread::get()
+            .exec_helper()
+            .instantiate_and_invoke_constructor(
+                "Exceptions$HidingLoader",
+                "()V",
+                std::iter::empty(),
+            )
+            .expect("failed to create loader");
+        let cls = define_test_class("Exceptions$MissingCatchType", WhichLoader::User(loader));
+        cls.ensure_init().expect("init failed");
+        assert_eq!(
+            get_static_field(&cls, "RESOLUTION_ERROR", "I"),
+            DataValue::Int(13)
+        );
+    }
+
+    #[test]
+    fn stack_traces() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("StackTraces");
+        for (name, expected) in [
+            ("THROWN_LINE", 22),
+            ("VM_RAISED_LINE", 43),
+            ("IN_INITIALISER_LINE", 18),
+            // the NPE is raised by the only instruction on its line
+            ("FIRST_ON_LINE", 61),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "I"),
+                DataValue::Int(expected),
+                "{}",
+                name
+            );
+        }
+
+        for (name, expected) in [("CALLER", "caller"), ("FILE", "StackTraces.java")] {
+            let value = get_static_field(&cls, name, "Ljava/lang/String;");
+            assert_eq!(
+                value.as_reference().and_then(|s| s.string_value_utf8()),
+                Some(expected.to_owned()),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Arithmetic");
+        for (name, expected) in [
+            ("LONG_CMP", 1),
+            ("DOUBLE_CMP", 1001),
+            ("DIVISION", 2147483350),
+            ("DIVIDE_BY_ZERO", 3),
+            ("BYTES", 994),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "I").as_int(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+
+        for (name, expected) in [
+            ("LONG_OPS", -66102292180),
+            ("CONVERSIONS", -9223371872157120040),
+            ("STACK", 1213),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "J").as_long(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+
+        assert_eq!(
+            get_static_field(&cls, "DOUBLE_OPS", "D").as_double(),
+            Some(0.2999999999999998)
+        );
+    }
+
+    #[test]
+    fn switches() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Switches");
+        for (name, expected) in [("DENSE", 5431), ("SPARSE", 4321), ("WIDE_LOCALS", 915)] {
+            assert_eq!(
+                get_static_field(&cls, name, "I").as_int(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn category_2_slots() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Slots");
+        for (name, expected) in [
+            ("STATIC_ARGS", 13580246791491),
+            ("INSTANCE_ARGS", -12),
+            ("POP2", 43),
+            ("DUP2", 612),
+            ("DUP2_X1", 42),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "J").as_long(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+
+        assert_eq!(
+            get_static_field(&cls, "DUP2_X2", "D").as_double(),
+            Some(3.5)
+        );
+    }
+
+    #[test]
+    fn numerics() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Numerics");
+        let array = |name: &'static str, desc: &'static str| {
+            let array = get_static_field(&cls, name, desc);
+            let elems = array.as_reference().expect("null array").array_unchecked();
+            elems.to_vec()
+        };
+
+        let (int_max, int_min) = (i32::MAX, i32::MIN);
+        for (name, expected) in [
+            (
+                "F2I",
+                &[
+                    0, int_max, int_min, int_max, int_min, int_max, int_min, 2, -2, 0, 0, 300,
+                    -70000, 0,
+                ][..],
+            ),
+            (
+                "D2I",
+                &[
+                    0, int_max, int_min, int_max, int_min, int_max, int_min, 2, -2, 0, 0, 300,
+                    -70000, int_max,
+                ],
+            ),
+            (
+                "NARROW_FLOAT",
+                &[
+                    0, 0, 0, -1, 65535, -1, 0, 0, 0, -1, 65535, -1, 0, 0, 0, -1, 65535, -1, 0, 0,
+                    0, 2, 2, 2, -2, 65534, -2, 0, 0, 0, 0, 0, 0, 44, 300, 300, -112, 61072, -4464,
+                    0, 0, 0,
+                ],
+            ),
+            (
+                "NARROW_DOUBLE",
+                &[
+                    0, 0, 0, -1, 65535, -1, 0, 0, 0, -1, 65535, -1, 0, 0, 0, -1, 65535, -1, 0, 0,
+                    0, 2, 2, 2, -2, 65534, -2, 0, 0, 0, 0, 0, 0, 44, 300, 300, -112, 61072, -4464,
+                    -1, 65535, -1,
+                ],
+            ),
+            (
+                "NARROW_INT",
+                &[
+                    0, 0, 0, 1, 1, 1, -1, 65535, -1, 127, 127, 127, -128, 128, 128, 127, 65407,
+                    -129, -1, 65535, -1, 0, 0, 0, -1, 32767, 32767, 1, 1, 1, -1, 65535, -1, 0, 0,
+                    0,
+                ],
+            ),
+            (
+                "FCMP",
+                &[
+                    0, 0, 0, 0, 0, 0, 28, 18, 18, 18, 0, 9, 28, 28, 18, 0, 9, 28, 28, 18, 0, 9, 9,
+                    9, 28,
+                ],
+            ),
+            (
+                "DCMP",
+                &[
+                    0, 0, 0, 0, 0, 0, 28, 18, 18, 18, 0, 9, 28, 28, 18, 0, 9, 28, 28, 18, 0, 9, 9,
+                    9, 28,
+                ],
+            ),
+            (
+                "INT_SHIFTS",
+                &[
+                    -12345, -12345, -12345, -24690, -6173, 2147477475, int_min, -1, 1, -12345,
+                    -12345, -12345, -24690, -6173, 2147477475, int_min, -1, 1, -12345, -12345,
+                    -12345, int_min, -1, 1, int_min, -1, 1,
+                ],
+            ),
+            ("CHARS", &[65535, 32768, 2048, 65535, 65536, -1, 0, -65438]),
+        ] {
+            let actual = array(name, "[I")
+                .iter()
+                .map(|v| v.as_int().expect("not int"))
+                .collect_vec();
+            assert_eq!(actual, expected, "{}", name);
+        }
+
+        let (long_max, long_min) = (i64::MAX, i64::MIN);
+        for (name, expected) in [
+            (
+                "F2L",
+                &[
+                    0,
+                    long_max,
+                    long_min,
+                    3000000000,
+                    -3000000000,
+                    long_max,
+                    long_min,
+                    2,
+                    -2,
+                    0,
+                    0,
+                    300,
+                    -70000,
+                    0,
+                ][..],
+            ),
+            (
+                "D2L",
+                &[
+                    0,
+                    long_max,
+                    long_min,
+                    3000000000,
+                    -3000000000,
+                    long_max,
+                    long_min,
+                    2,
+                    -2,
+                    0,
+                    0,
+                    300,
+                    -70000,
+                    long_max,
+                ],
+            ),
+            (
+                "LONG_SHIFTS",
+                &[
+                    -123456789012,
+                    -123456789012,
+                    -123456789012,
+                    -246913578024,
+                    -61728394506,
+                    9223371975126381302,
+                    -6867018605922353152,
+                    -58,
+                    8589934534,
+                    4712706861864845312,
+                    -29,
+                    4294967267,
+                    -9021330349979860992,
+                    -15,
+                    2147483633,
+                    0,
+                    -1,
+                    1,
+                    -123456789012,
+                    -123456789012,
+                    -123456789012,
+                    0,
+                    -1,
+                    1,
+                    -6867018605922353152,
+                    -58,
+                    8589934534,
+                ],
+            ),
+        ] {
+            let actual = array(name, "[J")
+                .iter()
+                .map(|v| v.as_long().expect("not long"))
+                .collect_vec();
+            assert_eq!(actual, expected, "{}", name);
+        }
+
+        // compared by bits so that -0.0 differs from 0.0, with any NaN equal to any other
+        let float_bits = |floats: &[f32]| {
+            floats
+                .iter()
+                .map(|f| if f.is_nan() { f32::NAN } else { *f }.to_bits())
+                .collect_vec()
+        };
+        let double_bits = |doubles: &[f64]| {
+            doubles
+                .iter()
+                .map(|d| if d.is_nan() { f64::NAN } else { *d }.to_bits())
+                .collect_vec()
+        };
+
+        for (name, expected) in [
+            (
+                "I2F",
+                &[
+                    0.0,
+                    1.0,
+                    -1.0,
+                    127.0,
+                    128.0,
+                    -129.0,
+                    65535.0,
+                    65536.0,
+                    -32769.0,
+                    1.6777216E7,
+                    2147483648.0,
+                    -2147483648.0,
+                ][..],
+            ),
+            (
+                "L2F",
+                &[
+                    0.0,
+                    -1.0,
+                    9007199254740992.0,
+                    9.223372E18,
+                    -9.223372E18,
+                    9.2233715E18,
+                    -4.2949673E9,
+                ],
+            ),
+            (
+                "D2F",
+                &[
+                    f32::NAN,
+                    f32::INFINITY,
+                    f32::NEG_INFINITY,
+                    3.0E9,
+                    -3.0E9,
+                    3.0E19,
+                    -3.0E19,
+                    2.9,
+                    -2.9,
+                    0.5,
+                    -0.0,
+                    300.7,
+                    -70000.5,
+                    f32::INFINITY,
+                ],
+            ),
+            (
+                "FREM",
+                &[
+                    1.5,
+                    -1.5,
+                    1.5,
+                    -1.5,
+                    f32::NAN,
+                    f32::NAN,
+                    3.0,
+                    -0.0,
+                    f32::NAN,
+                ],
+            ),
+        ] {
+            let actual = array(name, "[F")
+                .iter()
+                .map(|v| v.as_float().expect("not float"))
+                .collect_vec();
+            assert_eq!(
+                float_bits(&actual),
+                float_bits(expected),
+                "{}: {:?}",
+                name,
+                actual
+            );
+        }
+
+        for (name, expected) in [
+            (
+                "L2D",
+                &[
+                    0.0,
+                    -1.0,
+                    9.007199254740992E15,
+                    9.223372036854776E18,
+                    -9.223372036854776E18,
+                    9223371487098961920.0,
+                    -4.294967296E9,
+                ][..],
+            ),
+            (
+                "DREM",
+                &[
+                    1.5,
+                    -1.5,
+                    1.5,
+                    -1.5,
+                    f64::NAN,
+                    f64::NAN,
+                    3.0,
+                    -0.0,
+                    0.15321850028067768,
+                ],
+            ),
+        ] {
+            let actual = array(name, "[D")
+                .iter()
+                .map(|v| v.as_double().expect("not double"))
+                .collect_vec();
+            assert_eq!(
+                double_bits(&actual),
+                double_bits(expected),
+                "{}: {:?}",
+                name,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn safety_checks() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("SafetyChecks");
+        for (name, expected) in [
+            ("OUT_OF_BOUNDS", "Index 3 out of bounds for length 2"),
+            ("NEGATIVE_INDEX", "Index -1 out of bounds for length 1"),
+            ("NEGATIVE_SIZE", "-4"),
+            ("NULL_ARRAY_LOAD", "Cannot load from int array"),
+            ("NULL_ARRAY_STORE", "Cannot store to object array"),
+            ("NULL_ARRAY_LENGTH", "Cannot read the array length"),
+            ("NULL_FIELD_READ", "Cannot read field \"field\""),
+            ("NULL_FIELD_WRITE", "Cannot assign field \"field\""),
+            (
+                "NULL_INVOKE",
+                "Cannot invoke \"SafetyChecks.instance(int, String[])\"",
+            ),
+            ("NULL_THROW", "Cannot throw exception"),
+            ("NULL_SYNC", "Cannot enter synchronized block"),
+            ("ARRAY_STORE", "java.lang.Object"),
+            (
+                "CLASS_CAST",
+                "class java.lang.Object cannot be cast to class java.lang.String",
+            ),
+        ] {
+            let value = get_static_field(&cls, name, "Ljava/lang/String;");
+            assert_eq!(
+                value.as_reference().and_then(|s| s.string_value_utf8()),
+                Some(expected.to_owned()),
+                "{}",
+                name
+            );
+        }
+
+        for (name, expected) in [("COVARIANT_STORE", 35), ("NARROW_ELEMENTS", 73302)] {
+            assert_eq!(
+                get_static_field(&cls, name, "I").as_int(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn monitors() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Monitors");
+        for (name, expected) in [
+            ("REENTRANT", 1111),
+            ("NOT_OWNER", 111),
+            ("NULL_LOCK", 1),
+            ("RELEASED", 11),
+            ("STATIC_SYNC", 1),
+            ("TIMED_WAIT", 1),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "I").as_int(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn lambdas() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Lambdas");
+        for (name, expected) in [
+            ("ADD", 7),
+            ("CAPTURE", 22),
+            ("CAPTURE_THIS", 106),
+            ("STATIC_REF", 42),
+            ("UNBOUND_REF", 5),
+            ("VOID_RETURN", 2),
+            ("SERIALIZABLE", 1),
+            ("CALL_SITE_REUSE", 25),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "I").as_int(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+
+        for (name, expected) in [("WIDENING", 4294967294), ("UNBOXING", 42)] {
+            assert_eq!(
+                get_static_field(&cls, name, "J").as_long(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+
+        assert_eq!(
+            get_static_field(&cls, "WIDE_CAPTURE", "D").as_double(),
+            Some((1u64 << 40) as f64 + 3.75)
+        );
+
+        for (name, expected) in [
+            ("BOUND_REF", "hello world"),
+            ("CONSTRUCTOR_REF", "tliub"),
+            ("BRIDGE", "bridged7"),
+            (
+                "CONCAT",
+                "i=-5 l=8589934592 c=x z=true d=1.5 f=0.25 b=7 s=-300 str=null obj=Lambdas!",
+            ),
+            ("CONCAT_CONSTANTS", "tag\u{1}3\u{2}end"),
+            ("CONCAT_LOOP", "0,1,2,3,"),
+        ] {
+            let value = get_static_field(&cls, name, "Ljava/lang/String;");
+            assert_eq!(
+                value.as_reference().and_then(|s| s.string_value_utf8()),
+                Some(expected.to_owned()),
+                "{}",
+                name
+            );
+        }
+    }
+
+    /// Class for the dispatch test that javac rejects, with a constructor and optionally a package
+    /// private `level()I` returning the given level
+    fn generate_dispatch_class(
+        name: &[u8],
+        super_class: &[u8],
+        interfaces: &[&[u8]],
+        level: Option<i32>,
+    ) -> Vec<u8> {
+        use crate::interpreter::writer::{ClassWriter, CodeBuilder};
+        use crate::interpreter::Opcode;
+
+        let mut writer = ClassWriter::new(
+            name,
+            super_class,
+            ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        );
+        for interface in interfaces {
+            writer.add_interface(interface);
+        }
+
+        let mut code = CodeBuilder::default();
+        let this =
+            DataType::from_descriptor(mstr::from_literal("Ljava/lang/Object;")).expect("bad type");
+        code.load(&this, 0);
+        let constructor = writer.pool().method_ref(super_class, b"<init>", b"()V");
+        code.invoke(Opcode::Invokespecial, constructor, b"()V");
+        code.return_value(&ReturnType::Void);
+        writer.add_method(MethodAccessFlags::PUBLIC, b"<init>", b"()V", code);
+
+        if let Some(level) = level {
+            let mut code = CodeBuilder::default();
+            code.ldc(writer.pool().int(level));
+            code.return_value(&ReturnType::Returns(DataType::Primitive(
+                PrimitiveDataType::Int,
+            )));
+            writer.add_method(MethodAccessFlags::empty(), b"level", b"()I", code);
+        }
+
+        writer.finish()
+    }
+
+    #[test]
+    fn dispatch() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Dispatch");
+        for (name, expected) in [
+            ("OVERRIDE", 103),
+            ("INHERITED", 10),
+            ("SUPER_CALL", 3),
+            ("INTERFACE", 4135),
+            ("DEFAULT", 4),
+            ("SPECIFIC_DEFAULT", 0),
+            ("CLASS_OVER_DEFAULT", 3),
+            ("ABSTRACT_CLASS", 55),
+            ("OBJECT_METHOD", 99),
+        ] {
+            assert_eq!(
+                get_static_field(&cls, name, "I").as_int(),
+                Some(expected),
+                "{}",
+                name
+            );
+        }
+
+        let thread = thread::get();
+        let define = |name: &'static str, bytes: Vec<u8>| {
+            thread
+                .global()
+                .class_loader()
+                .define_class(
+                    mstr::from_literal(name),
+                    &bytes,
+                    WhichLoader::Bootstrap,
+                    None,
+                )
+                .unwrap_or_else(|err| panic!("failed to define class: {}", err.symbol()))
+        };
+        let instantiate = |cls: VmRef<Class>| {
+            thread
+                .exec_helper()
+                .instantiate_and_invoke_constructor(cls, "()V", std::iter::empty())
+                .expect("failed to instantiate")
+        };
+        let call = |name: &'static str, desc: &'static str, obj: VmRef<Object>| {
+            let method = cls
+                .find_callable_method(
+                    mstr::from_literal(name),
+                    mstr::from_literal(desc),
+                    MethodAccessFlags::STATIC,
+                )
+                .expect("missing method");
+
+            thread
+                .exec_helper()
+                .invoke_static(method, std::iter::once(DataValue::Reference(obj)))
+                .map_err(|err| err.symbol())
+        };
+
+        // more than one maximally specific default method
+        let conflicted = define(
+            "Dispatch$Conflicted",
+            generate_dispatch_class(
+                b"Dispatch$Conflicted",
+                b"java/lang/Object",
+                &[b"Dispatch$Left", b"Dispatch$Right"],
+                None,
+            ),
+        );
+        assert_eq!(
+            call("pick", "(LDispatch$Left;)I", instantiate(conflicted)),
+            Err("java/lang/IncompatibleClassChangeError")
+        );
+
+        // Level3 narrows level() to package private, so Level4 in another runtime package only
+        // overrides Level2's, and Level1's through that, not Level3's
+        let level3 = define(
+            "Dispatch$Level3",
+            generate_dispatch_class(b"Dispatch$Level3", b"Dispatch$Level2", &[], Some(3)),
+        );
+        let loader = thread
+            .exec_helper()
+            .instantiate_and_invoke_constructor("Dispatch$Loader", "()V", std::iter::empty())
+            .expect("failed to create loader");
+        let level4 = define_test_class("Dispatch$Level4", WhichLoader::User(loader));
+        assert!(!level4.is_same_runtime_package(&level3));
+
+        let level3 = instantiate(level3);
+        let level4 = instantiate(level4);
+        assert_eq!(
+            call("level", "(LDispatch$Level1;)I", level3),
+            Ok(Some(DataValue::Int(3)))
+        );
+        assert_eq!(
+            call("narrowedLevel", "(LDispatch$Level3;)I", level4.clone()),
+            Ok(Some(DataValue::Int(3)))
+        );
+        assert_eq!(
+            call("level", "(LDispatch$Level1;)I", level4.clone()),
+            Ok(Some(DataValue::Int(4)))
+        );
+        assert_eq!(
+            call("publicLevel", "(LDispatch$Level2;)I", level4),
+            Ok(Some(DataValue::Int(4)))
+        );
+    }
+
+    #[test]
+    fn unloading() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let thread = thread::get();
+        let class_loader = thread.global().class_loader();
+        let loader = thread
+            .exec_helper()
+            .instantiate_and_invoke_constructor("Unloading$Loader", "()V", std::iter::empty())
+            .expect("failed to create loader");
+
+        let victim = define_test_class("Unloading$Victim", WhichLoader::User(loader.clone()));
+        victim.ensure_init().expect("init failed");
+
+        // the bootstrap class was loaded through the user loader
+        assert_eq!(get_static_field(&victim, "VALUE", "I"), DataValue::Int(42));
+        let base = class_loader
+            .find_loaded(
+                mstr::from_literal("Unloading"),
+                &WhichLoader::User(loader.clone()),
+            )
+            .expect("user loader is not an initiating loader");
+        assert!(matches!(base.loader(), WhichLoader::Bootstrap));
+
+        // reachable through the loader and class
+        assert_eq!(class_loader.unload_unreachable_classes(), 0);
+        let weak_victim = Arc::downgrade(&victim);
+        let weak_loader = Arc::downgrade(&loader);
+        drop(victim);
+        assert_eq!(class_loader.unload_unreachable_classes(), 0);
+        assert!(weak_victim.upgrade().is_some());
+
+        // nothing left, including the static cycle through INSTANCE
+        drop(loader);
+        assert_eq!(class_loader.unload_unreachable_classes(), 1);
+        assert!(weak_victim.upgrade().is_none());
+        assert!(weak_loader.upgrade().is_none());
+
+        // bootstrap classes are untouched
+        assert!(class_loader
+            .find_loaded(mstr::from_literal("Unloading"), &WhichLoader::Bootstrap)
+            .is_some());
+    }
+
+    #[test]
+    fn access_checks() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let thread = thread::get();
+        let call = |cls: &VmRef<Class>, name: &'static str| {
+            let method = cls
+                .find_callable_method(
+                    mstr::from_literal(name),
+                    mstr::from_literal(if name == "newPackagePrivate" {
+                        "()Ljava/lang/Object;"
+                    } else {
+                        "()I"
+                    }),
+                    MethodAccessFlags::STATIC,
+                )
+                .expect("missing method");
+
+            thread
+                .exec_helper()
+                .invoke_static(method, std::iter::empty())
+                .map_err(|err| err.symbol())
+        };
+
+        // same runtime package and nest
+        let caller = get_class("Access$Caller");
+        assert_eq!(call(&caller, "callOpen"), Ok(Some(DataValue::Int(1))));
+        assert_eq!(
+            call(&caller, "callPackagePrivate"),
+            Ok(Some(DataValue::Int(2)))
+        );
+        assert_eq!(call(&caller, "callInherited"), Ok(Some(DataValue::Int(3))));
+        assert_eq!(call(&caller, "callPrivate"), Ok(Some(DataValue::Int(4))));
+        assert_eq!(
+            call(&caller, "getPackageField"),
+            Ok(Some(DataValue::Int(5)))
+        );
+        assert!(call(&caller, "newPackagePrivate").is_ok());
+
+        // same package name but a different loader, so only public and protected are accessible
+        let loader = thread
+            .exec_helper()
+            .instantiate_and_invoke_constructor("Access$Loader", "()V", std::iter::empty())
+            .expect("failed to create loader");
+        let outsider = define_test_class("Access$Caller", WhichLoader::User(loader));
+        assert!(!outsider.is_same_runtime_package(&caller));
+        assert!(!outsider.is_nestmate_of(&get_class("Access")));
+
+        let illegal = Err("java/lang/IllegalAccessError");
+        assert_eq!(call(&outsider, "callOpen"), Ok(Some(DataValue::Int(1))));
+        assert_eq!(call(&outsider, "callPackagePrivate"), illegal);
+        assert_eq!(
+            call(&outsider, "callInherited"),
+            Ok(Some(DataValue::Int(3)))
+        );
+        assert_eq!(call(&outsider, "callPrivate"), illegal);
+        assert_eq!(call(&outsider, "getPackageField"), illegal);
+        assert_eq!(
+            call(&outsider, "newPackagePrivate").err(),
+            Some("java/lang/IllegalAccessError")
+        );
+
+        // the failed resolution is cached
+        assert_eq!(call(&outsider, "callPrivate"), illegal);
+    }
+
+    /// Class with a static method per case, each loading method handle, method type or dynamic
+    /// constants and invoking them
+    fn generate_handles_class() -> Vec<u8> {
+        use crate::constant_pool::ReferenceKind;
+        use crate::interpreter::writer::{ClassWriter, CodeBuilder, ConstantPoolBuilder};
+        use crate::interpreter::Opcode;
+
+        const HANDLE: &[u8] = b"java/lang/invoke/MethodHandle";
+        const TYPE: &[u8] = b"java/lang/invoke/MethodType";
+        const BOOTSTRAPS: &[u8] = b"java/lang/invoke/ConstantBootstraps";
+
+        let mut writer = ClassWriter::new(
+            b"Handles",
+            b"java/lang/Object",
+            ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        );
+
+        let pool = writer.pool();
+        fn handle(
+            pool: &mut ConstantPoolBuilder,
+            kind: ReferenceKind,
+            class: &[u8],
+            name: &[u8],
+            desc: &[u8],
+        ) -> u16 {
+            let member = if kind == ReferenceKind::GetStatic {
+                pool.field_ref(class, name, desc)
+            } else {
+                pool.method_ref(class, name, desc)
+            };
+            pool.method_handle(kind, member)
+        }
+        let parse_int = handle(
+            pool,
+            ReferenceKind::InvokeStatic,
+            b"java/lang/Integer",
+            b"parseInt",
+            b"(Ljava/lang/String;)I",
+        );
+        let concat = handle(
+            pool,
+            ReferenceKind::InvokeVirtual,
+            b"java/lang/String",
+            b"concat",
+            b"(Ljava/lang/String;)Ljava/lang/String;",
+        );
+        let new_builder = handle(
+            pool,
+            ReferenceKind::NewInvokeSpecial,
+            b"java/lang/StringBuilder",
+            b"<init>",
+            b"(Ljava/lang/String;)V",
+        );
+        let max_value = handle(
+            pool,
+            ReferenceKind::GetStatic,
+            b"java/lang/Integer",
+            b"MAX_VALUE",
+            b"I",
+        );
+
+        // ConstantBootstraps methods are recognised rather than run
+        let bootstrap_args =
+            b"Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/Class;";
+        let bootstrap = |writer: &mut ClassWriter, name: &[u8], extra: &[u8], args: &[u16]| {
+            let desc = [&b"("[..], bootstrap_args, extra, b")Ljava/lang/Object;"].concat();
+            let handle = handle(
+                writer.pool(),
+                ReferenceKind::InvokeStatic,
+                BOOTSTRAPS,
+                name,
+                &desc,
+            );
+            writer.add_bootstrap_method(handle, args)
+        };
+        let ninety_nine = writer.pool().string(b"99");
+        let integer = writer.pool().class(b"java/lang/Integer");
+        let invoke = bootstrap(
+            &mut writer,
+            b"invoke",
+            b"Ljava/lang/invoke/MethodHandle;[Ljava/lang/Object;",
+            &[parse_int, ninety_nine],
+        );
+        let static_final = bootstrap(
+            &mut writer,
+            b"getStaticFinal",
+            b"Ljava/lang/Class;",
+            &[integer],
+        );
+        let primitive = bootstrap(&mut writer, b"primitiveClass", b"", &[]);
+        let null = bootstrap(&mut writer, b"nullConstant", b"", &[]);
+
+        enum Op<'a> {
+            Ldc(u16),
+            String(&'a [u8]),
+            Invoke(Opcode, &'a [u8], &'a [u8], &'a [u8]),
+        }
+        use Op::*;
+
+        let exact = |desc| Invoke(Opcode::Invokevirtual, HANDLE, b"invokeExact", desc);
+        let inexact = |desc| Invoke(Opcode::Invokevirtual, HANDLE, b"invoke", desc);
+        let to_descriptor = Invoke(
+            Opcode::Invokevirtual,
+            TYPE,
+            b"toMethodDescriptorString",
+            b"()Ljava/lang/String;",
+        );
+
+        let method_type = writer.pool().method_type(b"(I)J");
+        let condy_invoke = writer.pool().dynamic(invoke, b"_", b"I");
+        let condy_static_final = writer.pool().dynamic(static_final, b"MAX_VALUE", b"I");
+        let condy_primitive = writer.pool().dynamic(primitive, b"J", b"Ljava/lang/Class;");
+        let condy_null = writer.pool().dynamic(null, b"_", b"Ljava/lang/Object;");
+
+        let methods: Vec<(&[u8], &[u8], Vec<Op>)> = vec![
+            (
+                b"exact",
+                b"I",
+                vec![
+                    Ldc(parse_int),
+                    String(b"42"),
+                    exact(b"(Ljava/lang/String;)I"),
+                ],
+            ),
+            (
+                b"converted",
+                b"J",
+                vec![
+                    Ldc(parse_int),
+                    String(b"7"),
+                    inexact(b"(Ljava/lang/Object;)J"),
+                ],
+            ),
+            (
+                b"virtual",
+                b"Ljava/lang/String;",
+                vec![
+                    Ldc(concat),
+                    String(b"method"),
+                    String(b"handle"),
+                    exact(b"(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;"),
+                ],
+            ),
+            (
+                b"constructor",
+                b"Ljava/lang/String;",
+                vec![
+                    Ldc(new_builder),
+                    String(b"built"),
+                    exact(b"(Ljava/lang/String;)Ljava/lang/StringBuilder;"),
+                    Invoke(
+                        Opcode::Invokevirtual,
+                        b"java/lang/StringBuilder",
+                        b"toString",
+                        b"()Ljava/lang/String;",
+                    ),
+                ],
+            ),
+            (b"field", b"I", vec![Ldc(max_value), exact(b"()I")]),
+            (
+                b"handleType",
+                b"Ljava/lang/String;",
+                vec![
+                    Ldc(parse_int),
+                    Invoke(
+                        Opcode::Invokevirtual,
+                        HANDLE,
+                        b"type",
+                        b"()Ljava/lang/invoke/MethodType;",
+                    ),
+                    to_descriptor,
+                ],
+            ),
+            (
+                b"methodType",
+                b"Ljava/lang/String;",
+                vec![
+                    Ldc(method_type),
+                    Invoke(
+                        Opcode::Invokevirtual,
+                        TYPE,
+                        b"toMethodDescriptorString",
+                        b"()Ljava/lang/String;",
+                    ),
+                ],
+            ),
+            (
+                b"wrongExactType",
+                b"I",
+                vec![
+                    Ldc(parse_int),
+                    String(b"1"),
+                    exact(b"(Ljava/lang/Object;)I"),
+                ],
+            ),
+            (b"wrongArity", b"I", vec![Ldc(parse_int), inexact(b"()I")]),
+            (b"condyInvoke", b"I", vec![Ldc(condy_invoke)]),
+            (b"condyStaticFinal", b"I", vec![Ldc(condy_static_final)]),
+            (
+                b"condyPrimitive",
+                b"Ljava/lang/Class;",
+                vec![Ldc(condy_primitive)],
+            ),
+            (b"condyNull", b"Ljava/lang/Object;", vec![Ldc(condy_null)]),
+        ];
+
+        for (name, ret, ops) in methods {
+            let mut code = CodeBuilder::default();
+            for op in ops {
+                match op {
+                    Ldc(constant) => code.ldc(constant),
+                    String(s) => {
+                        let constant = writer.pool().string(s);
+                        code.ldc(constant)
+                    }
+                    Invoke(opcode, class, name, desc) => {
+                        let method = writer.pool().method_ref(class, name, desc);
+                        code.invoke(opcode, method, desc)
+                    }
+                }
+            }
+
+            let ret_type = DataType::from_descriptor(mstr::from_mutf8(ret)).expect("bad type");
+            code.return_value(&ReturnType::Returns(ret_type));
+            let desc = [&b"()"[..], ret].concat();
+            writer.add_method(
+                MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+                name,
+                &desc,
+                code,
+            );
+        }
+
+        writer.finish()
+    }
+
+    #[test]
+    fn method_handle_constants() {
+        test_logging();
+        let _jvm = test_jvm();
+
+        let thread = thread::get();
+        let class_loader = thread.global().class_loader();
+        let cls = class_loader
+            .define_class(
+                mstr::from_literal("Handles"),
+                &generate_handles_class(),
+                WhichLoader::Bootstrap,
+                None,
+            )
+            .unwrap_or_else(|err| panic!("failed to define class: {}", err.symbol()));
+
+        let call = |name: &'static str, desc: &'static str| {
+            let method = cls
+                .find_callable_method(
+                    mstr::from_literal(name),
+                    mstr::from_literal(desc),
+                    MethodAccessFlags::STATIC,
+                )
+                .expect("missing method");
+
+            thread
+                .exec_helper()
+                .invoke_static(method, std::iter::empty())
+                .map(|value| value.expect("no return value"))
+                .map_err(|err| err.symbol())
+        };
+        let string = |value: Result<DataValue, &'static str>| {
+            value.map(|value| {
+                value
+                    .as_reference()
+                    .and_then(|s| s.string_value_utf8())
+                    .expect("not a string")
+            })
+        };
+
+        // invokeExact and invoke of direct handles
+        assert_eq!(call("exact", "()I"), Ok(DataValue::Int(42)));
+        assert_eq!(call("converted", "()J"), Ok(DataValue::Long(7)));
+        assert_eq!(
+            string(call("virtual", "()Ljava/lang/String;")),
+            Ok("methodhandle".to_owned())
+        );
+        assert_eq!(
+            string(call("constructor", "()Ljava/lang/String;")),
+            Ok("built".to_owned())
+        );
+        assert_eq!(call("field", "()I"), Ok(DataValue::Int(i32::MAX)));
+        assert_eq!(
+            string(call("handleType", "()Ljava/lang/String;")),
+            Ok("(Ljava/lang/String;)I".to_owned())
+        );
+        assert_eq!(
+            string(call("methodType", "()Ljava/lang/String;")),
+            Ok("(I)J".to_owned())
+        );
+
+        let wrong_type = Err("java/lang/invoke/WrongMethodTypeException");
+        assert_eq!(call("wrongExactType", "()I"), wrong_type);
+        assert_eq!(call("wrongArity", "()I"), wrong_type);
+
+        // dynamically computed constants from ConstantBootstraps
+        assert_eq!(call("condyInvoke", "()I"), Ok(DataValue::Int(99)));
+        assert_eq!(
+            call("condyStaticFinal", "()I"),
+            Ok(DataValue::Int(i32::MAX))
+        );
+        let long_class = class_loader.get_primitive(PrimitiveDataType::Long);
+        let primitive = call("condyPrimitive", "()Ljava/lang/Class;").expect("failed");
+        assert!(primitive
+            .as_reference()
+            .is_some_and(|obj| vmref_eq(obj, long_class.class_object())));
+        let null = call("condyNull", "()Ljava/lang/Object;").expect("failed");
+        assert!(null.as_reference().is_some_and(|obj| obj.is_null()));
+    }
+
+    /// Times the interpreter on method calls and arithmetic loops. Only the timings are logged,
+    /// compare them against a build of the parent commit on the same machine. Run with
+    /// `cargo test --release benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn benchmark() {
+        let _ = env_logger::builder()
+            .is_test(true)
+            .filter_level(LevelFilter::Warn)
+            .filter_module(module_path!(), LevelFilter::Info)
+            .try_init();
+        let _jvm = test_jvm();
+
+        let cls = get_class("Benchmark");
+        let method = cls
+            .find_callable_method(
+                mstr::from_literal("run"),
+                mstr::from_literal("()I"),
+                MethodAccessFlags::STATIC,
+            )
+            .expect("missing method");
+
+        let thread = thread::get();
+        for _ in 0..5 {
+            let start = Instant::now();
+            let result = thread
+                .exec_helper()
+                .invoke_static(method.clone(), std::iter::empty())
+                .map_err(|err| err.symbol());
+            let elapsed = start.elapsed();
+
+            assert_eq!(result, Ok(Some(DataValue::Int(2939158))));
+            info!("Benchmark.run() took {:?}", elapsed);
+        }
+    }
 }
@@ -0,0 +1,291 @@
+//! Method tables for selecting the method to invoke on a receiver (JVMS 5.4.6), built when a
+//! class is linked.
+//!
+//! Every instance method that can be overridden gets an index when its class is linked. Methods
+//! declared in classes index the vtable, which starts as a copy of the super class's with
+//! overridden entries replaced and new methods appended, as well as package private methods that
+//! override others. Methods declared in interfaces index the itable for that interface in each
+//! implementing class. Interface methods without an implementation in the class hierarchy
+//! (miranda methods) are filled with the maximally specific default method, or left as the
+//! abstract method so invoking it throws `AbstractMethodError`. If there is more than one, the
+//! entry is ambiguous and invoking it throws `IncompatibleClassChangeError`.
+
+use cafebabe::mutf8::mstr;
+use cafebabe::{AccessFlags, MethodAccessFlags};
+use itertools::Itertools;
+
+use crate::alloc::{vmref_eq, VmRef};
+use crate::class::class::SuperIteration;
+use crate::class::{Class, Method, WhichLoader};
+use crate::error::{Throwables, VmResult};
+
+#[derive(Default)]
+pub(in crate::class) struct MethodTables {
+    vtable: Box<[VmRef<Method>]>,
+    itables: Box<[ITable]>,
+}
+
+struct ITable {
+    interface: VmRef<Class>,
+    /// Indexed by the index of the interface's methods
+    methods: Box<[Selected]>,
+}
+
+/// Itable entry for an interface method
+enum Selected {
+    Method(VmRef<Method>),
+    /// More than one maximally specific default method
+    Ambiguous(Box<[VmRef<Method>]>),
+}
+
+/// Instance methods that are selected from the receiver class when invoked
+fn is_dispatched(method: &Method) -> bool {
+    let flags = method.flags();
+    !flags.is_static() && !flags.is_private() && !method.is_instance_initializer()
+}
+
+fn is_public_or_protected(method: &Method) -> bool {
+    method
+        .flags()
+        .intersects(MethodAccessFlags::PUBLIC | MethodAccessFlags::PROTECTED)
+}
+
+/// If a method with the given name and descriptor declared in a class of the given runtime
+/// package overrides `other` directly (JVMS 5.4.5(a))
+fn overrides(name: &mstr, desc: &mstr, package: (&mstr, &WhichLoader), other: &Method) -> bool {
+    let other_class = other.class();
+    other.name() == name
+        && other.descriptor() == desc
+        && (is_public_or_protected(other)
+            || (other_class.package_name() == package.0 && other_class.loader() == package.1))
+}
+
+/// If a method with the given name and descriptor declared in a class of the given runtime
+/// package overrides the method in a vtable slot of `class`. Each method in a slot overrides the
+/// ones it replaced in super classes, so overriding any of them overrides the method first in the
+/// slot transitively (JVMS 5.4.5(b)), even if the method now in the slot is package private in
+/// another runtime package
+fn overrides_slot(
+    name: &mstr,
+    desc: &mstr,
+    package: (&mstr, &WhichLoader),
+    class: &VmRef<Class>,
+    idx: usize,
+) -> bool {
+    let mut class = Some(class);
+    while let Some(cls) = class {
+        match cls.method_tables().vtable.get(idx) {
+            Some(m) if overrides(name, desc, package, m) => return true,
+            Some(_) => class = cls.super_class(),
+            None => break,
+        }
+    }
+
+    false
+}
+
+/// Table index of each of the methods declared by a class, computed before the class is created
+/// as methods can't be modified once shared
+pub(in crate::class) fn method_indices(
+    super_class: Option<&VmRef<Class>>,
+    is_interface: bool,
+    package: (&mstr, &WhichLoader),
+    methods: &[VmRef<Method>],
+) -> Vec<Option<u32>> {
+    let super_class = super_class.filter(|_| !is_interface);
+    let super_len = super_class.map_or(0, |cls| cls.method_tables().vtable.len());
+
+    let mut next = super_len;
+    methods
+        .iter()
+        .map(|method| {
+            if !is_dispatched(method) {
+                return None;
+            }
+
+            // a package private method gets its own slot even if it overrides others, as methods
+            // in other runtime packages can override those without overriding it
+            let overridden = super_class
+                .filter(|_| is_public_or_protected(method))
+                .and_then(|cls| {
+                    (0..super_len).position(|idx| {
+                        overrides_slot(method.name(), method.descriptor(), package, cls, idx)
+                    })
+                });
+
+            let idx = overridden.unwrap_or_else(|| {
+                next += 1;
+                next - 1
+            });
+            Some(idx as u32)
+        })
+        .collect()
+}
+
+impl MethodTables {
+    /// Builds the tables of a class whose methods have been assigned indices by
+    /// [method_indices]. Interfaces have no tables as they are never the class of a receiver
+    pub(in crate::class) fn build(class: &VmRef<Class>) -> Self {
+        if class.is_interface() {
+            return Self::default();
+        }
+
+        let package = (class.package_name(), class.loader());
+        let super_class = class.super_class();
+        let mut vtable = match super_class {
+            Some(super_class) => super_class.method_tables().vtable.to_vec(),
+            None => Vec::new(),
+        };
+
+        for method in class.methods().iter().filter(|m| is_dispatched(m)) {
+            let idx = method
+                .vtable_index()
+                .expect("dispatched method has an index");
+
+            // a method can override the methods in more than one slot, e.g. a package private one
+            // and a public one that doesn't override it
+            if let Some(super_class) = super_class {
+                for (slot, entry) in vtable.iter_mut().enumerate() {
+                    if overrides_slot(
+                        method.name(),
+                        method.descriptor(),
+                        package,
+                        super_class,
+                        slot,
+                    ) {
+                        *entry = method.clone();
+                    }
+                }
+            }
+
+            if idx >= vtable.len() {
+                debug_assert_eq!(idx, vtable.len());
+                vtable.push(method.clone());
+            }
+        }
+
+        // every superinterface, including those of super classes
+        let mut interfaces = Vec::<VmRef<Class>>::new();
+        class.with_supers(|cls| {
+            if cls.is_interface() && !interfaces.iter().any(|i| vmref_eq(i, cls)) {
+                interfaces.push(cls.clone());
+            }
+            SuperIteration::KeepGoing
+        });
+
+        let itables = interfaces
+            .iter()
+            .map(|iface| ITable {
+                interface: iface.clone(),
+                methods: iface
+                    .methods()
+                    .iter()
+                    .filter(|m| is_dispatched(m))
+                    .map(|m| select_for_interface(&vtable, &interfaces, m))
+                    .collect(),
+            })
+            .collect();
+
+        MethodTables {
+            vtable: vtable.into_boxed_slice(),
+            itables,
+        }
+    }
+
+    /// Method to invoke for the given resolved method, or None if the resolved method is not
+    /// dispatched or not declared in a super of this class. Fails with
+    /// `IncompatibleClassChangeError` if there's no single default method to select
+    pub(in crate::class) fn select(&self, resolved: &Method) -> VmResult<Option<&VmRef<Method>>> {
+        let idx = match resolved.vtable_index() {
+            Some(idx) => idx,
+            None => return Ok(None),
+        };
+        let declaring = resolved.class();
+
+        if !declaring.is_interface() {
+            return Ok(self.vtable.get(idx));
+        }
+
+        let entry = self
+            .itables
+            .iter()
+            .find(|t| vmref_eq(&t.interface, declaring))
+            .and_then(|t| t.methods.get(idx));
+
+        match entry {
+            None => Ok(None),
+            Some(Selected::Method(method)) => Ok(Some(method)),
+            Some(Selected::Ambiguous(defaults)) => {
+                Err(Throwables::incompatible_class_change(format_args!(
+                    "Conflicting default methods: {}",
+                    defaults.iter().join(", ")
+                )))
+            }
+        }
+    }
+
+    /// All methods referenced by the tables, for class unloading
+    pub(in crate::class) fn methods(&self) -> impl Iterator<Item = &VmRef<Method>> {
+        self.vtable
+            .iter()
+            .chain(self.itables.iter().flat_map(|t| t.methods.iter()).flat_map(
+                |entry| match entry {
+                    Selected::Method(method) => std::slice::from_ref(method),
+                    Selected::Ambiguous(defaults) => defaults,
+                },
+            ))
+    }
+
+    /// All interfaces referenced by the tables, for class unloading
+    pub(in crate::class) fn interfaces(&self) -> impl Iterator<Item = &VmRef<Class>> {
+        self.itables.iter().map(|t| &t.interface)
+    }
+}
+
+/// Implementation of an interface method in a class: a method declared in the class hierarchy,
+/// otherwise the only non-abstract one of the maximally specific superinterface methods (JVMS
+/// 5.4.6). If they're all abstract, the interface method itself
+fn select_for_interface(
+    vtable: &[VmRef<Method>],
+    interfaces: &[VmRef<Class>],
+    method: &VmRef<Method>,
+) -> Selected {
+    let matches =
+        |m: &&VmRef<Method>| m.name() == method.name() && m.descriptor() == method.descriptor();
+
+    // interface methods are public so any class method overrides them, later entries are
+    // declared in more specific classes
+    if let Some(found) = vtable.iter().rev().find(matches) {
+        return Selected::Method(found.clone());
+    }
+
+    let candidates = interfaces
+        .iter()
+        .flat_map(|iface| {
+            iface
+                .methods()
+                .iter()
+                .filter(|m| matches(m) && is_dispatched(m))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // abstract methods in subinterfaces count too, as they re-abstract the defaults they override
+    let mut defaults = candidates
+        .iter()
+        .filter(|m| {
+            !candidates
+                .iter()
+                .any(|other| !vmref_eq(other, m) && other.class().is_instance_of(m.class()))
+        })
+        .filter(|m| !m.flags().contains(MethodAccessFlags::ABSTRACT))
+        .map(|m| (*m).clone())
+        .collect::<Vec<_>>();
+
+    match defaults.len() {
+        0 => Selected::Method(method.clone()),
+        1 => Selected::Method(defaults.remove(0)),
+        _ => Selected::Ambiguous(defaults.into_boxed_slice()),
+    }
+}
@@ -1,36 +1,53 @@
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::once;
+use std::path::Path;
 use std::sync::Arc;
 use std::thread::ThreadId;
+use std::time::Instant;
 
 use log::*;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use strum_macros::EnumDiscriminants;
 
 use cafebabe::mutf8::{mstr, StrExt};
-use cafebabe::{ClassError, MethodAccessFlags};
+use cafebabe::MethodAccessFlags;
 
 use crate::alloc::{vmref_ptr, InternedString, VmRef};
-use crate::class::class::Class;
+use crate::class::class::{Class, ClassDefinition};
 use crate::class::object::Object;
+use crate::class::package::{Manifests, Packages};
+use crate::class::prefetch::{self, ParsedClass};
 use crate::class::ClassType;
 use crate::classpath::{ClassPath, FindClassError};
+use crate::debug::{ClassEvent, ClassEventKind, ClassEventLog};
 use crate::error::{Throwables, VmResult};
 use crate::interpreter::Frame;
+use crate::share::{ClassSharing, ShareError};
 use crate::thread;
-use crate::types::{ArrayType, PrimitiveDataType};
+use crate::types::{ArrayType, DataValue, PrimitiveDataType};
+
+/// (class name, initiating loader) -> state
+pub(in crate::class) type LoadedClasses = HashMap<(InternedString, WhichLoader), LoadState>;
 
 pub struct ClassLoader {
-    classes: RwLock<Vec<(InternedString, WhichLoader, LoadState)>>,
-    bootclasspath: Arc<ClassPath>,
+    pub(in crate::class) classes: RwLock<LoadedClasses>,
+    pub(in crate::class) packages: RwLock<Packages>,
+    pub(in crate::class) manifests: RwLock<Manifests>,
+    pub(in crate::class) bootclasspath: Arc<ClassPath>,
+    pub(in crate::class) sharing: ClassSharing,
+    /// Parsed bootstrap classes waiting to be linked, by name
+    pub(in crate::class) prefetched: Mutex<HashMap<String, ParsedClass>>,
     /// Indexed by PrimitiveDataType, initialised during bootstrap
     primitives: RefCell<Option<Box<[VmRef<Class>]>>>,
-
-    #[cfg(feature = "log-class-loading")]
-    logger: parking_lot::Mutex<crate::debug::ClassLoadGraph>,
+    class_log: Option<ClassEventLog>,
 }
 
 #[derive(Clone, Debug, EnumDiscriminants)]
-enum LoadState {
+pub(in crate::class) enum LoadState {
     Unloaded,
     Loading(ThreadId, WhichLoader),
     Loaded(ThreadId, VmRef<Class>),
@@ -44,27 +61,54 @@ pub enum WhichLoader {
 }
 
 impl ClassLoader {
-    pub fn new(bootclasspath: Arc<ClassPath>) -> Self {
+    pub fn new(
+        bootclasspath: Arc<ClassPath>,
+        sharing: ClassSharing,
+        class_log: Option<ClassEventLog>,
+    ) -> Self {
         ClassLoader {
             bootclasspath,
+            sharing,
+            prefetched: Default::default(),
             classes: Default::default(),
+            packages: Default::default(),
+            manifests: Default::default(),
             primitives: RefCell::default(),
-            #[cfg(feature = "log-class-loading")]
-            logger: parking_lot::Mutex::new(
-                crate::debug::ClassLoadGraph::with_file("/tmp/jvm-classes.dot")
-                    .expect("failed to make class logger"),
-            ),
+            class_log,
+        }
+    }
+
+    /// Records a class loading, linking or initialisation event if -verbose:class is enabled
+    pub(in crate::class) fn record_class_event(&self, event: ClassEvent) {
+        if let Some(log) = &self.class_log {
+            log.record(&event);
         }
     }
 
+    /// Terminates and flushes the class event log, if enabled. Called at shutdown as the global
+    /// class loader is never dropped
+    pub fn finish_class_log(&self) {
+        if let Some(log) = &self.class_log {
+            log.finish();
+        }
+    }
+
+    pub(in crate::class) fn is_class_log_enabled(&self) -> bool {
+        self.class_log.is_some()
+    }
+
     fn load_state(&self, class_name: &mstr, loader: &WhichLoader) -> LoadState {
+        // a class can't have been loaded if its name hasn't been interned yet
+        let class_name = match InternedString::lookup(class_name) {
+            Some(name) => name,
+            None => return LoadState::Unloaded,
+        };
+
+        // TODO avoid cloning the loader just for the lookup
         let guard = self.classes.read();
-        match guard
-            .iter()
-            .find(|(c, l, _)| l == loader && c.as_mstr() == class_name)
-        {
+        match guard.get(&(class_name, loader.clone())) {
             None => LoadState::Unloaded,
-            Some((_, _, state)) => state.clone(),
+            Some(state) => state.clone(),
         }
     }
 
@@ -75,57 +119,20 @@ impl ClassLoader {
             LoadStateDiscriminants::from(&state)
         );
 
+        let class_name = InternedString::intern(class_name);
         let mut guard = self.classes.write();
-        match guard
-            .iter_mut()
-            .find(|(c, l, _)| l == loader && c.as_mstr() == class_name)
-        {
-            Some((_, _, s)) => {
-                *s = state;
-            }
-            None => {
-                guard.push((class_name.to_owned(), loader.clone(), state));
-            }
-        }
+        guard.insert((class_name, loader.clone()), state);
     }
 
     // TODO types for str to differentiate java/lang/Object, java.lang.Object and descrptors e.g. Ljava/lang/Object;
 
-    /// Loads class file and links it
-    fn do_load(
-        &self,
-        class_name: &mstr,
-        bytes: &[u8],
-        loader: WhichLoader,
-    ) -> VmResult<VmRef<Class>> {
-        // TODO register class "package" with loader (https://docs.oracle.com/javase/specs/jvms/se11/html/jvms-5.html#jvms-5.3)
-
-        // load class
-        let loaded = match cafebabe::load_from_buffer(bytes) {
-            Ok(cls) => cls,
-            Err(err) => {
-                // TODO actually instantiate exceptions
-                warn!("class loading failed: {}", err);
-                let exc = match err {
-                    ClassError::Unsupported(_) => Throwables::UnsupportedClassVersionError,
-                    ClassError::Io(_) => Throwables::Other("IOError"),
-                    _ => Throwables::ClassFormatError,
-                };
-                return Err(exc);
-            }
-        };
-
-        // link loaded .class
-        Class::link(class_name, loaded, loader, self)
-    }
-
     /// Loads and creates Class object with ClassState::Uninitialised
     /// TODO use a FnOnce() -> WhichLoader or &WhichLoader to avoid many useless clones
     fn do_load_class(
         &self,
         class_name: &mstr,
         mut loader: WhichLoader,
-        _cause: Option<&mstr>,
+        cause: Option<&mstr>,
     ) -> VmResult<VmRef<Class>> {
         // TODO run user classloader first
         // TODO array classes are treated differently
@@ -137,11 +144,11 @@ impl ClassLoader {
             loader = WhichLoader::Bootstrap;
         }
 
-        if let WhichLoader::User(_classloader) = &loader {
+        if let WhichLoader::User(java_loader) = &loader {
             match array_type {
                 None => {
                     // run user classloader instead of bootstrap
-                    todo!("run user classloader")
+                    return self.load_with_user_loader(class_name, &loader, java_loader);
                 }
                 Some(ArrayType::Reference(elem)) => {
                     // load element class first
@@ -171,8 +178,6 @@ impl ClassLoader {
         }
 
         debug!("loading class {:?}", class_name);
-        #[cfg(feature = "log-class-loading")]
-        self.logger.lock().register_class_load(class_name, _cause);
 
         // loading is required, update shared state
         // TODO record that this loader is an initiating loader
@@ -183,15 +188,75 @@ impl ClassLoader {
         );
 
         // load and link
+        let start = Instant::now();
         let link_result = match array_type {
             None => {
-                // non-array class
-                let class_bytes = self.find_boot_class(class_name.to_utf8().as_ref())?;
-                self.do_load(class_name, &class_bytes, loader.clone())
+                // user loaders define their classes through define_class, so only the bootstrap
+                // loader gets here and is the defining loader recorded below
+                debug_assert!(matches!(loader, WhichLoader::Bootstrap));
+
+                // non-array class, possibly already parsed during startup
+                let parsed = match self.take_prefetched(class_name) {
+                    Some(prefetched) => Ok(prefetched),
+                    None => prefetch::parse_boot_class(
+                        &self.bootclasspath,
+                        &self.sharing,
+                        class_name.to_utf8().as_ref(),
+                    ),
+                };
+
+                self.record_class_event(ClassEvent {
+                    kind: ClassEventKind::Load,
+                    class: class_name,
+                    loader: &loader,
+                    source: parsed.as_ref().ok().map(ParsedClass::source),
+                    initiator: cause,
+                    duration: parsed
+                        .as_ref()
+                        .map_or_else(|_| start.elapsed(), ParsedClass::load_time),
+                    failed: parsed.is_err(),
+                });
+
+                parsed.and_then(|parsed| {
+                    let package =
+                        self.define_package(class_name, &loader, Some(parsed.source()))?;
+
+                    // parsed class is consumed by linking
+                    let source = self
+                        .is_class_log_enabled()
+                        .then(|| parsed.source().to_owned());
+                    let start = Instant::now();
+                    let linked = parsed.link(loader.clone(), self);
+
+                    self.record_class_event(ClassEvent {
+                        kind: ClassEventKind::Link,
+                        class: class_name,
+                        loader: &loader,
+                        source: source.as_deref(),
+                        initiator: cause,
+                        duration: start.elapsed(),
+                        failed: linked.is_err(),
+                    });
+
+                    if linked.is_ok() {
+                        self.register_package(package, &loader);
+                    }
+                    linked
+                })
             }
             Some(array) => {
                 // array class
-                self.do_load_array_class(class_name, loader.clone(), array)
+                let array_cls = self.do_load_array_class(class_name, loader.clone(), array);
+                self.record_class_event(ClassEvent {
+                    kind: ClassEventKind::Load,
+                    class: class_name,
+                    loader: array_cls.as_ref().map_or(&loader, |cls| cls.loader()),
+                    source: None,
+                    initiator: cause,
+                    duration: start.elapsed(),
+                    failed: array_cls.is_err(),
+                });
+                array_cls
             }
         };
 
@@ -232,6 +297,149 @@ impl ClassLoader {
         self.do_load_class(class_name, loader, Some(cause))
     }
 
+    /// Loads a class by calling `loadClass` on a user class loader, unless it has already defined
+    /// the class or been the initiating loader of it. This runs Java code so must not be called
+    /// during an instruction, see [needs_java_loader](Self::needs_java_loader)
+    fn load_with_user_loader(
+        &self,
+        class_name: &mstr,
+        loader: &WhichLoader,
+        java_loader: &VmRef<Object>,
+    ) -> VmResult<VmRef<Class>> {
+        if let LoadState::Loaded(_, cls) = self.load_state(class_name, loader) {
+            return Ok(cls);
+        }
+
+        debug!("loading class {:?} with {:?}", class_name, loader);
+        let load_class = self
+            .get_bootstrap_class("java/lang/ClassLoader")
+            .find_callable_method(
+                "loadClass".as_mstr(),
+                "(Ljava/lang/String;)Ljava/lang/Class;".as_mstr(),
+                MethodAccessFlags::empty(),
+            )?;
+
+        let binary_name = Object::new_string_utf8(&class_name.to_utf8().replace('/', "."))?;
+        let not_found = || Throwables::NoClassDefFoundError(class_name.to_utf8().into_owned());
+        let thread = thread::get();
+        let class = match thread.exec_helper().invoke_virtual(
+            java_loader.clone(),
+            load_class,
+            once(DataValue::Reference(binary_name)),
+        ) {
+            Ok(Some(DataValue::Reference(obj))) if !obj.is_null() => {
+                obj.vmdata().0.expect("class object without class")
+            }
+            Ok(_) => return Err(not_found()),
+            Err(Throwables::Thrown(exc))
+                if exc.class_name == "java/lang/ClassNotFoundException" =>
+            {
+                return Err(not_found())
+            }
+            Err(err) => return Err(err),
+        };
+
+        // the user loader is an initiating loader of the class from now on
+        self.update_state(
+            class_name,
+            loader,
+            LoadState::Loaded(current_thread(), class.clone()),
+        );
+        Ok(class)
+    }
+
+    /// Finds a class that the given loader has defined or been the initiating loader of
+    pub fn find_loaded(&self, class_name: &mstr, loader: &WhichLoader) -> Option<VmRef<Class>> {
+        match self.load_state(class_name, loader) {
+            LoadState::Loaded(_, cls) => Some(cls),
+            _ => None,
+        }
+    }
+
+    /// If loading the given class with the given loader runs the loader's Java code, because it
+    /// is a user loader that hasn't loaded the class (or the array's element class) yet
+    pub fn needs_java_loader(&self, class_name: &mstr, loader: &WhichLoader) -> bool {
+        if !matches!(loader, WhichLoader::User(_)) {
+            return false;
+        }
+
+        match ArrayType::from_descriptor(class_name) {
+            Some(ArrayType::Primitive(_)) => false,
+            Some(ArrayType::Reference(elem)) => self.needs_java_loader(elem, loader),
+            None => !matches!(self.load_state(class_name, loader), LoadState::Loaded(..)),
+        }
+    }
+
+    /// Defines a class from the given class file bytes, e.g. one generated at runtime. It must not
+    /// already be loaded by the given loader. Initiator is the class that asked for the definition
+    pub fn define_class(
+        &self,
+        class_name: &mstr,
+        bytes: &[u8],
+        loader: WhichLoader,
+        initiator: Option<&mstr>,
+    ) -> VmResult<VmRef<Class>> {
+        if let LoadState::Loaded(..) | LoadState::Loading(..) = self.load_state(class_name, &loader)
+        {
+            return Err(Throwables::WithMessage(
+                "java/lang/LinkageError",
+                format!("duplicate class definition: {}", class_name),
+            ));
+        }
+
+        debug!("defining class {:?}", class_name);
+        self.update_state(
+            class_name,
+            &loader,
+            LoadState::Loading(current_thread(), loader.clone()),
+        );
+
+        let start = Instant::now();
+        let parsed = cafebabe::load_from_buffer(bytes)
+            .map_err(|e| {
+                warn!("failed to parse defined class {:?}: {}", class_name, e);
+                Throwables::ClassFormatError
+            })
+            .and_then(|class_file| ClassDefinition::parse(class_name, class_file, &loader));
+
+        // the given loader is the defining loader, whichever loader initiated loading
+        self.record_class_event(ClassEvent {
+            kind: ClassEventKind::Load,
+            class: class_name,
+            loader: &loader,
+            source: None,
+            initiator,
+            duration: start.elapsed(),
+            failed: parsed.is_err(),
+        });
+
+        let start = Instant::now();
+        let linked = parsed.and_then(|definition| {
+            self.check_defined_sealing(class_name, &loader)?;
+            let package = self.define_package(class_name, &loader, None)?;
+            let linked = Class::link(definition, loader.clone(), self)?;
+            self.register_package(package, &loader);
+            Ok(linked)
+        });
+
+        self.record_class_event(ClassEvent {
+            kind: ClassEventKind::Link,
+            class: class_name,
+            loader: &loader,
+            source: None,
+            initiator,
+            duration: start.elapsed(),
+            failed: linked.is_err(),
+        });
+
+        let state = match &linked {
+            Ok(class) => LoadState::Loaded(current_thread(), class.clone()),
+            Err(_) => LoadState::Failed,
+        };
+        self.update_state(class_name, &loader, state);
+        linked
+    }
+
     fn do_load_array_class(
         &self,
         name: &mstr,
@@ -257,13 +465,12 @@ impl ClassLoader {
         Ok(array_cls)
     }
 
-    fn find_boot_class(&self, class_name: &str) -> VmResult<Vec<u8>> {
-        trace!("looking for class {}", class_name);
-
-        match self.bootclasspath.find_and_load(class_name) {
-            Ok(bytes) => Ok(bytes),
-            Err(FindClassError::NotFound) => Err(Throwables::NoClassDefFoundError),
-            Err(FindClassError::Io(err)) => panic!("io error: {}", err), // TODO java.lang.IOError
+    /// Writes all boot classes loaded so far to the shared archive at `path`. Returns the number
+    /// of classes written
+    pub fn dump_shared_archive(&self, path: &Path) -> Result<usize, ShareError> {
+        match &self.sharing {
+            ClassSharing::Dumping(recorder) => recorder.write(path, &self.bootclasspath),
+            _ => Err(ShareError::NotDumping),
         }
     }
 
@@ -295,7 +502,7 @@ impl ClassLoader {
         let class_cls = self.get_bootstrap_class("java/lang/Class");
 
         let mut guard = self.classes.write();
-        for (_, loader, state) in guard.iter_mut() {
+        for ((_, loader), state) in guard.iter_mut() {
             debug_assert!(matches!(*loader, WhichLoader::Bootstrap));
 
             match state {
@@ -338,7 +545,7 @@ impl ClassLoader {
                 "reference array class expects non-primitive element type but got [{:?}",
                 p
             ),
-            ClassType::Array(_) => format!("[{}", element_type.name()),
+            ClassType::Array(..) => format!("[{}", element_type.name()),
             ClassType::Normal => format!("[L{};", element_type.name()),
         };
 
@@ -394,3 +601,67 @@ impl PartialEq for WhichLoader {
 }
 
 impl Eq for WhichLoader {}
+
+impl Display for WhichLoader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WhichLoader::Bootstrap => write!(f, "bootstrap"),
+            WhichLoader::User(obj) => match obj.class() {
+                Some(cls) => write!(f, "{}@{:#x}", cls.name(), vmref_ptr(obj)),
+                None => write!(f, "{:#x}", vmref_ptr(obj)),
+            },
+        }
+    }
+}
+
+impl Hash for WhichLoader {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            WhichLoader::Bootstrap => 0usize.hash(state),
+            WhichLoader::User(obj) => vmref_ptr(obj).hash(state),
+        }
+    }
+}
+
+/// Also returns the boot classpath entry the class was found in. Looks in the shared archive
+/// first if there is one, otherwise the boot classpath. Doesn't need the whole loader so can be
+/// used off the loading thread
+pub(in crate::class) fn find_boot_class<'a>(
+    bootclasspath: &'a ClassPath,
+    sharing: &'a ClassSharing,
+    class_name: &str,
+) -> VmResult<(Cow<'a, [u8]>, &'a Path)> {
+    trace!("looking for class {}", class_name);
+
+    if let ClassSharing::Mapped(archive) = sharing {
+        if let Some((bytes, source)) = archive.find(class_name) {
+            trace!("found class {} in shared archive", class_name);
+            return Ok((Cow::Borrowed(bytes), source));
+        }
+    }
+
+    match bootclasspath.find_and_load_with_source(class_name) {
+        Ok((bytes, source)) => {
+            if let ClassSharing::Dumping(recorder) = sharing {
+                recorder.record(class_name, source, &bytes);
+            }
+
+            Ok((Cow::Owned(bytes), source))
+        }
+        Err(FindClassError::NotFound) => {
+            // java.lang.invoke is missing from the classpath so is provided by the vm
+            match crate::interpreter::runtime_class(class_name) {
+                Some(bytes) => Ok((Cow::Owned(bytes), Path::new("<vm>"))),
+                None => Err(Throwables::NoClassDefFoundError(class_name.to_owned())),
+            }
+        }
+        Err(FindClassError::Io(err)) => {
+            // not remembered, so loading is attempted again on demand
+            warn!("failed to read class {}: {}", class_name, err);
+            Err(Throwables::WithMessage(
+                "java/io/IOError",
+                format!("{} ({})", class_name, err),
+            ))
+        }
+    }
+}
@@ -4,8 +4,14 @@ pub use class::{
 };
 pub use loader::{ClassLoader, WhichLoader};
 pub use object::{null, Object, ObjectStorage};
+pub use package::RuntimePackage;
 
 mod args;
 mod class;
+mod dispatch;
 mod loader;
 mod object;
+mod package;
+mod prefetch;
+mod resolve;
+mod unload;
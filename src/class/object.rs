@@ -76,13 +76,13 @@ impl Object {
     }
     pub(crate) fn new_array(array_cls: VmRef<Class>, len: usize) -> Self {
         let elem_cls = match array_cls.class_type() {
-            ClassType::Array(elem) => elem,
+            ClassType::Array(elem, _) => elem,
             _ => unreachable!("not an array class"),
         };
 
         let default_value = match elem_cls.class_type() {
             ClassType::Primitive(prim) => prim.default_value(),
-            ClassType::Normal | ClassType::Array(_) => DataValue::Reference(null()),
+            ClassType::Normal | ClassType::Array(..) => DataValue::Reference(null()),
         };
 
         Self::new_array_with_elements(array_cls, repeat_n(default_value, len))
@@ -92,7 +92,7 @@ impl Object {
         array_cls: VmRef<Class>,
         elems: impl ExactSizeIterator<Item = DataValue>,
     ) -> Self {
-        debug_assert!(matches!(array_cls.class_type(), ClassType::Array(_)));
+        debug_assert!(matches!(array_cls.class_type(), ClassType::Array(..)));
 
         let data: Box<[DataValue]> = elems.collect();
         Self::with_storage(array_cls, ObjectStorage::Array(Mutex::new(data)))
@@ -165,6 +165,10 @@ impl Object {
         self.monitor.enter()
     }
 
+    pub fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+
     pub fn fields(&self) -> Option<&FieldStorage> {
         match &self.storage {
             ObjectStorage::Fields(f) => Some(f),
@@ -235,6 +239,14 @@ impl Object {
         &self.storage
     }
 
+    /// Calls `f` on every field value or array element
+    pub(crate) fn for_each_value(&self, f: impl FnMut(&mut DataValue)) {
+        match &self.storage {
+            ObjectStorage::Fields(fields) => fields.for_each_value(f),
+            ObjectStorage::Array(array) => array.lock().iter_mut().for_each(f),
+        }
+    }
+
     /// Calculates and stores on first call
     pub fn identity_hashcode(self: &VmRef<Self>) -> i32 {
         let mut guard = self.hashcode.lock();
@@ -0,0 +1,288 @@
+//! Runtime packages, identified by package name and defining loader
+//! (https://docs.oracle.com/javase/specs/jvms/se11/html/jvms-5.html#jvms-5.3)
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::*;
+use parking_lot::Mutex;
+
+use cafebabe::mutf8::mstr;
+
+use crate::alloc::{InternedString, VmRef};
+use crate::class::{ClassLoader, Object, WhichLoader};
+use crate::error::{Throwables, VmResult};
+use crate::manifest::{Manifest, PackageInfo};
+
+pub struct RuntimePackage {
+    /// Slash separated e.g. java/lang, empty for the unnamed package
+    name: InternedString,
+    /// Classpath entry the first class in this package was loaded from
+    source: Option<PathBuf>,
+    info: PackageInfo,
+    /// java/lang/Package instance, created on first request
+    package_object: Mutex<Option<VmRef<Object>>>,
+}
+
+/// (package name, defining loader) -> package
+pub(in crate::class) type Packages = HashMap<(InternedString, WhichLoader), VmRef<RuntimePackage>>;
+
+/// Classpath entry -> its manifest, if it has one
+pub(in crate::class) type Manifests = HashMap<PathBuf, Option<Arc<Manifest>>>;
+
+impl RuntimePackage {
+    /// Slash separated package of the given class name, empty for the unnamed package
+    pub fn name_of_class(class_name: &mstr) -> &mstr {
+        let bytes = class_name.as_bytes();
+        let end = bytes.iter().rposition(|b| *b == b'/').unwrap_or(0);
+        mstr::from_mutf8(&bytes[..end])
+    }
+
+    pub fn name(&self) -> &mstr {
+        &self.name
+    }
+
+    pub fn info(&self) -> &PackageInfo {
+        &self.info
+    }
+
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.info.sealed
+    }
+
+    /// URL of the classpath entry that seals this package, None if it isn't sealed
+    pub fn seal_base(&self) -> Option<String> {
+        let source = self.source.as_deref().filter(|_| self.is_sealed())?;
+        let separator = if source.is_dir() { "/" } else { "" };
+        Some(format!("file:{}{}", source.display(), separator))
+    }
+
+    /// Returns the cached java/lang/Package instance, or creates one with the given fn
+    pub fn package_object(
+        &self,
+        create: impl FnOnce(&Self) -> VmResult<VmRef<Object>>,
+    ) -> VmResult<VmRef<Object>> {
+        let mut guard = self.package_object.lock();
+        if let Some(obj) = guard.as_ref() {
+            return Ok(obj.clone());
+        }
+
+        let obj = create(self)?;
+        *guard = Some(obj.clone());
+        Ok(obj)
+    }
+
+    /// A class from `source` can only be added to this package if it isn't sealed to a different
+    /// source, and if `source` doesn't seal it when it's already been defined elsewhere.
+    /// `manifest` is the manifest of `source`
+    fn check_sealing(
+        &self,
+        class_name: &mstr,
+        source: Option<&Path>,
+        manifest: Option<&Manifest>,
+    ) -> VmResult<()> {
+        if self.source() == source {
+            return Ok(());
+        }
+
+        let violation = if self.is_sealed() {
+            true
+        } else {
+            // not sealed yet, but might be sealed by the manifest of the new source
+            manifest
+                .map(|manifest| manifest.package_info(&self.name.to_utf8()).sealed)
+                .unwrap_or(false)
+        };
+
+        if violation {
+            warn!(
+                "sealing violation: class {:?} from {:?} can't be added to package {:?} from {:?}",
+                class_name, source, self.name, self.source
+            );
+            Err(Throwables::Other("java/lang/SecurityException"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl ClassLoader {
+    /// Finds or creates the runtime package of a class about to be defined by `loader` from the
+    /// given classpath entry, enforcing package sealing. A new package is only registered by
+    /// [register_package](Self::register_package) once the class has been linked
+    pub(in crate::class) fn define_package(
+        &self,
+        class_name: &mstr,
+        loader: &WhichLoader,
+        source: Option<&Path>,
+    ) -> VmResult<VmRef<RuntimePackage>> {
+        let name = InternedString::intern(RuntimePackage::name_of_class(class_name));
+        let manifest = source.and_then(|source| self.manifest_of(source));
+
+        let existing = self.packages.read().get(&(name, loader.clone())).cloned();
+        let package = match existing {
+            Some(package) => package,
+            None => VmRef::new(RuntimePackage {
+                name,
+                source: source.map(Path::to_owned),
+                info: manifest
+                    .as_ref()
+                    .map(|manifest| manifest.package_info(&name.to_utf8()))
+                    .unwrap_or_default(),
+                package_object: Mutex::new(None),
+            }),
+        };
+
+        package.check_sealing(class_name, source, manifest.as_deref())?;
+        Ok(package)
+    }
+
+    /// Classes defined at runtime have no classpath entry, so can't be added to a sealed package.
+    /// Besides its own packages, a user loader sees those of the bootstrap loader it delegates to
+    /// (`ClassLoader.getPackage`), so a package sealed there can't be extended by a user loader
+    pub(in crate::class) fn check_defined_sealing(
+        &self,
+        class_name: &mstr,
+        loader: &WhichLoader,
+    ) -> VmResult<()> {
+        let name = RuntimePackage::name_of_class(class_name);
+        let bootstrap = match loader {
+            WhichLoader::User(_) => Some(&WhichLoader::Bootstrap),
+            WhichLoader::Bootstrap => None,
+        };
+
+        for loader in std::iter::once(loader).chain(bootstrap) {
+            if let Some(package) = self.get_package(name, loader) {
+                package.check_sealing(class_name, None, None)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Registers the package returned by [define_package](Self::define_package) after its class
+    /// has been linked. If another class in the same package was linked first, that package is
+    /// kept
+    pub(in crate::class) fn register_package(
+        &self,
+        package: VmRef<RuntimePackage>,
+        loader: &WhichLoader,
+    ) {
+        self.packages
+            .write()
+            .entry((package.name, loader.clone()))
+            .or_insert_with(|| {
+                debug!(
+                    "defining package {:?} from {:?}",
+                    package.name, package.source
+                );
+                package
+            });
+    }
+
+    /// Manifest of the given classpath entry, read once per entry
+    fn manifest_of(&self, source: &Path) -> Option<Arc<Manifest>> {
+        if let Some(manifest) = self.manifests.read().get(source) {
+            return manifest.clone();
+        }
+
+        // read manifest outside of the lock
+        let manifest = Manifest::load_from_entry(source).map(Arc::new);
+        self.manifests
+            .write()
+            .entry(source.to_owned())
+            .or_insert(manifest)
+            .clone()
+    }
+
+    /// Package name is slash separated
+    pub fn get_package(&self, name: &mstr, loader: &WhichLoader) -> Option<VmRef<RuntimePackage>> {
+        let name = InternedString::lookup(name)?;
+        self.packages.read().get(&(name, loader.clone())).cloned()
+    }
+
+    /// All packages defined by the given loader
+    pub fn get_packages(&self, loader: &WhichLoader) -> Vec<VmRef<RuntimePackage>> {
+        self.packages
+            .read()
+            .iter()
+            .filter(|((_, l), _)| l == loader)
+            .map(|(_, package)| package.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_of_class() {
+        let pkg = |name| RuntimePackage::name_of_class(mstr::from_literal(name)).to_utf8();
+        assert_eq!(pkg("java/lang/Object"), "java/lang");
+        assert_eq!(pkg("Nop"), "");
+        assert_eq!(pkg("a/B$Inner"), "a");
+    }
+
+    fn package(source: &str, manifest: Option<&Manifest>) -> RuntimePackage {
+        let name = InternedString::intern(mstr::from_literal("a/b"));
+        RuntimePackage {
+            name,
+            source: Some(PathBuf::from(source)),
+            info: manifest
+                .map(|manifest| manifest.package_info(&name.to_utf8()))
+                .unwrap_or_default(),
+            package_object: Mutex::new(None),
+        }
+    }
+
+    #[test]
+    fn package_sealing() {
+        let class = mstr::from_literal("a/b/C");
+        let sealing = Manifest::parse("Manifest-Version: 1.0\n\nName: a/b/\nSealed: true\n");
+        let other = Manifest::parse("Manifest-Version: 1.0\n");
+        let is_violation = |result: VmResult<()>| {
+            matches!(
+                result,
+                Err(Throwables::Other("java/lang/SecurityException"))
+            )
+        };
+
+        // sealed to its own source
+        let sealed = package("sealed.jar", Some(&sealing));
+        assert!(sealed.is_sealed());
+        let source = Path::new("sealed.jar");
+        assert!(sealed
+            .check_sealing(class, Some(source), Some(&sealing))
+            .is_ok());
+        let source = Path::new("other.jar");
+        assert!(is_violation(sealed.check_sealing(
+            class,
+            Some(source),
+            Some(&other)
+        )));
+        assert!(is_violation(sealed.check_sealing(class, None, None)));
+        assert_eq!(sealed.seal_base().as_deref(), Some("file:sealed.jar"));
+
+        // a later source can't seal a package that's already open
+        let open = package("other.jar", Some(&other));
+        assert!(!open.is_sealed());
+        let source = Path::new("sealed.jar");
+        assert!(is_violation(open.check_sealing(
+            class,
+            Some(source),
+            Some(&sealing)
+        )));
+        let source = Path::new("more.jar");
+        assert!(open
+            .check_sealing(class, Some(source), Some(&other))
+            .is_ok());
+        assert!(open.check_sealing(class, None, None).is_ok());
+        assert!(open.seal_base().is_none());
+    }
+}
@@ -0,0 +1,295 @@
+//! Reading and parsing of bootstrap classes, in parallel during startup. Linking stays on the loading
+//! thread and happens in the same order as without prefetching, only the file I/O and validation of
+//! the class files is done ahead of time on a pool of worker threads. Workers hand over the class
+//! in owned form, so it isn't parsed again when linked.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::*;
+use parking_lot::{Condvar, Mutex};
+
+use cafebabe::mutf8::{mstr, StrExt};
+use cafebabe::{ClassError, ClassFile};
+
+use crate::alloc::VmRef;
+use crate::class::class::ClassDefinition;
+use crate::class::{Class, ClassLoader, WhichLoader};
+use crate::classpath::ClassPath;
+use crate::error::{Throwables, VmResult};
+use crate::share::ClassSharing;
+use crate::types::ArrayType;
+
+const MAX_WORKERS: usize = 8;
+
+/// A bootstrap class that is known to parse, waiting to be linked
+pub(in crate::class) struct ParsedClass {
+    definition: ClassDefinition,
+    /// Boot classpath entry the class was found in
+    source: PathBuf,
+    /// Time taken to read and parse, possibly on another thread
+    load_time: Duration,
+}
+
+/// Class names waiting to be fetched, shared by all workers
+struct WorkQueue {
+    state: Mutex<QueueState>,
+    cvar: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: Vec<String>,
+    seen: HashSet<String>,
+    /// Number of workers currently fetching a class, which might discover more
+    busy: usize,
+}
+
+impl ClassLoader {
+    /// Reads and parses the given bootstrap classes and their superclasses and superinterfaces in
+    /// parallel, blocking until all are done. They are linked later as usual by
+    /// [ClassLoader::load_class]. Classes that fail to be found or parsed are skipped here, so the
+    /// error is raised by the normal loading path instead
+    pub fn prefetch_boot_classes<'a>(&self, class_names: impl Iterator<Item = &'a str>) {
+        let queue = WorkQueue::default();
+        for name in class_names {
+            queue.push(name);
+        }
+
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS);
+        debug!("prefetching bootstrap classes with {} workers", workers);
+
+        // only the thread-safe parts of the loader are shared
+        let bootclasspath = &*self.bootclasspath;
+        let sharing = &self.sharing;
+        let fetched = Mutex::new(HashMap::new());
+
+        thread::scope(|scope| {
+            for i in 0..workers {
+                let (queue, fetched) = (&queue, &fetched);
+                thread::Builder::new()
+                    .name(format!("prefetch-{}", i))
+                    .spawn_scoped(scope, move || {
+                        while let Some(name) = queue.pop() {
+                            // released even if fetching panics, so other workers don't wait forever
+                            let _busy = Busy(queue);
+
+                            // errors are raised again when loaded on demand
+                            if let Ok(prefetched) = parse_boot_class(bootclasspath, sharing, &name)
+                            {
+                                for dep in prefetched.definition.dependencies() {
+                                    queue.push(&dep.to_utf8());
+                                }
+                                fetched.lock().insert(name, prefetched);
+                            }
+                        }
+                    })
+                    .expect("failed to spawn prefetch thread");
+            }
+        });
+
+        let fetched = fetched.into_inner();
+        debug!("prefetched {} bootstrap classes", fetched.len());
+        self.prefetched.lock().extend(fetched);
+    }
+
+    /// Removes the prefetched class with the given name, if any
+    pub(in crate::class) fn take_prefetched(&self, class_name: &mstr) -> Option<ParsedClass> {
+        let mut prefetched = self.prefetched.lock();
+        if prefetched.is_empty() {
+            return None;
+        }
+
+        prefetched.remove(class_name.to_utf8().as_ref())
+    }
+}
+
+/// Reads and parses a bootstrap class without linking it
+pub(in crate::class) fn parse_boot_class(
+    bootclasspath: &ClassPath,
+    sharing: &ClassSharing,
+    class_name: &str,
+) -> VmResult<ParsedClass> {
+    let start = Instant::now();
+    let (bytes, source) = super::loader::find_boot_class(bootclasspath, sharing, class_name)?;
+
+    let class_file = parse(&bytes, class_name)?;
+    let definition = ClassDefinition::parse(
+        class_name.to_mstr().as_ref(),
+        class_file,
+        &WhichLoader::Bootstrap,
+    )?;
+
+    Ok(ParsedClass {
+        definition,
+        source: source.to_owned(),
+        load_time: start.elapsed(),
+    })
+}
+
+fn parse<'b>(bytes: &'b [u8], class_name: &str) -> VmResult<ClassFile<'b>> {
+    cafebabe::load_from_buffer(bytes).map_err(|err| {
+        warn!("class loading failed: {}", err);
+        let symbol = match err {
+            ClassError::Unsupported(_) => "java/lang/UnsupportedClassVersionError",
+            ClassError::Io(_) => "java/io/IOError",
+            _ => "java/lang/ClassFormatError",
+        };
+        Throwables::WithMessage(symbol, format!("{} ({})", class_name, err))
+    })
+}
+
+impl ParsedClass {
+    pub(in crate::class) fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub(in crate::class) fn load_time(&self) -> Duration {
+        self.load_time
+    }
+
+    pub(in crate::class) fn link(
+        self,
+        loader: WhichLoader,
+        classloader: &ClassLoader,
+    ) -> VmResult<VmRef<Class>> {
+        Class::link(self.definition, loader, classloader)
+    }
+}
+
+impl Default for WorkQueue {
+    fn default() -> Self {
+        WorkQueue {
+            state: Mutex::new(QueueState::default()),
+            cvar: Condvar::new(),
+        }
+    }
+}
+
+impl WorkQueue {
+    fn push(&self, class_name: &str) {
+        // array classes aren't read from the classpath, but their element class is
+        let mstr_name = class_name.to_mstr();
+        let class_name = match ArrayType::from_descriptor(&mstr_name) {
+            None => class_name,
+            Some(ArrayType::Reference(elem)) => return self.push(&elem.to_utf8()),
+            Some(ArrayType::Primitive(_)) => return,
+        };
+
+        let mut state = self.state.lock();
+        if state.seen.insert(class_name.to_owned()) {
+            state.pending.push(class_name.to_owned());
+            self.cvar.notify_one();
+        }
+    }
+
+    /// Blocks until there is a class to fetch, or None when there is no more work. Every class
+    /// returned must be followed by a call to [WorkQueue::done]
+    fn pop(&self) -> Option<String> {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(name) = state.pending.pop() {
+                state.busy += 1;
+                return Some(name);
+            }
+
+            if state.busy == 0 {
+                return None;
+            }
+
+            // another worker might still discover more classes
+            self.cvar.wait(&mut state);
+        }
+    }
+
+    /// Prefer holding a [Busy] for the popped class
+    fn done(&self) {
+        let mut state = self.state.lock();
+        state.busy -= 1;
+        if state.busy == 0 && state.pending.is_empty() {
+            // wake up idle workers so they can exit
+            self.cvar.notify_all();
+        }
+    }
+}
+
+/// Marks a popped class as done when dropped
+struct Busy<'a>(&'a WorkQueue);
+
+impl Drop for Busy<'_> {
+    fn drop(&mut self) {
+        self.0.done();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn prefetch_queue_arrays_and_duplicates() {
+        let queue = WorkQueue::default();
+        queue.push("java/lang/String");
+        queue.push("[[Ljava/lang/String;");
+        queue.push("[I");
+        queue.push("java/lang/Object");
+
+        assert_eq!(queue.pop().as_deref(), Some("java/lang/Object"));
+        assert_eq!(queue.pop().as_deref(), Some("java/lang/String"));
+        queue.done();
+        queue.done();
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn prefetch_missing_classes() {
+        let loader = ClassLoader::new(Arc::new(ClassPath::default()), ClassSharing::Disabled, None);
+        loader.prefetch_boot_classes(["java/lang/Object", "java/lang/Nope"].into_iter());
+
+        // left for the normal loading path to fail on
+        assert!(loader.prefetched.lock().is_empty());
+    }
+
+    #[test]
+    fn prefetch_io_errors() {
+        use std::io::Write;
+        use zip::write::FileOptions;
+        use zip::{CompressionMethod, ZipWriter};
+
+        let jar = std::env::temp_dir().join(format!("jvm-prefetch-{}.jar", std::process::id()));
+        let mut zip = ZipWriter::new(std::fs::File::create(&jar).unwrap());
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file("Broken.class", options).unwrap();
+        zip.write_all(b"\xca\xfe\xba\xbe checksummed").unwrap();
+        zip.finish().unwrap();
+
+        // corrupt the stored contents so reading fails the checksum
+        let mut bytes = std::fs::read(&jar).unwrap();
+        let pos = bytes
+            .windows(4)
+            .position(|w| w == b"\xca\xfe\xba\xbe")
+            .unwrap();
+        bytes[pos] = 0;
+        std::fs::write(&jar, bytes).unwrap();
+
+        let classpath = Arc::new(ClassPath::new(vec![jar.clone()]));
+        let loader = ClassLoader::new(classpath.clone(), ClassSharing::Disabled, None);
+
+        // returns rather than waiting on a worker that failed
+        loader.prefetch_boot_classes(["Broken", "java/lang/Object"].into_iter());
+        assert!(loader.prefetched.lock().is_empty());
+
+        let err = parse_boot_class(&classpath, &ClassSharing::Disabled, "Broken")
+            .err()
+            .expect("read should fail");
+        assert_eq!(err.symbol(), "java/io/IOError");
+
+        let _ = std::fs::remove_file(&jar);
+    }
+}
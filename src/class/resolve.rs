@@ -0,0 +1,190 @@
+//! Resolution of symbolic references in the runtime constant pool (JVMS 5.4.3). Each class,
+//! field and method reference is resolved at most once per class, and the result or error is
+//! kept in its entry for every later use.
+
+use cafebabe::mutf8::mstr;
+use cafebabe::{AccessFlags, MethodAccessFlags};
+use log::*;
+use std::fmt::Display;
+
+use crate::alloc::{InternedString, VmRef};
+use crate::class::{Class, FieldSearchType, FoundField};
+use crate::constant_pool::{Entry, FieldLocation, ResolvedField, ResolvedMethod};
+use crate::error::Throwables;
+use crate::interpreter::InterpreterError;
+use crate::thread;
+
+impl Class {
+    /// Resolves the class reference at the given index (5.4.3.1)
+    pub fn resolve_class(self: &VmRef<Class>, idx: u16) -> Result<VmRef<Class>, InterpreterError> {
+        let entry = self
+            .constant_pool()
+            .class_entry(idx)
+            .ok_or(InterpreterError::NotClassRef(idx))?;
+
+        entry
+            .resolved
+            .get_or_resolve(|| self.load_accessible(&entry.name))
+    }
+
+    /// Resolves the field reference at the given index (5.4.3.2). Whether the field is static is
+    /// checked by the instruction using it
+    pub fn resolve_field(self: &VmRef<Class>, idx: u16) -> Result<ResolvedField, InterpreterError> {
+        let entry = self
+            .constant_pool()
+            .field_entry(idx)
+            .ok_or(InterpreterError::NotFieldRef(idx))?;
+
+        entry.resolved.get_or_resolve(|| {
+            trace!("resolving field {:?}", entry);
+            let class = self.load_accessible(&entry.class)?;
+            let no_such_field = || Throwables::NoSuchFieldError(entry.name.to_string());
+
+            // arrays have no fields
+            if class.class_type().is_array() {
+                return Err(no_such_field().into());
+            }
+
+            let location =
+                if let Some(id) = class.find_instance_field_recursive(&entry.name, &entry.desc) {
+                    FieldLocation::Instance(class.instance_fields_layout().instance_slot(id))
+                } else {
+                    // class holding static field data is not necessarily the same
+                    match class
+                        .find_static_field_recursive(&entry.name, &entry.desc)
+                        .ok_or_else(no_such_field)?
+                    {
+                        FoundField::InThisClass(id) => FieldLocation::Static(class.clone(), id),
+                        FoundField::InOtherClass(id, storage) => FieldLocation::Static(storage, id),
+                    }
+                };
+
+            let search = match location {
+                FieldLocation::Instance(_) => FieldSearchType::Instance,
+                FieldLocation::Static(..) => FieldSearchType::Static,
+            };
+            let (declaring, flags) = class
+                .find_field_declaration(&entry.name, &entry.desc, search)
+                .ok_or_else(no_such_field)?;
+            self.check_member_access(&declaring, flags, "field", &entry.name, None)?;
+
+            Ok(ResolvedField { class, location })
+        })
+    }
+
+    /// Resolves the method or interface method reference at the given index (5.4.3.3, 5.4.3.4).
+    /// The resolved method may be abstract or static, which is checked by the instruction using
+    /// it
+    pub fn resolve_method(
+        self: &VmRef<Class>,
+        idx: u16,
+    ) -> Result<ResolvedMethod, InterpreterError> {
+        let (entry, expect_interface) = match self.constant_pool().entry(idx) {
+            Some(Entry::MethodRef(m)) => (m, false),
+            Some(Entry::InterfaceMethodRef(m)) => (m, true),
+            _ => return Err(InterpreterError::NotMethodRef(idx)),
+        };
+
+        entry.resolved.get_or_resolve(|| {
+            trace!("resolving method {:?}", entry);
+            let class = self.load_accessible(&entry.class)?;
+
+            if class.is_interface() != expect_interface {
+                let kind = |interface| if interface { "interface" } else { "class" };
+                return Err(Throwables::incompatible_class_change(format_args!(
+                    "Found {} {}, but {} was expected",
+                    kind(class.is_interface()),
+                    class.java_name(),
+                    kind(expect_interface)
+                ))
+                .into());
+            }
+
+            let method = class
+                .find_method_recursive_in_superclasses(
+                    &entry.name,
+                    &entry.desc,
+                    MethodAccessFlags::empty(),
+                    MethodAccessFlags::empty(),
+                )
+                .ok_or_else(|| {
+                    Throwables::WithMessage(
+                        "java/lang/NoSuchMethodError",
+                        format!("{}.{}{}", class.name(), entry.name, entry.desc),
+                    )
+                })?;
+
+            self.check_member_access(
+                method.class(),
+                method.flags(),
+                "method",
+                &entry.name,
+                Some(&entry.desc),
+            )?;
+
+            Ok(ResolvedMethod { class, method })
+        })
+    }
+
+    /// Loads a class named by this class's constant pool, which must be accessible to this class
+    fn load_accessible(self: &VmRef<Class>, name: &mstr) -> Result<VmRef<Class>, InterpreterError> {
+        let class = self.load_referenced(name)?;
+        if !class.is_accessible_to(self) {
+            return Err(illegal_access(format_args!(
+                "class {} cannot access class {}",
+                self.name(),
+                class.name()
+            ))
+            .into());
+        }
+
+        Ok(class)
+    }
+
+    /// Throws IllegalAccessError if a member declared in `declaring` with the given flags is not
+    /// accessible to this class
+    fn check_member_access(
+        self: &VmRef<Class>,
+        declaring: &VmRef<Class>,
+        flags: impl AccessFlags,
+        kind: &str,
+        name: &mstr,
+        desc: Option<&mstr>,
+    ) -> Result<(), InterpreterError> {
+        if declaring.is_member_accessible_to(flags, self) {
+            return Ok(());
+        }
+
+        Err(illegal_access(format_args!(
+            "class {} tried to access {} {}.{}{}",
+            self.name(),
+            kind,
+            declaring.name(),
+            name,
+            desc.map(|desc| desc.to_string()).unwrap_or_default()
+        ))
+        .into())
+    }
+
+    /// Loads a class named by this class's constant pool with this class's loader
+    fn load_referenced(&self, name: &mstr) -> Result<VmRef<Class>, InterpreterError> {
+        let thread = thread::get();
+        let class_loader = thread.global().class_loader();
+        if class_loader.needs_java_loader(name, self.loader())
+            && thread.interpreter().is_executing()
+        {
+            // not cached in the constant pool, the instruction will be executed again
+            return Err(InterpreterError::UserClassLoad {
+                name: InternedString::intern(name),
+                loader: self.loader().clone(),
+            });
+        }
+
+        let class = class_loader.load_class_caused_by(name, self.loader().clone(), self.name())?;
+        Ok(class)
+    }
+}
+
+fn illegal_access(message: impl Display) -> Throwables {
+    Throwables::WithMessage("java/lang/IllegalAccessError", message.to_string())
+}
@@ -0,0 +1,377 @@
+//! Unloading of classes defined by user class loaders.
+//!
+//! There is no tracing GC yet, so everything is reference counted and classes are kept alive by
+//! the loaded class table and by cycles (class <-> methods, class <-> java/lang/Class instance,
+//! loader instance <-> its classes). Unloading finds these cycles by trial deletion: the object
+//! graph reachable from a loader and its classes is collected, references from inside that graph
+//! are subtracted from the strong counts, and anything left with a positive count must be
+//! referenced from outside (e.g. a frame, a static field of another class, a local ref). If
+//! nothing in the graph that keeps the loader alive is externally reachable, the cycles are broken
+//! and the whole lot is freed.
+
+use std::collections::HashMap;
+
+use log::*;
+
+use crate::alloc::{vmref_ptr, VmRef};
+use crate::class::loader::{LoadState, LoadedClasses};
+use crate::class::package::Packages;
+use crate::class::{null, Class, ClassLoader, Method, Object, WhichLoader};
+use crate::constant_pool::{Entry, FieldLocation};
+use crate::thread;
+use crate::types::DataValue;
+
+#[derive(Clone)]
+enum Node {
+    Object(VmRef<Object>),
+    Class(VmRef<Class>),
+    Method(VmRef<Method>),
+}
+
+struct NodeState {
+    node: Node,
+    /// Strong count minus references from inside the graph
+    external_refs: isize,
+    reachable: bool,
+}
+
+/// The graph of objects, classes and methods reachable from a single user loader, without
+/// crossing into classes of other loaders
+struct LoaderGraph {
+    loader: usize,
+    nodes: HashMap<usize, NodeState>,
+}
+
+impl ClassLoader {
+    /// Unloads all classes of user class loaders that are no longer reachable, along with the
+    /// native libraries they loaded. Returns the number of classes unloaded.
+    ///
+    /// Reference counts are sampled without stopping other threads, which can only cause a
+    /// reachable graph to be kept alive longer than needed, never the opposite.
+    pub fn unload_unreachable_classes(&self) -> usize {
+        let mut unloaded = 0;
+
+        {
+            let mut classes = self.classes.write();
+            let mut packages = self.packages.write();
+
+            // distinct user loaders
+            let mut loaders = Vec::new();
+            for (_, loader) in classes.keys() {
+                if let WhichLoader::User(obj) = loader {
+                    if !loaders.iter().any(|l| VmRef::ptr_eq(l, obj)) {
+                        loaders.push(obj.clone());
+                    }
+                }
+            }
+
+            for loader in loaders {
+                let mut graph = LoaderGraph::new(loader, &classes);
+                if !graph.is_unreachable(&classes, &packages) {
+                    continue;
+                }
+
+                let loader_ptr = graph.loader;
+                let is_other_loader = |l: &WhichLoader| !matches!(l, WhichLoader::User(o) if vmref_ptr(o) == loader_ptr);
+                classes.retain(|(_, l), _| is_other_loader(l));
+                packages.retain(|(_, l), _| is_other_loader(l));
+                unloaded += graph.release();
+            }
+        }
+
+        // libraries are dropped outside of the lock as JNI_OnUnload may call back into the vm
+        let libs = {
+            let thread = thread::get();
+            let mut native_libs = thread.global().native_libraries_mut();
+            native_libs.take_unloaded()
+        };
+        drop(libs);
+
+        if unloaded > 0 {
+            debug!("unloaded {} classes", unloaded);
+        }
+        unloaded
+    }
+}
+
+impl LoaderGraph {
+    #[allow(clippy::mutable_key_type)] // loaders are hashed by pointer only
+    fn new(loader: VmRef<Object>, classes: &LoadedClasses) -> Self {
+        let mut graph = LoaderGraph {
+            loader: vmref_ptr(&loader),
+            nodes: HashMap::new(),
+        };
+
+        // start with the loader instance and all classes it defined
+        let mut worklist = vec![Node::Object(loader)];
+        for state in classes.values() {
+            if let LoadState::Loaded(_, cls) = state {
+                if graph.is_own_class(cls) {
+                    worklist.push(Node::Class(cls.clone()));
+                }
+            }
+        }
+
+        while let Some(node) = worklist.pop() {
+            let ptr = node.ptr();
+            if graph.nodes.contains_key(&ptr) {
+                continue;
+            }
+
+            graph.for_each_edge(&node, &mut |edge| worklist.push(edge));
+            graph.nodes.insert(
+                ptr,
+                NodeState {
+                    node,
+                    external_refs: 0,
+                    reachable: false,
+                },
+            );
+        }
+
+        graph
+    }
+
+    fn is_own_class(&self, cls: &VmRef<Class>) -> bool {
+        matches!(cls.loader(), WhichLoader::User(o) if vmref_ptr(o) == self.loader)
+    }
+
+    /// Edges to other nodes that may be part of this graph. Classes of other loaders are never
+    /// followed, they are kept alive by their own loader or are bootstrap classes
+    fn for_each_edge(&self, node: &Node, f: &mut dyn FnMut(Node)) {
+        let class_edge = |cls: &VmRef<Class>, f: &mut dyn FnMut(Node)| {
+            if self.is_own_class(cls) {
+                f(Node::Class(cls.clone()))
+            }
+        };
+
+        let value_edge = |value: &mut DataValue, f: &mut dyn FnMut(Node)| match value {
+            DataValue::Reference(obj) if !obj.is_null() => f(Node::Object(obj.clone())),
+            DataValue::VmDataClass(cls) => class_edge(cls, f),
+            DataValue::VmDataStackTrace(trace) => trace
+                .frames()
+                .iter()
+                .map(|frame| frame.method())
+                .filter(|method| self.is_own_class(method.class()))
+                .for_each(|method| f(Node::Method(method.clone()))),
+            DataValue::VmDataMethodHandle(handle) => class_edge(handle.class(), f),
+            _ => {}
+        };
+
+        match node {
+            Node::Object(obj) => {
+                if let Some(cls) = obj.class() {
+                    class_edge(&cls, f);
+                }
+                obj.for_each_value(|v| value_edge(v, f));
+            }
+            Node::Class(cls) => {
+                if let WhichLoader::User(loader) = cls.loader() {
+                    f(Node::Object(loader.clone()));
+                }
+
+                cls.super_class()
+                    .into_iter()
+                    .chain(cls.interfaces())
+                    .chain(cls.class_type().array_class())
+                    .for_each(|super_cls| class_edge(super_cls, f));
+
+                cls.methods()
+                    .iter()
+                    .for_each(|m| f(Node::Method(m.clone())));
+                cls.method_tables()
+                    .methods()
+                    .filter(|m| self.is_own_class(m.class()))
+                    .for_each(|m| f(Node::Method(m.clone())));
+                cls.method_tables()
+                    .interfaces()
+                    .for_each(|iface| class_edge(iface, f));
+                f(Node::Object(cls.class_object().clone()));
+                cls.static_fields().for_each_value(|v| value_edge(v, f));
+                cls.resolved_constants()
+                    .values_mut()
+                    .for_each(|v| value_edge(v, f));
+
+                // resolved references in the constant pool. Exceptions of failed resolutions may
+                // also be referenced from outside, so they aren't followed and keep the loader
+                // alive instead
+                for (_, entry) in cls.constant_pool().entries() {
+                    match entry {
+                        Entry::ClassRef(class_ref) => {
+                            if let Some(resolved) = class_ref.resolved.value() {
+                                class_edge(&resolved, f);
+                            }
+                        }
+                        Entry::FieldRef(field_ref) => {
+                            if let Some(resolved) = field_ref.resolved.value() {
+                                class_edge(&resolved.class, f);
+                                if let FieldLocation::Static(storage, _) = &resolved.location {
+                                    class_edge(storage, f);
+                                }
+                            }
+                        }
+                        Entry::MethodRef(method_ref) | Entry::InterfaceMethodRef(method_ref) => {
+                            if let Some(resolved) = method_ref.resolved.value() {
+                                class_edge(&resolved.class, f);
+                                if self.is_own_class(resolved.method.class()) {
+                                    f(Node::Method(resolved.method.clone()));
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Node::Method(method) => {
+                class_edge(method.class(), f);
+
+                // resolved references of quickened instructions
+                for quick in method.translated_code().iter().flat_map(|c| c.quickened()) {
+                    quick.classes().for_each(|cls| class_edge(cls, f));
+                    if let Some(target) = quick.method() {
+                        if self.is_own_class(target.class()) {
+                            f(Node::Method(target.clone()));
+                        }
+                    }
+                    if let Some(value) = quick.constant() {
+                        value_edge(&mut value.clone(), f);
+                    }
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn is_unreachable(&mut self, classes: &LoadedClasses, packages: &Packages) -> bool {
+        // sample strong counts, excluding the reference held by this graph
+        for state in self.nodes.values_mut() {
+            state.external_refs = state.node.strong_count() as isize - 1;
+        }
+
+        // subtract references from inside the graph
+        let mut internal = Vec::new();
+        for state in self.nodes.values() {
+            self.for_each_edge(&state.node, &mut |edge| internal.push(edge.ptr()));
+        }
+
+        // the loaded class and package tables are also internal as the entries are removed with
+        // the graph
+        for (_, loader) in packages.keys() {
+            if matches!(loader, WhichLoader::User(obj) if vmref_ptr(obj) == self.loader) {
+                internal.push(self.loader);
+            }
+        }
+
+        for ((_, loader), state) in classes {
+            if let WhichLoader::User(obj) = loader {
+                if vmref_ptr(obj) == self.loader {
+                    internal.push(self.loader);
+                    match state {
+                        LoadState::Loading(_, WhichLoader::User(obj)) => {
+                            internal.push(vmref_ptr(obj))
+                        }
+                        LoadState::Loaded(_, cls) => internal.push(vmref_ptr(cls)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for ptr in internal {
+            if let Some(state) = self.nodes.get_mut(&ptr) {
+                state.external_refs -= 1;
+            }
+        }
+
+        // propagate reachability from externally referenced nodes
+        let mut worklist = self
+            .nodes
+            .iter()
+            .filter(|(_, state)| state.external_refs > 0)
+            .map(|(ptr, _)| *ptr)
+            .collect::<Vec<_>>();
+
+        while let Some(ptr) = worklist.pop() {
+            let state = match self.nodes.get_mut(&ptr) {
+                Some(state) if !state.reachable => state,
+                _ => continue,
+            };
+
+            state.reachable = true;
+            let node = state.node.clone();
+            self.for_each_edge(&node, &mut |edge| worklist.push(edge.ptr()));
+        }
+
+        // the loader stays if it or any of its classes can still be used
+        let alive = self.nodes.values().any(|state| {
+            state.reachable
+                && match &state.node {
+                    Node::Object(obj) => vmref_ptr(obj) == self.loader,
+                    Node::Class(_) | Node::Method(_) => true,
+                }
+        });
+
+        if alive {
+            trace!("class loader {:#x} is still reachable", self.loader);
+        }
+
+        !alive
+    }
+
+    /// Breaks all cycles between unreachable nodes so everything is freed once the graph is
+    /// dropped. Returns the number of classes released
+    fn release(self) -> usize {
+        let thread = thread::get();
+        let mut thunks = thread.global().native_thunks_mut();
+
+        let mut count = 0;
+        for (_, state) in self.nodes {
+            if state.reachable {
+                // referenced from elsewhere, but doesn't keep the loader alive
+                continue;
+            }
+
+            match state.node {
+                Node::Object(obj) => obj.for_each_value(clear_reference),
+                Node::Class(cls) => {
+                    debug!("unloading class {:?}", cls.name());
+                    cls.static_fields().for_each_value(clear_reference);
+                    cls.resolved_constants().clear();
+                    cls.release_methods(&mut thunks);
+                    count += 1;
+                }
+                Node::Method(_) => {}
+            }
+        }
+
+        count
+    }
+}
+
+fn clear_reference(value: &mut DataValue) {
+    if let DataValue::Reference(_)
+    | DataValue::VmDataClass(_)
+    | DataValue::VmDataStackTrace(_)
+    | DataValue::VmDataMethodHandle(_) = value
+    {
+        *value = DataValue::Reference(null());
+    }
+}
+
+impl Node {
+    fn ptr(&self) -> usize {
+        match self {
+            Node::Object(obj) => vmref_ptr(obj),
+            Node::Class(cls) => vmref_ptr(cls),
+            Node::Method(method) => vmref_ptr(method),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Object(obj) => VmRef::strong_count(obj),
+            Node::Class(cls) => VmRef::strong_count(cls),
+            Node::Method(method) => VmRef::strong_count(method),
+        }
+    }
+}
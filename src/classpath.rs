@@ -1,67 +1,63 @@
 use log::*;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use itertools::Itertools;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use zip::result::ZipError;
+use zip::ZipArchive;
 
-// TODO enum for path type, zip/jar or directory
-
-#[cfg(feature = "miri")]
-mod classpath_zip {
-    use crate::classpath::FindClassError;
-    use lazy_static::lazy_static;
-    use parking_lot::Mutex;
-    use rc_zip::prelude::ReadAt;
-    use rc_zip::{Archive, EntryContents, ReadZipWithSize};
-    use std::ffi::OsStr;
-    use std::io::Cursor;
-    use std::io::Read;
-    use std::ops::DerefMut;
-    use std::path::Path;
-
-    const CLASSPATH_ZIP: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/classpath.zip"));
-
-    static ARCHIVE: Mutex<Option<Archive>> = parking_lot::const_mutex(None);
-
-    fn archive() -> impl DerefMut<Target = Archive> {
-        let mut guard = ARCHIVE.lock();
-        if guard.is_none() {
-            log::debug!("parsing classpath zip");
-            let archive: Archive = (&CLASSPATH_ZIP as &dyn ReadAt)
-                .read_zip_with_size(CLASSPATH_ZIP.len() as u64)
-                .expect("failed to read classpath zip");
-            log::debug!("done!");
-            for e in archive.entries() {
-                log::debug!(" * {:?}", e.name());
-            }
-            *guard = Some(archive);
-        }
+/// Classpath entry standing in for the class library embedded in the executable with the
+/// `embedded-classpath` feature
+pub const EMBEDDED_ENTRY: &str = "<embedded>";
 
-        guard.as_mut().unwrap() // definitely initialized now
-    }
+/// Install prefixes searched for GNU Classpath, after the one relative to the executable
+const INSTALL_PREFIXES: &[&str] = &[
+    "/usr/local/classpath",
+    "/usr/local",
+    "/usr",
+    "/opt/classpath",
+];
 
-    pub fn file_exists(path: &Path) -> bool {
-        let archive = archive();
+#[cfg(feature = "embedded-classpath")]
+const EMBEDDED_ZIP: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/classpath.zip"));
 
-        archive.by_name(&*path.to_string_lossy()).is_some()
-    }
+/// Clones share the parsed central directory but read independently, so each reader of an archive
+/// gets its own handle
+type Archive = ZipArchive<ArchiveReader>;
 
-    pub fn read_file(path: &Path) -> Option<Result<Vec<u8>, std::io::Error>> {
-        let archive = archive();
+lazy_static! {
+    /// Zip and jar entries opened so far, None if the entry isn't a valid archive
+    static ref ARCHIVES: Mutex<HashMap<PathBuf, Option<Archive>>> = Mutex::new(HashMap::new());
+}
 
-        let ret = if let Some(entry) = archive.by_name(&*path.to_string_lossy()) {
-            let mut reader = entry.reader(|offset| {
-                let mut cursor = Cursor::new(CLASSPATH_ZIP);
-                cursor.set_position(offset as u64);
-                cursor
-            });
-            let mut bytes = Vec::with_capacity(entry.uncompressed_size as usize);
-            Some(reader.read_to_end(&mut bytes).map(|_| bytes))
-        } else {
-            None
-        };
+#[derive(Clone)]
+enum ArchiveReader {
+    Embedded(Cursor<&'static [u8]>),
+    File(FileReader),
+}
 
-        ret
-    }
+/// Reads with its own position from a file shared with other readers
+#[derive(Clone)]
+struct FileReader {
+    file: Arc<File>,
+    pos: u64,
+}
+
+/// A GNU Classpath install found on this machine
+#[derive(Debug)]
+pub struct ClasspathInstall {
+    /// glibj.zip or a directory of class files
+    pub classes: PathBuf,
+    /// Directory of JNI libraries, if installed. Without them the VM still runs, but native methods
+    /// implemented by the libraries are unavailable
+    pub natives: Option<PathBuf>,
 }
 
 #[derive(Default, Debug)]
@@ -81,44 +77,361 @@ impl ClassPath {
         Self(classpath.split(':').map(PathBuf::from).collect())
     }
 
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.0
+    }
+
+    /// Path of the class file for the given class name in a directory entry
+    pub fn class_file(entry: &Path, class_name: &str) -> PathBuf {
+        let mut file = entry.join(class_name);
+        file.set_extension("class");
+        file
+    }
+
+    /// Class file in a directory entry
     pub fn find(&self, class_name: &str) -> Option<PathBuf> {
-        self.0.iter().find_map(|dir| {
-            let mut file = dir.join(class_name);
-            file.set_extension("class");
-
-            #[cfg(feature = "miri")]
-            {
-                // TODO awful, fix this
-                if matches!(classpath_zip::read_file(&*file.as_path()), Some(Ok(_))) {
-                    Some(file.as_path().to_owned())
-                } else {
-                    None
-                }
-            }
+        self.find_with_source(class_name).map(|(_, file)| file)
+    }
 
-            #[cfg(not(feature = "miri"))]
-            {
-                trace!("checking {}", file.display());
-                if file.is_file() {
-                    trace!("found class at {}", file.display());
-                    Some(file)
-                } else {
-                    None
-                }
+    /// Also returns the classpath entry the class was found in. Only directory entries are
+    /// searched, as classes in archives have no path of their own
+    pub fn find_with_source(&self, class_name: &str) -> Option<(&Path, PathBuf)> {
+        self.0.iter().find_map(|dir| {
+            let file = Self::class_file(dir, class_name);
+            trace!("checking {}", file.display());
+            if file.is_file() {
+                trace!("found class at {}", file.display());
+                Some((dir.as_path(), file))
+            } else {
+                None
             }
         })
     }
 
     pub fn find_and_load(&self, class_name: &str) -> Result<Vec<u8>, FindClassError> {
-        self.find(class_name)
-            // TODO fix in miri
-            .map(|file| std::fs::read(file).map_err(FindClassError::Io))
+        self.find_and_load_with_source(class_name)
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Also returns the classpath entry the class was loaded from
+    pub fn find_and_load_with_source(
+        &self,
+        class_name: &str,
+    ) -> Result<(Vec<u8>, &Path), FindClassError> {
+        let file_name = format!("{}.class", class_name);
+        self.0
+            .iter()
+            .find_map(|entry| {
+                read_entry_file(entry, &file_name)
+                    .map(|result| result.map(|bytes| (bytes, entry.as_path())))
+            })
             .unwrap_or(Err(FindClassError::NotFound))
     }
 }
 
+/// Reads a file from a directory, zip/jar or the embedded classpath entry. None if the entry or
+/// file doesn't exist
+pub fn read_entry_file(entry: &Path, file_name: &str) -> Option<Result<Vec<u8>, FindClassError>> {
+    // empty entry is the current directory
+    if entry.as_os_str().is_empty() || entry.is_dir() {
+        let path = entry.join(file_name);
+        trace!("checking {}", path.display());
+        return if path.is_file() {
+            trace!("found file at {}", path.display());
+            Some(std::fs::read(path).map_err(FindClassError::Io))
+        } else {
+            None
+        };
+    }
+
+    let mut archive = open_archive(entry)?;
+    let result = match archive.by_name(file_name) {
+        Ok(mut file) => {
+            trace!("found {} in {}", file_name, entry.display());
+            let mut bytes = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut bytes)
+                .map(|_| bytes)
+                .map_err(FindClassError::Io)
+        }
+        Err(ZipError::FileNotFound) => return None,
+        Err(err) => Err(FindClassError::Io(err.into())),
+    };
+
+    Some(result)
+}
+
+/// Last modification time of a classpath entry, which is the executable for the embedded entry
+pub fn entry_modified(entry: &Path) -> std::io::Result<SystemTime> {
+    let path = if entry == Path::new(EMBEDDED_ENTRY) {
+        std::env::current_exe()?
+    } else {
+        entry.to_owned()
+    };
+
+    std::fs::metadata(path)?.modified()
+}
+
+fn open_archive(entry: &Path) -> Option<Archive> {
+    let mut archives = ARCHIVES.lock();
+    if let Some(archive) = archives.get(entry) {
+        return archive.clone();
+    }
+
+    let reader = if entry == Path::new(EMBEDDED_ENTRY) {
+        embedded_zip().map(|zip| ArchiveReader::Embedded(Cursor::new(zip)))
+    } else if entry.is_file() {
+        match File::open(entry) {
+            Ok(file) => Some(ArchiveReader::File(FileReader {
+                file: Arc::new(file),
+                pos: 0,
+            })),
+            Err(err) => {
+                warn!(
+                    "failed to open classpath entry {}: {}",
+                    entry.display(),
+                    err
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let archive = reader.and_then(|reader| match ZipArchive::new(reader) {
+        Ok(archive) => {
+            debug!(
+                "opened archive {} with {} entries",
+                entry.display(),
+                archive.len()
+            );
+            Some(archive)
+        }
+        Err(err) => {
+            warn!(
+                "classpath entry {} is not a valid archive: {}",
+                entry.display(),
+                err
+            );
+            None
+        }
+    });
+
+    archives.insert(entry.to_owned(), archive.clone());
+    archive
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            ArchiveReader::Embedded(cursor) => cursor.read(buf),
+            ArchiveReader::File(file) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for ArchiveReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            ArchiveReader::Embedded(cursor) => cursor.seek(pos),
+            ArchiveReader::File(file) => file.seek(pos),
+        }
+    }
+}
+
+impl Read for FileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FileReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.file.metadata()?.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        self.pos = pos.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+fn embedded_zip() -> Option<&'static [u8]> {
+    #[cfg(feature = "embedded-classpath")]
+    return Some(EMBEDDED_ZIP);
+
+    #[cfg(not(feature = "embedded-classpath"))]
+    None
+}
+
+impl ClasspathInstall {
+    /// The class library embedded in the executable if there is one, otherwise the first
+    /// GNU Classpath install found next to the executable or in a common prefix
+    pub fn discover() -> Option<Self> {
+        if embedded_zip().is_some() {
+            // JNI libraries can't be loaded from the executable, but are optional
+            let natives = install_prefixes().find_map(|prefix| Self::find_natives(&prefix));
+            debug!("using embedded boot classpath with natives {:?}", natives);
+            return Some(ClasspathInstall {
+                classes: PathBuf::from(EMBEDDED_ENTRY),
+                natives,
+            });
+        }
+
+        install_prefixes().find_map(|prefix| Self::find_in_prefix(&prefix))
+    }
+
+    fn find_natives(prefix: &Path) -> Option<PathBuf> {
+        Some(prefix.join("lib/classpath")).filter(|dir| dir.is_dir())
+    }
+
+    fn find_in_prefix(prefix: &Path) -> Option<Self> {
+        let share = prefix.join("share/classpath");
+        let glibj = share.join("glibj.zip");
+        let classes = if glibj.is_file() {
+            glibj
+        } else if ClassPath::class_file(&share, "java/lang/Object").is_file() {
+            // installed with --with-glibj=flat
+            share
+        } else {
+            trace!("no GNU Classpath install in {}", prefix.display());
+            return None;
+        };
+
+        let natives = Self::find_natives(prefix);
+        debug!(
+            "found GNU Classpath install at {} with natives {:?}",
+            classes.display(),
+            natives
+        );
+        Some(ClasspathInstall { classes, natives })
+    }
+}
+
+/// Next to the executable first for a relocatable install e.g. prefix/bin/jvm, then common prefixes
+fn install_prefixes() -> impl Iterator<Item = PathBuf> {
+    let exe_prefix = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.parent()?.to_owned()));
+
+    exe_prefix
+        .into_iter()
+        .chain(INSTALL_PREFIXES.iter().map(PathBuf::from))
+}
+
 impl ToString for ClassPath {
     fn to_string(&self) -> String {
         self.0.iter().map(|path| path.display()).join(":")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{Manifest, MANIFEST_PATH};
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("jvm-classpath-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, contents) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn classpath_archive_entry() {
+        let dir = temp_dir("archive");
+        let jar = dir.join("classes.jar");
+        write_zip(
+            &jar,
+            &[
+                ("java/lang/Object.class", b"\xca\xfe\xba\xbe"),
+                (MANIFEST_PATH, b"Manifest-Version: 1.0\r\nSealed: true\r\n"),
+            ],
+        );
+
+        let classpath = ClassPath::new(vec![dir.join("missing"), dir.clone(), jar.clone()]);
+        let (bytes, source) = classpath
+            .find_and_load_with_source("java/lang/Object")
+            .unwrap_or_else(|_| panic!("class not found"));
+        assert_eq!(bytes, b"\xca\xfe\xba\xbe");
+        assert_eq!(source, jar.as_path());
+        assert!(matches!(
+            classpath.find_and_load("java/lang/String"),
+            Err(FindClassError::NotFound)
+        ));
+
+        let manifest = Manifest::load_from_entry(&jar).expect("no manifest");
+        assert!(manifest.package_info("java/lang").sealed);
+        assert!(Manifest::load_from_entry(&dir).is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn classpath_archive_handles() {
+        let dir = temp_dir("handles");
+        let jar = dir.join("classes.jar");
+        write_zip(&jar, &[("A.class", b"aaaa"), ("B.class", b"bbbb")]);
+
+        // each handle reads from its own position in the shared file
+        let mut first = open_archive(&jar).expect("not an archive");
+        let mut second = open_archive(&jar).expect("not an archive");
+        let mut a = first.by_name("A.class").unwrap();
+        let mut b = second.by_name("B.class").unwrap();
+        let mut buf = [0; 2];
+        for _ in 0..2 {
+            a.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"aa");
+            b.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"bb");
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn classpath_install_discovery() {
+        let prefix = temp_dir("install");
+        assert!(ClasspathInstall::find_in_prefix(&prefix).is_none());
+
+        // flat install
+        let share = prefix.join("share/classpath");
+        std::fs::create_dir_all(share.join("java/lang")).unwrap();
+        std::fs::write(ClassPath::class_file(&share, "java/lang/Object"), b"").unwrap();
+        let install = ClasspathInstall::find_in_prefix(&prefix).expect("not found");
+        assert_eq!(install.classes, share);
+        assert_eq!(install.natives, None);
+
+        // glibj.zip takes precedence
+        let glibj = share.join("glibj.zip");
+        write_zip(&glibj, &[]);
+        std::fs::create_dir_all(prefix.join("lib/classpath")).unwrap();
+        let install = ClasspathInstall::find_in_prefix(&prefix).expect("not found");
+        assert_eq!(install.classes, glibj);
+        assert_eq!(install.natives, Some(prefix.join("lib/classpath")));
+
+        let _ = std::fs::remove_dir_all(&prefix);
+    }
+}
@@ -1,10 +1,17 @@
-use crate::alloc::{InternedString, NativeString};
-use crate::types::DataType;
-use cafebabe::mutf8::MString;
+use crate::alloc::{InternedString, NativeString, VmRef};
+use crate::class::{Class, Method};
+use crate::error::Throwables;
+use crate::interpreter::InterpreterError;
+use crate::storage::{FieldId, InstanceFieldSlot};
+use crate::types::{DataType, PrimitiveDataType};
+use cafebabe::attribute::{self, BootstrapMethods};
+use cafebabe::mutf8::{MString, StrExt};
 use cafebabe::{
-    ClassError, ClassRefEntry, ClassResult, FieldRefEntry, InterfaceMethodRefEntry, Item,
-    MethodRefEntry,
+    ClassError, ClassRefEntry, ClassResult, DynamicEntry, FieldRefEntry, InterfaceMethodRefEntry,
+    InvokeDynamicEntry, Item, MethodHandleEntry, MethodRefEntry, MethodTypeEntry,
 };
+use num_enum::TryFromPrimitive;
+use parking_lot::RwLock;
 use std::fmt::{Debug, Formatter};
 
 #[derive(Debug)]
@@ -19,27 +26,121 @@ pub enum Entry {
     Long(i64),
     Double(f64),
     Int(i32),
+    MethodHandle(MethodHandle),
+    /// Method descriptor
+    MethodType(NativeString),
+    InvokeDynamic(InvokeDynamic),
+    Dynamic(Dynamic),
 }
 
-// TODO method and field refs should be resolved vtable indices instead of loads of strings
-
 #[derive(Debug)]
 pub struct MethodRef {
     pub class: InternedString,
-    pub name: NativeString,
-    pub desc: NativeString,
+    pub name: InternedString,
+    pub desc: InternedString,
+    pub resolved: Resolution<ResolvedMethod>,
 }
 
 #[derive(Debug)]
 pub struct FieldRef {
     pub class: InternedString,
-    pub name: NativeString,
+    pub name: InternedString,
     pub desc: DataType<'static>,
+    pub resolved: Resolution<ResolvedField>,
 }
 
 #[derive(Debug)]
 pub struct ClassRef {
     pub name: InternedString,
+    pub resolved: Resolution<VmRef<Class>>,
+}
+
+/// Outcome of resolving a symbolic reference (JVMS 5.4.3), kept in its entry so every later
+/// resolution in the same class gives the same result. Failures with a Java exception are kept
+/// and rethrown, but internal errors are not. The outcome is behind a lock rather than set once,
+/// so that unloading can drop the references it holds without exclusive access to the class
+pub struct Resolution<T>(RwLock<Option<Result<T, Throwables>>>);
+
+/// Method found by method or interface method resolution
+#[derive(Clone)]
+pub struct ResolvedMethod {
+    /// Class or interface named by the reference
+    pub class: VmRef<Class>,
+    /// Declared by `class` or one of its supers
+    pub method: VmRef<Method>,
+}
+
+/// Field found by field resolution
+#[derive(Clone)]
+pub struct ResolvedField {
+    /// Class or interface named by the reference
+    pub class: VmRef<Class>,
+    pub location: FieldLocation,
+}
+
+#[derive(Clone)]
+pub enum FieldLocation {
+    /// Valid for instances of the named class and all its subclasses
+    Instance(InstanceFieldSlot),
+    /// Stored in the given class, which may be a super of the named class
+    Static(VmRef<Class>, FieldId),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum ReferenceKind {
+    GetField = 1,
+    GetStatic = 2,
+    PutField = 3,
+    PutStatic = 4,
+    InvokeVirtual = 5,
+    InvokeStatic = 6,
+    InvokeSpecial = 7,
+    NewInvokeSpecial = 8,
+    InvokeInterface = 9,
+}
+
+impl ReferenceKind {
+    /// Whether the handle accesses a field rather than invoking a method
+    pub fn is_field(self) -> bool {
+        matches!(
+            self,
+            ReferenceKind::GetField
+                | ReferenceKind::GetStatic
+                | ReferenceKind::PutField
+                | ReferenceKind::PutStatic
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct MethodHandle {
+    pub kind: ReferenceKind,
+    pub class: InternedString,
+    pub name: InternedString,
+    /// Field or method descriptor depending on the kind
+    pub desc: NativeString,
+}
+
+#[derive(Debug)]
+pub struct InvokeDynamic {
+    /// Method handle entry of the bootstrap method
+    pub bootstrap_method: u16,
+    /// Loadable entries passed as static arguments to the bootstrap method
+    pub bootstrap_args: Box<[u16]>,
+    pub name: InternedString,
+    pub desc: NativeString,
+}
+
+/// Dynamically computed constant
+#[derive(Debug)]
+pub struct Dynamic {
+    /// Method handle entry of the bootstrap method
+    pub bootstrap_method: u16,
+    /// Loadable entries passed as static arguments to the bootstrap method
+    pub bootstrap_args: Box<[u16]>,
+    pub name: InternedString,
+    pub desc: DataType<'static>,
 }
 
 pub struct RuntimeConstantPool(Vec<Option<Entry>>);
@@ -55,7 +156,10 @@ impl RuntimeConstantPool {
         RuntimeConstantPool(Vec::new())
     }
 
-    pub fn from_cafebabe(pool: &cafebabe::ConstantPool) -> ClassResult<Self> {
+    pub fn from_cafebabe(
+        pool: &cafebabe::ConstantPool,
+        bootstrap_methods: &BootstrapMethods,
+    ) -> ClassResult<Self> {
         let mut my_pool = Self::with_size(pool.size());
 
         for (idx, item) in pool.entries() {
@@ -69,9 +173,10 @@ impl RuntimeConstantPool {
                     my_pool.put_entry(
                         idx,
                         Entry::MethodRef(MethodRef {
-                            class: methodref.class.to_owned(),
-                            name: methodref.name.to_owned(),
-                            desc: methodref.desc.to_owned(),
+                            class: InternedString::intern(methodref.class),
+                            name: InternedString::intern(methodref.name),
+                            desc: InternedString::intern(methodref.desc),
+                            resolved: Resolution::default(),
                         }),
                     );
                 }
@@ -80,9 +185,10 @@ impl RuntimeConstantPool {
                     my_pool.put_entry(
                         idx,
                         Entry::InterfaceMethodRef(MethodRef {
-                            class: methodref.class.to_owned(),
-                            name: methodref.name.to_owned(),
-                            desc: methodref.desc.to_owned(),
+                            class: InternedString::intern(methodref.class),
+                            name: InternedString::intern(methodref.name),
+                            desc: InternedString::intern(methodref.desc),
+                            resolved: Resolution::default(),
                         }),
                     );
                 }
@@ -91,13 +197,14 @@ impl RuntimeConstantPool {
                     my_pool.put_entry(
                         idx,
                         Entry::FieldRef(FieldRef {
-                            class: fieldref.class.to_owned(),
-                            name: fieldref.name.to_owned(),
+                            class: InternedString::intern(fieldref.class),
+                            name: InternedString::intern(fieldref.name),
                             desc: DataType::from_descriptor(fieldref.desc)
                                 .ok_or_else(|| {
                                     ClassError::TypeDescriptor(fieldref.desc.to_owned())
                                 })?
                                 .to_owned(),
+                            resolved: Resolution::default(),
                         }),
                     );
                 }
@@ -106,7 +213,8 @@ impl RuntimeConstantPool {
                     my_pool.put_entry(
                         idx,
                         Entry::ClassRef(ClassRef {
-                            name: classref.name.to_owned(),
+                            name: InternedString::intern(classref.name),
+                            resolved: Resolution::default(),
                         }),
                     );
                 }
@@ -114,6 +222,51 @@ impl RuntimeConstantPool {
                 Item::Long { long } => my_pool.put_entry(idx, Entry::Long(*long)),
                 Item::Double { double } => my_pool.put_entry(idx, Entry::Double(*double)),
                 Item::Integer { int } => my_pool.put_entry(idx, Entry::Int(*int)),
+                Item::MethodHandle { .. } => {
+                    let handle = pool.entry::<MethodHandleEntry>(idx)?;
+                    my_pool.put_entry(
+                        idx,
+                        Entry::MethodHandle(MethodHandle {
+                            kind: ReferenceKind::try_from_primitive(handle.kind)
+                                .map_err(|_| ClassError::ReferenceKind(handle.kind))?,
+                            class: InternedString::intern(handle.class),
+                            name: InternedString::intern(handle.name),
+                            desc: handle.desc.to_owned(),
+                        }),
+                    );
+                }
+                Item::MethodType { .. } => {
+                    let method_type = pool.entry::<MethodTypeEntry>(idx)?;
+                    my_pool.put_entry(idx, Entry::MethodType(method_type.desc.to_owned()));
+                }
+                Item::InvokeDynamic { .. } => {
+                    let indy = pool.entry::<InvokeDynamicEntry>(idx)?;
+                    let bootstrap = bootstrap_method(bootstrap_methods, indy.bootstrap_method)?;
+                    my_pool.put_entry(
+                        idx,
+                        Entry::InvokeDynamic(InvokeDynamic {
+                            bootstrap_method: bootstrap.method_handle,
+                            bootstrap_args: bootstrap.arguments.clone(),
+                            name: InternedString::intern(indy.name),
+                            desc: indy.desc.to_owned(),
+                        }),
+                    );
+                }
+                Item::Dynamic { .. } => {
+                    let condy = pool.entry::<DynamicEntry>(idx)?;
+                    let bootstrap = bootstrap_method(bootstrap_methods, condy.bootstrap_method)?;
+                    my_pool.put_entry(
+                        idx,
+                        Entry::Dynamic(Dynamic {
+                            bootstrap_method: bootstrap.method_handle,
+                            bootstrap_args: bootstrap.arguments.clone(),
+                            name: InternedString::intern(condy.name),
+                            desc: DataType::from_descriptor(condy.desc)
+                                .ok_or_else(|| ClassError::TypeDescriptor(condy.desc.to_owned()))?
+                                .to_owned(),
+                        }),
+                    );
+                }
 
                 _ => continue,
             }
@@ -122,13 +275,25 @@ impl RuntimeConstantPool {
         Ok(my_pool)
     }
 
-    fn entries(&self) -> impl Iterator<Item = (usize, &Entry)> {
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &Entry)> {
         self.0
             .iter()
             .enumerate()
             .filter_map(|(i, item)| item.as_ref().map(|item| ((i + 1), item)))
     }
 
+    /// Forgets all resolved references, for class unloading
+    pub fn clear_resolutions(&self) {
+        for entry in self.0.iter().flatten() {
+            match entry {
+                Entry::MethodRef(m) | Entry::InterfaceMethodRef(m) => m.resolved.clear(),
+                Entry::FieldRef(f) => f.resolved.clear(),
+                Entry::ClassRef(c) => c.resolved.clear(),
+                _ => {}
+            }
+        }
+    }
+
     fn put_entry(&mut self, idx: u16, entry: Entry) {
         // adjust for 1-indexing
         let idx = (idx - 1) as usize;
@@ -181,6 +346,137 @@ impl RuntimeConstantPool {
             _ => None,
         })
     }
+
+    pub fn method_handle_entry(&self, idx: u16) -> Option<&MethodHandle> {
+        self.entry(idx).and_then(|e| match e {
+            Entry::MethodHandle(h) => Some(h),
+            _ => None,
+        })
+    }
+
+    pub fn invokedynamic_entry(&self, idx: u16) -> Option<&InvokeDynamic> {
+        self.entry(idx).and_then(|e| match e {
+            Entry::InvokeDynamic(i) => Some(i),
+            _ => None,
+        })
+    }
+}
+
+impl<T: Clone> Resolution<T> {
+    /// Result of the first completed resolution. Resolution itself doesn't hold the lock, as it
+    /// may load classes that resolve this entry again, so racing threads may both resolve it but
+    /// only the first to finish is kept. Only LinkageErrors are kept as the failed result
+    /// (JVMS 5.4.3), anything else e.g. an OutOfMemoryError is thrown and resolution is attempted
+    /// again next time
+    pub fn get_or_resolve(
+        &self,
+        resolve: impl FnOnce() -> Result<T, InterpreterError>,
+    ) -> Result<T, InterpreterError> {
+        if let Some(result) = self.0.read().as_ref() {
+            return Self::outcome(result);
+        }
+
+        let result = match resolve() {
+            Ok(value) => Ok(value),
+            Err(InterpreterError::ExceptionRaised(exc)) if is_linkage_error(&exc) => Err(exc),
+            Err(err) => return Err(err),
+        };
+
+        let mut resolved = self.0.write();
+        Self::outcome(resolved.get_or_insert(result))
+    }
+
+    /// Value if successfully resolved
+    pub fn value(&self) -> Option<T> {
+        self.0
+            .read()
+            .as_ref()
+            .and_then(|result| result.as_ref().ok().cloned())
+    }
+
+    /// Exception to rethrow if resolution failed
+    pub fn error(&self) -> Option<Throwables> {
+        self.0
+            .read()
+            .as_ref()
+            .and_then(|result| result.as_ref().err().cloned())
+    }
+
+    fn outcome(result: &Result<T, Throwables>) -> Result<T, InterpreterError> {
+        result.clone().map_err(InterpreterError::ExceptionRaised)
+    }
+}
+
+impl<T> Resolution<T> {
+    fn clear(&self) {
+        self.0.write().take();
+    }
+}
+
+impl<T> Default for Resolution<T> {
+    fn default() -> Self {
+        Resolution(RwLock::new(None))
+    }
+}
+
+impl<T> Debug for Resolution<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &*self.0.read() {
+            None => write!(f, "Unresolved"),
+            Some(Ok(_)) => write!(f, "Resolved"),
+            Some(Err(exc)) => write!(f, "Failed({})", exc.symbol()),
+        }
+    }
+}
+
+/// Subclasses of java/lang/LinkageError that resolution can throw
+const LINKAGE_ERRORS: &[&str] = &[
+    "java/lang/LinkageError",
+    "java/lang/NoClassDefFoundError",
+    "java/lang/ClassFormatError",
+    "java/lang/UnsupportedClassVersionError",
+    "java/lang/ClassCircularityError",
+    "java/lang/IncompatibleClassChangeError",
+    "java/lang/NoSuchFieldError",
+    "java/lang/NoSuchMethodError",
+    "java/lang/IllegalAccessError",
+    "java/lang/AbstractMethodError",
+    "java/lang/InstantiationError",
+];
+
+/// If a resolution failure must be rethrown on every later resolution attempt
+fn is_linkage_error(exc: &Throwables) -> bool {
+    match exc {
+        Throwables::NoClassDefFoundError(_)
+        | Throwables::LinkageError
+        | Throwables::ClassFormatError
+        | Throwables::UnsupportedClassVersionError
+        | Throwables::NoSuchFieldError(_) => true,
+        Throwables::Other(name) | Throwables::WithMessage(name, _) => LINKAGE_ERRORS.contains(name),
+        Throwables::Thrown(exc) => exc
+            .object
+            .as_ref()
+            .and_then(|obj| obj.class())
+            .map(|cls| cls.extends_by_name("java/lang/LinkageError".as_mstr()))
+            .unwrap_or_else(|| LINKAGE_ERRORS.contains(&exc.class_name)),
+        Throwables::ExceptionInInitializerError(_)
+        | Throwables::ClassNotFoundException(_)
+        | Throwables::OutOfMemoryError
+        | Throwables::NullPointerException
+        | Throwables::IoError => false,
+    }
+}
+
+fn bootstrap_method(
+    bootstrap_methods: &BootstrapMethods,
+    idx: u16,
+) -> ClassResult<&attribute::BootstrapMethod> {
+    bootstrap_methods
+        .0
+        .get(idx as usize)
+        .ok_or(ClassError::AttributeFormat(
+            "bootstrap method index out of range",
+        ))
 }
 
 impl Entry {
@@ -201,26 +497,40 @@ impl Entry {
             | Entry::FieldRef(_)
             | Entry::ClassRef(_)
             | Entry::Int(_)
-            | Entry::Float(_) => true,
+            | Entry::Float(_)
+            | Entry::MethodHandle(_)
+            | Entry::MethodType(_) => true,
 
-            Entry::Long(_) | Entry::Double(_) => false,
+            Entry::Dynamic(condy) => !is_wide(&condy.desc),
+
+            Entry::Long(_) | Entry::Double(_) | Entry::InvokeDynamic(_) => false,
         }
     }
     pub fn is_loadable_wide(&self) -> bool {
         match self {
             Entry::Double(_) | Entry::Long(_) => true,
-            // TODO OR A symbolic reference to a dynamically-computed constant whose field descriptor is J (denoting long) or D (denoting double)
+            Entry::Dynamic(condy) => is_wide(&condy.desc),
             Entry::String(_)
             | Entry::MethodRef(_)
             | Entry::InterfaceMethodRef(_)
             | Entry::FieldRef(_)
             | Entry::ClassRef(_)
             | Entry::Int(_)
-            | Entry::Float(_) => false,
+            | Entry::Float(_)
+            | Entry::MethodHandle(_)
+            | Entry::MethodType(_)
+            | Entry::InvokeDynamic(_) => false,
         }
     }
 }
 
+fn is_wide(ty: &DataType) -> bool {
+    matches!(
+        ty,
+        DataType::Primitive(PrimitiveDataType::Long | PrimitiveDataType::Double)
+    )
+}
+
 impl Debug for RuntimeConstantPool {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "RuntimeConstantPool(")?;
@@ -228,3 +538,76 @@ impl Debug for RuntimeConstantPool {
         write!(f, ")")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::constant_pool::Resolution;
+    use crate::error::Throwables;
+    use crate::interpreter::InterpreterError;
+
+    #[test]
+    fn resolution_cached() {
+        let resolution = Resolution::default();
+        let calls = Cell::new(0);
+        let resolve = || {
+            calls.set(calls.get() + 1);
+            Ok(5)
+        };
+
+        assert_eq!(resolution.get_or_resolve(resolve).ok(), Some(5));
+        assert_eq!(resolution.get_or_resolve(|| Ok(6)).ok(), Some(5));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn resolution_errors() {
+        // internal errors are not kept
+        let resolution = Resolution::<i32>::default();
+        assert!(matches!(
+            resolution.get_or_resolve(|| Err(InterpreterError::NotClassRef(1))),
+            Err(InterpreterError::NotClassRef(1))
+        ));
+        assert!(resolution.error().is_none());
+
+        // but exceptions are rethrown every time
+        let exc = Throwables::NoClassDefFoundError("Missing".to_owned());
+        assert!(resolution.get_or_resolve(|| Err(exc.into())).is_err());
+        assert!(matches!(
+            resolution.get_or_resolve(|| Ok(1)),
+            Err(InterpreterError::ExceptionRaised(Throwables::NoClassDefFoundError(name))) if name == "Missing"
+        ));
+        assert!(resolution.value().is_none());
+    }
+
+    #[test]
+    fn resolution_linkage_errors_only() {
+        // other exceptions are thrown but resolution is retried
+        let resolution = Resolution::<i32>::default();
+        assert!(matches!(
+            resolution.get_or_resolve(|| Err(Throwables::OutOfMemoryError.into())),
+            Err(InterpreterError::ExceptionRaised(
+                Throwables::OutOfMemoryError
+            ))
+        ));
+        assert!(resolution.error().is_none());
+        assert!(matches!(resolution.get_or_resolve(|| Ok(2)), Ok(2)));
+
+        // linkage errors with messages are kept
+        let resolution = Resolution::<i32>::default();
+        let icce = || Throwables::incompatible_class_change("Expected static method");
+        assert!(resolution.get_or_resolve(|| Err(icce().into())).is_err());
+        assert!(resolution.get_or_resolve(|| Ok(3)).is_err());
+        assert_eq!(
+            resolution.error().as_ref().map(Throwables::symbol),
+            Some("java/lang/IncompatibleClassChangeError")
+        );
+
+        // but not other exceptions with messages
+        let resolution = Resolution::<i32>::default();
+        let exc = Throwables::WithMessage("java/lang/InternalError", "oops".to_owned());
+        assert!(resolution.get_or_resolve(|| Err(exc.into())).is_err());
+        assert!(matches!(resolution.get_or_resolve(|| Ok(4)), Ok(4)));
+    }
+}
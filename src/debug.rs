@@ -1,36 +1,310 @@
-use cafebabe::mutf8::mstr;
+//! Class loading, linking and initialisation event log for `-verbose:class` and `-Xlog:class`
+
+use std::fmt::Write as _;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use cafebabe::mutf8::mstr;
+
+use crate::class::WhichLoader;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClassEventKind {
+    /// Class file found and parsed, or array class created
+    Load,
+    /// Includes loading and linking of superclasses and superinterfaces
+    Link,
+    /// Includes initialisation of superclasses and superinterfaces
+    Init,
+}
 
-pub struct ClassLoadGraph(Box<dyn Write>);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ClassLogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+    /// Graph of which class caused which to be loaded
+    Dot,
+}
+
+/// `format[:file]`, logging to stdout if no file is given
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ClassLogConfig {
+    pub format: ClassLogFormat,
+    pub path: Option<PathBuf>,
+}
+
+pub struct ClassEvent<'a> {
+    pub kind: ClassEventKind,
+    pub class: &'a mstr,
+    /// Defining loader
+    pub loader: &'a WhichLoader,
+    /// Classpath entry the class was loaded from
+    pub source: Option<&'a Path>,
+    /// Class that caused this class to be loaded
+    pub initiator: Option<&'a mstr>,
+    pub duration: Duration,
+    pub failed: bool,
+}
+
+pub struct ClassEventLog {
+    format: ClassLogFormat,
+    start: Instant,
+    out: Mutex<LogOutput>,
+}
 
-impl ClassLoadGraph {
-    pub fn with_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
-        let write = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(path.as_ref())?;
-        let mut this = ClassLoadGraph(Box::new(write));
-        this.init()?;
-        Ok(this)
+struct LogOutput {
+    writer: Box<dyn Write + Send>,
+    /// Set by [ClassEventLog::finish], after which events are dropped
+    finished: bool,
+}
+
+impl FromStr for ClassLogConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = match s.split_once(':') {
+            Some((format, path)) => (format, Some(PathBuf::from(path))),
+            None => (s, None),
+        };
+
+        let format = match format {
+            "text" | "" => ClassLogFormat::Text,
+            "json" => ClassLogFormat::Json,
+            "dot" => ClassLogFormat::Dot,
+            _ => return Err(format!("invalid class log format {:?}", format)),
+        };
+
+        Ok(ClassLogConfig { format, path })
     }
+}
 
-    fn init(&mut self) -> std::io::Result<()> {
-        self.0.write_all(b"digraph {\n")
+impl ClassEventKind {
+    fn name(self) -> &'static str {
+        match self {
+            ClassEventKind::Load => "load",
+            ClassEventKind::Link => "link",
+            ClassEventKind::Init => "init",
+        }
     }
+}
 
-    pub fn register_class_load(&mut self, class: &mstr, initiator: Option<&mstr>) {
-        let _ = match initiator {
-            Some(parent) => writeln!(&mut self.0, "{:?} -> {:?}\n", parent, class),
-            None => writeln!(&mut self.0, "{:?}\n", class),
+impl ClassEventLog {
+    pub fn open(config: &ClassLogConfig) -> std::io::Result<Self> {
+        // unbuffered so events aren't lost if the vm dies, each event is a single write
+        let out: Box<dyn Write + Send> = match &config.path {
+            Some(path) => Box::new(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)?,
+            ),
+            None => Box::new(std::io::stdout()),
         };
-        // TODO log IO error
+
+        Ok(Self::with_writer(config.format, out))
+    }
+
+    fn with_writer(format: ClassLogFormat, mut out: Box<dyn Write + Send>) -> Self {
+        if format == ClassLogFormat::Dot {
+            let _ = out.write_all(b"digraph {\n");
+        }
+
+        ClassEventLog {
+            format,
+            start: Instant::now(),
+            out: Mutex::new(LogOutput {
+                writer: out,
+                finished: false,
+            }),
+        }
+    }
+
+    /// Terminates and flushes the log at shutdown. Later events are ignored
+    pub fn finish(&self) {
+        let mut out = self.out.lock();
+        if std::mem::replace(&mut out.finished, true) {
+            return;
+        }
+
+        if self.format == ClassLogFormat::Dot {
+            let _ = out.writer.write_all(b"}\n");
+        }
+        let _ = out.writer.flush();
+    }
+
+    pub fn record(&self, event: &ClassEvent) {
+        let mut line = String::new();
+        let elapsed = self.start.elapsed();
+        let _ = match self.format {
+            ClassLogFormat::Text => {
+                let _ = write!(
+                    &mut line,
+                    "[{:.6}s][{}] {} loader: {}",
+                    elapsed.as_secs_f64(),
+                    event.kind.name(),
+                    event.class,
+                    event.loader,
+                );
+                if let Some(source) = event.source {
+                    let _ = write!(&mut line, " source: {}", source.display());
+                }
+                if let Some(initiator) = event.initiator {
+                    let _ = write!(&mut line, " initiator: {}", initiator);
+                }
+                writeln!(
+                    &mut line,
+                    " time: {}us{}",
+                    event.duration.as_micros(),
+                    if event.failed { " FAILED" } else { "" }
+                )
+            }
+            ClassLogFormat::Json => {
+                let string = |s: Option<String>| match s {
+                    Some(s) => json_string(&s),
+                    None => "null".to_owned(),
+                };
+                writeln!(
+                    &mut line,
+                    r#"{{"time_us":{},"event":"{}","class":{},"loader":{},"source":{},"initiator":{},"duration_us":{},"failed":{}}}"#,
+                    elapsed.as_micros(),
+                    event.kind.name(),
+                    json_string(&event.class.to_utf8()),
+                    json_string(&event.loader.to_string()),
+                    string(event.source.map(|p| p.display().to_string())),
+                    string(event.initiator.map(|s| s.to_utf8().into_owned())),
+                    event.duration.as_micros(),
+                    event.failed,
+                )
+            }
+            ClassLogFormat::Dot if event.kind == ClassEventKind::Load => match event.initiator {
+                Some(initiator) => writeln!(&mut line, "{:?} -> {:?}", initiator, event.class),
+                None => writeln!(&mut line, "{:?}", event.class),
+            },
+            ClassLogFormat::Dot => return,
+        };
+
+        // events from different threads are written whole
+        let mut out = self.out.lock();
+        if !out.finished {
+            let _ = out.writer.write_all(line.as_bytes());
+        }
     }
 }
 
-impl Drop for ClassLoadGraph {
+impl Drop for ClassEventLog {
     fn drop(&mut self) {
-        let _ = self.0.write_all(b"\n}");
+        self.finish();
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(&mut out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Cloneable writer to read back what was logged
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_events(format: ClassLogFormat) -> String {
+        let buf = SharedBuf::default();
+        let log = ClassEventLog::with_writer(format, Box::new(buf.clone()));
+
+        let source = PathBuf::from("/classpath");
+        let mut event = ClassEvent {
+            kind: ClassEventKind::Load,
+            class: mstr::from_literal("java/lang/String"),
+            loader: &WhichLoader::Bootstrap,
+            source: Some(&source),
+            initiator: Some(mstr::from_literal("java/lang/Object")),
+            duration: Duration::from_micros(50),
+            failed: false,
+        };
+        log.record(&event);
+
+        event.kind = ClassEventKind::Init;
+        event.initiator = None;
+        event.failed = true;
+        log.record(&event);
+
+        // ignored after shutdown
+        log.finish();
+        event.kind = ClassEventKind::Load;
+        log.record(&event);
+
+        drop(log);
+        let bytes = buf.0.lock().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn class_log_config() {
+        let parse = |s: &str| s.parse::<ClassLogConfig>();
+        assert_eq!(parse("text"), Ok(ClassLogConfig::default()));
+        assert_eq!(
+            parse("dot:/tmp/classes.dot"),
+            Ok(ClassLogConfig {
+                format: ClassLogFormat::Dot,
+                path: Some(PathBuf::from("/tmp/classes.dot")),
+            })
+        );
+        assert!(parse("xml").is_err());
+    }
+
+    #[test]
+    fn class_log_formats() {
+        let text = log_events(ClassLogFormat::Text);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("[load] java/lang/String loader: bootstrap source: /classpath initiator: java/lang/Object time: 50us"));
+        assert!(lines[1].ends_with(
+            "[init] java/lang/String loader: bootstrap source: /classpath time: 50us FAILED"
+        ));
+
+        let json = log_events(ClassLogFormat::Json);
+        let first = json.lines().next().unwrap();
+        assert!(first.contains(r#""event":"load","class":"java/lang/String","loader":"bootstrap","source":"/classpath","initiator":"java/lang/Object","duration_us":50,"failed":false}"#));
+
+        let dot = log_events(ClassLogFormat::Dot);
+        assert_eq!(
+            dot,
+            "digraph {\n\"java/lang/Object\" -> \"java/lang/String\"\n}\n"
+        );
     }
 }
@@ -1,9 +1,32 @@
-use std::fmt::{Debug, Formatter};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt::{Debug, Display, Formatter};
+use std::iter::{empty, once};
 
+use log::*;
 use thiserror::*;
 
-use crate::alloc::{vmref_alloc_exception, VmRef};
+use cafebabe::mutf8::StrExt;
+use cafebabe::MethodAccessFlags;
+
+use crate::alloc::{vmref_alloc_exception, vmref_eq, InternedString, VmRef};
+use crate::class::{Object, WhichLoader};
+use crate::interpreter::StackTrace;
+use crate::share::ShareError;
 use crate::thread;
+use crate::types::{DataType, DataValue};
+
+/// Limit on causes printed, in case of a cycle
+const MAX_CAUSES: usize = 16;
+
+/// Limit on VM-raised exceptions being instantiated at once by a thread, i.e. raised by the
+/// constructors of others
+const MAX_NESTED_INSTANTIATIONS: usize = 8;
+
+thread_local! {
+    /// Classes of the VM-raised exceptions currently being instantiated, innermost last
+    static INSTANTIATING: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
 
 pub type JvmResult<T> = Result<T, JvmError>;
 
@@ -12,6 +35,12 @@ pub type JvmResult<T> = Result<T, JvmError>;
 pub enum JvmError {
     #[error("Exception thrown: {0:?}")]
     ExceptionThrown(VmRef<Throwable>),
+
+    #[error("Shared archive: {0}")]
+    SharedArchive(#[from] ShareError),
+
+    #[error("Failed to open class log: {0}")]
+    ClassLog(std::io::Error),
 }
 
 pub type VmResult<T> = Result<T, Throwables>;
@@ -19,24 +48,35 @@ pub type VmResult<T> = Result<T, Throwables>;
 /// Well-known throwables
 #[derive(Debug, Clone)]
 pub enum Throwables {
-    NoClassDefFoundError,
+    /// Name of the class
+    NoClassDefFoundError(String),
+    /// Exception thrown by the static initializer
+    ExceptionInInitializerError(VmRef<Throwable>),
     LinkageError,
-    ClassNotFoundException,
+    /// Name of the class
+    ClassNotFoundException(String),
     ClassFormatError,
     UnsupportedClassVersionError,
     OutOfMemoryError,
     NullPointerException,
-    NoSuchFieldError,
+    /// Name of the field
+    NoSuchFieldError(String),
     IoError,
     Other(&'static str),
+    /// Class name and detail message
+    WithMessage(&'static str, String),
+    /// Already instantiated, e.g. thrown from Java or JNI
+    Thrown(VmRef<Throwable>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Throwable {
     // TODO reference to class instead of name
-    // TODO reference to cause
     // TODO backtrace
     pub class_name: &'static str,
+    /// Instance of java/lang/Throwable. Only None if instantiation itself failed, e.g. during
+    /// bootstrap before the exception classes can be loaded
+    pub object: Option<VmRef<Object>>,
 }
 
 pub trait ResultExt<T> {
@@ -58,20 +98,240 @@ impl<T> ResultExt<T> for VmResult<T> {
 }
 
 impl Throwables {
+    /// NullPointerException describing the operation that failed, e.g. `Cannot read field "x"`
+    pub fn null_pointer(action: impl Display) -> Self {
+        Throwables::WithMessage(
+            "java/lang/NullPointerException",
+            format!("Cannot {}", action),
+        )
+    }
+
+    /// IncompatibleClassChangeError, e.g. when an instruction finds a static member where it
+    /// expects an instance one
+    pub fn incompatible_class_change(message: impl Display) -> Self {
+        Throwables::WithMessage(
+            "java/lang/IncompatibleClassChangeError",
+            message.to_string(),
+        )
+    }
+
     pub fn symbol(&self) -> &'static str {
         match self {
-            Throwables::NoClassDefFoundError => "java/lang/NoClassDefFoundError",
+            Throwables::NoClassDefFoundError(_) => "java/lang/NoClassDefFoundError",
+            Throwables::ExceptionInInitializerError(_) => "java/lang/ExceptionInInitializerError",
             Throwables::LinkageError => "java/lang/LinkageError",
-            Throwables::ClassNotFoundException => "java/lang/ClassNotFoundException",
+            Throwables::ClassNotFoundException(_) => "java/lang/ClassNotFoundException",
             Throwables::ClassFormatError => "java/lang/ClassFormatError",
             Throwables::UnsupportedClassVersionError => "java/lang/UnsupportedClassVersionError",
             Throwables::OutOfMemoryError => "java/lang/OutOfMemoryError",
             Throwables::NullPointerException => "java/lang/NullPointerException",
-            Throwables::NoSuchFieldError => "java/lang/NoSuchFieldError",
+            Throwables::NoSuchFieldError(_) => "java/lang/NoSuchFieldError",
             Throwables::IoError => "java/io/IOError",
-            Throwables::Other(s) => s,
+            Throwables::Other(s) | Throwables::WithMessage(s, _) => s,
+            Throwables::Thrown(exc) => exc.class_name,
+        }
+    }
+
+    pub fn message(&self) -> Option<Cow<str>> {
+        match self {
+            Throwables::NoClassDefFoundError(msg)
+            | Throwables::NoSuchFieldError(msg)
+            | Throwables::WithMessage(_, msg) => Some(Cow::Borrowed(msg)),
+            // binary name is expected
+            Throwables::ClassNotFoundException(name) => Some(Cow::Owned(name.replace('/', "."))),
+            Throwables::Thrown(exc) => exc.message().map(Cow::Owned),
+            _ => None,
+        }
+    }
+
+    /// Instantiates the exception object, passing the message and cause to its constructor
+    fn instantiate(&self) -> VmResult<VmRef<Object>> {
+        let thread = thread::get();
+        let helper = thread.exec_helper();
+        let class_loader = thread.global().class_loader();
+        let class = class_loader.load_class(self.symbol().as_mstr(), WhichLoader::Bootstrap)?;
+
+        let message = match self.message() {
+            Some(msg) => Some(Object::new_string_utf8(&msg)?),
+            None => None,
+        };
+        let cause = match self {
+            Throwables::ExceptionInInitializerError(exc) => exc.object.clone(),
+            _ => None,
+        };
+
+        match (message, cause) {
+            (Some(msg), _) => helper.instantiate_and_invoke_constructor(
+                class,
+                "(Ljava/lang/String;)V",
+                once(DataValue::Reference(msg)),
+            ),
+            (None, Some(cause)) => helper.instantiate_and_invoke_constructor(
+                class,
+                "(Ljava/lang/Throwable;)V",
+                once(DataValue::Reference(cause)),
+            ),
+            (None, None) => helper.instantiate_and_invoke_constructor(class, "()V", empty()),
+        }
+    }
+}
+
+impl Throwable {
+    /// Instantiates a VM-raised exception as a Java object
+    pub(crate) fn new(throwable: Throwables) -> VmRef<Self> {
+        if let Throwables::Thrown(exc) = throwable {
+            return exc;
+        }
+
+        // the constructor might itself raise exceptions, which are instantiated as usual so they
+        // can be caught within it. Only raising the same exception again would recurse forever
+        let class_name = throwable.symbol();
+        let recursive = INSTANTIATING.with(|stack| {
+            let stack = stack.borrow();
+            stack.len() >= MAX_NESTED_INSTANTIATIONS || stack.contains(&class_name)
+        });
+
+        let object = if recursive {
+            warn!(
+                "not instantiating recursively raised exception {:?}",
+                throwable
+            );
+            None
+        } else {
+            INSTANTIATING.with(|stack| stack.borrow_mut().push(class_name));
+            let result = throwable.instantiate();
+            INSTANTIATING.with(|stack| stack.borrow_mut().pop());
+            match result {
+                Ok(obj) => Some(obj),
+                Err(err) => {
+                    warn!("failed to instantiate {:?}: {:?}", throwable, err);
+                    None
+                }
+            }
+        };
+
+        VmRef::new(Throwable { class_name, object })
+    }
+
+    /// Exception object thrown by `athrow` or JNI. Must not be null
+    pub fn from_object(object: VmRef<Object>) -> VmRef<Self> {
+        let class = object.class_not_null();
+        let name = InternedString::intern(class.name());
+        let class_name =
+            std::str::from_utf8(name.as_mstr().as_bytes()).unwrap_or("java/lang/Throwable");
+
+        VmRef::new(Throwable {
+            class_name,
+            object: Some(object),
+        })
+    }
+
+    /// Detail message passed to the constructor
+    pub fn message(&self) -> Option<String> {
+        let message = self.object.as_ref()?.find_instance_field(
+            "detailMessage".as_mstr(),
+            &DataType::Reference(Cow::Borrowed("java/lang/String".as_mstr())),
+        )?;
+
+        let message = message.as_reference()?;
+        if message.is_null() {
+            None
+        } else {
+            message.string_value_utf8()
+        }
+    }
+
+    /// Prints to stderr with `Throwable.printStackTrace`, or if that isn't possible then from
+    /// the VM's view of the exception and its captured stack trace
+    pub fn print_stack_trace(&self) {
+        if let Some(object) = self.object.as_ref() {
+            let thread = thread::get();
+            let printed = thread
+                .global()
+                .class_loader()
+                .get_bootstrap_class("java/lang/Throwable")
+                .find_callable_method(
+                    "printStackTrace".as_mstr(),
+                    "()V".as_mstr(),
+                    MethodAccessFlags::empty(),
+                )
+                .and_then(|method| {
+                    thread
+                        .exec_helper()
+                        .invoke_virtual(object.clone(), method, empty())
+                });
+
+            match printed {
+                Ok(_) => return,
+                Err(err) => warn!("printStackTrace failed: {:?}", err),
+            }
+        }
+
+        eprintln!("Exception {:?}", self);
+        for frame in self.stack_trace().iter().flat_map(|trace| trace.frames()) {
+            eprintln!("\tat {:?}", frame);
+        }
+    }
+
+    /// Captured when the exception object was created, if it was
+    fn stack_trace(&self) -> Option<VmRef<StackTrace>> {
+        let vm_state = self.object.as_ref()?.find_instance_field(
+            "vmState".as_mstr(),
+            &DataType::Reference(Cow::Borrowed("java/lang/VMThrowable".as_mstr())),
+        )?;
+
+        let vmdata = vm_state.as_reference()?.find_instance_field(
+            "vmdata".as_mstr(),
+            &DataType::Reference(Cow::Borrowed("java/lang/Object".as_mstr())),
+        )?;
+
+        match vmdata {
+            DataValue::VmDataStackTrace(trace) => Some(trace),
+            _ => None,
         }
     }
+
+    /// None if there is no cause, or it is unknown
+    pub fn cause(&self) -> Option<VmRef<Throwable>> {
+        let object = self.object.as_ref()?;
+        let cause = object.find_instance_field(
+            "cause".as_mstr(),
+            &DataType::Reference(Cow::Borrowed("java/lang/Throwable".as_mstr())),
+        )?;
+
+        // initialised to itself until set
+        let cause = cause.as_reference()?;
+        if cause.is_null() || vmref_eq(cause, object) {
+            None
+        } else {
+            Some(Throwable::from_object(cause.clone()))
+        }
+    }
+}
+
+impl Debug for Throwable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.class_name)?;
+        if let Some(msg) = self.message() {
+            write!(f, ": {}", msg)?;
+        }
+
+        let mut cause = self.cause();
+        for _ in 0..MAX_CAUSES {
+            match cause {
+                Some(exc) => {
+                    write!(f, ", caused by {}", exc.class_name)?;
+                    if let Some(msg) = exc.message() {
+                        write!(f, ": {}", msg)?;
+                    }
+                    cause = exc.cause();
+                }
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Debug for JvmError {
@@ -85,3 +345,9 @@ impl From<Throwables> for VmRef<Throwable> {
         vmref_alloc_exception(exc)
     }
 }
+
+impl From<VmRef<Throwable>> for Throwables {
+    fn from(exc: VmRef<Throwable>) -> Self {
+        Throwables::Thrown(exc)
+    }
+}
@@ -99,6 +99,31 @@ impl<'a> ExecHelper<'a> {
         self.invoke_method(method, Option::<DataValue>::None, args)
     }
 
+    /// Invokes the method selected from the receiver's class for the given method, as
+    /// invokevirtual does. Args should be in decl order
+    pub fn invoke_virtual(
+        &self,
+        obj: VmRef<Object>,
+        method: VmRef<Method>,
+        args: impl DoubleEndedIterator<Item = DataValue>,
+    ) -> VmResult<Option<DataValue>> {
+        let method = match obj.class() {
+            Some(cls) => cls.select_method(&method)?,
+            None => method,
+        };
+
+        self.invoke_method(method, Some(obj), args)
+    }
+
+    /// Args should be in decl order
+    pub fn invoke_static(
+        &self,
+        method: VmRef<Method>,
+        args: impl DoubleEndedIterator<Item = DataValue>,
+    ) -> VmResult<Option<DataValue>> {
+        self.invoke_method(method, Option::<DataValue>::None, args)
+    }
+
     /// Args should be in decl order
     fn invoke_method(
         &self,
@@ -112,11 +137,7 @@ impl<'a> ExecHelper<'a> {
             Frame::new_with_args(method, args.rev().chain(this.map(Into::into).into_iter()))
                 .unwrap(); // TODO handle exc
 
-        interp.execute_frame(frame).map_err(|exc| {
-            let exc_name = exc.class_name;
-            self.state.set_exception(exc);
-            Throwables::Other(exc_name) // unsure this is fine
-        })
+        interp.execute_frame(frame).map_err(Throwables::Thrown)
     }
 
     pub fn collect_array(
@@ -154,7 +175,8 @@ impl<'a> ExecHelper<'a> {
             for (elem, dst) in items.zip(slice.iter_mut()) {
                 let elem = elem?;
                 debug_assert!(
-                    elem.data_type_checked().is_none() || elem.data_type_checked() == Some(elem_ty.clone()),
+                    elem.data_type_checked().is_none()
+                        || elem.data_type_checked() == Some(elem_ty.clone()),
                     "element mismatch, expected {:?} but got {:?}",
                     elem_ty,
                     elem.data_type()
@@ -179,7 +201,7 @@ impl ExecHelperStandalone {
         let datatype = value.data_type();
         let field_id = obj
             .find_field_in_this_only(name.as_ref(), &datatype, FieldSearchType::Instance)
-            .ok_or(Throwables::Other("java/lang/NoSuchFieldError"))?;
+            .ok_or_else(|| Throwables::NoSuchFieldError(field.to_owned()))?;
 
         obj.fields().expect("no fields").ensure_set(field_id, value);
         Ok(())
@@ -195,7 +217,7 @@ impl ExecHelperStandalone {
         let name = field.as_mstr();
         let field_id = obj
             .find_field_in_this_only(name.as_ref(), ty, FieldSearchType::Instance)
-            .ok_or(Throwables::Other("java/lang/NoSuchFieldError"))?;
+            .ok_or_else(|| Throwables::NoSuchFieldError(field.to_owned()))?;
 
         Ok(obj.fields().expect("no fields").ensure_get(field_id))
     }
@@ -224,6 +246,11 @@ impl IntoMethodRef for &'static str {
     /// Method descriptor
     fn into_method_ref(self, cls: &VmRef<Class>) -> VmResult<VmRef<Method>> {
         cls.find_instance_constructor(self.as_mstr())
-            .ok_or(Throwables::Other("java/lang/NoSuchMethodError"))
+            .ok_or_else(|| {
+                Throwables::WithMessage(
+                    "java/lang/NoSuchMethodError",
+                    format!("{}.<init>{}", cls.name(), self),
+                )
+            })
     }
 }
@@ -0,0 +1,175 @@
+//! `StringConcatFactory`, concatenating the call site arguments and constants with a
+//! `StringBuilder`
+
+use std::borrow::Cow;
+
+use cafebabe::mutf8::mstr;
+use cafebabe::MethodAccessFlags;
+
+use crate::constant_pool::{Entry, InvokeDynamic, RuntimeConstantPool};
+use crate::interpreter::callsite::writer::{slots, ClassWriter, CodeBuilder, ConstantPoolBuilder};
+use crate::interpreter::callsite::{bootstrap_arg, class_flags, signature, TARGET_NAME};
+use crate::interpreter::insn::Opcode;
+use crate::interpreter::InterpreterError;
+use crate::types::{DataType, PrimitiveDataType, ReturnType};
+
+/// Recipe placeholder for the next argument
+const TAG_ARG: u8 = 1;
+/// Recipe placeholder for the next constant
+const TAG_CONST: u8 = 2;
+
+const STRING_BUILDER: &[u8] = b"java/lang/StringBuilder";
+
+enum Piece<'a> {
+    /// Modified UTF-8 string
+    Literal(&'a [u8]),
+    Int(i32),
+    /// Index of the argument
+    Arg(usize),
+}
+
+/// Concatenates all arguments
+pub(super) fn make_concat(
+    _: &RuntimeConstantPool,
+    indy: &InvokeDynamic,
+    name: &[u8],
+) -> Result<ClassWriter, InterpreterError> {
+    let (args, _) = signature(&indy.desc)?;
+    let pieces = (0..args.len()).map(Piece::Arg).collect::<Vec<_>>();
+    generate(indy, &args, &pieces, name)
+}
+
+/// Concatenates according to the recipe, the first static argument
+pub(super) fn make_concat_with_constants(
+    pool: &RuntimeConstantPool,
+    indy: &InvokeDynamic,
+    name: &[u8],
+) -> Result<ClassWriter, InterpreterError> {
+    let (args, _) = signature(&indy.desc)?;
+    let recipe = match bootstrap_arg(pool, indy, 0)? {
+        Entry::String(recipe) => recipe.as_bytes(),
+        _ => return Err(InterpreterError::InvalidBootstrapArgs("expected recipe")),
+    };
+
+    // tags are single bytes in modified UTF-8 too
+    let mut pieces = Vec::new();
+    let (mut next_arg, mut next_const) = (0, 1);
+    for chunk in recipe.split_inclusive(|b| matches!(*b, TAG_ARG | TAG_CONST)) {
+        let (literal, tag) = match chunk.split_last() {
+            Some((tag @ (&TAG_ARG | &TAG_CONST), literal)) => (literal, Some(*tag)),
+            _ => (chunk, None),
+        };
+
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+
+        match tag {
+            Some(TAG_ARG) => {
+                pieces.push(Piece::Arg(next_arg));
+                next_arg += 1;
+            }
+            Some(_) => {
+                pieces.push(match bootstrap_arg(pool, indy, next_const)? {
+                    Entry::String(s) => Piece::Literal(s.as_bytes()),
+                    Entry::Int(int) => Piece::Int(*int),
+                    _ => {
+                        return Err(InterpreterError::InvalidBootstrapArgs(
+                            "unsupported concat constant",
+                        ))
+                    }
+                });
+                next_const += 1;
+            }
+            None => {}
+        }
+    }
+
+    if next_arg != args.len() {
+        return Err(InterpreterError::InvalidBootstrapArgs(
+            "recipe does not match the argument count",
+        ));
+    }
+
+    generate(indy, &args, &pieces, name)
+}
+
+fn generate(
+    indy: &InvokeDynamic,
+    args: &[DataType],
+    pieces: &[Piece],
+    name: &[u8],
+) -> Result<ClassWriter, InterpreterError> {
+    let mut writer = ClassWriter::new(name, b"java/lang/Object", class_flags());
+    let pool = writer.pool();
+
+    // local of each argument
+    let locals = args
+        .iter()
+        .scan(0, |local, arg| {
+            let this = *local;
+            *local += slots(arg);
+            Some(this)
+        })
+        .collect::<Vec<_>>();
+
+    let mut code = CodeBuilder::default();
+    let builder_init = pool.method_ref(STRING_BUILDER, b"<init>", b"()V");
+    code.new_object(pool.class(STRING_BUILDER));
+    code.dup();
+    code.invoke(Opcode::Invokespecial, builder_init, b"()V");
+
+    for piece in pieces {
+        let ty = match piece {
+            Piece::Literal(s) => {
+                code.ldc(pool.string(s));
+                string_type()
+            }
+            Piece::Int(int) => {
+                code.ldc(pool.int(*int));
+                DataType::Primitive(PrimitiveDataType::Int)
+            }
+            Piece::Arg(i) => {
+                code.load(&args[*i], locals[*i]);
+                args[*i].clone()
+            }
+        };
+
+        append(&mut code, pool, &ty);
+    }
+
+    let to_string = pool.method_ref(STRING_BUILDER, b"toString", b"()Ljava/lang/String;");
+    code.invoke(Opcode::Invokevirtual, to_string, b"()Ljava/lang/String;");
+    code.return_value(&ReturnType::Returns(string_type()));
+
+    writer.add_method(
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        TARGET_NAME,
+        indy.desc.as_bytes(),
+        code,
+    );
+    Ok(writer)
+}
+
+fn string_type() -> DataType<'static> {
+    DataType::Reference(Cow::Borrowed(mstr::from_mutf8(b"java/lang/String")))
+}
+
+/// Appends the value of the given type on top of the stack to the builder beneath it
+fn append(code: &mut CodeBuilder, pool: &mut ConstantPoolBuilder, ty: &DataType) {
+    let desc: &[u8] = match ty {
+        DataType::Primitive(PrimitiveDataType::Boolean) => b"(Z)Ljava/lang/StringBuilder;",
+        DataType::Primitive(PrimitiveDataType::Char) => b"(C)Ljava/lang/StringBuilder;",
+        DataType::Primitive(PrimitiveDataType::Long) => b"(J)Ljava/lang/StringBuilder;",
+        DataType::Primitive(PrimitiveDataType::Float) => b"(F)Ljava/lang/StringBuilder;",
+        DataType::Primitive(PrimitiveDataType::Double) => b"(D)Ljava/lang/StringBuilder;",
+        DataType::Primitive(_) => b"(I)Ljava/lang/StringBuilder;",
+        DataType::Reference(class) if class.as_bytes() == b"java/lang/String" => {
+            b"(Ljava/lang/String;)Ljava/lang/StringBuilder;"
+        }
+        _ => b"(Ljava/lang/Object;)Ljava/lang/StringBuilder;",
+    };
+
+    let method = pool.method_ref(STRING_BUILDER, b"append", desc);
+    code.invoke(Opcode::Invokevirtual, method, desc);
+}
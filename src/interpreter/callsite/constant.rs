@@ -0,0 +1,202 @@
+//! Loadable constants. Method handles, method types and dynamically computed constants are
+//! resolved once per class. As with call sites, the bootstrap methods of dynamically computed
+//! constants aren't run, instead those of `ConstantBootstraps` are recognised and reproduced
+
+use std::borrow::Cow;
+
+use cafebabe::mutf8::{mstr, StrExt};
+
+use crate::alloc::VmRef;
+use crate::class::{null, Class, FoundField, WhichLoader};
+use crate::constant_pool::{Dynamic, Entry, RuntimeConstantPool};
+use crate::interpreter::callsite::handle::{self, METHOD_HANDLE, METHOD_TYPE};
+use crate::interpreter::callsite::lambda::wrapper;
+use crate::interpreter::callsite::writer::descriptor;
+use crate::interpreter::InterpreterError;
+use crate::thread;
+use crate::types::{DataType, DataValue, PrimitiveDataType};
+
+/// Value of the loadable constant at the given index of the class's constant pool
+pub fn load_constant(class: &VmRef<Class>, idx: u16) -> Result<DataValue, InterpreterError> {
+    let entry = class
+        .constant_pool()
+        .entry(idx)
+        .ok_or(InterpreterError::NotLoadable(idx))?;
+
+    let thread = thread::get();
+    let class_loader = thread.global().class_loader();
+    let value = match entry {
+        Entry::String(s) => {
+            let string_class =
+                class_loader.load_class("java/lang/String".as_mstr(), WhichLoader::Bootstrap)?;
+            string_class.ensure_init()?;
+
+            let string_instance = thread.global().intern_string(s.as_mstr())?;
+            DataValue::Reference(string_instance)
+        }
+        Entry::Float(f) => DataValue::from(*f),
+        Entry::Int(i) => DataValue::from(*i),
+        Entry::Long(l) => DataValue::from(*l),
+        Entry::Double(d) => DataValue::from(*d),
+        Entry::ClassRef(_) => {
+            let cls = class.resolve_class(idx)?;
+            DataValue::Reference(cls.class_object().clone())
+        }
+        Entry::MethodHandle(member) => class.resolved_constant(idx, || {
+            handle::method_handle_object(class, member).map(DataValue::Reference)
+        })?,
+        Entry::MethodType(desc) => class.resolved_constant(idx, || {
+            handle::method_type_object(desc).map(DataValue::Reference)
+        })?,
+        Entry::Dynamic(condy) => class.resolved_constant(idx, || resolve_dynamic(class, condy))?,
+        _ => return Err(InterpreterError::NotLoadable(idx)),
+    };
+
+    Ok(value)
+}
+
+/// Type of the value of a loadable constant
+fn constant_type(pool: &RuntimeConstantPool, idx: u16) -> Result<DataType<'_>, InterpreterError> {
+    let reference = |class: &'static str| DataType::Reference(Cow::Borrowed(class.as_mstr()));
+    let ty = match pool.entry(idx) {
+        Some(Entry::String(_)) => reference("java/lang/String"),
+        Some(Entry::Int(_)) => DataType::Primitive(PrimitiveDataType::Int),
+        Some(Entry::Float(_)) => DataType::Primitive(PrimitiveDataType::Float),
+        Some(Entry::Long(_)) => DataType::Primitive(PrimitiveDataType::Long),
+        Some(Entry::Double(_)) => DataType::Primitive(PrimitiveDataType::Double),
+        Some(Entry::ClassRef(_)) => reference("java/lang/Class"),
+        Some(Entry::MethodHandle(_)) => reference(METHOD_HANDLE),
+        Some(Entry::MethodType(_)) => reference(METHOD_TYPE),
+        Some(Entry::Dynamic(condy)) => condy.desc.clone(),
+        _ => return Err(InterpreterError::NotLoadable(idx)),
+    };
+
+    Ok(ty)
+}
+
+fn resolve_dynamic(class: &VmRef<Class>, condy: &Dynamic) -> Result<DataValue, InterpreterError> {
+    let pool = class.constant_pool();
+    let bootstrap = pool
+        .method_handle_entry(condy.bootstrap_method)
+        .ok_or(InterpreterError::NotMethodHandle(condy.bootstrap_method))?;
+
+    let unsupported = || InterpreterError::UnsupportedBootstrapMethod {
+        class: bootstrap.class,
+        name: bootstrap.name,
+    };
+
+    if bootstrap.class.as_bytes() != b"java/lang/invoke/ConstantBootstraps" {
+        return Err(unsupported());
+    }
+
+    match (bootstrap.name.as_bytes(), &condy.desc) {
+        (b"nullConstant", DataType::Reference(_)) => Ok(DataValue::Reference(null())),
+        (b"primitiveClass", _) => {
+            let prim = match condy.name.as_bytes() {
+                [c] => PrimitiveDataType::from_char(*c),
+                _ => None,
+            }
+            .ok_or(InterpreterError::InvalidBootstrapArgs(
+                "expected primitive descriptor",
+            ))?;
+
+            let thread = thread::get();
+            let class = thread.global().class_loader().get_primitive(prim);
+            Ok(DataValue::Reference(class.class_object().clone()))
+        }
+        (b"enumConstant", DataType::Reference(enum_class)) => {
+            static_field(class, enum_class, condy)
+        }
+        (b"getStaticFinal", _) => {
+            let declaring =
+                match (condy.bootstrap_args.first(), &condy.desc) {
+                    (Some(idx), _) => {
+                        let declaring = pool.class_entry(*idx).ok_or(
+                            InterpreterError::InvalidBootstrapArgs("expected declaring class"),
+                        )?;
+                        declaring.name.as_mstr()
+                    }
+                    (None, DataType::Reference(class)) => class.as_ref(),
+                    (None, DataType::Primitive(prim)) => wrapper(*prim).as_mstr(),
+                    (None, DataType::ReturnAddress) => unreachable!(),
+                };
+
+            static_field(class, declaring, condy)
+        }
+        (b"invoke", _) => invoke(class, condy),
+        (b"nullConstant" | b"enumConstant", _) => Err(InterpreterError::InvalidBootstrapArgs(
+            "constant must be a reference",
+        )),
+        _ => Err(unsupported()),
+    }
+}
+
+/// Value of the static field named after the constant, initialising its class
+fn static_field(
+    caller: &VmRef<Class>,
+    declaring: &mstr,
+    condy: &Dynamic,
+) -> Result<DataValue, InterpreterError> {
+    let class = thread::get().global().class_loader().load_class_caused_by(
+        declaring,
+        caller.loader().clone(),
+        caller.name(),
+    )?;
+
+    let found = class
+        .find_static_field_recursive(&condy.name, &condy.desc)
+        .ok_or_else(|| InterpreterError::FieldNotFound {
+            name: condy.name,
+            desc: condy.desc.clone(),
+        })?;
+
+    let (storage_class, field_id) = match found {
+        FoundField::InThisClass(id) => (class.clone(), id),
+        FoundField::InOtherClass(id, cls) => (cls, id),
+    };
+
+    class.ensure_init()?;
+    Ok(storage_class.static_fields().ensure_get(field_id))
+}
+
+/// Result of invoking the method handle of the first static argument with the rest
+fn invoke(class: &VmRef<Class>, condy: &Dynamic) -> Result<DataValue, InterpreterError> {
+    let pool = class.constant_pool();
+    let (handle, args) = match condy.bootstrap_args.split_first() {
+        Some((handle, args)) if pool.method_handle_entry(*handle).is_some() => (*handle, args),
+        _ => {
+            return Err(InterpreterError::InvalidBootstrapArgs(
+                "expected method handle",
+            ))
+        }
+    };
+
+    let handle = match load_constant(class, handle)? {
+        DataValue::Reference(handle) => handle,
+        value => {
+            return Err(InterpreterError::InvalidOperandForObjectOp(
+                value.data_type(),
+            ))
+        }
+    };
+
+    // the invoker converts the arguments and result as needed
+    let mut site_desc = vec![b'('];
+    let mut values = vec![DataValue::Reference(handle.clone())];
+    for arg in args {
+        site_desc.extend_from_slice(&descriptor(&constant_type(pool, *arg)?));
+        values.push(load_constant(class, *arg)?);
+    }
+    site_desc.push(b')');
+    site_desc.extend_from_slice(&descriptor(&condy.desc));
+
+    let invoker = handle::link_invoker(&handle, mstr::from_mutf8(&site_desc), false)?;
+
+    let thread = thread::get();
+    let value = thread
+        .exec_helper()
+        .invoke_static(invoker, values.into_iter())?;
+    value.ok_or(InterpreterError::InvalidBootstrapArgs(
+        "constant must have a value",
+    ))
+}
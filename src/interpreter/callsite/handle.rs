@@ -0,0 +1,531 @@
+//! Minimal `java.lang.invoke` runtime, as GNU Classpath has none. The VM supplies `MethodHandle` and
+//! `MethodType` classes that can only represent direct handles to fields and methods loaded from
+//! the constant pool. Invoking a handle with `invoke` or `invokeExact` links an invoker for the call
+//! site's descriptor, which accesses the member directly and converts the arguments and return value
+//! like `asType` would
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+
+use cafebabe::mutf8::{mstr, StrExt};
+use cafebabe::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags};
+
+use crate::alloc::{InternedString, NativeString, VmRef};
+use crate::class::{Class, Method, Object, WhichLoader};
+use crate::constant_pool::{MethodHandle, MethodRef, ReferenceKind};
+use crate::error::Throwables;
+use crate::exec_helper::ExecHelperStandalone;
+use crate::interpreter::callsite::lambda::convert;
+use crate::interpreter::callsite::writer::{descriptor, slots, ClassWriter, CodeBuilder};
+use crate::interpreter::callsite::{class_flags, define_generated, signature, TARGET_NAME};
+use crate::interpreter::insn::Opcode;
+use crate::interpreter::InterpreterError;
+use crate::thread;
+use crate::types::{DataType, DataValue, MethodSignature, ReturnType};
+
+pub const METHOD_HANDLE: &str = "java/lang/invoke/MethodHandle";
+pub const METHOD_TYPE: &str = "java/lang/invoke/MethodType";
+const WRONG_METHOD_TYPE: &str = "java/lang/invoke/WrongMethodTypeException";
+
+/// `java/lang/invoke/MethodHandle.vmdata`, a handle to a field or method
+#[derive(Debug)]
+pub struct DirectMethodHandle {
+    member: MethodHandle,
+    /// Resolved class of the member, which the invokers are defined alongside
+    class: VmRef<Class>,
+    /// Descriptor of the handle's method type
+    type_desc: NativeString,
+    /// Generated invokers, by call site descriptor
+    invokers: Mutex<HashMap<InternedString, VmRef<Method>>>,
+}
+
+/// Class file of the `java.lang.invoke` class with the given name, if the VM supplies it
+pub fn runtime_class(name: &str) -> Option<Vec<u8>> {
+    let public = ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER;
+    let field_flags = FieldAccessFlags::PRIVATE | FieldAccessFlags::FINAL;
+
+    let writer = match name {
+        METHOD_HANDLE => {
+            let mut writer = ClassWriter::new(
+                name.as_bytes(),
+                b"java/lang/Object",
+                public | ClassAccessFlags::ABSTRACT,
+            );
+            writer.add_field(field_flags, b"type", b"Ljava/lang/invoke/MethodType;");
+            writer.add_field(field_flags, b"vmdata", b"Ljava/lang/Object;");
+            add_getter(
+                &mut writer,
+                name,
+                b"type",
+                b"type",
+                b"Ljava/lang/invoke/MethodType;",
+            );
+
+            // signature polymorphic, linked by the interpreter rather than called
+            let flags =
+                MethodAccessFlags::PUBLIC | MethodAccessFlags::FINAL | MethodAccessFlags::VARARGS;
+            for method in [&b"invokeExact"[..], b"invoke"] {
+                writer.add_native_method(flags, method, b"([Ljava/lang/Object;)Ljava/lang/Object;");
+            }
+            writer
+        }
+        METHOD_TYPE => {
+            let mut writer = ClassWriter::new(
+                name.as_bytes(),
+                b"java/lang/Object",
+                public | ClassAccessFlags::FINAL,
+            );
+            writer.add_field(field_flags, b"descriptor", b"Ljava/lang/String;");
+            add_getter(
+                &mut writer,
+                name,
+                b"toMethodDescriptorString",
+                b"descriptor",
+                b"Ljava/lang/String;",
+            );
+            writer
+        }
+        WRONG_METHOD_TYPE => {
+            let mut writer =
+                ClassWriter::new(name.as_bytes(), b"java/lang/RuntimeException", public);
+            for desc in [&b"()V"[..], b"(Ljava/lang/String;)V"] {
+                let (params, _) = signature(mstr::from_mutf8(desc)).ok()?;
+                let mut code = CodeBuilder::default();
+                let super_init =
+                    writer
+                        .pool()
+                        .method_ref(b"java/lang/RuntimeException", b"<init>", desc);
+                code.load(&reference(mstr::from_mutf8(name.as_bytes())), 0);
+                code.load_all(params.iter(), 1);
+                code.invoke(Opcode::Invokespecial, super_init, desc);
+                code.return_value(&ReturnType::Void);
+                writer.add_method(MethodAccessFlags::PUBLIC, b"<init>", desc, code);
+            }
+            writer
+        }
+        _ => return None,
+    };
+
+    Some(writer.finish())
+}
+
+/// Adds a public method returning the value of a field of this class
+fn add_getter(writer: &mut ClassWriter, class: &str, method: &[u8], field: &[u8], desc: &[u8]) {
+    let ty = DataType::from_descriptor(mstr::from_mutf8(desc)).expect("bad descriptor");
+    let field = writer.pool().field_ref(class.as_bytes(), field, desc);
+
+    let mut code = CodeBuilder::default();
+    code.load(&reference(mstr::from_mutf8(class.as_bytes())), 0);
+    code.getfield(field, &ty);
+    code.return_value(&ReturnType::Returns(ty));
+
+    let mut method_desc = b"()".to_vec();
+    method_desc.extend_from_slice(desc);
+    writer.add_method(MethodAccessFlags::PUBLIC, method, &method_desc, code);
+}
+
+fn reference(class: &mstr) -> DataType<'_> {
+    DataType::Reference(Cow::Borrowed(class))
+}
+
+/// Whether the given method is `MethodHandle.invokeExact` rather than `invoke`, if it's either.
+/// These are signature polymorphic, so are linked for each call site's descriptor rather than
+/// resolved
+pub fn signature_polymorphic(method: &MethodRef) -> Option<bool> {
+    if method.class.as_bytes() != METHOD_HANDLE.as_bytes() {
+        return None;
+    }
+
+    match method.name.as_bytes() {
+        b"invokeExact" => Some(true),
+        b"invoke" => Some(false),
+        _ => None,
+    }
+}
+
+/// New `MethodType` instance with the given descriptor
+pub fn method_type_object(desc: &mstr) -> Result<VmRef<Object>, InterpreterError> {
+    let thread = thread::get();
+    let class = thread
+        .global()
+        .class_loader()
+        .load_class(METHOD_TYPE.as_mstr(), WhichLoader::Bootstrap)?;
+    class.ensure_init()?;
+
+    let (object, _) = thread.exec_helper().instantiate(class)?;
+    let descriptor = thread.global().intern_string(desc)?;
+    ExecHelperStandalone.set_instance_field(
+        &object,
+        "descriptor",
+        DataValue::Reference(descriptor),
+    )?;
+    Ok(object)
+}
+
+/// New `MethodHandle` instance for the given constant in the pool of `caller`, whose class and
+/// member are resolved
+pub fn method_handle_object(
+    caller: &VmRef<Class>,
+    member: &MethodHandle,
+) -> Result<VmRef<Object>, InterpreterError> {
+    let thread = thread::get();
+    let class_loader = thread.global().class_loader();
+    let class =
+        class_loader.load_class_caused_by(&member.class, caller.loader().clone(), caller.name())?;
+    resolve_member(&class, member)?;
+
+    let type_desc = handle_type(member)?;
+    let method_type = method_type_object(&type_desc)?;
+
+    let handle_class = class_loader.load_class(METHOD_HANDLE.as_mstr(), WhichLoader::Bootstrap)?;
+    handle_class.ensure_init()?;
+
+    let (object, _) = thread.exec_helper().instantiate(handle_class)?;
+    let handle = DirectMethodHandle {
+        member: MethodHandle {
+            kind: member.kind,
+            class: member.class,
+            name: member.name,
+            desc: member.desc.clone(),
+        },
+        class,
+        type_desc,
+        invokers: Mutex::default(),
+    };
+
+    ExecHelperStandalone.set_instance_field(&object, "type", DataValue::Reference(method_type))?;
+    ExecHelperStandalone.set_instance_field(
+        &object,
+        "vmdata",
+        DataValue::VmDataMethodHandle(VmRef::new(handle)),
+    )?;
+    Ok(object)
+}
+
+/// Ensures the member of a handle exists, as it's only accessed later by invokers
+fn resolve_member(class: &VmRef<Class>, member: &MethodHandle) -> Result<(), InterpreterError> {
+    if member.kind.is_field() {
+        let desc = DataType::from_descriptor(&member.desc).ok_or(Throwables::ClassFormatError)?;
+        let found = match member.kind {
+            ReferenceKind::GetStatic | ReferenceKind::PutStatic => class
+                .find_static_field_recursive(&member.name, &desc)
+                .is_some(),
+            _ => class
+                .find_instance_field_recursive(&member.name, &desc)
+                .is_some(),
+        };
+
+        return match found {
+            true => Ok(()),
+            false => Err(InterpreterError::FieldNotFound {
+                name: member.name,
+                desc: desc.to_owned(),
+            }),
+        };
+    }
+
+    if !MethodSignature::is_valid(&member.desc) {
+        return Err(Throwables::ClassFormatError.into());
+    }
+
+    class
+        .find_method_recursive_in_superclasses(
+            &member.name,
+            &member.desc,
+            MethodAccessFlags::empty(),
+            MethodAccessFlags::empty(),
+        )
+        .map(|_| ())
+        .ok_or_else(|| InterpreterError::MethodNotFound {
+            class: member.class,
+            name: member.name,
+            desc: member.desc.clone(),
+        })
+}
+
+/// Method type descriptor of a handle to the given member
+fn handle_type(member: &MethodHandle) -> Result<NativeString, InterpreterError> {
+    let receiver = descriptor(&reference(&member.class));
+    let desc = member.desc.as_bytes();
+    let (params, ret) = match desc.iter().position(|b| *b == b')') {
+        Some(end) if desc.starts_with(b"(") => (&desc[1..end], &desc[end + 1..]),
+        _ => (&[][..], desc),
+    };
+
+    let mut out = vec![b'('];
+    match member.kind {
+        ReferenceKind::GetField => {
+            out.extend_from_slice(&receiver);
+            out.push(b')');
+            out.extend_from_slice(desc);
+        }
+        ReferenceKind::GetStatic => {
+            out.push(b')');
+            out.extend_from_slice(desc);
+        }
+        ReferenceKind::PutField => {
+            out.extend_from_slice(&receiver);
+            out.extend_from_slice(desc);
+            out.extend_from_slice(b")V");
+        }
+        ReferenceKind::PutStatic => {
+            out.extend_from_slice(desc);
+            out.extend_from_slice(b")V");
+        }
+        ReferenceKind::InvokeVirtual
+        | ReferenceKind::InvokeSpecial
+        | ReferenceKind::InvokeInterface => {
+            out.extend_from_slice(&receiver);
+            out.extend_from_slice(params);
+            out.push(b')');
+            out.extend_from_slice(ret);
+        }
+        ReferenceKind::InvokeStatic => {
+            out.extend_from_slice(params);
+            out.push(b')');
+            out.extend_from_slice(ret);
+        }
+        ReferenceKind::NewInvokeSpecial => {
+            out.extend_from_slice(params);
+            out.push(b')');
+            out.extend_from_slice(&receiver);
+        }
+    }
+
+    let out = mstr::from_mutf8(&out);
+    if MethodSignature::is_valid(out) {
+        Ok(out.to_owned())
+    } else {
+        Err(Throwables::ClassFormatError.into())
+    }
+}
+
+/// Invoker for a call site of `invoke` or `invokeExact` on the given handle with the given
+/// descriptor. It's a static method taking the handle followed by the call site's arguments
+pub fn link_invoker(
+    handle: &VmRef<Object>,
+    site_desc: &mstr,
+    exact: bool,
+) -> Result<VmRef<Method>, InterpreterError> {
+    let vmdata = ExecHelperStandalone.get_instance_field(
+        handle,
+        "vmdata",
+        &reference(mstr::from_literal("java/lang/Object")),
+    )?;
+    let handle = match vmdata {
+        DataValue::VmDataMethodHandle(handle) => handle,
+        value => {
+            return Err(InterpreterError::InvalidOperandForObjectOp(
+                value.data_type(),
+            ))
+        }
+    };
+
+    if exact && site_desc != handle.type_desc.as_mstr() {
+        return Err(wrong_method_type(format!(
+            "expected {} but found {}",
+            handle.type_desc, site_desc
+        )));
+    }
+
+    let key = InternedString::intern(site_desc);
+    if let Some(invoker) = handle.invokers.lock().get(&key) {
+        return Ok(invoker.clone());
+    }
+
+    // link without holding the lock, as it loads and defines classes
+    let invoker_desc = invoker_descriptor(site_desc);
+    let invoker = define_generated(&handle.class, "Invoker", &invoker_desc, |name| {
+        generate_invoker(
+            &handle.member,
+            handle.class.is_interface(),
+            &handle.type_desc,
+            site_desc,
+            name,
+        )
+    })?;
+
+    // invokers have no initialiser or super interfaces, so this doesn't run any Java code
+    invoker.class().ensure_init()?;
+
+    let invoker = handle.invokers.lock().entry(key).or_insert(invoker).clone();
+    Ok(invoker)
+}
+
+/// Call site descriptor with the handle prepended
+fn invoker_descriptor(site_desc: &mstr) -> NativeString {
+    let mut desc = format!("(L{};", METHOD_HANDLE).into_bytes();
+    desc.extend_from_slice(&site_desc.as_bytes()[1..]);
+    mstr::from_mutf8(&desc).to_owned()
+}
+
+fn wrong_method_type(message: String) -> InterpreterError {
+    Throwables::WithMessage(WRONG_METHOD_TYPE, message).into()
+}
+
+/// Writes an invoker that accesses the member of a handle with the call site's arguments
+fn generate_invoker(
+    member: &MethodHandle,
+    interface: bool,
+    type_desc: &mstr,
+    site_desc: &mstr,
+    name: &[u8],
+) -> Result<ClassWriter, InterpreterError> {
+    let (site_params, site_ret) = signature(site_desc)?;
+    let (params, ret) = signature(type_desc)?;
+    let cannot_convert =
+        || wrong_method_type(format!("cannot convert {} to {}", type_desc, site_desc));
+    if site_params.len() != params.len() {
+        return Err(cannot_convert());
+    }
+
+    let mut writer = ClassWriter::new(name, b"java/lang/Object", class_flags());
+    let pool = writer.pool();
+    let (class, member_name, member_desc) = (
+        member.class.as_bytes(),
+        member.name.as_bytes(),
+        member.desc.as_bytes(),
+    );
+
+    let mut code = CodeBuilder::default();
+    if member.kind == ReferenceKind::NewInvokeSpecial {
+        code.new_object(pool.class(class));
+        code.dup();
+    }
+
+    // the handle itself is the first argument
+    let mut local = 1;
+    for (param, target) in site_params.iter().zip(&params) {
+        code.load(param, local);
+        convert(&mut code, pool, param, None, target);
+        local += slots(param);
+    }
+
+    if member.kind.is_field() {
+        let field = pool.field_ref(class, member_name, member_desc);
+        let ty = DataType::from_descriptor(&member.desc).ok_or(Throwables::ClassFormatError)?;
+        match member.kind {
+            ReferenceKind::GetField => code.getfield(field, &ty),
+            ReferenceKind::GetStatic => code.getstatic(field, &ty),
+            ReferenceKind::PutField => code.putfield(field, &ty),
+            _ => code.putstatic(field, &ty),
+        }
+    } else {
+        let method = if interface {
+            pool.interface_method_ref(class, member_name, member_desc)
+        } else {
+            pool.method_ref(class, member_name, member_desc)
+        };
+
+        let opcode = match member.kind {
+            ReferenceKind::InvokeStatic => Opcode::Invokestatic,
+            ReferenceKind::InvokeInterface => Opcode::Invokeinterface,
+            ReferenceKind::InvokeSpecial | ReferenceKind::NewInvokeSpecial => Opcode::Invokespecial,
+            _ => Opcode::Invokevirtual,
+        };
+        code.invoke(opcode, method, member_desc);
+    }
+
+    match (&site_ret, &ret) {
+        (ReturnType::Void, ReturnType::Void) => {}
+        (ReturnType::Void, ReturnType::Returns(value)) => code.pop(value),
+        (ReturnType::Returns(_), ReturnType::Void) => return Err(cannot_convert()),
+        (ReturnType::Returns(to), ReturnType::Returns(from)) => {
+            convert(&mut code, pool, from, None, to)
+        }
+    }
+
+    code.return_value(&site_ret);
+    writer.add_method(
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        TARGET_NAME,
+        invoker_descriptor(site_desc).as_bytes(),
+        code,
+    );
+    Ok(writer)
+}
+
+impl DirectMethodHandle {
+    pub fn class(&self) -> &VmRef<Class> {
+        &self.class
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(kind: ReferenceKind, class: &str, name: &str, desc: &str) -> MethodHandle {
+        MethodHandle {
+            kind,
+            class: InternedString::intern(class.as_mstr()),
+            name: InternedString::intern(name.as_mstr()),
+            desc: desc.to_mstr().into_owned(),
+        }
+    }
+
+    #[test]
+    fn handle_types() {
+        use ReferenceKind::*;
+        for (kind, name, desc, expected) in [
+            (GetField, "count", "I", "(LCls;)I"),
+            (
+                GetStatic,
+                "NAME",
+                "Ljava/lang/String;",
+                "()Ljava/lang/String;",
+            ),
+            (PutField, "count", "J", "(LCls;J)V"),
+            (PutStatic, "ARR", "[I", "([I)V"),
+            (InvokeVirtual, "m", "(ID)V", "(LCls;ID)V"),
+            (
+                InvokeSpecial,
+                "m",
+                "()Ljava/lang/Object;",
+                "(LCls;)Ljava/lang/Object;",
+            ),
+            (InvokeInterface, "m", "(J)Z", "(LCls;J)Z"),
+            (InvokeStatic, "m", "(LCls;)I", "(LCls;)I"),
+            (
+                NewInvokeSpecial,
+                "<init>",
+                "(Ljava/lang/String;)V",
+                "(Ljava/lang/String;)LCls;",
+            ),
+        ] {
+            let desc = handle_type(&member(kind, "Cls", name, desc)).expect("invalid");
+            assert_eq!(desc.as_bytes(), expected.as_bytes(), "{:?}", kind);
+        }
+
+        assert!(handle_type(&member(GetField, "Cls", "x", "(")).is_err());
+    }
+
+    #[test]
+    fn invoker_parses() {
+        let member = member(
+            ReferenceKind::InvokeStatic,
+            "java/lang/Long",
+            "valueOf",
+            "(J)Ljava/lang/Long;",
+        );
+        let type_desc = handle_type(&member).expect("invalid");
+
+        // boxes the argument and unboxes the result
+        let site_desc = mstr::from_literal("(Ljava/lang/Object;)J");
+        let writer =
+            generate_invoker(&member, false, &type_desc, site_desc, b"Invoker").expect("failed");
+        let bytes = writer.finish();
+        let class = cafebabe::load_from_buffer(&bytes).expect("invalid class");
+        let method = class.methods().next().expect("no method");
+        assert_eq!(
+            method.descriptor.as_bytes(),
+            b"(Ljava/lang/invoke/MethodHandle;Ljava/lang/Object;)J"
+        );
+
+        // arity must match
+        let site_desc = mstr::from_literal("()J");
+        assert!(generate_invoker(&member, false, &type_desc, site_desc, b"Invoker").is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! `LambdaMetafactory`, implementing a functional interface with a class that stores the captured
+//! arguments and passes them to the implementation method along with the interface method's own
+
+use std::borrow::Cow;
+
+use cafebabe::mutf8::mstr;
+use cafebabe::{FieldAccessFlags, MethodAccessFlags};
+
+use crate::constant_pool::{
+    Entry, InvokeDynamic, MethodHandle, ReferenceKind, RuntimeConstantPool,
+};
+use crate::interpreter::callsite::writer::{
+    descriptor, slots, ClassWriter, CodeBuilder, ConstantPoolBuilder,
+};
+use crate::interpreter::callsite::{bootstrap_arg, class_flags, signature, TARGET_NAME};
+use crate::interpreter::insn::Opcode;
+use crate::interpreter::InterpreterError;
+use crate::types::{DataType, PrimitiveDataType, ReturnType};
+
+const FLAG_SERIALIZABLE: i32 = 1 << 0;
+const FLAG_MARKERS: i32 = 1 << 1;
+const FLAG_BRIDGES: i32 = 1 << 2;
+
+struct Lambda<'a> {
+    /// Interface method name
+    name: &'a mstr,
+    /// Erased interface method descriptor, followed by those of any bridge methods
+    descriptors: Vec<&'a mstr>,
+    /// Interface method descriptor with generic types specialised
+    instantiated: &'a mstr,
+    implementation: &'a MethodHandle,
+    /// Extra marker interfaces to implement
+    markers: Vec<&'a mstr>,
+}
+
+pub(super) fn metafactory(
+    pool: &RuntimeConstantPool,
+    indy: &InvokeDynamic,
+    name: &[u8],
+) -> Result<ClassWriter, InterpreterError> {
+    Lambda::from_args(pool, indy)?.generate(indy, name)
+}
+
+pub(super) fn alt_metafactory(
+    pool: &RuntimeConstantPool,
+    indy: &InvokeDynamic,
+    name: &[u8],
+) -> Result<ClassWriter, InterpreterError> {
+    let mut lambda = Lambda::from_args(pool, indy)?;
+    let int_arg = |idx| match bootstrap_arg(pool, indy, idx)? {
+        Entry::Int(int) => Ok(*int),
+        _ => Err(InterpreterError::InvalidBootstrapArgs("expected int")),
+    };
+
+    let count_arg = |idx| {
+        int_arg(idx).and_then(|count| {
+            usize::try_from(count)
+                .map_err(|_| InterpreterError::InvalidBootstrapArgs("negative count"))
+        })
+    };
+
+    let flags = int_arg(3)?;
+    let mut next = 4;
+    if flags & FLAG_SERIALIZABLE != 0 {
+        lambda
+            .markers
+            .push(mstr::from_mutf8(b"java/io/Serializable"));
+    }
+
+    if flags & FLAG_MARKERS != 0 {
+        let count = count_arg(next)?;
+        for idx in next + 1..=next + count {
+            match bootstrap_arg(pool, indy, idx)? {
+                Entry::ClassRef(class) => lambda.markers.push(&class.name),
+                _ => return Err(InterpreterError::InvalidBootstrapArgs("expected class")),
+            }
+        }
+        next += 1 + count;
+    }
+
+    if flags & FLAG_BRIDGES != 0 {
+        let count = count_arg(next)?;
+        for idx in next + 1..=next + count {
+            lambda.descriptors.push(method_type(pool, indy, idx)?);
+        }
+    }
+
+    lambda.generate(indy, name)
+}
+
+fn method_type<'a>(
+    pool: &'a RuntimeConstantPool,
+    indy: &InvokeDynamic,
+    idx: usize,
+) -> Result<&'a mstr, InterpreterError> {
+    match bootstrap_arg(pool, indy, idx)? {
+        Entry::MethodType(desc) => Ok(desc),
+        _ => Err(InterpreterError::InvalidBootstrapArgs(
+            "expected method type",
+        )),
+    }
+}
+
+impl<'a> Lambda<'a> {
+    /// From the static arguments shared by both metafactories
+    fn from_args(
+        pool: &'a RuntimeConstantPool,
+        indy: &'a InvokeDynamic,
+    ) -> Result<Self, InterpreterError> {
+        let implementation = match bootstrap_arg(pool, indy, 1)? {
+            Entry::MethodHandle(handle) => handle,
+            _ => {
+                return Err(InterpreterError::InvalidBootstrapArgs(
+                    "expected method handle",
+                ))
+            }
+        };
+
+        Ok(Lambda {
+            name: &indy.name,
+            descriptors: vec![method_type(pool, indy, 0)?],
+            instantiated: method_type(pool, indy, 2)?,
+            implementation,
+            markers: Vec::new(),
+        })
+    }
+
+    fn generate(&self, indy: &InvokeDynamic, name: &[u8]) -> Result<ClassWriter, InterpreterError> {
+        let (captured, interface) = signature(&indy.desc)?;
+        let interface = match interface {
+            ReturnType::Returns(DataType::Reference(interface)) => interface,
+            _ => {
+                return Err(InterpreterError::InvalidBootstrapArgs(
+                    "call site must return an interface",
+                ))
+            }
+        };
+
+        let mut writer = ClassWriter::new(name, b"java/lang/Object", class_flags());
+        writer.add_interface(interface.as_bytes());
+        for marker in &self.markers {
+            writer.add_interface(marker.as_bytes());
+        }
+
+        // captured arguments are stored in fields
+        let fields = captured
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                let field_name = format!("arg${}", i + 1);
+                let desc = descriptor(ty);
+                let flags = FieldAccessFlags::PRIVATE | FieldAccessFlags::FINAL;
+                writer.add_field(flags, field_name.as_bytes(), &desc);
+                writer.pool().field_ref(name, field_name.as_bytes(), &desc)
+            })
+            .collect::<Vec<_>>();
+
+        let this = DataType::Reference(Cow::Borrowed(mstr::from_mutf8(name)));
+        let init_desc = {
+            let mut desc = vec![b'('];
+            captured
+                .iter()
+                .for_each(|ty| desc.extend_from_slice(&descriptor(ty)));
+            desc.extend_from_slice(b")V");
+            desc
+        };
+
+        // constructor takes the captured arguments
+        let mut code = CodeBuilder::default();
+        let object_init = writer
+            .pool()
+            .method_ref(b"java/lang/Object", b"<init>", b"()V");
+        code.load(&this, 0);
+        code.invoke(Opcode::Invokespecial, object_init, b"()V");
+        let mut local = 1;
+        for (field, ty) in fields.iter().zip(&captured) {
+            code.load(&this, 0);
+            code.load(ty, local);
+            code.putfield(*field, ty);
+            local += slots(ty);
+        }
+        code.return_value(&ReturnType::Void);
+        writer.add_method(MethodAccessFlags::PRIVATE, b"<init>", &init_desc, code);
+
+        // call site target instantiates it
+        let mut code = CodeBuilder::default();
+        let this_class = writer.pool().class(name);
+        let init = writer.pool().method_ref(name, b"<init>", &init_desc);
+        code.new_object(this_class);
+        code.dup();
+        code.load_all(captured.iter(), 0);
+        code.invoke(Opcode::Invokespecial, init, &init_desc);
+        code.return_value(&ReturnType::Returns(DataType::Reference(interface)));
+        writer.add_method(
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            TARGET_NAME,
+            indy.desc.as_bytes(),
+            code,
+        );
+
+        // interface method and bridges all call the implementation
+        for (i, desc) in self.descriptors.iter().enumerate() {
+            if self.descriptors[..i].contains(desc) {
+                continue;
+            }
+
+            let code = self.forward(writer.pool(), &this, &fields, &captured, desc)?;
+            writer.add_method(
+                MethodAccessFlags::PUBLIC,
+                self.name.as_bytes(),
+                desc.as_bytes(),
+                code,
+            );
+        }
+
+        Ok(writer)
+    }
+
+    /// Body of an interface method with the given descriptor
+    fn forward(
+        &self,
+        pool: &mut ConstantPoolBuilder,
+        this: &DataType,
+        fields: &[u16],
+        captured: &[DataType],
+        desc: &mstr,
+    ) -> Result<CodeBuilder, InterpreterError> {
+        let handle = self.implementation;
+        let (params, ret) = signature(desc)?;
+        let (instantiated_params, instantiated_ret) = signature(self.instantiated)?;
+        let (impl_params, impl_ret) = signature(&handle.desc)?;
+        let impl_class = DataType::Reference(Cow::Borrowed(&handle.class));
+
+        let (opcode, method) = match handle.kind {
+            ReferenceKind::InvokeStatic => (Opcode::Invokestatic, None),
+            ReferenceKind::InvokeVirtual => (Opcode::Invokevirtual, Some(impl_class)),
+            ReferenceKind::InvokeInterface => (Opcode::Invokeinterface, Some(impl_class)),
+            // private methods of the caller, which don't need special lookup
+            ReferenceKind::InvokeSpecial => (Opcode::Invokevirtual, Some(impl_class)),
+            ReferenceKind::NewInvokeSpecial => (Opcode::Invokespecial, None),
+            _ => {
+                return Err(InterpreterError::InvalidBootstrapArgs(
+                    "implementation must be a method",
+                ))
+            }
+        };
+
+        // receiver is the first argument of instance methods
+        let targets = method.iter().chain(impl_params.iter()).collect::<Vec<_>>();
+        if targets.len() != captured.len() + params.len() {
+            return Err(InterpreterError::InvalidBootstrapArgs(
+                "implementation method has the wrong number of arguments",
+            ));
+        }
+
+        let mut code = CodeBuilder::default();
+        let impl_ret = if handle.kind == ReferenceKind::NewInvokeSpecial {
+            code.new_object(pool.class(handle.class.as_bytes()));
+            code.dup();
+            ReturnType::Returns(DataType::Reference(Cow::Borrowed(&handle.class)))
+        } else {
+            impl_ret
+        };
+
+        for (field, ty) in fields.iter().zip(captured) {
+            code.load(this, 0);
+            code.getfield(*field, ty);
+        }
+
+        let mut local = 1;
+        for (i, (param, target)) in params.iter().zip(&targets[captured.len()..]).enumerate() {
+            code.load(param, local);
+            convert(&mut code, pool, param, instantiated_params.get(i), target);
+            local += slots(param);
+        }
+
+        let method = match handle.kind {
+            ReferenceKind::InvokeInterface => pool.interface_method_ref(
+                handle.class.as_bytes(),
+                handle.name.as_bytes(),
+                handle.desc.as_bytes(),
+            ),
+            _ => pool.method_ref(
+                handle.class.as_bytes(),
+                handle.name.as_bytes(),
+                handle.desc.as_bytes(),
+            ),
+        };
+        code.invoke(opcode, method, handle.desc.as_bytes());
+
+        match (&ret, impl_ret) {
+            (ReturnType::Void, ReturnType::Void) => {}
+            (ReturnType::Void, ReturnType::Returns(value)) => code.pop(&value),
+            (ReturnType::Returns(_), ReturnType::Void) => {
+                return Err(InterpreterError::InvalidBootstrapArgs(
+                    "implementation method must return a value",
+                ))
+            }
+            (ReturnType::Returns(to), ReturnType::Returns(from)) => {
+                let hint = match &instantiated_ret {
+                    ReturnType::Returns(ty) => Some(ty),
+                    ReturnType::Void => None,
+                };
+                convert(&mut code, pool, &from, hint, to);
+            }
+        }
+
+        code.return_value(&ret);
+        Ok(code)
+    }
+}
+
+/// Adapts the value on top of the stack by boxing, unboxing, widening or casting, as the
+/// metafactory allows. `hint` is a more specific type of the value if known
+pub(super) fn convert(
+    code: &mut CodeBuilder,
+    pool: &mut ConstantPoolBuilder,
+    from: &DataType,
+    hint: Option<&DataType>,
+    to: &DataType,
+) {
+    match (from, to) {
+        _ if from == to => {}
+        (DataType::Primitive(from), DataType::Primitive(to)) => code.widen(*from, *to),
+        (DataType::Primitive(prim), DataType::Reference(_)) => {
+            let wrapper = wrapper(*prim);
+            let desc = format!("({})L{};", prim.char(), wrapper);
+            let method = pool.method_ref(wrapper.as_bytes(), b"valueOf", desc.as_bytes());
+            code.invoke(Opcode::Invokestatic, method, desc.as_bytes());
+        }
+        (DataType::Reference(from), DataType::Primitive(to)) => {
+            // unbox from the most specific known wrapper, then widen
+            let unboxed = std::iter::once(from.as_ref())
+                .chain(hint.and_then(|hint| match hint {
+                    DataType::Reference(hint) => Some(hint.as_ref()),
+                    _ => None,
+                }))
+                .find_map(unwrapped)
+                .unwrap_or(*to);
+
+            let wrapper = wrapper(unboxed);
+            if from.as_bytes() != wrapper.as_bytes() {
+                code.checkcast(pool.class(wrapper.as_bytes()));
+            }
+
+            let (name, desc) = (
+                format!("{}Value", unboxed.name()),
+                format!("(){}", unboxed.char()),
+            );
+            let method = pool.method_ref(wrapper.as_bytes(), name.as_bytes(), desc.as_bytes());
+            code.invoke(Opcode::Invokevirtual, method, desc.as_bytes());
+            code.widen(unboxed, *to);
+        }
+        (DataType::Reference(_), DataType::Reference(to))
+            if to.as_bytes() != b"java/lang/Object" =>
+        {
+            code.checkcast(pool.class(to.as_bytes()));
+        }
+        _ => {}
+    }
+}
+
+pub(super) fn wrapper(prim: PrimitiveDataType) -> &'static str {
+    match prim {
+        PrimitiveDataType::Boolean => "java/lang/Boolean",
+        PrimitiveDataType::Byte => "java/lang/Byte",
+        PrimitiveDataType::Short => "java/lang/Short",
+        PrimitiveDataType::Int => "java/lang/Integer",
+        PrimitiveDataType::Long => "java/lang/Long",
+        PrimitiveDataType::Char => "java/lang/Character",
+        PrimitiveDataType::Float => "java/lang/Float",
+        PrimitiveDataType::Double => "java/lang/Double",
+    }
+}
+
+/// Primitive type of a wrapper class
+fn unwrapped(class: &mstr) -> Option<PrimitiveDataType> {
+    PrimitiveDataType::TYPES
+        .iter()
+        .map(|(prim, _)| *prim)
+        .find(|prim| wrapper(*prim).as_bytes() == class.as_bytes())
+}
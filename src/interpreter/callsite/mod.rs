@@ -0,0 +1,141 @@
+//! Linkage of invokedynamic call sites, method handle invocations and dynamically computed
+//! constants. Rather than running bootstrap methods and their method handle machinery, the
+//! bootstrap methods javac uses are recognised and their result reproduced by generating a class,
+//! whose static target method is then invoked directly by the call site
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cafebabe::mutf8::mstr;
+use cafebabe::{ClassAccessFlags, MethodAccessFlags};
+
+use crate::alloc::VmRef;
+use crate::class::{Class, Method};
+use crate::constant_pool::{Entry, InvokeDynamic, RuntimeConstantPool};
+use crate::interpreter::callsite::writer::ClassWriter;
+use crate::interpreter::InterpreterError;
+use crate::thread;
+use crate::types::{DataType, MethodSignature, ReturnType};
+
+mod concat;
+mod constant;
+mod handle;
+mod lambda;
+pub(crate) mod writer;
+
+pub use constant::load_constant;
+pub use handle::{link_invoker, runtime_class, signature_polymorphic, DirectMethodHandle};
+
+/// Static method of the generated class with the same descriptor as the call site
+const TARGET_NAME: &[u8] = b"target";
+
+/// Unique suffix for generated class names
+static NEXT_CLASS_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Writes the class for a call site in the given pool, with the given name
+type Generator =
+    fn(&RuntimeConstantPool, &InvokeDynamic, &[u8]) -> Result<ClassWriter, InterpreterError>;
+
+/// Generates and defines the class implementing the given call site of `caller`, and returns its
+/// target method
+pub fn link_call_site(
+    caller: &VmRef<Class>,
+    indy: &InvokeDynamic,
+) -> Result<VmRef<Method>, InterpreterError> {
+    let bootstrap = caller
+        .constant_pool()
+        .method_handle_entry(indy.bootstrap_method)
+        .ok_or(InterpreterError::NotMethodHandle(indy.bootstrap_method))?;
+
+    let (kind, generate): (&str, Generator) =
+        match (bootstrap.class.as_bytes(), bootstrap.name.as_bytes()) {
+            (b"java/lang/invoke/LambdaMetafactory", b"metafactory") => {
+                ("Lambda", lambda::metafactory)
+            }
+            (b"java/lang/invoke/LambdaMetafactory", b"altMetafactory") => {
+                ("Lambda", lambda::alt_metafactory)
+            }
+            (b"java/lang/invoke/StringConcatFactory", b"makeConcatWithConstants") => {
+                ("Concat", concat::make_concat_with_constants)
+            }
+            (b"java/lang/invoke/StringConcatFactory", b"makeConcat") => {
+                ("Concat", concat::make_concat)
+            }
+            _ => {
+                return Err(InterpreterError::UnsupportedBootstrapMethod {
+                    class: bootstrap.class,
+                    name: bootstrap.name,
+                })
+            }
+        };
+
+    define_generated(caller, kind, &indy.desc, |name| {
+        generate(caller.constant_pool(), indy, name)
+    })
+}
+
+/// Defines the class written by `generate` alongside `owner`, with the same loader and nest, and
+/// returns its static target method with the given descriptor
+fn define_generated(
+    owner: &Class,
+    kind: &str,
+    target_desc: &mstr,
+    generate: impl FnOnce(&[u8]) -> Result<ClassWriter, InterpreterError>,
+) -> Result<VmRef<Method>, InterpreterError> {
+    let id = NEXT_CLASS_ID.fetch_add(1, Ordering::Relaxed);
+    let mut name = owner.name().as_bytes().to_vec();
+    name.extend_from_slice(format!("$${}${}", kind, id).as_bytes());
+    let mut writer = generate(&name)?;
+    writer.set_nest_host(owner.nest_host_name().as_bytes());
+
+    let class = thread::get().global().class_loader().define_class(
+        mstr::from_mutf8(&name),
+        &writer.finish(),
+        owner.loader().clone(),
+        Some(owner.name()),
+    )?;
+
+    class
+        .find_method_in_this_only(
+            mstr::from_mutf8(TARGET_NAME),
+            target_desc,
+            MethodAccessFlags::STATIC,
+            MethodAccessFlags::empty(),
+        )
+        .ok_or(InterpreterError::InvalidBootstrapArgs(
+            "generated class is missing its target",
+        ))
+}
+
+/// Flags of all generated classes
+fn class_flags() -> ClassAccessFlags {
+    ClassAccessFlags::FINAL | ClassAccessFlags::SUPER | ClassAccessFlags::SYNTHETIC
+}
+
+/// Static argument of the bootstrap method
+fn bootstrap_arg<'a>(
+    pool: &'a RuntimeConstantPool,
+    indy: &InvokeDynamic,
+    idx: usize,
+) -> Result<&'a Entry, InterpreterError> {
+    indy.bootstrap_args
+        .get(idx)
+        .and_then(|arg| pool.entry(*arg))
+        .ok_or(InterpreterError::InvalidBootstrapArgs(
+            "missing static argument",
+        ))
+}
+
+/// Argument and return types of a method descriptor
+fn signature(
+    desc: &mstr,
+) -> Result<(Vec<DataType<'static>>, ReturnType<'static>), InterpreterError> {
+    let mut sig = MethodSignature::from_descriptor(desc);
+    let args = sig.iter_args().map(|arg| arg.to_owned()).collect();
+    if sig.errored() {
+        return Err(InterpreterError::InvalidBootstrapArgs(
+            "invalid method descriptor",
+        ));
+    }
+
+    Ok((args, sig.return_type().to_owned()))
+}
@@ -0,0 +1,593 @@
+//! Minimal class file writer for classes generated at runtime. Methods are straight-line code
+//! only, as no stack map frames are written
+
+use std::collections::HashMap;
+
+use cafebabe::{AccessFlags, ClassAccessFlags, FieldAccessFlags, MethodAccessFlags};
+
+use crate::constant_pool::ReferenceKind;
+use crate::interpreter::insn::Opcode;
+use crate::types::{DataType, MethodSignature, PrimitiveDataType, ReturnType};
+
+/// Java 8
+const MAJOR_VERSION: u16 = 52;
+
+/// Strings are all modified UTF-8 bytes
+#[derive(Clone, Hash, Eq, PartialEq)]
+enum Constant {
+    Utf8(Box<[u8]>),
+    Int(i32),
+    Class(u16),
+    String(u16),
+    NameAndType(u16, u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    InterfaceMethodRef(u16, u16),
+    MethodHandle(u8, u16),
+    MethodType(u16),
+    /// Bootstrap method index and name and type
+    Dynamic(u16, u16),
+}
+
+/// Deduplicates constants
+#[derive(Default)]
+pub struct ConstantPoolBuilder {
+    constants: Vec<Constant>,
+    indices: HashMap<Constant, u16>,
+}
+
+pub struct ClassWriter {
+    pool: ConstantPoolBuilder,
+    flags: ClassAccessFlags,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Vec<u16>,
+    fields: Vec<Member>,
+    methods: Vec<Member>,
+    /// NestHost attribute name and class
+    nest_host: Option<(u16, u16)>,
+    /// BootstrapMethods attribute name and methods
+    bootstrap_methods: Option<(u16, Vec<BootstrapMethod>)>,
+}
+
+struct BootstrapMethod {
+    handle: u16,
+    args: Vec<u16>,
+}
+
+struct Member {
+    flags: u16,
+    name: u16,
+    desc: u16,
+    /// Code attribute name and contents
+    code: Option<(u16, Vec<u8>)>,
+}
+
+/// Bytecode of a single method, tracking the maximum operand stack depth
+#[derive(Default)]
+pub struct CodeBuilder {
+    code: Vec<u8>,
+    stack: u16,
+    max_stack: u16,
+}
+
+impl ConstantPoolBuilder {
+    fn add(&mut self, constant: Constant) -> u16 {
+        if let Some(idx) = self.indices.get(&constant) {
+            return *idx;
+        }
+
+        // 1-indexed, and there are no wide constants
+        self.constants.push(constant.clone());
+        let idx = self.constants.len() as u16;
+        self.indices.insert(constant, idx);
+        idx
+    }
+
+    pub fn utf8(&mut self, s: &[u8]) -> u16 {
+        self.add(Constant::Utf8(s.into()))
+    }
+
+    pub fn int(&mut self, int: i32) -> u16 {
+        self.add(Constant::Int(int))
+    }
+
+    pub fn class(&mut self, name: &[u8]) -> u16 {
+        let name = self.utf8(name);
+        self.add(Constant::Class(name))
+    }
+
+    pub fn string(&mut self, s: &[u8]) -> u16 {
+        let s = self.utf8(s);
+        self.add(Constant::String(s))
+    }
+
+    fn name_and_type(&mut self, name: &[u8], desc: &[u8]) -> u16 {
+        let name = self.utf8(name);
+        let desc = self.utf8(desc);
+        self.add(Constant::NameAndType(name, desc))
+    }
+
+    pub fn field_ref(&mut self, class: &[u8], name: &[u8], desc: &[u8]) -> u16 {
+        let class = self.class(class);
+        let name_and_type = self.name_and_type(name, desc);
+        self.add(Constant::FieldRef(class, name_and_type))
+    }
+
+    pub fn method_ref(&mut self, class: &[u8], name: &[u8], desc: &[u8]) -> u16 {
+        let class = self.class(class);
+        let name_and_type = self.name_and_type(name, desc);
+        self.add(Constant::MethodRef(class, name_and_type))
+    }
+
+    pub fn interface_method_ref(&mut self, class: &[u8], name: &[u8], desc: &[u8]) -> u16 {
+        let class = self.class(class);
+        let name_and_type = self.name_and_type(name, desc);
+        self.add(Constant::InterfaceMethodRef(class, name_and_type))
+    }
+
+    // loadable constants are only generated by tests, the vm links them itself instead
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn method_handle(&mut self, kind: ReferenceKind, reference: u16) -> u16 {
+        self.add(Constant::MethodHandle(kind as u8, reference))
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn method_type(&mut self, desc: &[u8]) -> u16 {
+        let desc = self.utf8(desc);
+        self.add(Constant::MethodType(desc))
+    }
+
+    /// Dynamically computed constant, with the index returned by
+    /// [ClassWriter::add_bootstrap_method]
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn dynamic(&mut self, bootstrap_method: u16, name: &[u8], desc: &[u8]) -> u16 {
+        let name_and_type = self.name_and_type(name, desc);
+        self.add(Constant::Dynamic(bootstrap_method, name_and_type))
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        put_u16(out, self.constants.len() as u16 + 1);
+        for constant in &self.constants {
+            match constant {
+                Constant::Utf8(s) => {
+                    out.push(1);
+                    put_u16(out, s.len() as u16);
+                    out.extend_from_slice(s);
+                }
+                Constant::Int(int) => {
+                    out.push(3);
+                    out.extend_from_slice(&int.to_be_bytes());
+                }
+                Constant::Class(name) => {
+                    out.push(7);
+                    put_u16(out, *name);
+                }
+                Constant::String(s) => {
+                    out.push(8);
+                    put_u16(out, *s);
+                }
+                Constant::MethodHandle(kind, reference) => {
+                    out.push(15);
+                    out.push(*kind);
+                    put_u16(out, *reference);
+                }
+                Constant::MethodType(desc) => {
+                    out.push(16);
+                    put_u16(out, *desc);
+                }
+                Constant::Dynamic(bootstrap_method, name_and_type) => {
+                    out.push(17);
+                    put_u16(out, *bootstrap_method);
+                    put_u16(out, *name_and_type);
+                }
+                Constant::FieldRef(a, b)
+                | Constant::MethodRef(a, b)
+                | Constant::InterfaceMethodRef(a, b)
+                | Constant::NameAndType(a, b) => {
+                    out.push(match constant {
+                        Constant::FieldRef(..) => 9,
+                        Constant::MethodRef(..) => 10,
+                        Constant::InterfaceMethodRef(..) => 11,
+                        _ => 12,
+                    });
+                    put_u16(out, *a);
+                    put_u16(out, *b);
+                }
+            }
+        }
+    }
+}
+
+impl ClassWriter {
+    pub fn new(name: &[u8], super_class: &[u8], flags: ClassAccessFlags) -> Self {
+        let mut pool = ConstantPoolBuilder::default();
+        let this_class = pool.class(name);
+        let super_class = pool.class(super_class);
+        ClassWriter {
+            pool,
+            flags,
+            this_class,
+            super_class,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            nest_host: None,
+            bootstrap_methods: None,
+        }
+    }
+
+    /// Makes the class a nestmate of the given host, so it can access its private members
+    pub fn set_nest_host(&mut self, host: &[u8]) {
+        let name = self.pool.utf8(b"NestHost");
+        let class = self.pool.class(host);
+        self.nest_host = Some((name, class));
+    }
+
+    /// Returns the index of the bootstrap method, for dynamically computed constants
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn add_bootstrap_method(&mut self, handle: u16, args: &[u16]) -> u16 {
+        let pool = &mut self.pool;
+        let (_, methods) = self
+            .bootstrap_methods
+            .get_or_insert_with(|| (pool.utf8(b"BootstrapMethods"), Vec::new()));
+        methods.push(BootstrapMethod {
+            handle,
+            args: args.to_vec(),
+        });
+        methods.len() as u16 - 1
+    }
+
+    pub fn pool(&mut self) -> &mut ConstantPoolBuilder {
+        &mut self.pool
+    }
+
+    pub fn add_interface(&mut self, name: &[u8]) {
+        let interface = self.pool.class(name);
+        self.interfaces.push(interface);
+    }
+
+    pub fn add_field(&mut self, flags: FieldAccessFlags, name: &[u8], desc: &[u8]) {
+        let member = Member {
+            flags: flags.bits(),
+            name: self.pool.utf8(name),
+            desc: self.pool.utf8(desc),
+            code: None,
+        };
+        self.fields.push(member);
+    }
+
+    pub fn add_method(
+        &mut self,
+        flags: MethodAccessFlags,
+        name: &[u8],
+        desc: &[u8],
+        code: CodeBuilder,
+    ) {
+        let max_locals = {
+            let mut sig = MethodSignature::from_descriptor(cafebabe::mutf8::mstr::from_mutf8(desc));
+            let args = sig.iter_args().map(|arg| slots(&arg)).sum::<u16>();
+            args + if flags.is_static() { 0 } else { 1 }
+        };
+
+        let mut attr = Vec::with_capacity(code.code.len() + 12);
+        put_u16(&mut attr, code.max_stack);
+        put_u16(&mut attr, max_locals);
+        attr.extend_from_slice(&(code.code.len() as u32).to_be_bytes());
+        attr.extend_from_slice(&code.code);
+        put_u16(&mut attr, 0); // exception handlers
+        put_u16(&mut attr, 0); // attributes
+
+        let member = Member {
+            flags: flags.bits(),
+            name: self.pool.utf8(name),
+            desc: self.pool.utf8(desc),
+            code: Some((self.pool.utf8(b"Code"), attr)),
+        };
+        self.methods.push(member);
+    }
+
+    /// Adds a method without code, with the NATIVE flag added
+    pub fn add_native_method(&mut self, flags: MethodAccessFlags, name: &[u8], desc: &[u8]) {
+        let member = Member {
+            flags: (flags | MethodAccessFlags::NATIVE).bits(),
+            name: self.pool.utf8(name),
+            desc: self.pool.utf8(desc),
+            code: None,
+        };
+        self.methods.push(member);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1024);
+        out.extend_from_slice(&0xcafebabe_u32.to_be_bytes());
+        put_u16(&mut out, 0);
+        put_u16(&mut out, MAJOR_VERSION);
+        self.pool.write(&mut out);
+
+        put_u16(&mut out, self.flags.bits());
+        put_u16(&mut out, self.this_class);
+        put_u16(&mut out, self.super_class);
+        put_u16(&mut out, self.interfaces.len() as u16);
+        for interface in &self.interfaces {
+            put_u16(&mut out, *interface);
+        }
+
+        for members in [&self.fields, &self.methods] {
+            put_u16(&mut out, members.len() as u16);
+            for member in members {
+                put_u16(&mut out, member.flags);
+                put_u16(&mut out, member.name);
+                put_u16(&mut out, member.desc);
+                match &member.code {
+                    None => put_u16(&mut out, 0),
+                    Some((name, attr)) => {
+                        put_u16(&mut out, 1);
+                        put_u16(&mut out, *name);
+                        out.extend_from_slice(&(attr.len() as u32).to_be_bytes());
+                        out.extend_from_slice(attr);
+                    }
+                }
+            }
+        }
+
+        let attribute_count = self.nest_host.iter().count() + self.bootstrap_methods.iter().count();
+        put_u16(&mut out, attribute_count as u16);
+        if let Some((name, class)) = self.nest_host {
+            put_u16(&mut out, name);
+            out.extend_from_slice(&2_u32.to_be_bytes());
+            put_u16(&mut out, class);
+        }
+
+        if let Some((name, methods)) = &self.bootstrap_methods {
+            let mut attr = Vec::new();
+            put_u16(&mut attr, methods.len() as u16);
+            for method in methods {
+                put_u16(&mut attr, method.handle);
+                put_u16(&mut attr, method.args.len() as u16);
+                for arg in &method.args {
+                    put_u16(&mut attr, *arg);
+                }
+            }
+
+            put_u16(&mut out, *name);
+            out.extend_from_slice(&(attr.len() as u32).to_be_bytes());
+            out.extend_from_slice(&attr);
+        }
+        out
+    }
+}
+
+impl CodeBuilder {
+    fn emit(&mut self, opcode: Opcode, pushed: u16, popped: u16) {
+        self.code.push(opcode as u8);
+        self.stack -= popped;
+        self.stack += pushed;
+        self.max_stack = self.max_stack.max(self.stack);
+    }
+
+    fn emit_u16(&mut self, opcode: Opcode, operand: u16, pushed: u16, popped: u16) {
+        self.emit(opcode, pushed, popped);
+        put_u16(&mut self.code, operand);
+    }
+
+    pub fn load(&mut self, ty: &DataType, local: u16) {
+        let opcode = match ty {
+            DataType::Primitive(PrimitiveDataType::Long) => Opcode::Lload,
+            DataType::Primitive(PrimitiveDataType::Float) => Opcode::Fload,
+            DataType::Primitive(PrimitiveDataType::Double) => Opcode::Dload,
+            DataType::Primitive(_) => Opcode::Iload,
+            _ => Opcode::Aload,
+        };
+
+        if local > u8::MAX as u16 {
+            self.code.push(Opcode::Wide as u8);
+            self.emit_u16(opcode, local, slots(ty), 0);
+        } else {
+            self.emit(opcode, slots(ty), 0);
+            self.code.push(local as u8);
+        }
+    }
+
+    /// Loads the given arguments from consecutive locals starting at `local`
+    pub fn load_all<'a>(&mut self, args: impl Iterator<Item = &'a DataType<'a>>, mut local: u16) {
+        for arg in args {
+            self.load(arg, local);
+            local += slots(arg);
+        }
+    }
+
+    pub fn ldc(&mut self, constant: u16) {
+        self.emit_u16(Opcode::LdcW, constant, 1, 0);
+    }
+
+    pub fn new_object(&mut self, class: u16) {
+        self.emit_u16(Opcode::New, class, 1, 0);
+    }
+
+    pub fn dup(&mut self) {
+        self.emit(Opcode::Dup, 2, 1);
+    }
+
+    pub fn checkcast(&mut self, class: u16) {
+        self.emit_u16(Opcode::Checkcast, class, 1, 1);
+    }
+
+    pub fn getfield(&mut self, field: u16, ty: &DataType) {
+        self.emit_u16(Opcode::Getfield, field, slots(ty), 1);
+    }
+
+    pub fn putfield(&mut self, field: u16, ty: &DataType) {
+        self.emit_u16(Opcode::Putfield, field, 0, 1 + slots(ty));
+    }
+
+    pub fn getstatic(&mut self, field: u16, ty: &DataType) {
+        self.emit_u16(Opcode::Getstatic, field, slots(ty), 0);
+    }
+
+    pub fn putstatic(&mut self, field: u16, ty: &DataType) {
+        self.emit_u16(Opcode::Putstatic, field, 0, slots(ty));
+    }
+
+    /// Pops the receiver too if not invokestatic
+    pub fn invoke(&mut self, opcode: Opcode, method: u16, desc: &[u8]) {
+        let mut sig = MethodSignature::from_descriptor(cafebabe::mutf8::mstr::from_mutf8(desc));
+        let mut popped = sig.iter_args().map(|arg| slots(&arg)).sum::<u16>();
+        let pushed = match sig.return_type() {
+            ReturnType::Void => 0,
+            ReturnType::Returns(ty) => slots(&ty),
+        };
+
+        if opcode != Opcode::Invokestatic {
+            popped += 1;
+        }
+
+        self.emit_u16(opcode, method, pushed, popped);
+        if opcode == Opcode::Invokeinterface {
+            self.code.push(popped as u8);
+            self.code.push(0);
+        }
+    }
+
+    /// Primitive widening conversion, nothing if the types are the same or only differ in int
+    /// representation
+    pub fn widen(&mut self, from: PrimitiveDataType, to: PrimitiveDataType) {
+        use PrimitiveDataType::*;
+        let opcode = match (from, to) {
+            (Long, Float) => Opcode::L2F,
+            (Long, Double) => Opcode::L2D,
+            (Float, Double) => Opcode::F2D,
+            (Long | Float | Double, _) => return,
+            (_, Long) => Opcode::I2L,
+            (_, Float) => Opcode::I2F,
+            (_, Double) => Opcode::I2D,
+            _ => return,
+        };
+
+        let (from, to) = (DataType::Primitive(from), DataType::Primitive(to));
+        self.emit(opcode, slots(&to), slots(&from));
+    }
+
+    pub fn pop(&mut self, ty: &DataType) {
+        match slots(ty) {
+            2 => self.emit(Opcode::Pop2, 0, 2),
+            _ => self.emit(Opcode::Pop, 0, 1),
+        }
+    }
+
+    pub fn return_value(&mut self, ty: &ReturnType) {
+        let (opcode, popped) = match ty {
+            ReturnType::Void => (Opcode::Return, 0),
+            ReturnType::Returns(ty) => {
+                let opcode = match ty {
+                    DataType::Primitive(PrimitiveDataType::Long) => Opcode::Lreturn,
+                    DataType::Primitive(PrimitiveDataType::Float) => Opcode::Freturn,
+                    DataType::Primitive(PrimitiveDataType::Double) => Opcode::Dreturn,
+                    DataType::Primitive(_) => Opcode::Ireturn,
+                    _ => Opcode::Areturn,
+                };
+                (opcode, slots(ty))
+            }
+        };
+
+        self.emit(opcode, 0, popped);
+    }
+}
+
+/// Local variable and operand stack slots taken by a value of the given type
+pub fn slots(ty: &DataType) -> u16 {
+    match ty {
+        DataType::Primitive(PrimitiveDataType::Long | PrimitiveDataType::Double) => 2,
+        _ => 1,
+    }
+}
+
+/// Field descriptor of the given type, e.g. `I` or `Ljava/lang/String;`
+pub fn descriptor(ty: &DataType) -> Vec<u8> {
+    match ty {
+        DataType::Primitive(prim) => vec![prim.char() as u8],
+        DataType::Reference(name) if name.as_bytes().starts_with(b"[") => name.as_bytes().to_vec(),
+        DataType::Reference(name) => {
+            let mut desc = Vec::with_capacity(name.len() + 2);
+            desc.push(b'L');
+            desc.extend_from_slice(name.as_bytes());
+            desc.push(b';');
+            desc
+        }
+        DataType::ReturnAddress => unreachable!("return address has no descriptor"),
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cafebabe::mutf8::StrExt;
+
+    #[test]
+    fn written_class_parses() {
+        let mut writer = ClassWriter::new(
+            b"Generated",
+            b"java/lang/Object",
+            ClassAccessFlags::FINAL | ClassAccessFlags::SUPER,
+        );
+        writer.add_interface(b"java/lang/Runnable");
+        writer.add_field(FieldAccessFlags::PRIVATE, b"value", b"J");
+
+        let field = writer.pool().field_ref(b"Generated", b"value", b"J");
+        let mut code = CodeBuilder::default();
+        code.load(&DataType::Reference("Generated".to_mstr()), 0);
+        code.getfield(field, &DataType::Primitive(PrimitiveDataType::Long));
+        code.load(&DataType::Primitive(PrimitiveDataType::Int), 1);
+        code.widen(PrimitiveDataType::Int, PrimitiveDataType::Long);
+        code.pop(&DataType::Primitive(PrimitiveDataType::Long));
+        code.return_value(&ReturnType::Returns(DataType::Primitive(
+            PrimitiveDataType::Long,
+        )));
+        assert_eq!(code.max_stack, 4);
+        writer.add_method(MethodAccessFlags::PUBLIC, b"get", b"(I)J", code);
+        writer.set_nest_host(b"Host");
+
+        let target = writer.pool().method_ref(b"Generated", b"get", b"(I)J");
+        let handle = writer
+            .pool()
+            .method_handle(ReferenceKind::InvokeVirtual, target);
+        let bootstrap = writer.add_bootstrap_method(handle, &[handle]);
+        writer.pool().dynamic(bootstrap, b"_", b"J");
+
+        let bytes = writer.finish();
+        let class = cafebabe::load_from_buffer(&bytes).expect("should parse");
+        assert_eq!(class.this_class().unwrap(), "Generated".as_mstr());
+        assert_eq!(class.super_class().unwrap(), "java/lang/Object".as_mstr());
+        assert_eq!(class.interface_count(), 1);
+        assert_eq!(class.fields().len(), 1);
+        let host = class.attribute::<cafebabe::attribute::NestHost>();
+        assert_eq!(host.unwrap().0.as_mstr(), "Host".as_mstr());
+        let bootstrap = class.attribute::<cafebabe::attribute::BootstrapMethods>();
+        let bootstrap = bootstrap.expect("no bootstrap methods");
+        assert_eq!(bootstrap.0.len(), 1);
+        assert_eq!(bootstrap.0[0].arguments.len(), 1);
+
+        let method = class.methods().next().unwrap();
+        assert_eq!(method.name, "get".as_mstr());
+        let code = match method.attributes[0].to_owned(class.constant_pool()) {
+            Ok(cafebabe::attribute::OwnedAttribute::Code(code)) => code,
+            _ => panic!("should have code"),
+        };
+        assert_eq!((code.max_stack, code.max_locals), (4, 2));
+        assert_eq!(code.code[0], Opcode::Aload as u8);
+    }
+
+    #[test]
+    fn descriptors() {
+        let desc = |s: &str| descriptor(&DataType::from_descriptor(&s.to_mstr()).unwrap());
+        assert_eq!(desc("I"), b"I");
+        assert_eq!(desc("Ljava/lang/String;"), b"Ljava/lang/String;");
+        assert_eq!(desc("[[J"), b"[[J");
+        assert_eq!(desc("[Ljava/lang/Object;"), b"[Ljava/lang/Object;");
+    }
+}
@@ -4,8 +4,8 @@ use crate::types::{DataType, DataValue, ReturnType};
 use cafebabe::mutf8::MString;
 use thiserror::*;
 
-use crate::alloc::VmRef;
-use crate::class::{Class, ClassType};
+use crate::alloc::{InternedString, VmRef};
+use crate::class::{Class, ClassType, WhichLoader};
 
 // TODO combine repetetive errors for different data types
 
@@ -38,19 +38,40 @@ pub enum InterpreterError {
     #[error("Constant pool entry {0} is not present or a class ref")]
     NotClassRef(u16),
 
+    #[error("Constant pool entry {0} is not present or a method handle")]
+    NotMethodHandle(u16),
+
+    #[error("Constant pool entry {0} is not present or an invokedynamic")]
+    NotInvokeDynamic(u16),
+
+    #[error("Bootstrap method {class:?}.{name:?} is not supported")]
+    UnsupportedBootstrapMethod {
+        class: InternedString,
+        name: InternedString,
+    },
+
+    #[error("Invalid bootstrap method arguments: {0}")]
+    InvalidBootstrapArgs(&'static str),
+
     #[error("The method {class:?}.{name:?}:{desc:?} could not be resolved")]
     MethodNotFound {
-        class: MString,
-        name: MString,
+        class: InternedString,
+        name: InternedString,
         desc: MString,
     },
 
     #[error("The field {name:?}:{desc:?} could not be resolved")]
     FieldNotFound {
-        name: MString,
+        name: InternedString,
         desc: DataType<'static>,
     },
 
+    #[error("Instances of {class:?} have no field {name:?}")]
+    NoInstanceField {
+        class: ClassType,
+        name: InternedString,
+    },
+
     #[error("Not enough operands on stack to pop, expected {expected} but only have {actual}")]
     NotEnoughArgs { expected: usize, actual: usize },
 
@@ -70,9 +91,15 @@ pub enum InterpreterError {
     #[error("Cannot load uninitialised local var {0}")]
     UninitialisedLoad(usize),
 
+    #[error("Cannot load local var {0} as it is the second half of a long or double")]
+    SplitWideLocalVar(usize),
+
     #[error("Cannot pop from empty operand stack")]
     NoOperand,
 
+    #[error("Stack instruction would split a long or double operand")]
+    SplitWideOperand,
+
     #[error("Expected non-array reference but got {0:?} instead")]
     UnexpectedArray(ClassType),
 
@@ -94,9 +121,18 @@ pub enum InterpreterError {
     #[error("Unexpected array type")]
     UnexpectedArrayType,
 
+    #[error("Cannot store {value:?} in an array of {element:?}")]
+    InvalidArrayStore {
+        element: ClassType,
+        value: DataType<'static>,
+    },
+
     #[error("Invalid array element type {0}")]
     InvalidArrayType(u8),
 
+    #[error("Cannot create {dimensions} dimensions of array class {class:?}")]
+    InvalidArrayDimensions { dimensions: u8, class: ClassType },
+
     #[error("Expected return type of {expected:?} but got {actual:?}")]
     InvalidReturnValue {
         expected: ReturnType<'static>,
@@ -106,6 +142,14 @@ pub enum InterpreterError {
     #[error("Cannot pop from empty frame stack")]
     NoFrame,
 
+    /// Not really an error, the class has to be loaded by running the user class loader once the
+    /// current instruction has released the interpreter state
+    #[error("Class {name:?} must be loaded by {loader:?}")]
+    UserClassLoad {
+        name: InternedString,
+        loader: WhichLoader,
+    },
+
     /// Not really an error
     #[error("Exception raised: {0:?}")]
     ExceptionRaised(Throwables),
@@ -113,7 +157,7 @@ pub enum InterpreterError {
     #[error("The native method {}.{name:?}:{desc:?} could not be resolved", .class.name())]
     NativeMethodNotFound {
         class: VmRef<Class>,
-        name: MString,
+        name: InternedString,
         desc: MString,
     },
 
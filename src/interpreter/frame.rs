@@ -1,13 +1,14 @@
 use crate::alloc::VmRef;
 use crate::class::{Class, ClassType, Method, MethodCode, NativeCode, NativeFunction, Object};
 use crate::interpreter::error::InterpreterError;
+use crate::interpreter::insn::DecodedCode;
 use crate::types::DataValue;
 
 use log::*;
 
 use crate::error::Throwables;
 
-use cafebabe::AccessFlags;
+use cafebabe::{AccessFlags, MethodAccessFlags};
 
 use std::borrow::Cow;
 use std::cell::RefCell;
@@ -17,6 +18,8 @@ use std::sync::Arc;
 enum StackValue {
     Uninitialised,
     Initialised(DataValue),
+    /// Second slot of the long or double in the slot before
+    SecondHalf,
 }
 
 pub struct LocalVariables(Vec<StackValue>);
@@ -30,7 +33,12 @@ pub struct JavaFrame {
     pub method: VmRef<Method>,
     pub local_vars: LocalVariables,
     pub operand_stack: OperandStack,
-    pub code: Arc<[u8]>,
+    pub code: Arc<DecodedCode>,
+    /// The stored pc is usually past the executing instruction, but is set to the instruction
+    /// itself when it throws, until the exception is caught or the frame popped
+    pub pc_is_exact: bool,
+    /// Entered when pushed and exited when popped, for synchronized methods
+    monitor: Option<VmRef<Object>>,
 }
 
 pub struct NativeFrame {
@@ -40,6 +48,8 @@ pub struct NativeFrame {
     /// Some on init, None after exec
     pub args: Option<Box<[DataValue]>>,
     local_refs: RefCell<Vec<VmRef<()>>>,
+    /// Entered when pushed and exited when popped, for synchronized methods
+    monitor: Option<VmRef<Object>>,
 }
 
 pub enum NativeFrameInner {
@@ -75,15 +85,33 @@ impl LocalVariables {
         LocalVariables(vars)
     }
 
+    /// Longs and doubles occupy `idx` and `idx+1`. Overwriting either half of a wide value
+    /// invalidates the other
     pub fn store(&mut self, idx: usize, value: DataValue) -> Result<(), InterpreterError> {
         let count = self.0.len();
-        self.0
-            .get_mut(idx)
-            .ok_or(InterpreterError::InvalidLocalVar {
-                requested: idx,
+        let last = if value.is_wide() { idx + 1 } else { idx };
+        if last >= count {
+            return Err(InterpreterError::InvalidLocalVar {
+                requested: last,
                 count,
-            })
-            .map(|val| *val = StackValue::Initialised(value))
+            });
+        }
+
+        // storing into the second half of a wide value
+        if let Some(StackValue::SecondHalf) = self.0.get(idx) {
+            self.0[idx - 1] = StackValue::Uninitialised;
+        }
+
+        // overwriting the first half of a wide value
+        if let Some(StackValue::SecondHalf) = self.0.get(last + 1) {
+            self.0[last + 1] = StackValue::Uninitialised;
+        }
+
+        if value.is_wide() {
+            self.0[idx + 1] = StackValue::SecondHalf;
+        }
+        self.0[idx] = StackValue::Initialised(value);
+        Ok(())
     }
 
     pub fn load(&mut self, idx: usize) -> Result<DataValue, InterpreterError> {
@@ -95,6 +123,7 @@ impl LocalVariables {
             })
             .and_then(|val| match val {
                 StackValue::Uninitialised => Err(InterpreterError::UninitialisedLoad(idx)),
+                StackValue::SecondHalf => Err(InterpreterError::SplitWideLocalVar(idx)),
                 StackValue::Initialised(val) => Ok(val.clone()),
             })
     }
@@ -105,8 +134,6 @@ impl LocalVariables {
             v => Err(InterpreterError::NotReference(idx, v)),
         })
     }
-
-    // TODO validate local var slot in case of wide vars
 }
 
 impl OperandStack {
@@ -115,7 +142,6 @@ impl OperandStack {
     }
 
     pub fn push(&mut self, value: DataValue) {
-        // TODO longs and doubles take 2 slots!
         debug!(
             "pushing {:?} onto operand stack, count is now {:?}",
             value,
@@ -147,6 +173,10 @@ impl OperandStack {
         }
     }
 
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
     pub fn depth(&self) -> usize {
         self.0.iter().map(|v| if v.is_wide() { 2 } else { 1 }).sum()
     }
@@ -162,10 +192,28 @@ impl OperandStack {
 
     /// idx is n from the last element e.g. 0 is the last
     pub fn peek_at(&self, idx: usize) -> Option<&DataValue> {
-        let idx = self.0.len() - 1 - idx;
+        let idx = self.0.len().checked_sub(idx + 1)?;
         self.0.get(idx)
     }
 
+    /// Number of operands starting `idx` from the top that occupy exactly `slots` slots, where
+    /// longs and doubles take 2. Used by the untyped stack instructions like `pop2` and `dup_x2`
+    pub fn operands_in_slots(&self, idx: usize, slots: usize) -> Result<usize, InterpreterError> {
+        let mut taken = 0;
+        let mut n = 0;
+        while taken < slots {
+            let val = self.peek_at(idx + n).ok_or(InterpreterError::NoOperand)?;
+            taken += if val.is_wide() { 2 } else { 1 };
+            n += 1;
+        }
+
+        if taken == slots {
+            Ok(n)
+        } else {
+            Err(InterpreterError::SplitWideOperand)
+        }
+    }
+
     /// idx is n from the end e.g. 1 is before the last element
     pub fn insert_at(&mut self, val: DataValue, idx: usize) {
         let idx = self.0.len() - idx;
@@ -209,6 +257,22 @@ impl FrameStack {
         })
     }
 
+    /// Stores the pc of the frame at the given depth from the bottom of the stack, if it hasn't
+    /// been popped
+    pub fn set_pc(&mut self, idx: usize, new_pc: usize) {
+        if let Some((_, pc)) = self.0.get_mut(idx) {
+            *pc = new_pc;
+        }
+    }
+
+    /// Points the top java frame's pc at the given throwing instruction, so handlers and stack
+    /// traces use it as is
+    pub fn set_throwing_pc(&mut self, throwing_pc: usize) {
+        let (frame, pc) = self.top_java_mut().expect("no java frame");
+        *pc = throwing_pc;
+        frame.pc_is_exact = true;
+    }
+
     pub fn top_java(&self) -> Option<&JavaFrame> {
         self.0.last().and_then(|(frame, _)| match frame {
             Frame::Java(frame) => Some(frame),
@@ -223,6 +287,11 @@ impl FrameStack {
     pub fn iter(&self) -> impl Iterator<Item = &Frame> + '_ {
         self.0.iter().rev().map(|(frame, _)| frame)
     }
+
+    /// Top down, with the pc stored for each frame
+    pub fn iter_with_pc(&self) -> impl Iterator<Item = (&Frame, usize)> + '_ {
+        self.0.iter().rev().map(|(frame, pc)| (frame, *pc))
+    }
 }
 
 impl StackValue {
@@ -245,8 +314,9 @@ impl Frame {
         match method.code() {
             MethodCode::Abstract => {
                 warn!("method {} is abstract", method);
-                Err(InterpreterError::ExceptionRaised(Throwables::Other(
+                Err(InterpreterError::ExceptionRaised(Throwables::WithMessage(
                     "java/lang/AbstractMethodError",
+                    method.to_string(),
                 )))
             }
             MethodCode::Java(code) => {
@@ -260,9 +330,7 @@ impl Frame {
 
                     if thisref.is_null() {
                         debug!("`this` is null");
-                        return Err(InterpreterError::ExceptionRaised(
-                            Throwables::NullPointerException,
-                        ));
+                        return Err(InterpreterError::ExceptionRaised(invoke_on_null(&method)));
                     }
 
                     trace!("`this`: {:?}", thisref.print_fields());
@@ -272,13 +340,15 @@ impl Frame {
                     0
                 };
 
-                for (i, arg) in args.rev().enumerate() {
-                    let var_offset = i + offset;
-                    trace!("local var {} = {:?}", var_offset, arg);
+                let this = local_vars.load_reference(0).ok();
+                let monitor = Self::synchronized_on(&method, this)?;
 
-                    // TODO long and double are wide
-                    debug_assert!(!arg.is_wide(), "wide local var");
+                let mut var_offset = offset;
+                for arg in args.rev() {
+                    trace!("local var {} = {:?}", var_offset, arg);
+                    let slots = if arg.is_wide() { 2 } else { 1 };
                     local_vars.store(var_offset, arg)?;
+                    var_offset += slots;
                 }
 
                 Ok(Frame::Java(JavaFrame {
@@ -286,7 +356,9 @@ impl Frame {
                     method: method.clone(),
                     local_vars,
                     operand_stack: OperandStack::new(code.max_stack as usize),
-                    code: code.code.clone(),
+                    code: method.decoded_code().expect("java method").clone(),
+                    pc_is_exact: false,
+                    monitor,
                 }))
             }
 
@@ -298,8 +370,9 @@ impl Frame {
                     }
                     NativeCode::FailedToBind => {
                         warn!("native method {} could not be bound", method);
-                        Err(InterpreterError::ExceptionRaised(Throwables::Other(
+                        Err(InterpreterError::ExceptionRaised(Throwables::WithMessage(
                             "java/lang/UnsatisfiedLinkError",
+                            method.to_string(),
                         )))
                     }
                     NativeCode::Bound(function) => {
@@ -308,7 +381,10 @@ impl Frame {
 
                         function.ensure_native_trampoline(&method)?;
 
-                        let args = args.collect();
+                        // `this` is last, args are in reverse order
+                        let args: Box<[DataValue]> = args.collect();
+                        let monitor = Self::synchronized_on(&method, args.last().cloned())?;
+
                         Ok(Frame::Native(NativeFrame {
                             inner: NativeFrameInner::Method {
                                 class,
@@ -317,6 +393,7 @@ impl Frame {
                             function: Some(function.clone()),
                             args: Some(args),
                             local_refs: Default::default(),
+                            monitor,
                         }))
                     }
                 }
@@ -324,6 +401,26 @@ impl Frame {
         }
     }
 
+    /// The object whose monitor a synchronized method holds: the class object for static
+    /// methods, otherwise `this`
+    fn synchronized_on(
+        method: &Method,
+        this: Option<DataValue>,
+    ) -> Result<Option<VmRef<Object>>, InterpreterError> {
+        if !method.flags().contains(MethodAccessFlags::SYNCHRONIZED) {
+            return Ok(None);
+        }
+
+        if method.flags().is_static() {
+            return Ok(Some(method.class().class_object().clone()));
+        }
+
+        match this.and_then(|this| this.into_reference().ok()) {
+            Some(obj) if !obj.is_null() => Ok(Some(obj)),
+            _ => Err(InterpreterError::ExceptionRaised(invoke_on_null(method))),
+        }
+    }
+
     pub fn new_no_args(method: VmRef<Method>) -> Result<Self, InterpreterError> {
         Self::new_with_args(method, std::iter::empty())
     }
@@ -363,6 +460,14 @@ impl Frame {
     fn is_java(&self) -> bool {
         matches!(self, Frame::Java(_))
     }
+
+    /// Object whose monitor is held for the duration of this synchronized method
+    pub fn monitor(&self) -> Option<&VmRef<Object>> {
+        match self {
+            Frame::Java(frame) => frame.monitor.as_ref(),
+            Frame::Native(frame) => frame.monitor.as_ref(),
+        }
+    }
 }
 
 impl<'a> FrameInfo<'a> {
@@ -418,6 +523,20 @@ impl JavaFrame {
         })
     }
 
+    pub fn pop_long(&mut self) -> Result<i64, InterpreterError> {
+        self.pop_value().and_then(|v| {
+            v.as_long()
+                .ok_or_else(|| InterpreterError::InvalidOperandForIntOp(v.data_type()))
+        })
+    }
+
+    pub fn pop_double(&mut self) -> Result<f64, InterpreterError> {
+        self.pop_value().and_then(|v| {
+            v.as_double()
+                .ok_or_else(|| InterpreterError::InvalidOperandForFloatOp(v.data_type()))
+        })
+    }
+
     pub fn pop_reference_value(&mut self) -> Result<DataValue, InterpreterError> {
         self.pop_value().and_then(|v| {
             if v.is_reference() {
@@ -531,8 +650,35 @@ impl JavaFrame {
         Ok((val1, val2))
     }
 
+    /// "..., value1, value2 →"
+    /// Returns (value1, value2)
+    pub fn pop_2_doubles(&mut self) -> Result<(f64, f64), InterpreterError> {
+        let (val1, val2) = {
+            let mut objs = self.pop_values(2)?;
+
+            // popped in reverse order
+            let val2 = objs.next().unwrap();
+            let val1 = objs.next().unwrap();
+
+            (val1, val2)
+        };
+
+        let val1 = val1
+            .as_double()
+            .ok_or_else(|| InterpreterError::InvalidOperandForFloatOp(val1.data_type()))?;
+
+        let val2 = val2
+            .as_double()
+            .ok_or_else(|| InterpreterError::InvalidOperandForFloatOp(val2.data_type()))?;
+
+        Ok((val1, val2))
+    }
+
+    /// Pops an index and an array whose element class passes `elem_check`, throwing if the array
+    /// is null or the index is out of bounds. `action` describes the access for the former
     pub fn pop_arrayref_and_idx(
         &mut self,
+        action: impl FnOnce() -> String,
         elem_check: impl FnOnce(&VmRef<Class>) -> bool,
     ) -> Result<(VmRef<Object>, usize), InterpreterError> {
         let idx = self.pop_int()?;
@@ -542,15 +688,15 @@ impl JavaFrame {
 
         let cls_type = match obj_cls.as_ref() {
             None => {
-                return Err(InterpreterError::ExceptionRaised(
-                    Throwables::NullPointerException,
-                ))
+                return Err(InterpreterError::ExceptionRaised(Throwables::null_pointer(
+                    action(),
+                )))
             }
             Some(cls) => cls.class_type(),
         };
 
         match cls_type {
-            ClassType::Array(ty) => {
+            ClassType::Array(ty, _) => {
                 if !elem_check(ty) {
                     error!("array has the wrong element type ({})", ty.name());
                     return Err(InterpreterError::UnexpectedArrayType);
@@ -566,8 +712,13 @@ impl JavaFrame {
                 idx,
                 obj.array_unchecked().len()
             );
-            Err(InterpreterError::ExceptionRaised(Throwables::Other(
+            Err(InterpreterError::ExceptionRaised(Throwables::WithMessage(
                 "java/lang/ArrayIndexOutOfBoundsException",
+                format!(
+                    "Index {} out of bounds for length {}",
+                    idx,
+                    obj.array_unchecked().len()
+                ),
             )))
         } else {
             Ok((obj, idx as usize))
@@ -580,6 +731,32 @@ impl JavaFrame {
             .cloned()
             .ok_or(InterpreterError::NoOperand)
     }
+
+    /// idx is n from the top e.g. 0 is the top
+    pub fn peek_value_at(&mut self, idx: usize) -> Result<DataValue, InterpreterError> {
+        if idx >= self.operand_stack.count() {
+            return Err(InterpreterError::NoOperand);
+        }
+
+        Ok(self.operand_stack.peek_at(idx).unwrap().clone()) // just checked
+    }
+
+    /// Class of the receiver below the args of an invocation of the instance `method`, throwing
+    /// if it is null
+    pub fn peek_receiver_class(&self, method: &Method) -> Result<VmRef<Class>, InterpreterError> {
+        let receiver = self
+            .operand_stack
+            .peek_at(method.args().len())
+            .ok_or(InterpreterError::NoOperand)?;
+
+        let receiver = receiver
+            .as_reference()
+            .ok_or_else(|| InterpreterError::InvalidOperandForObjectOp(receiver.data_type()))?;
+
+        receiver
+            .class()
+            .ok_or_else(|| InterpreterError::ExceptionRaised(invoke_on_null(method)))
+    }
 }
 
 impl NativeFrame {
@@ -591,6 +768,7 @@ impl NativeFrame {
             function: Some(NativeFunction::JniDirect(func_ptr)),
             args: None, // passed in manually
             local_refs: RefCell::new(Vec::new()),
+            monitor: None,
         }
     }
 
@@ -611,6 +789,10 @@ impl Drop for NativeFrame {
     }
 }
 
+fn invoke_on_null(method: &Method) -> Throwables {
+    Throwables::null_pointer(format_args!("invoke \"{}\"", method.java_signature()))
+}
+
 impl Debug for NativeFrameInner {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -624,7 +806,9 @@ impl Debug for NativeFrameInner {
 
 #[cfg(test)]
 mod tests {
-    use crate::interpreter::frame::OperandStack;
+    use crate::interpreter::error::InterpreterError;
+    use crate::interpreter::frame::{LocalVariables, OperandStack};
+    use crate::interpreter::insn::DecodedCode;
     use crate::types::DataValue;
     use itertools::Itertools;
 
@@ -673,4 +857,75 @@ mod tests {
             .collect_vec();
         assert_eq!(ints, vec![3, 10, 2, 1]);
     }
+
+    #[test]
+    fn operand_slots() {
+        let mut stack = OperandStack::new(6);
+        stack.push(DataValue::Int(1));
+        stack.push(DataValue::Long(2));
+        stack.push(DataValue::Int(3));
+        stack.push(DataValue::Int(4));
+
+        assert_eq!(stack.operands_in_slots(0, 1).unwrap(), 1);
+        assert_eq!(stack.operands_in_slots(0, 2).unwrap(), 2);
+        assert_eq!(stack.operands_in_slots(2, 2).unwrap(), 1);
+        assert_eq!(stack.operands_in_slots(2, 3).unwrap(), 2);
+
+        // would split the long
+        assert!(matches!(
+            stack.operands_in_slots(1, 2),
+            Err(InterpreterError::SplitWideOperand)
+        ));
+        assert!(matches!(
+            stack.operands_in_slots(2, 1),
+            Err(InterpreterError::SplitWideOperand)
+        ));
+
+        assert!(matches!(
+            stack.operands_in_slots(3, 2),
+            Err(InterpreterError::NoOperand)
+        ));
+        assert!(stack.peek_at(4).is_none());
+    }
+
+    #[test]
+    fn local_var_wide() {
+        let mut vars = LocalVariables::new_static(4);
+
+        vars.store(0, DataValue::Long(1)).unwrap();
+        vars.store(2, DataValue::Int(2)).unwrap();
+        assert_eq!(vars.load(0).unwrap().as_long(), Some(1));
+        assert!(matches!(
+            vars.load(1),
+            Err(InterpreterError::SplitWideLocalVar(1))
+        ));
+
+        // no room for the second half
+        assert!(vars.store(3, DataValue::Double(3.0)).is_err());
+
+        // overwriting the second half invalidates the first
+        vars.store(1, DataValue::Int(5)).unwrap();
+        assert!(matches!(
+            vars.load(0),
+            Err(InterpreterError::UninitialisedLoad(0))
+        ));
+        assert_eq!(vars.load(1).unwrap().as_int(), Some(5));
+
+        // overwriting the first half invalidates the second
+        vars.store(2, DataValue::Double(6.0)).unwrap();
+        vars.store(2, DataValue::Int(7)).unwrap();
+        assert!(matches!(
+            vars.load(3),
+            Err(InterpreterError::UninitialisedLoad(3))
+        ));
+
+        // a wide value straddling another wide value
+        vars.store(0, DataValue::Long(8)).unwrap();
+        vars.store(2, DataValue::Long(9)).unwrap();
+        vars.store(1, DataValue::Long(10)).unwrap();
+        assert!(vars.load(0).is_err());
+        assert_eq!(vars.load(1).unwrap().as_long(), Some(10));
+        assert!(vars.load(2).is_err());
+        assert!(vars.load(3).is_err());
+    }
 }
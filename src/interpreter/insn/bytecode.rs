@@ -9,44 +9,11 @@ pub(crate) struct InsnReader<'a> {
     pc: usize,
 }
 
-#[derive(Default)]
-pub struct InstructionBlob([u8; Self::MAX_INSN_SIZE]);
-
-impl InstructionBlob {
-    const MAX_INSN_SIZE: usize = 4;
-
-    fn fill<I>(&mut self, insn: &I) {
-        let insn_size = std::mem::size_of::<I>();
-        debug_assert!(
-            insn_size <= Self::MAX_INSN_SIZE,
-            "raise max instruction size to {} for {}",
-            insn_size,
-            std::any::type_name::<I>()
-        );
-
-        let src = insn as *const I as *const u8;
-        let dst = self.0.as_mut_ptr();
-
-        unsafe {
-            src.copy_to_nonoverlapping(dst, insn_size);
-        }
-    }
-
-    pub unsafe fn transmute<I>(&self) -> &I {
-        let insn_size = std::mem::size_of::<I>();
-        debug_assert!(
-            insn_size <= Self::MAX_INSN_SIZE,
-            "raise max instruction size to {} for {}",
-            insn_size,
-            std::any::type_name::<I>()
-        );
-
-        &*(self.0.as_ref() as *const [u8] as *const I)
-    }
-}
-
 //noinspection RsLiveness
-pub fn get_insn(bytecode: &[u8], pc: usize, blob: &mut InstructionBlob) -> Option<(usize, Opcode)> {
+/// Decodes the instruction at the given pc, returning it and the pc of the next instruction.
+/// Variable length instructions only hold the location of their tables in the code, not the
+/// tables themselves
+pub fn get_insn(bytecode: &[u8], pc: usize) -> Option<(usize, Instruction)> {
     let insn = *bytecode.get(pc)?;
     let mut reader = InsnReader {
         bytes: bytecode,
@@ -54,14 +21,12 @@ pub fn get_insn(bytecode: &[u8], pc: usize, blob: &mut InstructionBlob) -> Optio
     };
 
     macro_rules! insn {
-        ($insn:ident) => {{
-            let insn = $insn::parse(&mut reader)?;
-            blob.fill(&insn);
-            Opcode::$insn
-        }};
+        ($insn:ident) => {
+            Instruction::$insn($insn::parse(&mut reader)?)
+        };
     }
 
-    let opcode = match insn {
+    let insn = match insn {
         Aaload::OPCODE => insn!(Aaload),
         Aastore::OPCODE => insn!(Aastore),
         AconstNull::OPCODE => insn!(AconstNull),
@@ -232,7 +197,7 @@ pub fn get_insn(bytecode: &[u8], pc: usize, blob: &mut InstructionBlob) -> Optio
         Lload3::OPCODE => insn!(Lload3),
         Lmul::OPCODE => insn!(Lmul),
         Lneg::OPCODE => insn!(Lneg),
-        // Lookupswitch::OPCODE => insn!(Lookupswitch),
+        Lookupswitch::OPCODE => insn!(Lookupswitch),
         Lor::OPCODE => insn!(Lor),
         Lrem::OPCODE => insn!(Lrem),
         Lreturn::OPCODE => insn!(Lreturn),
@@ -248,7 +213,7 @@ pub fn get_insn(bytecode: &[u8], pc: usize, blob: &mut InstructionBlob) -> Optio
         Lxor::OPCODE => insn!(Lxor),
         Monitorenter::OPCODE => insn!(Monitorenter),
         Monitorexit::OPCODE => insn!(Monitorexit),
-        // Multianewarray::OPCODE => insn!(Multianewarray),
+        Multianewarray::OPCODE => insn!(Multianewarray),
         New::OPCODE => insn!(New),
         Newarray::OPCODE => insn!(Newarray),
         Nop::OPCODE => insn!(Nop),
@@ -262,18 +227,42 @@ pub fn get_insn(bytecode: &[u8], pc: usize, blob: &mut InstructionBlob) -> Optio
         Sastore::OPCODE => insn!(Sastore),
         Sipush::OPCODE => insn!(Sipush),
         Swap::OPCODE => insn!(Swap),
-        // Tableswitch::OPCODE => insn!(Tableswitch),
-        // Wide::OPCODE => insn!(Wide),
+        Tableswitch::OPCODE => insn!(Tableswitch),
+        Wide::OPCODE => insn!(Wide),
         o => {
             error!("unimplemented opcode {:?}", Opcode::try_from_primitive(o));
             return None;
         }
     };
 
-    Some((reader.pc, opcode))
+    Some((reader.pc, insn))
 }
 
-impl InsnReader<'_> {
+impl<'a> InsnReader<'a> {
+    pub fn new(bytes: &'a [u8], pc: usize) -> Self {
+        InsnReader { bytes, pc }
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Skips padding up to the next multiple of `n` from the start of the code
+    pub fn align(&mut self, n: usize) {
+        self.pc = self.pc.next_multiple_of(n);
+    }
+
+    /// Fails if it would move past the end of the code
+    pub fn skip(&mut self, n: usize) -> Option<()> {
+        let pc = self.pc.checked_add(n)?;
+        if pc > self.bytes.len() {
+            None
+        } else {
+            self.pc = pc;
+            Some(())
+        }
+    }
+
     pub fn read_u8(&mut self) -> Option<u8> {
         let byte = self.bytes.get(self.pc).copied();
         self.pc += 1;
@@ -330,8 +319,8 @@ impl InsnReader<'_> {
 
 #[cfg(test)]
 mod tests {
-    use crate::interpreter::insn::bytecode::InsnReader;
-    use crate::interpreter::insn::{get_insn, Aload, Getfield, InstructionBlob, Opcode};
+    use crate::interpreter::insn::bytecode::{get_insn, InsnReader};
+    use crate::interpreter::insn::{Aload, Getfield, Instruction, Opcode};
 
     #[test]
     fn reader() {
@@ -348,21 +337,64 @@ mod tests {
 
     #[test]
     fn parse_simple_insns() {
-        let mut blob = InstructionBlob::default();
         let bytes = vec![0x2a, 0x19, 0x08, 0xb4, 0x56, 0x78];
 
         let pc = 0;
-        let (pc, opcode) = get_insn(&bytes, pc, &mut blob).unwrap();
-        assert_eq!(opcode, Opcode::Aload0);
+        let (pc, insn) = get_insn(&bytes, pc).unwrap();
+        assert_eq!(insn.opcode(), Some(Opcode::Aload0));
+
+        let (pc, insn) = get_insn(&bytes, pc).unwrap();
+        assert!(matches!(insn, Instruction::Aload(Aload(0x08))));
+
+        let (pc, insn) = get_insn(&bytes, pc).unwrap();
+        assert!(matches!(insn, Instruction::Getfield(Getfield(0x5678))));
+
+        assert!(get_insn(&bytes, pc).is_none());
+    }
+
+    #[test]
+    fn parse_variable_length_insns() {
+        #[rustfmt::skip]
+        let bytes = vec![
+            0x00, // nop
+            0xaa, // tableswitch
+            0x00, 0x00, // padding
+            0x00, 0x00, 0x00, 0x10, // default
+            0x00, 0x00, 0x00, 0x01, // low
+            0x00, 0x00, 0x00, 0x02, // high
+            0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x30, // offsets
+            0xab, // lookupswitch
+            0x00, 0x00, 0x00, // padding
+            0x00, 0x00, 0x00, 0x10, // default
+            0x00, 0x00, 0x00, 0x01, // pair count
+            0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x08, // -1 => 8
+            0xc4, 0x15, 0x01, 0x02, // wide iload 0x102
+            0xc4, 0x84, 0x01, 0x02, 0xff, 0xfe, // wide iinc 0x102 -2
+            0xc4, 0xb1, 0x00, 0x00, // wide return is invalid
+        ];
+
+        let (pc, insn) = get_insn(&bytes, 0).unwrap();
+        assert_eq!(insn.opcode(), Some(Opcode::Nop));
+
+        let (pc, insn) = get_insn(&bytes, pc).unwrap();
+        assert_eq!(insn.opcode(), Some(Opcode::Tableswitch));
+        assert_eq!(pc, 24);
+
+        let (pc, insn) = get_insn(&bytes, pc).unwrap();
+        assert_eq!(insn.opcode(), Some(Opcode::Lookupswitch));
+        assert_eq!(pc, 44);
+
+        let (pc, insn) = get_insn(&bytes, pc).unwrap();
+        assert_eq!(insn.opcode(), Some(Opcode::Wide));
+        assert_eq!(pc, 48);
 
-        let (pc, opcode) = get_insn(&bytes, pc, &mut blob).unwrap();
-        assert_eq!(opcode, Opcode::Aload);
-        assert_eq!(unsafe { blob.transmute::<Aload>() }.0, 0x08);
+        let (pc, insn) = get_insn(&bytes, pc).unwrap();
+        assert_eq!(insn.opcode(), Some(Opcode::Wide));
+        assert_eq!(pc, 54);
 
-        let (pc, opcode) = get_insn(&bytes, pc, &mut blob).unwrap();
-        assert_eq!(opcode, Opcode::Getfield);
-        assert_eq!(unsafe { blob.transmute::<Getfield>() }.0, 0x5678);
+        assert!(get_insn(&bytes, pc).is_none());
 
-        assert!(get_insn(&bytes, pc, &mut blob).is_none());
+        // table runs past the end of the code
+        assert!(get_insn(&bytes[..20], 1).is_none());
     }
 }
@@ -0,0 +1,218 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, OnceLock};
+
+use log::*;
+
+use crate::interpreter::insn::bytecode::get_insn;
+use crate::interpreter::insn::instruction::*;
+use crate::interpreter::insn::quick::Quick;
+use crate::interpreter::interp::InterpreterState;
+
+/// Marks a pc that isn't the start of an instruction, or a branch whose target isn't known ahead
+/// of time
+const NO_INSN: u32 = u32::MAX;
+
+/// Method code translated once into instructions that are executed without decoding again
+pub struct DecodedCode {
+    /// Original bytecode, still needed for the tables of switch instructions
+    bytes: Arc<[u8]>,
+    /// Always ends with an instruction that throws if executed, so execution can run on to the
+    /// next index without checking for the end of the code
+    insns: Box<[DecodedInsn]>,
+    /// Index of the instruction starting at each pc, including one past the end of the code
+    index: Box<[u32]>,
+}
+
+pub struct DecodedInsn {
+    pub insn: Instruction,
+    pub pc: u32,
+    pub next_pc: u32,
+    /// Index of the target of a branch with a constant offset, resolved when translated
+    pub target: u32,
+    /// Replaces this instruction once its symbolic reference is resolved. Only checked by
+    /// instructions that have a quick form
+    quick: OnceLock<Quick>,
+}
+
+impl DecodedCode {
+    pub fn translate(bytes: Arc<[u8]>) -> Self {
+        let mut insns = Vec::new();
+        let mut index = vec![NO_INSN; bytes.len() + 1].into_boxed_slice();
+
+        let mut pc = 0;
+        while pc < bytes.len() {
+            let (next_pc, insn) = match get_insn(&bytes, pc) {
+                Some(insn) => insn,
+                None => {
+                    warn!("invalid instruction at pc {}, ending translation", pc);
+                    break;
+                }
+            };
+
+            index[pc] = insns.len() as u32;
+            insns.push(DecodedInsn {
+                insn,
+                pc: pc as u32,
+                next_pc: next_pc as u32,
+                target: NO_INSN,
+                quick: OnceLock::new(),
+            });
+            pc = next_pc;
+        }
+
+        // running past the last valid instruction throws instead
+        index[pc] = insns.len() as u32;
+        insns.push(DecodedInsn {
+            insn: Instruction::Invalid,
+            pc: pc as u32,
+            next_pc: pc as u32,
+            target: NO_INSN,
+            quick: OnceLock::new(),
+        });
+
+        // branches into the middle of an instruction are left to fail when taken
+        for insn in insns.iter_mut() {
+            if let Some(offset) = branch_offset(&insn.insn) {
+                insn.target = usize::try_from(insn.pc as i64 + offset as i64)
+                    .ok()
+                    .and_then(|pc| index.get(pc).copied())
+                    .unwrap_or(NO_INSN);
+            }
+        }
+
+        DecodedCode {
+            bytes,
+            insns: insns.into_boxed_slice(),
+            index,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Index of the instruction starting at the given pc
+    pub fn index_of(&self, pc: usize) -> Option<usize> {
+        match self.index.get(pc) {
+            Some(&idx) if idx != NO_INSN => Some(idx as usize),
+            _ => None,
+        }
+    }
+
+    /// Panics if out of range, but the index of the last instruction is never exceeded as it
+    /// doesn't continue
+    pub fn insn(&self, idx: usize) -> &DecodedInsn {
+        &self.insns[idx]
+    }
+
+    /// Quick forms of all instructions that have been resolved so far
+    pub fn quickened(&self) -> impl Iterator<Item = &Quick> {
+        self.insns.iter().filter_map(|insn| insn.quick())
+    }
+}
+
+impl DecodedInsn {
+    /// Index of the resolved branch target
+    pub fn target(&self) -> Option<usize> {
+        (self.target != NO_INSN).then_some(self.target as usize)
+    }
+
+    pub fn execute(&self, interp: &mut InterpreterState) -> PostExecuteAction {
+        self.insn.execute(&self.quick, interp)
+    }
+
+    pub fn quick(&self) -> Option<&Quick> {
+        self.quick.get()
+    }
+
+    /// Installs the quick form to execute from now on. If another thread got there first its
+    /// form is kept instead, which is equivalent
+    pub fn quicken(&self, quick: Quick) -> &Quick {
+        self.quick.get_or_init(|| quick)
+    }
+}
+
+impl Debug for DecodedCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DecodedCode({} insns)", self.insns.len() - 1)
+    }
+}
+
+/// Constant offset relative to the opcode, if the instruction is a branch
+fn branch_offset(insn: &Instruction) -> Option<i32> {
+    let offset = match insn {
+        Instruction::Goto(Goto(offset))
+        | Instruction::IfAcmpeq(IfAcmpeq(offset))
+        | Instruction::IfAcmpne(IfAcmpne(offset))
+        | Instruction::IfIcmpeq(IfIcmpeq(offset))
+        | Instruction::IfIcmpge(IfIcmpge(offset))
+        | Instruction::IfIcmpgt(IfIcmpgt(offset))
+        | Instruction::IfIcmple(IfIcmple(offset))
+        | Instruction::IfIcmplt(IfIcmplt(offset))
+        | Instruction::IfIcmpne(IfIcmpne(offset))
+        | Instruction::Ifeq(Ifeq(offset))
+        | Instruction::Ifge(Ifge(offset))
+        | Instruction::Ifgt(Ifgt(offset))
+        | Instruction::Ifle(Ifle(offset))
+        | Instruction::Iflt(Iflt(offset))
+        | Instruction::Ifne(Ifne(offset))
+        | Instruction::Ifnonnull(Ifnonnull(offset))
+        | Instruction::Ifnull(Ifnull(offset))
+        | Instruction::Jsr(Jsr(offset)) => *offset as i32,
+        Instruction::GotoW(GotoW(offset)) | Instruction::JsrW(JsrW(offset)) => *offset,
+        _ => return None,
+    };
+
+    Some(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::insn::decoded::DecodedCode;
+    use crate::interpreter::insn::{Iload, Instruction, Opcode};
+
+    #[test]
+    fn translate() {
+        #[rustfmt::skip]
+        let bytes = vec![
+            0x03, // iconst_0
+            0x15, 0x04, // iload 4
+            0x99, 0xff, 0xfe, // ifeq -2
+            0xa7, 0x00, 0x02, // goto +2, into the middle of itself
+            0xa7, 0xff, 0xf7, // goto -9
+        ];
+
+        let code = DecodedCode::translate(bytes.into());
+        let pcs = code.insns.iter().map(|insn| insn.pc).collect::<Vec<_>>();
+        assert_eq!(pcs, vec![0, 1, 3, 6, 9, 12]);
+
+        assert_eq!(code.index_of(3), Some(2));
+        assert_eq!(code.index_of(4), None);
+        assert_eq!(code.index_of(12), Some(5));
+        assert_eq!(code.index_of(13), None);
+
+        let iload = code.insn(1);
+        assert!(matches!(iload.insn, Instruction::Iload(Iload(4))));
+        assert_eq!(iload.next_pc, 3);
+
+        assert_eq!(code.insn(2).target(), Some(1));
+        assert_eq!(code.insn(3).target(), None);
+        assert_eq!(code.insn(4).target(), Some(0));
+
+        // falls off the end
+        assert_eq!(code.insn(5).insn.opcode(), None);
+    }
+
+    #[test]
+    fn translate_invalid() {
+        // getfield is missing its index
+        let bytes = vec![0x00, 0xb4, 0x00];
+        let code = DecodedCode::translate(bytes.into());
+
+        assert_eq!(code.insns.len(), 2);
+        assert_eq!(code.insn(0).insn.opcode(), Some(Opcode::Nop));
+        assert_eq!(code.index_of(1), Some(1));
+        assert_eq!(code.insn(1).insn.opcode(), None);
+        assert_eq!(code.index_of(2), None);
+    }
+}
@@ -5,26 +5,28 @@
 
 use std::cmp::Ordering;
 use std::fmt::Debug;
+use std::sync::OnceLock;
 
 use log::*;
 use num_enum::TryFromPrimitive;
 
-use cafebabe::mutf8::StrExt;
 use cafebabe::{AccessFlags, ClassAccessFlags, MethodAccessFlags};
 
-use crate::alloc::{vmref_alloc_object, vmref_eq, VmRef};
-use crate::class::{null, Class, ClassType, Object};
-use crate::class::{FoundField, WhichLoader};
-use crate::constant_pool::Entry;
+use crate::alloc::{vmref_alloc_object, vmref_eq, InternedString, VmRef};
+use crate::class::{null, Class, ClassType, Method, Object, WhichLoader};
+use crate::constant_pool::FieldLocation;
 use crate::error::{Throwable, Throwables};
+use crate::interpreter::callsite;
 use crate::interpreter::error::InterpreterError;
+use crate::interpreter::frame::JavaFrame;
 use crate::interpreter::insn::bytecode::InsnReader;
 use crate::interpreter::insn::opcode::Opcode;
-use crate::interpreter::insn::InstructionBlob;
+use crate::interpreter::insn::quick::Quick;
 use crate::interpreter::{Frame, InterpreterState};
+use crate::storage::{FieldId, InstanceFieldSlot};
 use crate::thread;
-use crate::types::{DataType, DataValue, NewarrayType, PrimitiveDataType};
-use std::ops::{BitAnd, BitXor, Shr};
+use crate::types::{DataType, DataValue, MethodSignature, NewarrayType, PrimitiveDataType};
+use std::ops::{BitAnd, BitOr, BitXor, Shr};
 
 // TODO operand stack pop then verify might be wrong - only pop if its the right type?
 
@@ -38,36 +40,41 @@ pub enum PostExecuteAction {
     Jmp(i32),
     /// Absolute jump to pc
     JmpAbsolute(usize),
-    /// Initialise the given class then rerun this instruction
-    /// TODO might be possible to continue with resolved methods/fields state instead of replay
-    ClassInit(VmRef<Class>),
+    /// Symbolic reference has been resolved, replace this instruction with the given quick form
+    /// and execute that instead
+    Quicken(Quick),
+    /// The instruction needs a class from a user class loader, load it outside of the instruction
+    /// and execute this instruction again
+    LoadClass {
+        name: InternedString,
+        loader: WhichLoader,
+    },
 }
 
 pub type ExecuteResult = Result<PostExecuteAction, InterpreterError>;
 
+pub(crate) fn into_action(result: ExecuteResult) -> PostExecuteAction {
+    match result {
+        Ok(action) => action,
+        Err(InterpreterError::ExceptionRaised(exc)) => PostExecuteAction::ThrowException(exc),
+        Err(InterpreterError::UserClassLoad { name, loader }) => {
+            PostExecuteAction::LoadClass { name, loader }
+        }
+        Err(err) => {
+            error!("interpreter error: {}", err);
+            PostExecuteAction::ThrowException(Throwables::WithMessage(
+                "java/lang/InternalError",
+                err.to_string(),
+            ))
+        }
+    }
+}
+
 macro_rules! insn_common {
     ($insn:ident, $str:expr) => {
         impl $insn {
             pub const OPCODE: u8 = Opcode::$insn as u8;
             pub const INSN: &'static str = $str;
-
-            pub fn trampoline(
-                insn: &InstructionBlob,
-                interp: &mut InterpreterState,
-            ) -> PostExecuteAction {
-                let myself: &Self = unsafe { insn.transmute() };
-
-                match myself.execute(interp) {
-                    Ok(action) => action,
-                    Err(err) => {
-                        error!("interpreter error: {}", err);
-                        // TODO better handling of interpreter error
-                        PostExecuteAction::ThrowException(Throwables::Other(
-                            "java/lang/InternalError",
-                        ))
-                    }
-                }
-            }
         }
     };
 }
@@ -132,32 +139,34 @@ macro_rules! insn_2s {
     };
 }
 
-/// 2 separate u8s
-macro_rules! insn_2x {
+macro_rules! insn_4s {
     ($insn:ident, $str:expr) => {
         #[derive(Debug)]
-        pub struct $insn(pub u8, pub u8);
+        pub struct $insn(pub i32);
 
         insn_common!($insn, $str);
 
         impl $insn {
             pub(crate) fn parse(reader: &mut InsnReader) -> Option<Self> {
-                reader.read_u8s().map(|(a, b)| Self(a, b))
+                reader.read_i32().map(Self)
             }
         }
     };
 }
 
-macro_rules! insn_4s {
+/// u16 and u8
+macro_rules! insn_3x {
     ($insn:ident, $str:expr) => {
         #[derive(Debug)]
-        pub struct $insn(pub i32);
+        pub struct $insn(pub u16, pub u8);
 
         insn_common!($insn, $str);
 
         impl $insn {
             pub(crate) fn parse(reader: &mut InsnReader) -> Option<Self> {
-                reader.read_i32().map(Self)
+                let idx = reader.read_u16()?;
+                let b = reader.read_u8()?;
+                Some(Self(idx, b))
             }
         }
     };
@@ -181,6 +190,22 @@ macro_rules! insn_4x {
     };
 }
 
+/// Local variable index, u8 unless widened to u16 by a `wide` prefix
+macro_rules! insn_local {
+    ($insn:ident, $str:expr) => {
+        #[derive(Debug)]
+        pub struct $insn(pub u16);
+
+        insn_common!($insn, $str);
+
+        impl $insn {
+            pub(crate) fn parse(reader: &mut InsnReader) -> Option<Self> {
+                reader.read_u8().map(|idx| Self(idx as u16))
+            }
+        }
+    };
+}
+
 macro_rules! insn_delegate {
     ($delegate:expr) => {
         fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
@@ -194,7 +219,7 @@ macro_rules! insn_delegate {
 insn_0!(Aaload, "aaload");
 insn_0!(Aastore, "aastore");
 insn_0!(AconstNull, "aconst_null");
-insn_1!(Aload, "aload");
+insn_local!(Aload, "aload");
 insn_0!(Aload0, "aload_0");
 insn_0!(Aload1, "aload_1");
 insn_0!(Aload2, "aload_2");
@@ -202,7 +227,7 @@ insn_0!(Aload3, "aload_3");
 insn_2!(Anewarray, "anewarray");
 insn_0!(Areturn, "areturn");
 insn_0!(Arraylength, "arraylength");
-insn_1!(Astore, "astore");
+insn_local!(Astore, "astore");
 insn_0!(Astore0, "astore_0");
 insn_0!(Astore1, "astore_1");
 insn_0!(Astore2, "astore_2");
@@ -225,7 +250,7 @@ insn_0!(Dcmpl, "dcmpl");
 insn_0!(Dconst0, "dconst_0");
 insn_0!(Dconst1, "dconst_1");
 insn_0!(Ddiv, "ddiv");
-insn_1!(Dload, "dload");
+insn_local!(Dload, "dload");
 insn_0!(Dload0, "dload_0");
 insn_0!(Dload1, "dload_1");
 insn_0!(Dload2, "dload_2");
@@ -234,7 +259,7 @@ insn_0!(Dmul, "dmul");
 insn_0!(Dneg, "dneg");
 insn_0!(Drem, "drem");
 insn_0!(Dreturn, "dreturn");
-insn_1!(Dstore, "dstore");
+insn_local!(Dstore, "dstore");
 insn_0!(Dstore0, "dstore_0");
 insn_0!(Dstore1, "dstore_1");
 insn_0!(Dstore2, "dstore_2");
@@ -258,7 +283,7 @@ insn_0!(Fconst0, "fconst_0");
 insn_0!(Fconst1, "fconst_1");
 insn_0!(Fconst2, "fconst_2");
 insn_0!(Fdiv, "fdiv");
-insn_1!(Fload, "fload");
+insn_local!(Fload, "fload");
 insn_0!(Fload0, "fload_0");
 insn_0!(Fload1, "fload_1");
 insn_0!(Fload2, "fload_2");
@@ -267,7 +292,7 @@ insn_0!(Fmul, "fmul");
 insn_0!(Fneg, "fneg");
 insn_0!(Frem, "frem");
 insn_0!(Freturn, "freturn");
-insn_1!(Fstore, "fstore");
+insn_local!(Fstore, "fstore");
 insn_0!(Fstore0, "fstore_0");
 insn_0!(Fstore1, "fstore_1");
 insn_0!(Fstore2, "fstore_2");
@@ -311,8 +336,10 @@ insn_2s!(Iflt, "iflt");
 insn_2s!(Ifne, "ifne");
 insn_2s!(Ifnonnull, "ifnonnull");
 insn_2s!(Ifnull, "ifnull");
-insn_2x!(Iinc, "iinc");
-insn_1!(Iload, "iload");
+#[derive(Debug)]
+pub struct Iinc(pub u16, pub i16);
+insn_common!(Iinc, "iinc");
+insn_local!(Iload, "iload");
 insn_0!(Iload0, "iload_0");
 insn_0!(Iload1, "iload_1");
 insn_0!(Iload2, "iload_2");
@@ -330,7 +357,7 @@ insn_0!(Irem, "irem");
 insn_0!(Ireturn, "ireturn");
 insn_0!(Ishl, "ishl");
 insn_0!(Ishr, "ishr");
-insn_1!(Istore, "istore");
+insn_local!(Istore, "istore");
 insn_0!(Istore0, "istore_0");
 insn_0!(Istore1, "istore_1");
 insn_0!(Istore2, "istore_2");
@@ -354,20 +381,27 @@ insn_1!(Ldc, "ldc");
 insn_2!(Ldc2W, "ldc2_w");
 insn_2!(LdcW, "ldc_w");
 insn_0!(Ldiv, "ldiv");
-insn_1!(Lload, "lload");
+insn_local!(Lload, "lload");
 insn_0!(Lload0, "lload_0");
 insn_0!(Lload1, "lload_1");
 insn_0!(Lload2, "lload_2");
 insn_0!(Lload3, "lload_3");
 insn_0!(Lmul, "lmul");
 insn_0!(Lneg, "lneg");
-// insn_n!(Lookupswitch, "lookupswitch");
+#[derive(Debug)]
+pub struct Lookupswitch {
+    default: i32,
+    pair_count: u32,
+    /// Offset of the sorted match-offset pairs in the code
+    pairs_pc: u32,
+}
+insn_common!(Lookupswitch, "lookupswitch");
 insn_0!(Lor, "lor");
 insn_0!(Lrem, "lrem");
 insn_0!(Lreturn, "lreturn");
 insn_0!(Lshl, "lshl");
 insn_0!(Lshr, "lshr");
-insn_1!(Lstore, "lstore");
+insn_local!(Lstore, "lstore");
 insn_0!(Lstore0, "lstore_0");
 insn_0!(Lstore1, "lstore_1");
 insn_0!(Lstore2, "lstore_2");
@@ -377,7 +411,7 @@ insn_0!(Lushr, "lushr");
 insn_0!(Lxor, "lxor");
 insn_0!(Monitorenter, "monitorenter");
 insn_0!(Monitorexit, "monitorexit");
-// insn_n!(Multianewarray, "multianewarray");
+insn_3x!(Multianewarray, "multianewarray");
 insn_2!(New, "new");
 insn_1!(Newarray, "newarray");
 insn_0!(Nop, "nop");
@@ -385,18 +419,108 @@ insn_0!(Pop, "pop");
 insn_0!(Pop2, "pop2");
 insn_2!(Putfield, "putfield");
 insn_2!(Putstatic, "putstatic");
-insn_1!(Ret, "ret");
+insn_local!(Ret, "ret");
 insn_0!(Return, "return");
 insn_0!(Saload, "saload");
 insn_0!(Sastore, "sastore");
 insn_2!(Sipush, "sipush");
 insn_0!(Swap, "swap");
-// insn_n!(Tableswitch, "tableswitch");
-// insn_n!(Wide, "wide");
+#[derive(Debug)]
+pub struct Tableswitch {
+    default: i32,
+    low: i32,
+    high: i32,
+    /// Offset of the jump offsets in the code
+    offsets_pc: u32,
+}
+insn_common!(Tableswitch, "tableswitch");
+#[derive(Debug)]
+pub struct Wide {
+    opcode: u8,
+    index: u16,
+    /// Only for iinc
+    constant: i16,
+}
+insn_common!(Wide, "wide");
+
+/// Generates [Instruction] from the instructions that are executed as they are, and those that
+/// are replaced by a [Quick] form once resolved
+macro_rules! instructions {
+    (plain: $($plain:ident),*; quickened: $($quickened:ident),*;) => {
+        /// A decoded instruction and its operands
+        #[derive(Debug)]
+        pub enum Instruction {
+            $($plain($plain),)*
+            $($quickened($quickened),)*
+            /// Stands in for bytecode that couldn't be decoded, and for running off the end of the
+            /// code
+            Invalid,
+        }
+
+        impl Instruction {
+            /// None for [Instruction::Invalid]
+            pub fn opcode(&self) -> Option<Opcode> {
+                match self {
+                    $(Instruction::$plain(_) => Some(Opcode::$plain),)*
+                    $(Instruction::$quickened(_) => Some(Opcode::$quickened),)*
+                    Instruction::Invalid => None,
+                }
+            }
+
+            /// Executes the quick form instead if this instruction has been quickened
+            pub fn execute(
+                &self,
+                quick: &OnceLock<Quick>,
+                interp: &mut InterpreterState,
+            ) -> PostExecuteAction {
+                match self {
+                    $(Instruction::$plain(insn) => into_action(insn.execute(interp)),)*
+                    $(Instruction::$quickened(insn) => match quick.get() {
+                        Some(quick) => quick.execute(interp),
+                        None => into_action(insn.execute(interp)),
+                    },)*
+                    Instruction::Invalid => invalid_code(interp),
+                }
+            }
+        }
+    };
+}
+
+instructions! {
+    plain:
+        Aaload, Aastore, AconstNull, Aload, Aload0, Aload1, Aload2, Aload3, Anewarray, Areturn,
+        Arraylength, Astore, Astore0, Astore1, Astore2, Astore3, Athrow, Baload, Bastore, Bipush,
+        Caload, Castore, Checkcast, D2F, D2I, D2L, Dadd, Daload, Dastore, Dcmpg, Dcmpl, Dconst0,
+        Dconst1, Ddiv, Dload, Dload0, Dload1, Dload2, Dload3, Dmul, Dneg, Drem, Dreturn, Dstore,
+        Dstore0, Dstore1, Dstore2, Dstore3, Dsub, Dup, Dup2, Dup2X1, Dup2X2, DupX1, DupX2, F2D,
+        F2I, F2L, Fadd, Faload, Fastore, Fcmpg, Fcmpl, Fconst0, Fconst1, Fconst2, Fdiv, Fload,
+        Fload0, Fload1, Fload2, Fload3, Fmul, Fneg, Frem, Freturn, Fstore, Fstore0, Fstore1,
+        Fstore2, Fstore3, Fsub, Goto, GotoW, I2B, I2C, I2D, I2F, I2L, I2S, Iadd, Iaload, Iand,
+        Iastore, Iconst0, Iconst1, Iconst2, Iconst3, Iconst4, Iconst5, IconstM1, Idiv, IfAcmpeq,
+        IfAcmpne, IfIcmpeq, IfIcmpge, IfIcmpgt, IfIcmple, IfIcmplt, IfIcmpne, Ifeq, Ifge, Ifgt,
+        Ifle, Iflt, Ifne, Ifnonnull, Ifnull, Iinc, Iload, Iload0, Iload1, Iload2, Iload3, Imul,
+        Ineg, Instanceof, Ior, Irem, Ireturn, Ishl, Ishr, Istore, Istore0, Istore1, Istore2,
+        Istore3, Isub, Iushr, Ixor, Jsr, JsrW, L2D, L2F, L2I, Ladd, Laload, Land, Lastore, Lcmp,
+        Lconst0, Lconst1, Ldiv, Lload, Lload0, Lload1, Lload2, Lload3, Lmul, Lneg, Lookupswitch,
+        Lor, Lrem, Lreturn, Lshl, Lshr, Lstore, Lstore0, Lstore1, Lstore2, Lstore3, Lsub, Lushr,
+        Lxor, Monitorenter, Monitorexit, Multianewarray, Newarray, Nop, Pop, Pop2, Ret, Return,
+        Saload, Sastore, Sipush, Swap, Tableswitch, Wide;
+    quickened:
+        Getfield, Getstatic, Invokedynamic, Invokeinterface, Invokespecial, Invokestatic,
+        Invokevirtual, Ldc, Ldc2W, LdcW, New, Putfield, Putstatic;
+}
+
+fn invalid_code(interp: &mut InterpreterState) -> PostExecuteAction {
+    let method = &interp.current_frame_mut().method;
+    PostExecuteAction::ThrowException(Throwables::WithMessage(
+        "java/lang/VerifyError",
+        format!("invalid bytecode in {}", method),
+    ))
+}
 
 fn do_load_primitive(
     interp: &mut InterpreterState,
-    idx: u8,
+    idx: u16,
     f: impl FnOnce(&DataValue) -> bool,
     prim: PrimitiveDataType,
 ) -> ExecuteResult {
@@ -419,24 +543,13 @@ fn do_load_primitive(
 
 impl Aaload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        let frame = interp.current_frame_mut();
-
-        // pop reference type array and idx
-        let (array, idx) = frame.pop_arrayref_and_idx(|cls| {
-            matches!(cls.class_type(), ClassType::Normal | ClassType::Array(_))
-        })?;
-
-        let value = array.array_get_unchecked(idx);
-        frame.operand_stack.push(value);
-        Ok(PostExecuteAction::Continue)
+        do_array_load(interp, ArrayKind::Reference)
     }
 }
 
 impl Aastore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_store(interp, |cls| {
-            matches!(cls.class_type(), ClassType::Normal | ClassType::Array(_))
-        })
+        do_array_store(interp, ArrayKind::Reference)
     }
 }
 
@@ -481,23 +594,14 @@ impl Anewarray {
         let class_loader = thread.global().class_loader();
 
         // resolve element type
-        let elem_type = frame
-            .class
-            .constant_pool()
-            .class_entry(self.0)
-            .ok_or(InterpreterError::NotClassRef(self.0))?;
-
-        let elem_class = class_loader.load_class_caused_by(
-            &elem_type.name,
-            frame.class.loader().clone(),
-            frame.class.name(),
-        )?;
+        let elem_class = frame.class.resolve_class(self.0)?;
 
         // pop length
         let length = frame.pop_int()?;
         if length < 0 {
-            return Ok(PostExecuteAction::ThrowException(Throwables::Other(
+            return Ok(PostExecuteAction::ThrowException(Throwables::WithMessage(
                 "java/lang/NegativeArraySizeException",
+                length.to_string(),
             )));
         }
 
@@ -547,9 +651,9 @@ impl Arraylength {
         let obj = frame.pop_reference()?;
 
         if obj.is_null() {
-            return Ok(PostExecuteAction::ThrowException(
-                Throwables::NullPointerException,
-            ));
+            return Ok(PostExecuteAction::ThrowException(Throwables::null_pointer(
+                "read the array length",
+            )));
         }
 
         // get length
@@ -601,26 +705,26 @@ impl Athrow {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
         let exc = frame.pop_reference()?;
-        debug!("throw {:?}", exc.print_fields());
+        if exc.is_null() {
+            return Ok(PostExecuteAction::ThrowException(Throwables::null_pointer(
+                "throw exception",
+            )));
+        }
 
-        todo!("instruction Athrow")
+        debug!("throw {:?}", exc.print_fields());
+        Ok(PostExecuteAction::Exception(Throwable::from_object(exc)))
     }
 }
 
 impl Baload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_load(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Boolean)
-            )
-        })
+        do_array_load(interp, ArrayKind::ByteOrBoolean)
     }
 }
 
 impl Bastore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Bastore")
+        do_array_store(interp, ArrayKind::ByteOrBoolean)
     }
 }
 
@@ -636,42 +740,122 @@ impl Bipush {
 
 impl Caload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_load(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Char)
-            )
-        })
+        do_array_load(interp, ArrayKind::Char)
+    }
+}
+
+/// Element types distinguished by the array load and store instructions
+#[derive(Copy, Clone)]
+enum ArrayKind {
+    /// baload and bastore are shared by byte and boolean arrays
+    ByteOrBoolean,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+}
+
+impl ArrayKind {
+    fn accepts(self, elem: &Class) -> bool {
+        use PrimitiveDataType as P;
+        match elem.class_type() {
+            ClassType::Primitive(prim) => matches!(
+                (self, prim),
+                (ArrayKind::ByteOrBoolean, P::Byte | P::Boolean)
+                    | (ArrayKind::Char, P::Char)
+                    | (ArrayKind::Short, P::Short)
+                    | (ArrayKind::Int, P::Int)
+                    | (ArrayKind::Long, P::Long)
+                    | (ArrayKind::Float, P::Float)
+                    | (ArrayKind::Double, P::Double)
+            ),
+            ClassType::Normal | ClassType::Array(..) => matches!(self, ArrayKind::Reference),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ArrayKind::ByteOrBoolean => "byte/boolean",
+            ArrayKind::Char => "char",
+            ArrayKind::Short => "short",
+            ArrayKind::Int => "int",
+            ArrayKind::Long => "long",
+            ArrayKind::Float => "float",
+            ArrayKind::Double => "double",
+            ArrayKind::Reference => "object",
+        }
     }
 }
 
-fn do_array_store(
-    interp: &mut InterpreterState,
-    array_check: impl FnOnce(&VmRef<Class>) -> bool,
-) -> ExecuteResult {
+/// Converts a stored operand to the array's element type, truncating ints for the narrower
+/// primitives. None if the operand is of the wrong type
+fn array_element_value(elem: &Class, value: &DataValue) -> Option<DataValue> {
+    let prim = match elem.class_type() {
+        ClassType::Primitive(prim) => *prim,
+        _ => return value.is_reference().then(|| value.clone()),
+    };
+
+    Some(match prim {
+        PrimitiveDataType::Boolean => DataValue::Boolean(value.as_int()? & 1 != 0),
+        PrimitiveDataType::Byte => DataValue::Byte(value.as_int()? as i8),
+        PrimitiveDataType::Char => DataValue::Char(value.as_int()? as u16),
+        PrimitiveDataType::Short => DataValue::Short(value.as_int()? as i16),
+        PrimitiveDataType::Int => DataValue::Int(value.as_int()?),
+        PrimitiveDataType::Long => DataValue::Long(value.as_long()?),
+        PrimitiveDataType::Float => DataValue::Float(value.as_float()?),
+        PrimitiveDataType::Double => DataValue::Double(value.as_double()?),
+    })
+}
+
+fn do_array_store(interp: &mut InterpreterState, kind: ArrayKind) -> ExecuteResult {
     let frame = interp.current_frame_mut();
 
     // pop value
     let value = frame.pop_value()?;
-    // TODO check value type, throw if bad
 
-    // pop reference type array and idx
-    let (array, idx) = frame.pop_arrayref_and_idx(array_check)?;
+    // pop array and idx
+    let (array, idx) = frame.pop_arrayref_and_idx(
+        || format!("store to {} array", kind.name()),
+        |cls| kind.accepts(cls),
+    )?;
+
+    let array_cls = array.class_not_null();
+    let elem = array_cls
+        .class_type()
+        .array_class()
+        .expect("array has an element class");
+
+    let elem_value =
+        array_element_value(elem, &value).ok_or_else(|| InterpreterError::InvalidArrayStore {
+            element: elem.class_type().to_owned(),
+            value: value.data_type(),
+        })?;
+
+    // assignment compatibility check
+    if let Some(value_cls) = value.as_reference().and_then(|obj| obj.class()) {
+        if !value_cls.is_instance_of(elem) {
+            return Ok(PostExecuteAction::ThrowException(Throwables::WithMessage(
+                "java/lang/ArrayStoreException",
+                value_cls.java_name(),
+            )));
+        }
+    }
 
-    // TODO actually bounds check
-    // TODO assignment compatibility check
-    array.array_set_unchecked(idx, value);
+    array.array_set_unchecked(idx, elem_value);
     Ok(PostExecuteAction::Continue)
 }
 
-fn do_array_load(
-    interp: &mut InterpreterState,
-    array_check: impl FnOnce(&VmRef<Class>) -> bool,
-) -> ExecuteResult {
+fn do_array_load(interp: &mut InterpreterState, kind: ArrayKind) -> ExecuteResult {
     let frame = interp.current_frame_mut();
 
-    // pop reference type array and idx
-    let (array, idx) = frame.pop_arrayref_and_idx(array_check)?;
+    // pop array and idx
+    let (array, idx) = frame.pop_arrayref_and_idx(
+        || format!("load from {} array", kind.name()),
+        |cls| kind.accepts(cls),
+    )?;
 
     let value = array.array_get_unchecked(idx);
     frame.operand_stack.push(value);
@@ -680,12 +864,7 @@ fn do_array_load(
 
 impl Castore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_store(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Char)
-            )
-        })
+        do_array_store(interp, ArrayKind::Char)
     }
 }
 
@@ -697,24 +876,19 @@ impl Checkcast {
         match obj.into_reference() {
             Ok(obj) => {
                 if let Some(cls_to_check) = obj.class() {
-                    let class_ref = frame
-                        .class
-                        .constant_pool()
-                        .class_entry(self.0)
-                        .ok_or(InterpreterError::NotClassRef(self.0))?;
-
-                    let cls = thread::get().global().class_loader().load_class_caused_by(
-                        &class_ref.name,
-                        frame.class.loader().clone(),
-                        frame.class.name(),
-                    )?;
+                    let cls = frame.class.resolve_class(self.0)?;
 
                     trace!("checkcast {:?} is {:?}", cls_to_check.name(), cls.name());
                     if cls_to_check.is_instance_of(&cls) {
                         Ok(PostExecuteAction::Continue)
                     } else {
-                        Ok(PostExecuteAction::ThrowException(Throwables::Other(
+                        Ok(PostExecuteAction::ThrowException(Throwables::WithMessage(
                             "java/lang/ClassCastException",
+                            format!(
+                                "class {} cannot be cast to class {}",
+                                cls_to_check.java_name(),
+                                cls.java_name()
+                            ),
                         )))
                     }
                 } else {
@@ -729,77 +903,84 @@ impl Checkcast {
 
 impl D2F {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction D2F")
+        let frame = interp.current_frame_mut();
+        let double = frame.pop_double()?;
+        frame.operand_stack.push(DataValue::Float(double as f32));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl D2I {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction D2I")
+        let frame = interp.current_frame_mut();
+        let double = frame.pop_double()?;
+        frame.operand_stack.push(DataValue::Int(double as i32));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl D2L {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction D2L")
+        let frame = interp.current_frame_mut();
+        let double = frame.pop_double()?;
+        frame.operand_stack.push(DataValue::Long(double as i64));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Dadd {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dadd")
+        double_two_op(interp, "+", |a, b| a + b)
     }
 }
 
 impl Daload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_load(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Double)
-            )
-        })
+        do_array_load(interp, ArrayKind::Double)
     }
 }
 
 impl Dastore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_store(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Double)
-            )
-        })
+        do_array_store(interp, ArrayKind::Double)
     }
 }
 
 impl Dcmpg {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dcmpg")
+        double_cmp(interp, "dcmpg", 1)
     }
 }
 
 impl Dcmpl {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dcmpl")
+        double_cmp(interp, "dcmpl", -1)
     }
 }
 
 impl Dconst0 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dconst0")
+        interp
+            .current_frame_mut()
+            .operand_stack
+            .push(DataValue::Double(0.0));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Dconst1 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dconst1")
+        interp
+            .current_frame_mut()
+            .operand_stack
+            .push(DataValue::Double(1.0));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Ddiv {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Ddiv")
+        double_two_op(interp, "/", |a, b| a / b)
     }
 }
 
@@ -827,19 +1008,23 @@ impl Dload3 {
 
 impl Dmul {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dmul")
+        double_two_op(interp, "*", |a, b| a * b)
     }
 }
 
 impl Dneg {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dneg")
+        let frame = interp.current_frame_mut();
+        let double = frame.pop_double()?;
+        frame.operand_stack.push(DataValue::Double(-double));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Drem {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Drem")
+        // truncating like C fmod, not IEEE 754 remainder
+        double_two_op(interp, "%", |a, b| a % b)
     }
 }
 
@@ -847,44 +1032,40 @@ impl Dreturn {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
         let val = frame.pop_value()?.as_double().expect("not double");
-        // TODO value set conversion
         do_return_value(interp, DataValue::Double(val))
     }
 }
 
 impl Dstore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dstore")
+        let frame = interp.current_frame_mut();
+        let val = frame.pop_double()?;
+        frame
+            .local_vars
+            .store(self.0 as usize, DataValue::Double(val))?;
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Dstore0 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dstore0")
-    }
+    insn_delegate!(Dstore(0));
 }
 
 impl Dstore1 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dstore1")
-    }
+    insn_delegate!(Dstore(1));
 }
 
 impl Dstore2 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dstore2")
-    }
+    insn_delegate!(Dstore(2));
 }
 
 impl Dstore3 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dstore3")
-    }
+    insn_delegate!(Dstore(3));
 }
 
 impl Dsub {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dsub")
+        double_two_op(interp, "-", |a, b| a - b)
     }
 }
 
@@ -892,17 +1073,8 @@ impl Dup {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
 
-        // peek top operand
-        let obj = frame
-            .operand_stack
-            .peek()
-            .ok_or(InterpreterError::NoOperand)?;
-
-        // push clone
-        let obj_clone = obj.clone();
-        frame.operand_stack.push(obj_clone);
-
-        Ok(PostExecuteAction::Continue)
+        let count = frame.operand_stack.operands_in_slots(0, 1)?;
+        dup_and_insert(frame, count, 0)
     }
 }
 
@@ -910,39 +1082,47 @@ impl Dup2 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
 
-        // peek top operand
-        let obj = frame
-            .operand_stack
-            .peek()
-            .ok_or(InterpreterError::NoOperand)?
-            .clone();
-
-        if !obj.is_wide() {
-            // dup second from top too
-            let second_obj = frame
-                .operand_stack
-                .peek_at(1)
-                .ok_or(InterpreterError::NoOperand)?
-                .clone();
-
-            frame.operand_stack.push(second_obj);
-        }
+        // value1 and value2 may be a single category 2 value
+        let count = frame.operand_stack.operands_in_slots(0, 2)?;
+        dup_and_insert(frame, count, 0)
+    }
+}
 
-        frame.operand_stack.push(obj);
+/// Duplicates the top `count` operands and inserts the copies beneath the `below` operands under
+/// them. Category 2 values are a single operand
+fn dup_and_insert(frame: &mut JavaFrame, count: usize, below: usize) -> ExecuteResult {
+    if frame.operand_stack.count() < count + below {
+        return Err(InterpreterError::NoOperand);
+    }
 
-        Ok(PostExecuteAction::Continue)
+    // originals stay on top, so each copy is inserted one deeper than the last
+    for i in 0..count {
+        let val = frame.operand_stack.peek_at(i).unwrap().clone(); // just checked
+        frame.operand_stack.insert_at(val, count + below + i);
     }
+
+    Ok(PostExecuteAction::Continue)
 }
 
 impl Dup2X1 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dup2X1")
+        let frame = interp.current_frame_mut();
+
+        // value1 and value2 may be a single category 2 value
+        let count = frame.operand_stack.operands_in_slots(0, 2)?;
+        let below = frame.operand_stack.operands_in_slots(count, 1)?;
+        dup_and_insert(frame, count, below)
     }
 }
 
 impl Dup2X2 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Dup2X2")
+        let frame = interp.current_frame_mut();
+
+        // both pairs may be a single category 2 value
+        let count = frame.operand_stack.operands_in_slots(0, 2)?;
+        let below = frame.operand_stack.operands_in_slots(count, 2)?;
+        dup_and_insert(frame, count, below)
     }
 }
 
@@ -950,21 +1130,29 @@ impl DupX1 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
 
-        let val = frame.peek_value()?;
-        frame.operand_stack.insert_at(val, 2);
-        Ok(PostExecuteAction::Continue)
+        let count = frame.operand_stack.operands_in_slots(0, 1)?;
+        let below = frame.operand_stack.operands_in_slots(count, 1)?;
+        dup_and_insert(frame, count, below)
     }
 }
 
 impl DupX2 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction DupX2")
+        let frame = interp.current_frame_mut();
+
+        // value2 and value3 may be a single category 2 value
+        let count = frame.operand_stack.operands_in_slots(0, 1)?;
+        let below = frame.operand_stack.operands_in_slots(count, 2)?;
+        dup_and_insert(frame, count, below)
     }
 }
 
 impl F2D {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction F2D")
+        let frame = interp.current_frame_mut();
+        let float = frame.pop_float()?;
+        frame.operand_stack.push(DataValue::Double(float as f64));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -975,7 +1163,7 @@ impl F2I {
         // pop float
         let float = frame.pop_float()?;
 
-        // TODO narrow float to int properly
+        // saturating, with NaN as 0
         let int = float as i32;
 
         frame.operand_stack.push(DataValue::Int(int));
@@ -985,7 +1173,10 @@ impl F2I {
 
 impl F2L {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction F2L")
+        let frame = interp.current_frame_mut();
+        let float = frame.pop_float()?;
+        frame.operand_stack.push(DataValue::Long(float as i64));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -997,23 +1188,13 @@ impl Fadd {
 
 impl Faload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_load(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Float)
-            )
-        })
+        do_array_load(interp, ArrayKind::Float)
     }
 }
 
 impl Fastore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_store(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Float)
-            )
-        })
+        do_array_store(interp, ArrayKind::Float)
     }
 }
 
@@ -1046,6 +1227,35 @@ fn float_cmp(interp: &mut InterpreterState, op: &'static str, nan_fallback: i32)
     Ok(PostExecuteAction::Continue)
 }
 
+fn double_cmp(interp: &mut InterpreterState, op: &'static str, nan_fallback: i32) -> ExecuteResult {
+    let frame = interp.current_frame_mut();
+
+    // pop values
+    let (val1, val2) = frame.pop_2_doubles()?;
+
+    // do comparison
+    let result = val1
+        .partial_cmp(&val2)
+        .map(|cmp| match cmp {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        })
+        .unwrap_or(nan_fallback);
+
+    trace!(
+        "cmp {a} {op} {b} => {}",
+        result,
+        a = val1,
+        op = op,
+        b = val2
+    );
+
+    frame.operand_stack.push(DataValue::Int(result));
+
+    Ok(PostExecuteAction::Continue)
+}
+
 impl Fcmpg {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         float_cmp(interp, "fcmpg", 1)
@@ -1054,7 +1264,7 @@ impl Fcmpg {
 
 impl Fcmpl {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        float_cmp(interp, "fcmpg", -1)
+        float_cmp(interp, "fcmpl", -1)
     }
 }
 
@@ -1138,6 +1348,28 @@ fn float_two_op(
     Ok(PostExecuteAction::Continue)
 }
 
+fn double_two_op(
+    interp: &mut InterpreterState,
+    wat: &'static str,
+    op: impl FnOnce(f64, f64) -> f64,
+) -> ExecuteResult {
+    let frame = interp.current_frame_mut();
+
+    let (val1, val2) = frame.pop_2_doubles()?;
+    let result = op(val1, val2);
+
+    trace!(
+        "{a} {op} {b} = {result}",
+        a = val1,
+        op = wat,
+        b = val2,
+        result = result
+    );
+
+    frame.operand_stack.push(DataValue::Double(result));
+    Ok(PostExecuteAction::Continue)
+}
+
 impl Fmul {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         float_two_op(interp, "*", |a, b| a * b)
@@ -1146,14 +1378,17 @@ impl Fmul {
 
 impl Fneg {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Fneg")
+        let frame = interp.current_frame_mut();
+        let float = frame.pop_float()?;
+        frame.operand_stack.push(DataValue::Float(-float));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Frem {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        // TODO is probably wrong
-        float_two_op(interp, "%", |a, b| a.rem_euclid(b))
+        // truncating like C fmod, not IEEE 754 remainder
+        float_two_op(interp, "%", |a, b| a % b)
     }
 }
 
@@ -1161,39 +1396,35 @@ impl Freturn {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
         let val = frame.pop_value()?.as_float().expect("not float");
-        // TODO value set conversion
         do_return_value(interp, DataValue::Float(val))
     }
 }
 
 impl Fstore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Fstore")
+        let frame = interp.current_frame_mut();
+        let val = frame.pop_float()?;
+        frame
+            .local_vars
+            .store(self.0 as usize, DataValue::Float(val))?;
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Fstore0 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Fstore0")
-    }
+    insn_delegate!(Fstore(0));
 }
 
 impl Fstore1 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Fstore1")
-    }
+    insn_delegate!(Fstore(1));
 }
 
 impl Fstore2 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Fstore2")
-    }
+    insn_delegate!(Fstore(2));
 }
 
 impl Fstore3 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Fstore3")
-    }
+    insn_delegate!(Fstore(3));
 }
 
 impl Fsub {
@@ -1205,101 +1436,70 @@ impl Fsub {
 impl Getfield {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
+        let (slot, name) = resolve_instance_field(frame, self.0)?;
+        Ok(PostExecuteAction::Quicken(Quick::Getfield { slot, name }))
+    }
+}
 
-        // resolve field
-        let field = frame
-            .class
-            .constant_pool()
-            .field_entry(self.0)
-            .ok_or(InterpreterError::NotFieldRef(self.0))?;
-
-        trace!("getfield {:?}", field);
-
-        // pop operand
-        let obj = frame
-            .operand_stack
-            .pop()
-            .ok_or(InterpreterError::NoOperand)?;
-
-        // ensure non-null non-array reference
-        let obj = obj
-            .as_reference()
-            .ok_or_else(|| InterpreterError::InvalidOperandForObjectOp(obj.data_type()))?;
-
-        let obj_class = match obj.class() {
-            Some(cls) => cls,
-            None => {
-                return Ok(PostExecuteAction::ThrowException(
-                    Throwables::NullPointerException,
-                ))
-            }
-        };
+/// Resolves an instance field against the class named in the field ref, to a slot valid for
+/// instances of that class and all its subclasses
+fn resolve_instance_field(
+    frame: &JavaFrame,
+    idx: u16,
+) -> Result<(InstanceFieldSlot, InternedString), InterpreterError> {
+    let resolved = frame.class.resolve_field(idx)?;
+    let name = field_name(frame, idx)?;
 
-        if obj_class.class_type().is_array() {
-            return Err(InterpreterError::UnexpectedArray(
-                obj_class.class_type().to_owned(),
-            ));
-        }
+    match &resolved.location {
+        FieldLocation::Instance(slot) => Ok((*slot, name)),
+        FieldLocation::Static(..) => Err(Throwables::incompatible_class_change(format_args!(
+            "Expected non-static field {}.{}",
+            resolved.class.java_name(),
+            name
+        ))
+        .into()),
+    }
+}
 
-        // get field value
-        let value = obj
-            .find_instance_field(field.name.as_mstr(), &field.desc)
-            .ok_or_else(|| InterpreterError::FieldNotFound {
-                name: field.name.to_owned(),
-                desc: field.desc.clone(),
-            })?;
+/// Resolves a static field and the class to initialise, which may differ from the class holding
+/// its storage
+fn resolve_static_field(
+    frame: &JavaFrame,
+    idx: u16,
+) -> Result<(VmRef<Class>, VmRef<Class>, FieldId), InterpreterError> {
+    let resolved = frame.class.resolve_field(idx)?;
 
-        // push onto operand stack
-        frame.operand_stack.push(value);
-        Ok(PostExecuteAction::Continue)
+    match &resolved.location {
+        FieldLocation::Static(storage, id) => Ok((resolved.class.clone(), storage.clone(), *id)),
+        FieldLocation::Instance(_) => Err(Throwables::incompatible_class_change(format_args!(
+            "Expected static field {}.{}",
+            resolved.class.java_name(),
+            field_name(frame, idx)?
+        ))
+        .into()),
     }
 }
 
+fn field_name(frame: &JavaFrame, idx: u16) -> Result<InternedString, InterpreterError> {
+    frame
+        .class
+        .constant_pool()
+        .field_entry(idx)
+        .map(|field| field.name)
+        .ok_or(InterpreterError::NotFieldRef(idx))
+}
+
 impl Getstatic {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
+        let (class, storage, field) = resolve_static_field(frame, self.0)?;
 
-        // resolve field
-        let field = frame
-            .class
-            .constant_pool()
-            .field_entry(self.0)
-            .ok_or(InterpreterError::NotFieldRef(self.0))?;
-
-        trace!("getstatic {:?}", field);
-
-        // resolve class
-        let class = thread::get().global().class_loader().load_class_caused_by(
-            &field.class,
-            frame.class.loader().clone(),
-            frame.class.name(),
-        )?;
-
-        // get field id and owning class
-        let found = class
-            .find_static_field_recursive(&field.name, &field.desc)
-            .ok_or_else(|| InterpreterError::FieldNotFound {
-                name: field.name.clone(),
-                desc: field.desc.clone(),
-            })?;
-
-        // class holding static field data is not necessarily the same
-        let (storage_class, field_id) = match found {
-            FoundField::InThisClass(id) => (class.clone(), id),
-            FoundField::InOtherClass(id, cls) => (cls, id),
-        };
-
-        // initialise class on successful resolution
-        if class.needs_init() {
-            return Ok(PostExecuteAction::ClassInit(class));
-        }
-
-        // get field value from storage class
-        let value = storage_class.static_fields().ensure_get(field_id);
-
-        // push onto stack
-        frame.operand_stack.push(value);
-        Ok(PostExecuteAction::Continue)
+        // class is initialised on successful resolution
+        Ok(PostExecuteAction::Quicken(Quick::Getstatic {
+            class,
+            storage,
+            field,
+        }))
     }
 }
 
@@ -1311,13 +1511,20 @@ impl Goto {
 
 impl GotoW {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction GotoW")
+        Ok(PostExecuteAction::Jmp(self.0))
     }
 }
 
 impl I2B {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction I2B")
+        let frame = interp.current_frame_mut();
+
+        // pop int
+        let int = frame.pop_int()?;
+
+        // truncate to byte and sign extend back to int
+        frame.operand_stack.push(DataValue::Int(int as i8 as i32));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -1339,7 +1546,10 @@ impl I2C {
 
 impl I2D {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction I2D")
+        let frame = interp.current_frame_mut();
+        let int = frame.pop_int()?;
+        frame.operand_stack.push(DataValue::Double(int as f64));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -1350,8 +1560,7 @@ impl I2F {
         // pop int
         let int = frame.pop_int()?;
 
-        // convert to float
-        // TODO "converted to the float result using IEEE 754 round to nearest mode"
+        // convert to float, rounding to nearest
         let float = int as f32;
 
         frame.operand_stack.push(DataValue::Float(float));
@@ -1374,7 +1583,14 @@ impl I2L {
 
 impl I2S {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction I2S")
+        let frame = interp.current_frame_mut();
+
+        // pop int
+        let int = frame.pop_int()?;
+
+        // truncate to short and sign extend back to int
+        frame.operand_stack.push(DataValue::Int(int as i16 as i32));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -1386,12 +1602,7 @@ impl Iadd {
 
 impl Iaload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_load(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Int)
-            )
-        })
+        do_array_load(interp, ArrayKind::Int)
     }
 }
 
@@ -1403,12 +1614,7 @@ impl Iand {
 
 impl Iastore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_store(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Int)
-            )
-        })
+        do_array_store(interp, ArrayKind::Int)
     }
 }
 
@@ -1446,7 +1652,19 @@ impl IconstM1 {
 
 impl Idiv {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Idiv")
+        let frame = interp.current_frame_mut();
+        let (val1, val2) = frame.pop_2_ints()?;
+
+        if val2 == 0 {
+            return Ok(divide_by_zero());
+        }
+
+        // MIN / -1 overflows back to MIN
+        let result = val1.wrapping_div(val2);
+        trace!("idiv {} / {} => {}", val1, val2, result);
+
+        frame.operand_stack.push(DataValue::Int(result));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -1612,6 +1830,13 @@ fn int_two_op(
     Ok(PostExecuteAction::Continue)
 }
 
+fn divide_by_zero() -> PostExecuteAction {
+    PostExecuteAction::ThrowException(Throwables::WithMessage(
+        "java/lang/ArithmeticException",
+        "/ by zero".to_owned(),
+    ))
+}
+
 fn long_two_op(
     interp: &mut InterpreterState,
     wat: &'static str,
@@ -1719,6 +1944,11 @@ impl Ifnull {
 }
 
 impl Iinc {
+    pub(crate) fn parse(reader: &mut InsnReader) -> Option<Self> {
+        let (idx, constant) = reader.read_u8s()?;
+        Some(Self(idx as u16, constant as i8 as i16))
+    }
+
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
 
@@ -1729,7 +1959,7 @@ impl Iinc {
                 .ok_or_else(|| InterpreterError::InvalidOperandForIntOp(val.data_type()))
         })?;
 
-        let new_val = val + (*constant as i8) as i32;
+        let new_val = val.wrapping_add(*constant as i32);
         trace!("iinc {} to {}", val, new_val);
 
         frame
@@ -1770,7 +2000,7 @@ impl Imul {
 
 impl Ineg {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        int_one_op(interp, "-", |a| -a)
+        int_one_op(interp, "-", |a| a.wrapping_neg())
     }
 }
 
@@ -1785,17 +2015,7 @@ impl Instanceof {
                 0
             }
             Ok(Some(cls_to_check)) => {
-                let class_ref = frame
-                    .class
-                    .constant_pool()
-                    .class_entry(self.0)
-                    .ok_or(InterpreterError::NotClassRef(self.0))?;
-
-                let cls = thread::get().global().class_loader().load_class_caused_by(
-                    &class_ref.name,
-                    frame.class.loader().clone(),
-                    frame.class.name(),
-                )?;
+                let cls = frame.class.resolve_class(self.0)?;
 
                 let result = cls_to_check.is_instance_of(&cls);
                 trace!(
@@ -1816,90 +2036,52 @@ impl Instanceof {
 
 impl Invokedynamic {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Invokedynamic")
+        let frame = interp.current_frame_mut();
+        let entry = frame
+            .class
+            .constant_pool()
+            .invokedynamic_entry(self.0)
+            .ok_or(InterpreterError::NotInvokeDynamic(self.0))?;
+
+        // each instruction is its own call site, linked once when quickened
+        let target = callsite::link_call_site(&frame.class, entry)?;
+        trace!("invokedynamic linked to {}", target);
+
+        Ok(PostExecuteAction::Quicken(Quick::Invoke(target)))
     }
 }
 
 // TODO invokeinterface throws a lot more exceptions
-// TODO NoSuchMethod error
 
 impl Invokeinterface {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
-        let thread = thread::get();
-        let class_loader = thread.global().class_loader();
 
-        let entry = frame
+        if frame
             .class
             .constant_pool()
             .interface_entry(self.0)
-            .ok_or(InterpreterError::NotInterfaceRef(self.0))?;
+            .is_none()
+        {
+            return Err(InterpreterError::NotInterfaceRef(self.0));
+        }
 
-        // resolve class and method
-        let class = class_loader.load_class_caused_by(
-            &entry.class,
-            frame.class.loader().clone(),
-            frame.class.name(),
-        )?;
-
-        let resolved_method = class
-            .find_method_recursive_in_superclasses(
-                &entry.name,
-                &entry.desc,
-                MethodAccessFlags::empty(),
-                MethodAccessFlags::empty(), // resolved method can be abstract
-            )
-            .ok_or_else(|| InterpreterError::MethodNotFound {
-                class: entry.class.clone(),
-                name: entry.name.clone(),
-                desc: entry.desc.clone(),
-            })?;
-
-        // TODO ensure method is not static, IncompatibleClassChangeError
-        assert!(!resolved_method.flags().is_static());
+        // resolved method can be abstract
+        let resolved_method = frame.class.resolve_method(self.0)?.method.clone();
+        expect_instance_method(&resolved_method)?;
         // TODO verify this
         assert!(
             !resolved_method.is_instance_initializer() && !resolved_method.is_class_initializer()
         );
 
-        // now select method (5.4.6)
-        let selected_method = {
-            if resolved_method.flags().contains(MethodAccessFlags::PRIVATE) {
-                // chosen if private
-                resolved_method
-            } else {
-                // get `this` object
-                let this_obj = frame
-                    .operand_stack
-                    .peek_at(resolved_method.args().len())
-                    .and_then(|val| val.as_reference())
-                    .expect("invalid arg len?");
-
-                if let Some(this_cls) = this_obj.class() {
-                    let fml = resolved_method.name().to_utf8();
-                    Class::find_overriding_method(this_cls, &resolved_method)
-                        .unwrap_or(resolved_method)
-                } else {
-                    // if obj is null this will be caught when creating the frame
-                    resolved_method
-                }
-            }
-        };
-
-        // TODO ensure not abstract
-        assert!(!selected_method
-            .flags()
-            .contains(MethodAccessFlags::ABSTRACT));
-
-        trace!("invokeinterface {}", selected_method);
-
-        // pop args and call method
-        let arg_count = selected_method.args().len() + 1; // +1 for this
-        debug_assert_eq!(arg_count, self.1 as usize, "wrong redundant count");
-        let callee_frame = Frame::new_with_caller(selected_method, frame, arg_count)?;
-        interp.push_frame(callee_frame);
+        debug_assert_eq!(
+            resolved_method.args().len() + 1, // +1 for this
+            self.1 as usize,
+            "wrong redundant count"
+        );
 
-        Ok(PostExecuteAction::MethodCall)
+        trace!("invokeinterface resolved to {}", resolved_method);
+        Ok(PostExecuteAction::Quicken(select_later(resolved_method)))
     }
 }
 
@@ -1919,24 +2101,10 @@ impl Invokespecial {
 
         let (class, method) = {
             // resolve specified class and method
-            let resolved_class = class_loader.load_class_caused_by(
-                &entry.class,
-                frame.class.loader().clone(),
-                frame.class.name(),
-            )?;
-
-            let resolved_method = Class::find_method_recursive_in_superclasses(
-                &resolved_class,
-                &entry.name,
-                &entry.desc,
-                MethodAccessFlags::empty(),
-                MethodAccessFlags::ABSTRACT,
-            )
-            .ok_or_else(|| InterpreterError::MethodNotFound {
-                class: entry.class.clone(),
-                name: entry.name.clone(),
-                desc: entry.desc.clone(),
-            })?;
+            let resolved = frame.class.resolve_method(self.0)?;
+            let (resolved_class, resolved_method) =
+                (resolved.class.clone(), resolved.method.clone());
+            expect_instance_method(&resolved_method)?;
 
             // choose actual class
             let class = if
@@ -2006,78 +2174,46 @@ impl Invokespecial {
             let method = lookup_actual_method();
             trace!("invokespecial resolved method to {}", method);
 
-            // TODO ensure method is not static, IncompatibleClassChangeError
-            assert!(!method.flags().is_static());
-
             // TODO native method
             assert!(!method.flags().is_native(), "native not implemented");
 
             (class, method)
         };
 
-        // pop args and call method
-        let arg_count = method.args().len() + 1; // +1 for this
-        let callee_frame = Frame::new_with_caller(method, frame, arg_count)?;
-        interp.push_frame(callee_frame);
-
-        Ok(PostExecuteAction::MethodCall)
+        Ok(PostExecuteAction::Quicken(Quick::Invoke(method)))
     }
 }
 
 impl Invokestatic {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
-        let entry = frame
-            .class
-            .constant_pool()
-            .method_entry(self.0)
-            .ok_or(InterpreterError::NotMethodRef(self.0))?;
-        // TODO ensure class is not interface, method not abstract, not constructor
+        // TODO ensure method not abstract, not constructor
 
         // resolve class and method
-        let class = thread::get().global().class_loader().load_class_caused_by(
-            &entry.class,
-            frame.class.loader().clone(),
-            frame.class.name(),
-        )?;
-
-        let method = Class::find_method_recursive_in_superclasses(
-            &class,
-            &entry.name,
-            &entry.desc,
-            MethodAccessFlags::STATIC,
-            MethodAccessFlags::ABSTRACT,
-        )
-        .ok_or_else(|| InterpreterError::MethodNotFound {
-            class: entry.class.clone(),
-            name: entry.name.clone(),
-            desc: entry.desc.clone(),
-        })?;
-
-        // On successful resolution of the method, the class or interface that declared the
-        // resolved method is initialized if that class or interface has not already been
-        // initialized (§5.5).
-        if class.needs_init() {
-            return Ok(PostExecuteAction::ClassInit(class));
+        let resolved = frame.class.resolve_method(self.0)?;
+        let method = resolved.method.clone();
+        if !method.flags().is_static() {
+            return Err(Throwables::incompatible_class_change(format_args!(
+                "Expected static method {}",
+                method
+            ))
+            .into());
         }
 
         // ensure native method is bound
-        class.ensure_method_bound(&method)?;
+        resolved.class.ensure_method_bound(&method)?;
 
+        // On successful resolution of the method, the class or interface that declared the
+        // resolved method is initialized if that class or interface has not already been
+        // initialized (§5.5).
         // TODO typecheck args at verification time
-        let arg_count = method.args().len();
-        let callee_frame = Frame::new_with_caller(method, frame, arg_count)?;
-        interp.push_frame(callee_frame);
-
-        Ok(PostExecuteAction::MethodCall)
+        Ok(PostExecuteAction::Quicken(Quick::Invoke(method)))
     }
 }
 
 impl Invokevirtual {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
-        let thread = thread::get();
-        let class_loader = thread.global().class_loader();
 
         let entry = frame
             .class
@@ -2085,70 +2221,72 @@ impl Invokevirtual {
             .method_or_interface_entry(self.0)
             .ok_or(InterpreterError::NotMethodRef(self.0))?;
 
-        // resolve class and method
-        let class = class_loader.load_class_caused_by(
-            &entry.class,
-            frame.class.loader().clone(),
-            frame.class.name(),
-        )?;
-
-        let resolved_method = Class::find_method_recursive_in_superclasses(
-            &class,
-            &entry.name,
-            &entry.desc,
-            MethodAccessFlags::empty(),
-            MethodAccessFlags::empty(), // resolved method can be abstract
-        )
-        .ok_or_else(|| InterpreterError::MethodNotFound {
-            class: entry.class.clone(),
-            name: entry.name.clone(),
-            desc: entry.desc.clone(),
-        })?;
+        // MethodHandle.invokeExact and invoke take the call site's own descriptor
+        if let Some(exact) = callsite::signature_polymorphic(entry) {
+            let arg_count = MethodSignature::from_descriptor(&entry.desc)
+                .iter_args()
+                .count();
+            let handle = frame
+                .operand_stack
+                .peek_at(arg_count)
+                .ok_or(InterpreterError::NoOperand)?;
+            let handle = handle
+                .as_reference()
+                .ok_or_else(|| InterpreterError::InvalidOperandForObjectOp(handle.data_type()))?;
+
+            if handle.is_null() {
+                return Ok(PostExecuteAction::ThrowException(Throwables::null_pointer(
+                    format_args!(
+                        "invoke \"java.lang.invoke.MethodHandle.{}(Object[])\"",
+                        entry.name
+                    ),
+                )));
+            }
 
-        // should already be initialised if its been instantiated
-        // debug_assert!(!class.needs_init());
+            // not quickened as the invoker depends on the handle
+            let invoker = callsite::link_invoker(handle, &entry.desc, exact)?;
+            trace!("invokevirtual {}", invoker);
 
-        // TODO ensure method is not static, IncompatibleClassChangeError
-        assert!(!resolved_method.flags().is_static());
+            let callee_frame = Frame::new_with_caller(invoker, frame, arg_count + 1)?;
+            interp.push_frame(callee_frame);
+            return Ok(PostExecuteAction::MethodCall);
+        }
 
-        // now select method (5.4.6)
-        let selected_method = {
-            if resolved_method.flags().contains(MethodAccessFlags::PRIVATE) {
-                // chosen if private
-                resolved_method
-            } else {
-                // get `this` object
-                let this_obj = frame
-                    .operand_stack
-                    .peek_at(resolved_method.args().len())
-                    .and_then(|val| val.as_reference())
-                    .expect("invalid arg len?");
-
-                if let Some(this_cls) = this_obj.class() {
-                    let fml = resolved_method.name().to_utf8();
-                    Class::find_overriding_method(this_cls, &resolved_method)
-                        .unwrap_or(resolved_method)
-                } else {
-                    // if obj is null this will be caught when creating the frame
-                    resolved_method
-                }
-            }
-        };
+        // resolve class and method, which can be abstract
+        let resolved_method = frame.class.resolve_method(self.0)?.method.clone();
+        expect_instance_method(&resolved_method)?;
 
-        trace!("invokevirtual {}", selected_method);
+        trace!("invokevirtual resolved to {}", resolved_method);
+        Ok(PostExecuteAction::Quicken(select_later(resolved_method)))
+    }
+}
 
-        // pop args and call method
-        let arg_count = selected_method.args().len() + 1; // +1 for this
-        let callee_frame = Frame::new_with_caller(selected_method, frame, arg_count)?;
-        interp.push_frame(callee_frame);
+/// Instance method invocations throw if the resolved method is static
+fn expect_instance_method(resolved_method: &Method) -> Result<(), InterpreterError> {
+    if resolved_method.flags().is_static() {
+        return Err(Throwables::incompatible_class_change(format_args!(
+            "Expecting non-static method {}",
+            resolved_method
+        ))
+        .into());
+    }
+
+    Ok(())
+}
 
-        Ok(PostExecuteAction::MethodCall)
+/// Quick form of invokevirtual and invokeinterface. The method is selected from the receiver
+/// class on each invocation (5.4.6), unless it's private in which case it's chosen now
+fn select_later(resolved_method: VmRef<Method>) -> Quick {
+    if resolved_method.flags().contains(MethodAccessFlags::PRIVATE) {
+        Quick::Invoke(resolved_method)
+    } else {
+        Quick::Virtual(resolved_method)
     }
 }
 
 impl Ior {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Ior")
+        int_two_op(interp, "|", |a, b| a.bitor(b))
     }
 }
 
@@ -2157,15 +2295,12 @@ impl Irem {
         let frame = interp.current_frame_mut();
         let (val1, val2) = frame.pop_2_ints()?;
 
-        let result = match val1.checked_div(val2) {
-            Some(div) => val1 - div * val2,
-            None => {
-                return Ok(PostExecuteAction::ThrowException(Throwables::Other(
-                    "java/lang/ArithmeticException",
-                )))
-            }
-        };
+        if val2 == 0 {
+            return Ok(divide_by_zero());
+        }
 
+        // MIN % -1 is 0 rather than overflowing
+        let result = val1.wrapping_rem(val2);
         trace!("irem {} % {} => {}", val1, val2, result);
 
         frame.operand_stack.push(DataValue::Int(result));
@@ -2255,7 +2390,7 @@ impl Iushr {
 
         // unsigned for logical shift
         let result = (value as u32).shr(shift_by as u32) as i32;
-        trace!("{} << {} => {}", value, shift_by, result);
+        trace!("{} >>> {} => {}", value, shift_by, result);
 
         frame.operand_stack.push(DataValue::Int(result));
         Ok(PostExecuteAction::Continue)
@@ -2282,25 +2417,40 @@ impl Jsr {
 
 impl JsrW {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction JsrW")
+        let (frame, pc) = interp.current_frame_mut_with_next_pc();
+
+        // push pc of next instruction to stack
+        frame.operand_stack.push(DataValue::ReturnAddress(pc));
+
+        // relative jmp to arg
+        Ok(PostExecuteAction::Jmp(self.0))
     }
 }
 
 impl L2D {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction L2D")
+        let frame = interp.current_frame_mut();
+        let long = frame.pop_long()?;
+        frame.operand_stack.push(DataValue::Double(long as f64));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl L2F {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction L2F")
+        let frame = interp.current_frame_mut();
+        let long = frame.pop_long()?;
+        frame.operand_stack.push(DataValue::Float(long as f32));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl L2I {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction L2I")
+        let frame = interp.current_frame_mut();
+        let long = frame.pop_long()?;
+        frame.operand_stack.push(DataValue::Int(long as i32));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -2312,12 +2462,7 @@ impl Ladd {
 
 impl Laload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_load(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Long)
-            )
-        })
+        do_array_load(interp, ArrayKind::Long)
     }
 }
 
@@ -2329,18 +2474,24 @@ impl Land {
 
 impl Lastore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_store(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Long)
-            )
-        })
+        do_array_store(interp, ArrayKind::Long)
     }
 }
 
 impl Lcmp {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lcmp")
+        let frame = interp.current_frame_mut();
+        let (val1, val2) = frame.pop_2_longs()?;
+
+        let result = match val1.cmp(&val2) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        };
+        trace!("lcmp {} {} => {}", val1, val2, result);
+
+        frame.operand_stack.push(DataValue::Int(result));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
@@ -2362,153 +2513,176 @@ impl Lconst1 {
 
 impl Ldc {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        let frame = interp.current_frame_mut();
-
-        let pool = frame.class.constant_pool();
-        let entry = pool
-            .entry_and(self.0 as u16, |e| e.is_loadable())
-            .ok_or_else(|| InterpreterError::NotLoadable(self.0 as u16))?;
-
-        let to_push = match entry {
-            Entry::String(s) => {
-                // TODO lookup natively interned string instance
-
-                let string_class = thread::get()
-                    .global()
-                    .class_loader()
-                    .load_class("java/lang/String".as_mstr(), WhichLoader::Bootstrap)?;
-
-                // ensure initialised
-                if string_class.needs_init() {
-                    return Ok(PostExecuteAction::ClassInit(string_class));
-                }
-
-                // create string instance
-                let string_instance = Object::new_string(s.as_mstr())?;
-
-                // TODO natively intern new string instance
-                DataValue::Reference(string_instance)
-            }
-            Entry::Float(f) => DataValue::from(*f),
-            Entry::Int(i) => DataValue::from(*i),
-            // TODO deny long and double
-            Entry::ClassRef(cls_ref) => {
-                // resolve class
-                let cls = thread::get()
-                    .global()
-                    .class_loader()
-                    .load_class(&cls_ref.name, frame.class.loader().clone())?;
-
-                // get class object reference
-                DataValue::Reference(cls.class_object().clone())
-            }
-            e => unimplemented!("loadable entry {:?}", e),
-        };
-
-        frame.operand_stack.push(to_push);
-
-        Ok(PostExecuteAction::Continue)
+        load_constant(interp, self.0 as u16, false)
     }
 }
 
 impl Ldc2W {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        let frame = interp.current_frame_mut();
-
-        let pool = frame.class.constant_pool();
-        let entry = pool
-            .entry_and(self.0 as u16, |e| e.is_loadable_wide())
-            .ok_or_else(|| InterpreterError::NotLoadable(self.0 as u16))?;
-
-        let to_push = match entry {
-            Entry::Long(l) => DataValue::from(*l),
-            Entry::Double(d) => DataValue::from(*d),
-            e => unimplemented!("wide loadable entry {:?}", e),
-        };
-
-        frame.operand_stack.push(to_push);
-        Ok(PostExecuteAction::Continue)
+        load_constant(interp, self.0, true)
     }
 }
 
 impl LdcW {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction LdcW")
+        load_constant(interp, self.0, false)
     }
 }
 
+fn load_constant(interp: &mut InterpreterState, idx: u16, wide: bool) -> ExecuteResult {
+    let frame = interp.current_frame_mut();
+    frame
+        .class
+        .constant_pool()
+        .entry_and(idx, |e| {
+            if wide {
+                e.is_loadable_wide()
+            } else {
+                e.is_loadable()
+            }
+        })
+        .ok_or(InterpreterError::NotLoadable(idx))?;
+
+    // resolved before first use as it may run Java code
+    Ok(PostExecuteAction::Quicken(Quick::Ldc {
+        class: frame.class.clone(),
+        idx,
+    }))
+}
+
 impl Ldiv {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Ldiv")
+        let frame = interp.current_frame_mut();
+        let (val1, val2) = frame.pop_2_longs()?;
+
+        if val2 == 0 {
+            return Ok(divide_by_zero());
+        }
+
+        // MIN / -1 overflows back to MIN
+        let result = val1.wrapping_div(val2);
+        trace!("ldiv {} / {} => {}", val1, val2, result);
+
+        frame.operand_stack.push(DataValue::Long(result));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Lload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lload")
+        do_load_primitive(interp, self.0, |v| v.is_long(), PrimitiveDataType::Long)
     }
 }
 
 impl Lload0 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lload0")
-    }
+    insn_delegate!(Lload(0));
 }
 
 impl Lload1 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lload1")
-    }
+    insn_delegate!(Lload(1));
 }
 
 impl Lload2 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lload2")
-    }
+    insn_delegate!(Lload(2));
 }
 
 impl Lload3 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lload3")
-    }
+    insn_delegate!(Lload(3));
 }
 
 impl Lmul {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lmul")
+        long_two_op(interp, "*", |a, b| a.wrapping_mul(b))
     }
 }
 
 impl Lneg {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lneg")
+        let frame = interp.current_frame_mut();
+        let long = frame.pop_long()?;
+        frame
+            .operand_stack
+            .push(DataValue::Long(long.wrapping_neg()));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
-// impl Lookupswitch {
-//     fn execute(
-//         &self,
-//         interp: &mut InterpreterState
-//     ) -> ExecuteResult {
-//         todo!("instruction Lookupswitch")
-//     }
-// }
+impl Lookupswitch {
+    pub(crate) fn parse(reader: &mut InsnReader) -> Option<Self> {
+        reader.align(4);
+        let default = reader.read_i32()?;
+        let pair_count = u32::try_from(reader.read_i32()?).ok()?;
+
+        let pairs_pc = reader.pc();
+        reader.skip(pair_count as usize * 8)?;
+
+        Some(Self {
+            default,
+            pair_count,
+            pairs_pc: pairs_pc as u32,
+        })
+    }
+
+    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
+        let frame = interp.current_frame_mut();
+        let key = frame.pop_int()?;
+
+        // pairs are sorted by match value, and were bounds checked when parsed
+        let pair = |i: u32| {
+            let mut reader = InsnReader::new(frame.code.bytes(), (self.pairs_pc + i * 8) as usize);
+            let value = reader.read_i32().unwrap();
+            let offset = reader.read_i32().unwrap();
+            (value, offset)
+        };
+
+        let (mut lo, mut hi) = (0, self.pair_count);
+        let mut offset = self.default;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let (value, target) = pair(mid);
+            match value.cmp(&key) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => {
+                    offset = target;
+                    break;
+                }
+            }
+        }
+
+        trace!("lookupswitch {} => jmp {}", key, offset);
+        Ok(PostExecuteAction::Jmp(offset))
+    }
+}
 
 impl Lor {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lor")
+        long_two_op(interp, "|", |a, b| a.bitor(b))
     }
 }
 
 impl Lrem {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lrem")
+        let frame = interp.current_frame_mut();
+        let (val1, val2) = frame.pop_2_longs()?;
+
+        if val2 == 0 {
+            return Ok(divide_by_zero());
+        }
+
+        let result = val1.wrapping_rem(val2);
+        trace!("lrem {} % {} => {}", val1, val2, result);
+
+        frame.operand_stack.push(DataValue::Long(result));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Lreturn {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lreturn")
+        let frame = interp.current_frame_mut();
+        let val = frame.pop_long()?;
+        do_return_value(interp, DataValue::Long(val))
     }
 }
 
@@ -2529,125 +2703,182 @@ impl Lshl {
 
 impl Lshr {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lshr")
+        let frame = interp.current_frame_mut();
+
+        let shift_by = frame.pop_int()? & 0x3f; // low 6 bits only
+        let value = frame.pop_long()?;
+
+        let result = value.wrapping_shr(shift_by as u32);
+        trace!("{} >> {} => {}", value, shift_by, result);
+
+        frame.operand_stack.push(DataValue::Long(result));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Lstore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lstore")
+        let frame = interp.current_frame_mut();
+        let val = frame.pop_long()?;
+        frame
+            .local_vars
+            .store(self.0 as usize, DataValue::Long(val))?;
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Lstore0 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lstore0")
-    }
+    insn_delegate!(Lstore(0));
 }
 
 impl Lstore1 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lstore1")
-    }
+    insn_delegate!(Lstore(1));
 }
 
 impl Lstore2 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lstore2")
-    }
+    insn_delegate!(Lstore(2));
 }
 
 impl Lstore3 {
-    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lstore3")
-    }
+    insn_delegate!(Lstore(3));
 }
 
 impl Lsub {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lsub")
+        long_two_op(interp, "-", |a, b| a.wrapping_sub(b))
     }
 }
 
 impl Lushr {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lushr")
+        let frame = interp.current_frame_mut();
+
+        let shift_by = frame.pop_int()? & 0x3f; // low 6 bits only
+        let value = frame.pop_long()?;
+
+        // unsigned for logical shift
+        let result = (value as u64).shr(shift_by as u32) as i64;
+        trace!("{} >>> {} => {}", value, shift_by, result);
+
+        frame.operand_stack.push(DataValue::Long(result));
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Lxor {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Lxor")
+        long_two_op(interp, "^", |a, b| a.bitxor(b))
     }
 }
 
 impl Monitorenter {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        // TODO monitorenter
         let obj = interp.current_frame_mut().pop_reference()?;
+        if obj.is_null() {
+            return Err(InterpreterError::ExceptionRaised(Throwables::null_pointer(
+                "enter synchronized block",
+            )));
+        }
+
         trace!("monitorenter for {:?}", obj);
+        obj.monitor().lock();
         Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Monitorexit {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        // TODO monitorexit
         let obj = interp.current_frame_mut().pop_reference()?;
+        if obj.is_null() {
+            return Err(InterpreterError::ExceptionRaised(Throwables::null_pointer(
+                "exit synchronized block",
+            )));
+        }
+
         trace!("monitorexit for {:?}", obj);
+        obj.monitor().unlock().map_err(Throwables::from)?;
         Ok(PostExecuteAction::Continue)
     }
 }
 
-// impl Multianewarray {
-//     fn execute(
-//         &self,
-//         interp: &mut InterpreterState
-//     ) -> ExecuteResult {
-//         todo!("instruction Multianewarray")
-//     }
-// }
-
-impl New {
+impl Multianewarray {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
 
-        // find class name
-        let classref = frame
-            .class
-            .constant_pool()
-            .class_entry(self.0)
-            .ok_or(InterpreterError::NotClassRef(self.0))?;
-
-        // resolve and init class
-        let class = thread::get().global().class_loader().load_class_caused_by(
-            &classref.name,
-            frame.class.loader().clone(),
-            frame.class.name(),
-        )?;
+        // resolve array type
+        let array_cls = frame.class.resolve_class(self.0)?;
+
+        let dimensions = self.1;
+        match array_cls.class_type().array_dimensions() {
+            Some(dims) if dimensions >= 1 && dimensions <= dims => {}
+            _ => {
+                return Err(InterpreterError::InvalidArrayDimensions {
+                    dimensions,
+                    class: array_cls.class_type().to_owned(),
+                })
+            }
+        }
 
-        // TODO ensure not abstract, throw InstantiationError
+        // pop counts, outermost dimension is deepest on the stack
+        let mut counts = vec![0; dimensions as usize];
+        for count in counts.iter_mut().rev() {
+            *count = frame.pop_int()?;
+        }
 
-        // initialise class on successful resolution
-        if class.needs_init() {
-            return Ok(PostExecuteAction::ClassInit(class));
+        if let Some(count) = counts.iter().find(|count| **count < 0) {
+            return Ok(PostExecuteAction::ThrowException(Throwables::WithMessage(
+                "java/lang/NegativeArraySizeException",
+                count.to_string(),
+            )));
         }
 
-        // instantiate
-        let obj = vmref_alloc_object(|| Ok(Object::new(class)))?;
-        trace!(
-            "instantiated new instance of {:?}: {:?}",
-            obj.class().unwrap().name(),
-            obj
-        );
+        let array_instance = alloc_multi_array(&array_cls, &counts)?;
 
-        // push onto stack
-        frame.operand_stack.push(DataValue::Reference(obj));
+        // push to stack
+        frame
+            .operand_stack
+            .push(DataValue::Reference(array_instance));
 
         Ok(PostExecuteAction::Continue)
     }
 }
 
+/// Allocates nested arrays for each count, later dimensions are left as null
+fn alloc_multi_array(
+    array_cls: &VmRef<Class>,
+    counts: &[i32],
+) -> Result<VmRef<Object>, Throwables> {
+    let (length, rest) = counts.split_first().expect("no dimensions");
+    let array = vmref_alloc_object(|| Ok(Object::new_array(array_cls.clone(), *length as usize)))?;
+
+    if !rest.is_empty() {
+        let component = array_cls
+            .class_type()
+            .array_class()
+            .expect("not an array class");
+        let mut contents = array.array().unwrap();
+        for elem in contents.iter_mut() {
+            *elem = DataValue::Reference(alloc_multi_array(component, rest)?);
+        }
+    }
+
+    Ok(array)
+}
+
+impl New {
+    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
+        let frame = interp.current_frame_mut();
+
+        // resolve class, initialised before the quick form executes
+        let class = frame.class.resolve_class(self.0)?;
+
+        // TODO ensure not abstract, throw InstantiationError
+
+        // class is initialised on successful resolution
+        Ok(PostExecuteAction::Quicken(Quick::New(class)))
+    }
+}
+
 impl Newarray {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
@@ -2659,8 +2890,9 @@ impl Newarray {
         // get length
         let length = frame.pop_int()?;
         if length < 0 {
-            return Ok(PostExecuteAction::ThrowException(Throwables::Other(
+            return Ok(PostExecuteAction::ThrowException(Throwables::WithMessage(
                 "java/lang/NegativeArraySizeException",
+                length.to_string(),
             )));
         }
 
@@ -2685,14 +2917,17 @@ impl Newarray {
 
 impl Nop {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Nop")
+        Ok(PostExecuteAction::Continue)
     }
 }
 
 impl Pop {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        let popped = interp.current_frame_mut().pop_value()?;
-        assert!(!popped.is_wide());
+        let frame = interp.current_frame_mut();
+        let count = frame.operand_stack.operands_in_slots(0, 1)?;
+        for _ in 0..count {
+            frame.pop_value()?;
+        }
         Ok(PostExecuteAction::Continue)
     }
 }
@@ -2700,10 +2935,11 @@ impl Pop {
 impl Pop2 {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
         let frame = interp.current_frame_mut();
-        let popped = frame.pop_value()?;
-        if !popped.is_wide() {
-            let second = frame.pop_value()?;
-            assert!(!second.is_wide());
+
+        // value1 and value2 may be a single category 2 value
+        let count = frame.operand_stack.operands_in_slots(0, 2)?;
+        for _ in 0..count {
+            frame.pop_value()?;
         }
         Ok(PostExecuteAction::Continue)
     }
@@ -2711,124 +2947,28 @@ impl Pop2 {
 
 impl Putfield {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        let frame = interp.current_frame_mut();
-
-        // resolve field
-        let field = frame
-            .class
-            .constant_pool()
-            .field_entry(self.0)
-            .ok_or(InterpreterError::NotFieldRef(self.0))?;
-
-        trace!("putfield {:?}", field);
-
-        // pop objects
-        let (value, object, class) = {
-            let mut popped = frame
-                .operand_stack
-                .pop_n(2)
-                .ok_or(InterpreterError::NoOperand)?;
-
-            let value = popped.next().unwrap();
-            let object = popped.next().unwrap();
-
-            // ensure object is non-null non-array reference
-            let object = object
-                .into_reference()
-                .map_err(InterpreterError::InvalidOperandForObjectOp)?;
-
-            let class = if let Some(cls) = object.class() {
-                cls
-            } else {
-                return Ok(PostExecuteAction::ThrowException(
-                    Throwables::NullPointerException,
-                ));
-            };
-
-            (value, object, class)
-        };
-
-        // TODO verify not array class
-        let fields = object.fields().expect("unexpected array");
-
-        let class: VmRef<Class> = class;
-
-        // get field id
-        // TODO throw IncompatibleClassChangeError
-        let field_id = class
-            .find_instance_field_recursive(field.name.as_mstr(), &field.desc)
-            .ok_or_else(|| InterpreterError::FieldNotFound {
-                name: field.name.clone(),
-                desc: field.desc.clone(),
-            })?;
-
         // TODO check value is compatible with field desc
         // TODO if final can only be in constructor
-
-        // set field
-        trace!(
-            "putfield {:?}.{} = {:?}",
-            object,
-            field.name.as_mstr(),
-            value
-        );
-        fields.ensure_set(field_id, value);
-
-        Ok(PostExecuteAction::Continue)
+        let frame = interp.current_frame_mut();
+        let (slot, name) = resolve_instance_field(frame, self.0)?;
+        Ok(PostExecuteAction::Quicken(Quick::Putfield { slot, name }))
     }
 }
 
 impl Putstatic {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        let frame = interp.current_frame_mut();
-
-        // resolve field
-        let field = frame
-            .class
-            .constant_pool()
-            .field_entry(self.0)
-            .ok_or(InterpreterError::NotFieldRef(self.0))?;
-
-        trace!("putstatic {:?}", field);
-
-        // resolve class
-        let class = thread::get().global().class_loader().load_class_caused_by(
-            &field.class,
-            frame.class.loader().clone(),
-            frame.class.name(),
-        )?;
-
-        // get field id
-        // TODO throw IncompatibleClassChangeError
-        let found = class
-            .find_static_field_recursive(&field.name, &field.desc)
-            .ok_or_else(|| InterpreterError::FieldNotFound {
-                name: field.name.clone(),
-                desc: field.desc.clone(),
-            })?;
-
-        // class holding static field data is not necessarily the same
-        let (storage_class, field_id) = match found {
-            FoundField::InThisClass(id) => (class.clone(), id),
-            FoundField::InOtherClass(id, cls) => (cls, id),
-        };
-
-        // initialise class on successful resolution
-        if class.needs_init() {
-            return Ok(PostExecuteAction::ClassInit(class));
-        }
-
-        // pop value
-        let val = frame.pop_value()?;
-
         // TODO check value is compatible with field desc
         // TODO if final can only be in constructor
         // TODO if class is interface then can only be in constructor
+        let frame = interp.current_frame_mut();
+        let (class, storage, field) = resolve_static_field(frame, self.0)?;
 
-        // set field in storage class
-        storage_class.static_fields().ensure_set(field_id, val);
-
-        Ok(PostExecuteAction::Continue)
+        // class is initialised on successful resolution
+        Ok(PostExecuteAction::Quicken(Quick::Putstatic {
+            class,
+            storage,
+            field,
+        }))
     }
 }
 
@@ -2854,23 +2994,13 @@ impl Return {
 
 impl Saload {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_load(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Short)
-            )
-        })
+        do_array_load(interp, ArrayKind::Short)
     }
 }
 
 impl Sastore {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        do_array_store(interp, |cls| {
-            matches!(
-                cls.class_type(),
-                ClassType::Primitive(PrimitiveDataType::Short)
-            )
-        })
+        do_array_store(interp, ArrayKind::Short)
     }
 }
 
@@ -2887,24 +3017,106 @@ impl Sipush {
 
 impl Swap {
     fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
-        todo!("instruction Swap")
+        let frame = interp.current_frame_mut();
+
+        // both must be category 1
+        if frame.operand_stack.operands_in_slots(0, 2)? != 2 {
+            return Err(InterpreterError::SplitWideOperand);
+        }
+
+        let val1 = frame.pop_value()?;
+        let val2 = frame.pop_value()?;
+
+        frame.operand_stack.push(val1);
+        frame.operand_stack.push(val2);
+        Ok(PostExecuteAction::Continue)
     }
 }
 
-// impl Tableswitch {
-//     fn execute(
-//         &self,
-//         interp: &mut InterpreterState
-//     ) -> ExecuteResult {
-//         todo!("instruction Tableswitch")
-//     }
-// }
+impl Tableswitch {
+    pub(crate) fn parse(reader: &mut InsnReader) -> Option<Self> {
+        reader.align(4);
+        let default = reader.read_i32()?;
+        let low = reader.read_i32()?;
+        let high = reader.read_i32()?;
+        if low > high {
+            return None;
+        }
+
+        let offsets_pc = reader.pc();
+        let count = (high as i64 - low as i64 + 1) as usize;
+        reader.skip(count * 4)?;
 
-// impl Wide {
-//     fn execute(
-//         &self,
-//         interp: &mut InterpreterState
-//     ) -> ExecuteResult {
-//         todo!("instruction Wide")
-//     }
-// }
+        Some(Self {
+            default,
+            low,
+            high,
+            offsets_pc: offsets_pc as u32,
+        })
+    }
+
+    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
+        let frame = interp.current_frame_mut();
+        let idx = frame.pop_int()?;
+
+        let offset = if idx < self.low || idx > self.high {
+            self.default
+        } else {
+            // bounds checked when parsed
+            let entry = (idx as i64 - self.low as i64) as usize;
+            let mut reader =
+                InsnReader::new(frame.code.bytes(), self.offsets_pc as usize + entry * 4);
+            reader.read_i32().unwrap()
+        };
+
+        trace!("tableswitch {} => jmp {}", idx, offset);
+        Ok(PostExecuteAction::Jmp(offset))
+    }
+}
+
+impl Wide {
+    pub(crate) fn parse(reader: &mut InsnReader) -> Option<Self> {
+        let opcode = reader.read_u8()?;
+        let index = reader.read_u16()?;
+        let constant = match opcode {
+            Iinc::OPCODE => reader.read_i16()?,
+            Iload::OPCODE
+            | Fload::OPCODE
+            | Aload::OPCODE
+            | Lload::OPCODE
+            | Dload::OPCODE
+            | Istore::OPCODE
+            | Fstore::OPCODE
+            | Astore::OPCODE
+            | Lstore::OPCODE
+            | Dstore::OPCODE
+            | Ret::OPCODE => 0,
+            _ => return None,
+        };
+
+        Some(Self {
+            opcode,
+            index,
+            constant,
+        })
+    }
+
+    fn execute(&self, interp: &mut InterpreterState) -> ExecuteResult {
+        let idx = self.index;
+        match self.opcode {
+            Iload::OPCODE => Iload(idx).execute(interp),
+            Fload::OPCODE => Fload(idx).execute(interp),
+            Aload::OPCODE => Aload(idx).execute(interp),
+            Lload::OPCODE => Lload(idx).execute(interp),
+            Dload::OPCODE => Dload(idx).execute(interp),
+            Istore::OPCODE => Istore(idx).execute(interp),
+            Fstore::OPCODE => Fstore(idx).execute(interp),
+            Astore::OPCODE => Astore(idx).execute(interp),
+            Lstore::OPCODE => Lstore(idx).execute(interp),
+            Dstore::OPCODE => Dstore(idx).execute(interp),
+            Ret::OPCODE => Ret(idx).execute(interp),
+            Iinc::OPCODE => Iinc(idx, self.constant).execute(interp),
+            _ => unreachable!("checked when parsed"),
+        }
+    }
+}
@@ -1,10 +1,9 @@
 mod bytecode;
-mod exec;
+mod decoded;
 mod instruction;
 mod opcode;
+mod quick;
 
-pub use bytecode::get_insn;
-pub use bytecode::InstructionBlob;
-pub use exec::InstructionLookupTable;
+pub use decoded::DecodedCode;
 pub use instruction::*;
 pub use opcode::Opcode;
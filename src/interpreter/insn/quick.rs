@@ -0,0 +1,236 @@
+//! Quick forms of instructions that reference the constant pool. The first execution of such an
+//! instruction resolves its symbolic reference and replaces itself in the method's decoded code,
+//! so later executions skip straight to the field or method
+
+use std::fmt::{Debug, Formatter};
+
+use cafebabe::AccessFlags;
+use log::*;
+
+use crate::alloc::{vmref_alloc_object, InternedString, VmRef};
+use crate::class::{Class, Method, Object};
+use crate::error::Throwables;
+use crate::interpreter::callsite;
+use crate::interpreter::error::InterpreterError;
+use crate::interpreter::insn::instruction::{into_action, ExecuteResult, PostExecuteAction};
+use crate::interpreter::{Frame, InterpreterState};
+use crate::storage::{FieldId, FieldStorage, InstanceFieldSlot};
+use crate::types::DataValue;
+
+pub enum Quick {
+    Getfield {
+        slot: InstanceFieldSlot,
+        name: InternedString,
+    },
+    Putfield {
+        slot: InstanceFieldSlot,
+        name: InternedString,
+    },
+    /// Static field of `class`, stored in `storage` which may be a super class
+    Getstatic {
+        class: VmRef<Class>,
+        storage: VmRef<Class>,
+        field: FieldId,
+    },
+    Putstatic {
+        class: VmRef<Class>,
+        storage: VmRef<Class>,
+        field: FieldId,
+    },
+    /// Method chosen at resolution, including the linked target of an invokedynamic
+    Invoke(VmRef<Method>),
+    /// Resolved method that is selected from the method tables of each receiver's class
+    Virtual(VmRef<Method>),
+    New(VmRef<Class>),
+    /// Loadable constant that hasn't been resolved yet, as that may run Java code. Prepared into
+    /// a [Constant](Self::Constant)
+    Ldc {
+        class: VmRef<Class>,
+        idx: u16,
+    },
+    Constant(DataValue),
+}
+
+impl Quick {
+    /// Class that must be initialised before this can execute
+    fn init_class(&self) -> Option<&VmRef<Class>> {
+        match self {
+            Quick::Getstatic { class, .. } | Quick::Putstatic { class, .. } | Quick::New(class) => {
+                Some(class)
+            }
+            Quick::Invoke(method) if method.flags().is_static() => Some(method.class()),
+            _ => None,
+        }
+    }
+
+    /// If [prepare] needs to be called before the first execution
+    ///
+    /// [prepare]: Self::prepare
+    pub fn needs_prepare(&self) -> bool {
+        match self {
+            Quick::Ldc { .. } => true,
+            _ => self
+                .init_class()
+                .map(|class| !class.is_initialised())
+                .unwrap_or(false),
+        }
+    }
+
+    /// Finishes resolution by initialising classes and resolving constants. This runs Java code
+    /// so must be called without the interpreter state borrowed
+    pub fn prepare(self) -> Result<Self, InterpreterError> {
+        if let Quick::Ldc { class, idx } = self {
+            return callsite::load_constant(&class, idx).map(Quick::Constant);
+        }
+
+        if let Some(class) = self.init_class() {
+            class.ensure_init()?;
+        }
+
+        Ok(self)
+    }
+
+    /// False while the class to initialise is still being initialised by this thread, in which
+    /// case this can only be executed once and the next execution must check again
+    pub fn is_reusable(&self) -> bool {
+        self.init_class()
+            .map(|class| class.is_initialised())
+            .unwrap_or(true)
+    }
+
+    /// Every class referenced, for class unloading
+    pub fn classes(&self) -> impl Iterator<Item = &VmRef<Class>> {
+        let (a, b) = match self {
+            Quick::Getstatic { class, storage, .. } | Quick::Putstatic { class, storage, .. } => {
+                (Some(class), Some(storage))
+            }
+            Quick::New(class) | Quick::Ldc { class, .. } => (Some(class), None),
+            _ => (None, None),
+        };
+
+        a.into_iter().chain(b)
+    }
+
+    /// Method referenced, for class unloading
+    pub fn method(&self) -> Option<&VmRef<Method>> {
+        match self {
+            Quick::Invoke(method) | Quick::Virtual(method) => Some(method),
+            _ => None,
+        }
+    }
+
+    /// Constant value referenced, for class unloading
+    pub fn constant(&self) -> Option<&DataValue> {
+        match self {
+            Quick::Constant(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn execute(&self, interp: &mut InterpreterState) -> PostExecuteAction {
+        into_action(self.execute_inner(interp))
+    }
+
+    fn execute_inner(&self, interp: &mut InterpreterState) -> ExecuteResult {
+        let frame = interp.current_frame_mut();
+        match self {
+            Quick::Getfield { slot, name } => {
+                let obj = frame.pop_reference()?;
+                let value = instance_fields(&obj, *name, "read")?
+                    .try_get_slot(*slot)
+                    .ok_or_else(|| no_field(&obj, *name))?;
+                frame.operand_stack.push(value);
+            }
+            Quick::Putfield { slot, name } => {
+                let value = frame.pop_value()?;
+                let obj = frame.pop_reference()?;
+                if !instance_fields(&obj, *name, "assign")?.try_set_slot(*slot, value) {
+                    return Err(no_field(&obj, *name));
+                }
+            }
+            Quick::Getstatic { storage, field, .. } => {
+                let value = storage.static_fields().ensure_get(*field);
+                frame.operand_stack.push(value);
+            }
+            Quick::Putstatic { storage, field, .. } => {
+                let value = frame.pop_value()?;
+                storage.static_fields().ensure_set(*field, value);
+            }
+            Quick::Invoke(method) => {
+                trace!("invoke {}", method);
+                let arg_count = method.args().len() + usize::from(!method.flags().is_static());
+                let callee_frame = Frame::new_with_caller(method.clone(), frame, arg_count)?;
+                interp.push_frame(callee_frame);
+                return Ok(PostExecuteAction::MethodCall);
+            }
+            Quick::Virtual(resolved) => {
+                // select method (5.4.6), abstract methods throw when called
+                let this_cls = frame.peek_receiver_class(resolved)?;
+                let selected = this_cls.select_method(resolved)?;
+
+                trace!("invoke virtual {}", selected);
+                let arg_count = selected.args().len() + 1; // +1 for this
+                let callee_frame = Frame::new_with_caller(selected, frame, arg_count)?;
+                interp.push_frame(callee_frame);
+                return Ok(PostExecuteAction::MethodCall);
+            }
+            Quick::New(class) => {
+                let obj = vmref_alloc_object(|| Ok(Object::new(class.clone())))?;
+                trace!("instantiated new instance of {:?}: {:?}", class.name(), obj);
+                frame.operand_stack.push(DataValue::Reference(obj));
+            }
+            Quick::Ldc { class, idx } => {
+                let value = callsite::load_constant(class, *idx)?;
+                frame.operand_stack.push(value);
+            }
+            Quick::Constant(value) => frame.operand_stack.push(value.clone()),
+        }
+
+        Ok(PostExecuteAction::Continue)
+    }
+}
+
+/// Field storage of a non-null non-array object
+fn instance_fields<'a>(
+    obj: &'a VmRef<Object>,
+    name: InternedString,
+    verb: &str,
+) -> Result<&'a FieldStorage, InterpreterError> {
+    let class = obj.class().ok_or_else(|| {
+        InterpreterError::ExceptionRaised(Throwables::null_pointer(format_args!(
+            "{} field \"{}\"",
+            verb, name
+        )))
+    })?;
+
+    obj.fields()
+        .ok_or_else(|| InterpreterError::UnexpectedArray(class.class_type().to_owned()))
+}
+
+fn no_field(obj: &VmRef<Object>, name: InternedString) -> InterpreterError {
+    let class = obj.class().expect("not null");
+    InterpreterError::NoInstanceField {
+        class: class.class_type().to_owned(),
+        name,
+    }
+}
+
+impl Debug for Quick {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Quick::Getfield { name, .. } => write!(f, "Getfield({:?})", name),
+            Quick::Putfield { name, .. } => write!(f, "Putfield({:?})", name),
+            Quick::Getstatic { storage, field, .. } => {
+                write!(f, "Getstatic({:?}, {:?})", storage.name(), field)
+            }
+            Quick::Putstatic { storage, field, .. } => {
+                write!(f, "Putstatic({:?}, {:?})", storage.name(), field)
+            }
+            Quick::Invoke(method) => write!(f, "Invoke({})", method),
+            Quick::Virtual(method) => write!(f, "Virtual({})", method),
+            Quick::New(class) => write!(f, "New({:?})", class.name()),
+            Quick::Ldc { idx, .. } => write!(f, "Ldc({})", idx),
+            Quick::Constant(value) => write!(f, "Constant({:?})", value),
+        }
+    }
+}
@@ -3,18 +3,19 @@ use log::*;
 
 use crate::error::{Throwable, Throwables};
 use crate::interpreter::frame::{Frame, FrameStack, JavaFrame, NativeFrame, NativeFrameInner};
-use crate::interpreter::insn::{get_insn, InstructionBlob, PostExecuteAction};
+use crate::interpreter::insn::{into_action, Instruction, PostExecuteAction};
 use crate::thread;
 
-use crate::class::{Class, FunctionArgs, Method, NativeFunction};
-use crate::interpreter::InterpreterError;
+use crate::class::{Class, FunctionArgs, Method, MethodCode, NativeFunction, WhichLoader};
+use crate::interpreter::{InterpreterError, StackTrace};
 
 use crate::types::{DataType, DataValue, PrimitiveDataType, ReturnType};
+use cafebabe::mutf8::StrExt;
 use cafebabe::AccessFlags;
 
 use std::cell::{RefCell, RefMut};
 
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 
 #[derive(Debug)]
 pub enum InterpreterResult {
@@ -34,6 +35,11 @@ pub struct InterpFrameStackPrinter<'a>(&'a FrameStack);
 
 impl InterpreterState {
     pub fn push_frame(&mut self, frame: Frame) {
+        if let Some(obj) = frame.monitor() {
+            trace!("entering monitor of {:?} for synchronized {:?}", obj, frame);
+            obj.monitor().lock();
+        }
+
         trace!(
             "pushed new frame, stack depth is now {}: {:?}",
             self.frames.depth() + 1,
@@ -42,6 +48,7 @@ impl InterpreterState {
         self.frames.push(frame, 0);
     }
 
+    /// Exits the monitor of a synchronized method, whether returning normally or not
     pub fn pop_frame(&mut self) -> Option<Frame> {
         match self.frames.pop() {
             Some((f, _)) => {
@@ -50,6 +57,13 @@ impl InterpreterState {
                     self.frames.depth(),
                     self.frames.top(),
                 );
+
+                if let Some(obj) = f.monitor() {
+                    trace!("exiting monitor of {:?} for synchronized {:?}", obj, f);
+                    if obj.monitor().unlock().is_err() {
+                        warn!("{:?} no longer holds the monitor of {:?}", f, obj);
+                    }
+                }
                 Some(f)
             }
             None => {
@@ -128,6 +142,15 @@ impl InterpreterState {
             ret_val_orig
         };
 
+        // a synchronized method must still hold the monitor it entered on invocation
+        if let Some(obj) = self.frames.top().and_then(Frame::monitor) {
+            if !obj.monitor().is_held() {
+                return Err(InterpreterError::ExceptionRaised(Throwables::Other(
+                    "java/lang/IllegalMonitorStateException",
+                )));
+            }
+        }
+
         // pop frame
         if self.pop_frame().is_none() {
             return Err(InterpreterError::NoFrame);
@@ -194,49 +217,118 @@ impl Interpreter {
                 PostExecuteAction::MethodCall => depth += 1,
                 PostExecuteAction::Return => depth -= 1,
                 PostExecuteAction::ThrowException(exc) => {
-                    thread::get().set_exception(exc.into());
-                    return InterpreterResult::Exception;
+                    if !self.dispatch_exception(exc.into(), &mut depth) {
+                        return InterpreterResult::Exception;
+                    }
                 }
                 PostExecuteAction::Exception(exc) => {
-                    thread::get().set_exception(exc);
-                    return InterpreterResult::Exception;
-                }
-                PostExecuteAction::JmpAbsolute(new_pc) => {
-                    let mut state = self.state_mut();
-                    let (_, pc) = state.frames.top_java_mut().unwrap(); // jmps only happen in java frames
-
-                    debug!("jmping to insn {:?}", new_pc);
-                    *pc = new_pc;
-                }
-                PostExecuteAction::ClassInit(cls) => {
-                    debug!(
-                        "initialising class {:?} before replaying last instruction",
-                        cls.name()
-                    );
-
-                    if let Err(err) = cls.ensure_init() {
-                        warn!("class initialisation failed: {:?}", err);
-                        thread::get().set_exception(err.into());
+                    if !self.dispatch_exception(exc, &mut depth) {
                         return InterpreterResult::Exception;
                     }
                 }
-
-                PostExecuteAction::Jmp(_) => {
-                    unreachable!("execute() should have filtered out relative jumps")
+                PostExecuteAction::Jmp(_) | PostExecuteAction::JmpAbsolute(_) => {
+                    unreachable!("execute() should have followed jumps")
                 }
                 PostExecuteAction::Continue => {
                     unreachable!("execute() should have filtered out continues")
                 }
+                PostExecuteAction::Quicken(_) => {
+                    unreachable!("execute() should have executed the quick form")
+                }
+                PostExecuteAction::LoadClass { .. } => {
+                    unreachable!("execute() should have loaded the class")
+                }
             }
         }
 
         InterpreterResult::Success
     }
 
-    fn execute(&self) -> PostExecuteAction {
-        // TODO pass these into execute()
-        let mut insn_blob = InstructionBlob::default();
+    /// Searches for a handler for the given exception, starting in the current frame and popping
+    /// frames (including native frames) until one is found. Returns true if the exception was
+    /// caught and execution can continue at the handler, or false if it propagated out of the
+    /// frame this call to [Interpreter::execute_until_return] started in, in which case it is set
+    /// as the thread's pending exception.
+    ///
+    /// If the catch type of a handler can't be resolved, the resolution error replaces the
+    /// exception and is dispatched as if thrown from that handler
+    fn dispatch_exception(&self, mut exc: VmRef<Throwable>, depth: &mut usize) -> bool {
         let thread = thread::get();
+
+        // pc of the handler whose catch type failed to resolve
+        let mut failed_handler_pc = None;
+
+        loop {
+            let java_frame = {
+                let mut state = self.state_mut();
+                state.frames.top_java_mut().map(|(frame, pc)| {
+                    // the throwing frame's pc is exact, but callers' pcs are past the invoke
+                    let pc = match failed_handler_pc.take() {
+                        Some(handler_pc) => handler_pc,
+                        None if frame.pc_is_exact => *pc,
+                        None => *pc - 1,
+                    };
+                    (frame.class.clone(), frame.method.clone(), pc)
+                })
+            };
+
+            // state is not borrowed while loading catch types, as that may execute java code
+            if let Some((class, method, pc)) = java_frame {
+                let handler = match find_exception_handler(&class, &method, pc, &exc) {
+                    Ok(handler) => handler,
+                    Err((handler_pc, err)) => {
+                        debug!(
+                            "failed to resolve catch type of handler at {} in {}, throwing {} instead of {}",
+                            handler_pc,
+                            method,
+                            err.symbol(),
+                            exc.class_name
+                        );
+                        exc = err.into();
+                        failed_handler_pc = Some(handler_pc);
+                        continue;
+                    }
+                };
+
+                if let Some(handler_pc) = handler {
+                    match exc.object.clone() {
+                        Some(obj) => {
+                            debug!(
+                                "caught {} in {} at pc {}, jumping to handler at {}",
+                                exc.class_name, method, pc, handler_pc
+                            );
+
+                            let mut state = self.state_mut();
+                            let (frame, pc) = state.frames.top_java_mut().unwrap();
+                            frame.operand_stack.clear();
+                            frame.operand_stack.push(DataValue::Reference(obj));
+                            frame.pc_is_exact = false;
+                            *pc = handler_pc;
+
+                            thread.take_exception();
+                            return true;
+                        }
+                        None => {
+                            // nothing to push for the handler
+                            warn!("cannot catch uninstantiated exception {}", exc.class_name);
+                        }
+                    }
+                }
+            }
+
+            // not caught here, propagate to caller
+            trace!("{} not caught, popping frame", exc.class_name);
+            self.state_mut().pop_frame();
+            *depth -= 1;
+
+            if *depth == 0 {
+                thread.set_exception(exc);
+                return false;
+            }
+        }
+    }
+
+    fn execute(&self) -> PostExecuteAction {
         let mut state = self.state_mut();
 
         if let Some(native) = state.frames.top_native_mut() {
@@ -273,7 +365,7 @@ impl Interpreter {
                     } else {
                         todo!("instance method")
                     };
-                    let jni_ref = thread.jni_env();
+                    let jni_ref = thread::get().jni_env();
 
                     debug!("invoking jni function {}", method);
                     let raw_result = trampoline.invoke(jni_ref, cls_ref, args);
@@ -311,67 +403,132 @@ impl Interpreter {
             };
 
             // we made it! go mutable again to push return value onto caller's stack
-            return match self.state_mut().return_value_to_caller(return_value) {
-                Err(err) => {
-                    error!("interpreter error: {}", err);
-                    // TODO better handling of interpreter error
-                    PostExecuteAction::ThrowException(Throwables::Other("java/lang/InternalError"))
-                }
-                Ok(()) => PostExecuteAction::Return,
-            };
+            let result = self.state_mut().return_value_to_caller(return_value);
+            return into_action(result.map(|_| PostExecuteAction::Return));
         }
 
-        loop {
-            // get current java frame
-            let (frame, pc) = state.frames.top_java_mut().expect("no frame");
+        // the current instruction is only tracked by its index while executing in this frame, and
+        // the frame's pc is written back whenever something else could look at it
+        let frame_idx = state.frames.depth() - 1;
+        let (frame, pc) = state.frames.top_java_mut().expect("no frame");
+        let code = frame.code.clone();
+        let mut idx = match code.index_of(*pc) {
+            Some(idx) => idx,
+            None => return invalid_jump(*pc),
+        };
 
-            // get current instruction
-            let code = frame.code.as_ref();
-            let old_pc = *pc;
-            let (new_pc, opcode) = get_insn(code, *pc, &mut insn_blob).expect("bad opcode");
-            *pc = new_pc;
+        loop {
+            let insn = code.insn(idx);
+            if let Instruction::Jsr(_) | Instruction::JsrW(_) = insn.insn {
+                // the return address is read from the frame
+                state.frames.set_pc(frame_idx, insn.next_pc as usize);
+            }
 
-            // lookup execute function
+            #[cfg(feature = "trace-instructions")]
             trace!(
                 "{}: executing {:?} ({:?})",
-                old_pc,
-                opcode,
+                insn.pc,
+                insn.insn,
                 state.frames.top().unwrap()
             );
-            let exec_fn = thread.global().insn_lookup().resolve(opcode);
-            let result = exec_fn(&insn_blob, &mut *state);
+            let mut result = insn.execute(&mut state);
 
-            match result {
-                PostExecuteAction::Continue => {
-                    // keep executing this frame
+            if let PostExecuteAction::Quicken(mut quick) = result {
+                if quick.needs_prepare() {
+                    // pc is left past this instruction while preparing, so stack traces taken
+                    // during class initialisers point at it like any other call
+                    state.frames.set_pc(frame_idx, insn.next_pc as usize);
+                    drop(state);
+                    let prepared = quick.prepare();
+                    state = self.state_mut();
+
+                    quick = match prepared {
+                        Ok(quick) => quick,
+                        Err(err) => {
+                            debug!("resolution of insn at {} failed: {}", insn.pc, err);
+                            state.frames.set_throwing_pc(insn.pc as usize);
+                            return into_action(Err(err));
+                        }
+                    };
                 }
-                PostExecuteAction::Jmp(offset) => {
-                    // jmp is relative to this opcode, make absolute
-                    let new_offset = offset + (old_pc as i32);
-                    trace!("adjusted jmp offset from {:?} to {:?}", offset, new_offset);
-                    return PostExecuteAction::JmpAbsolute(new_offset as usize);
+
+                trace!("{}: quickened to {:?}", insn.pc, quick);
+                result = if quick.is_reusable() {
+                    insn.quicken(quick).execute(&mut state)
+                } else {
+                    // class is still being initialised by this thread
+                    quick.execute(&mut state)
+                };
+            }
+
+            if let PostExecuteAction::LoadClass { name, loader } = result {
+                // the user loader runs java code, so can't run during the instruction. Once
+                // loaded the instruction is executed again and finds it in the loaded classes
+                state.frames.set_pc(frame_idx, insn.next_pc as usize);
+                drop(state);
+                let loaded = thread::get()
+                    .global()
+                    .class_loader()
+                    .load_class(&name, loader);
+                state = self.state_mut();
+
+                if let Err(exc) = loaded {
+                    state.frames.set_throwing_pc(insn.pc as usize);
+                    return PostExecuteAction::ThrowException(exc);
                 }
-                ret @ PostExecuteAction::ClassInit(_) => {
-                    // after the class is initialised we want to replay the opcode that caused it,
-                    // so rewind pc
-                    trace!(
-                        "rewinding pc from {} to {} to replay after class init",
-                        new_pc,
-                        old_pc
-                    );
 
-                    drop(state);
-                    let mut state = self.state_mut();
-                    let (_, pc) = state.frames.top_java_mut().unwrap();
-                    *pc = old_pc;
+                continue;
+            }
 
+            // branches continue in this loop without leaving the frame
+            let target = match result {
+                PostExecuteAction::Continue => {
+                    idx += 1;
+                    continue;
+                }
+                PostExecuteAction::Jmp(offset) => match insn.target() {
+                    Some(target) => Ok(target),
+                    // jmp is relative to this opcode
+                    None => Err(insn.pc as i32 + offset),
+                },
+                PostExecuteAction::JmpAbsolute(new_pc) => Err(new_pc as i32),
+                ret @ (PostExecuteAction::ThrowException(_) | PostExecuteAction::Exception(_)) => {
+                    // handlers are looked up by the pc of the throwing instruction
+                    state.frames.set_throwing_pc(insn.pc as usize);
                     return ret;
                 }
-                ret => return ret,
-            }
+                ret => {
+                    // calls return here past this instruction, returns have already popped it
+                    state.frames.set_pc(frame_idx, insn.next_pc as usize);
+                    return ret;
+                }
+            };
+
+            idx = match target {
+                Ok(target) => target,
+                Err(new_pc) => {
+                    trace!("jmping to insn {:?}", new_pc);
+                    match usize::try_from(new_pc)
+                        .ok()
+                        .and_then(|pc| code.index_of(pc))
+                    {
+                        Some(idx) => idx,
+                        None => {
+                            state.frames.set_throwing_pc(insn.pc as usize);
+                            return invalid_jump(new_pc);
+                        }
+                    }
+                }
+            };
         }
     }
 
+    /// If an instruction is currently executing on this thread, so the state is borrowed and java
+    /// code can't be run until it finishes
+    pub fn is_executing(&self) -> bool {
+        self.state.try_borrow_mut().is_err()
+    }
+
     pub fn state_mut(&self) -> RefMut<InterpreterState> {
         self.state.borrow_mut()
     }
@@ -393,6 +550,14 @@ impl Interpreter {
         }
     }
 
+    /// Class of the method in the current frame. None if there isn't one, or while an instruction
+    /// is executing and the frames can't be inspected
+    pub fn current_class(&self) -> Option<VmRef<Class>> {
+        let state = self.state.try_borrow().ok()?;
+        let frame = state.frames.top()?;
+        frame.class_and_method().class().cloned()
+    }
+
     /// Called in top down order, first is current frame
     pub fn with_frames(&self, mut f: impl FnMut(&Frame)) {
         let state = self.state.borrow();
@@ -401,6 +566,12 @@ impl Interpreter {
         }
     }
 
+    /// Snapshot of the current frames for a throwable of the given class being constructed
+    pub fn capture_stack_trace(&self, throwable: &VmRef<Class>) -> StackTrace {
+        let state = self.state.borrow();
+        StackTrace::capture(state.frames.iter_with_pc(), throwable)
+    }
+
     /// 0 = current, 1 = calling, etc.
     ///
     /// Fn is not called if frame doesn't exist
@@ -429,6 +600,76 @@ impl Default for Interpreter {
     }
 }
 
+/// Branch to a pc that isn't the start of an instruction
+fn invalid_jump(pc: impl Display) -> PostExecuteAction {
+    PostExecuteAction::ThrowException(Throwables::WithMessage(
+        "java/lang/VerifyError",
+        format!("branch to invalid pc {}", pc),
+    ))
+}
+
+/// First handler in the method's exception table that covers the given pc and catches the
+/// exception. If a catch type can't be resolved, that handler's pc and the resolution error is
+/// returned instead
+fn find_exception_handler(
+    class: &VmRef<Class>,
+    method: &Method,
+    pc: usize,
+    exc: &Throwable,
+) -> Result<Option<usize>, (usize, Throwables)> {
+    let handlers = match method.code() {
+        MethodCode::Java(code) => &code.exception_handlers,
+        _ => return Ok(None),
+    };
+
+    // only loaded if there is a handler with a catch type in range
+    let mut exc_class = None;
+
+    for handler in handlers.iter() {
+        if !(handler.start_pc as usize..handler.end_pc as usize).contains(&pc) {
+            continue;
+        }
+
+        let handler_pc = handler.handler_pc as usize;
+        let catches = match &handler.catch_type {
+            None => true,
+            Some(catch_type) => {
+                let exc_class = match &mut exc_class {
+                    Some(cls) => cls,
+                    None => match exception_class(exc) {
+                        Some(cls) => exc_class.insert(cls),
+                        None => return Ok(None),
+                    },
+                };
+
+                let catch_class = thread::get()
+                    .global()
+                    .class_loader()
+                    .load_class_caused_by(catch_type, class.loader().clone(), class.name())
+                    .map_err(|err| (handler_pc, err))?;
+                exc_class.is_instance_of(&catch_class)
+            }
+        };
+
+        if catches {
+            return Ok(Some(handler_pc));
+        }
+    }
+
+    Ok(None)
+}
+
+fn exception_class(exc: &Throwable) -> Option<VmRef<Class>> {
+    match &exc.object {
+        Some(obj) => obj.class(),
+        None => thread::get()
+            .global()
+            .class_loader()
+            .load_class(exc.class_name.as_mstr(), WhichLoader::Bootstrap)
+            .ok(),
+    }
+}
+
 impl DataValue {
     unsafe fn from_raw_return_value(ret: u64, ty: &DataType) -> Self {
         use DataType::*;
@@ -1,11 +1,18 @@
+mod callsite;
 mod error;
 mod frame;
 mod insn;
 mod interp;
 mod native;
+mod stacktrace;
+
+pub use callsite::{runtime_class, DirectMethodHandle};
 
 pub use error::InterpreterError;
 pub use frame::{Frame, FrameInfo, NativeFrame, NativeFrameInner};
-pub use insn::InstructionLookupTable;
+pub use insn::DecodedCode;
 pub use interp::{Interpreter, InterpreterResult, InterpreterState};
 pub use native::{NativeThunkHandle, NativeThunks};
+pub use stacktrace::{StackTrace, StackTraceFrame};
+#[cfg(test)]
+pub(crate) use {callsite::writer, insn::Opcode};
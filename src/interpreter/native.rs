@@ -12,7 +12,6 @@ use crate::types::{DataType, DataValue};
 use itertools::repeat_n;
 use std::arch::asm;
 use std::io::Cursor;
-use std::mem::transmute_copy;
 
 const BACKING_CAPACITY: usize = 4096 * 16;
 
@@ -20,7 +19,10 @@ struct ThunkBacking {
     backing: Allocation,
     /// Offset into backing where next thunk can start
     next: usize,
-    // TODO support freeing of thunks when a class is unloaded, and reuse it
+    /// Offsets of freed thunks that can be reused
+    free: Vec<usize>,
+    /// Number of thunks currently handed out
+    live: usize,
 }
 
 #[derive(Default)]
@@ -62,6 +64,8 @@ impl NativeThunks {
         self.backing.push(ThunkBacking {
             backing: allocation,
             next: 0,
+            free: Vec::new(),
+            live: 0,
         });
 
         let backing = self.backing.last_mut().unwrap(); // just added
@@ -70,6 +74,30 @@ impl NativeThunks {
             .expect("new backing allocation is too small");
         Ok(NativeThunkHandle(ptr, len))
     }
+
+    /// Releases the thunk for reuse, e.g. when its class is unloaded. The handle must not be
+    /// invoked again. Backings with no live thunks left are unmapped
+    pub fn free(&mut self, handle: NativeThunkHandle) {
+        let idx = self.backing.iter().position(|b| b.contains(handle.0));
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                warn!("freeing unknown thunk {:?}", handle.0);
+                return;
+            }
+        };
+
+        let backing = &mut self.backing[idx];
+        backing.free(handle);
+
+        if backing.live == 0 {
+            trace!(
+                "releasing empty thunk backing {:?}",
+                backing.backing.as_ptr::<u8>()
+            );
+            self.backing.swap_remove(idx);
+        }
+    }
 }
 
 impl NativeThunkHandle {
@@ -98,26 +126,25 @@ impl NativeThunkHandle {
 
         native_args.extend(repeat_n(0, args.len()));
 
-        for (arg, arg_out) in args.take_all().zip(&mut native_args[2..]) {
-            // squidge all arg types into a u64, which will be read correctly by thunk
-            let mut val = 0u64;
-
-            unsafe {
-                match arg {
-                    DataValue::Boolean(arg) => val = transmute_copy(&arg),
-                    DataValue::Byte(arg) => val = transmute_copy(&arg),
-                    DataValue::Short(arg) => val = transmute_copy(&arg),
-                    DataValue::Int(arg) => val = transmute_copy(&arg),
-                    DataValue::Long(arg) => val = transmute_copy(&arg),
-                    DataValue::Char(arg) => val = transmute_copy(&arg),
-                    DataValue::Float(arg) => val = transmute_copy(&arg),
-                    DataValue::Double(arg) => val = transmute_copy(&arg),
-                    DataValue::Reference(arg) => val = vmref_into_raw(arg) as u64,
-                    DataValue::VmDataClass(_) | DataValue::ReturnAddress(_) => unreachable!(),
-                }
-            }
-
-            *arg_out = val;
+        // args are stored last first, as popped from the operand stack
+        for (arg, arg_out) in args.take_all().rev().zip(&mut native_args[2..]) {
+            // squidge all arg types into the low bits of a u64, which will be read correctly by
+            // thunk. longs and doubles are a single u64 like any other arg
+            *arg_out = match arg {
+                DataValue::Boolean(arg) => arg as u64,
+                DataValue::Byte(arg) => arg as u8 as u64,
+                DataValue::Short(arg) => arg as u16 as u64,
+                DataValue::Int(arg) => arg as u32 as u64,
+                DataValue::Long(arg) => arg as u64,
+                DataValue::Char(arg) => arg as u64,
+                DataValue::Float(arg) => arg.to_bits() as u64,
+                DataValue::Double(arg) => arg.to_bits(),
+                DataValue::Reference(arg) => vmref_into_raw(arg) as u64,
+                DataValue::VmDataClass(_)
+                | DataValue::VmDataStackTrace(_)
+                | DataValue::VmDataMethodHandle(_)
+                | DataValue::ReturnAddress(_) => unreachable!(),
+            };
         }
 
         let ret: u64;
@@ -137,21 +164,43 @@ impl NativeThunkHandle {
     }
 }
 
+const THUNK_SIZE: usize = 1024;
+
 impl ThunkBacking {
     fn allocate(&mut self) -> Option<(*mut u8, usize)> {
-        const SIZE: usize = 1024;
-        let new_end = self.next + SIZE;
-
-        if new_end >= self.backing.len() {
-            None
-        } else {
-            let start = std::mem::replace(&mut self.next, new_end);
-            let len = SIZE;
-            // TODO size depends on number of args to pass
-
-            let start = unsafe { self.backing.as_mut_ptr::<u8>().add(start) };
-            Some((start, len))
-        }
+        // TODO size depends on number of args to pass
+        let start = match self.free.pop() {
+            Some(start) => start,
+            None => {
+                let new_end = self.next + THUNK_SIZE;
+                if new_end >= self.backing.len() {
+                    return None;
+                }
+
+                std::mem::replace(&mut self.next, new_end)
+            }
+        };
+
+        self.live += 1;
+        let start = unsafe { self.backing.as_mut_ptr::<u8>().add(start) };
+        Some((start, THUNK_SIZE))
+    }
+
+    fn contains(&self, ptr: *mut u8) -> bool {
+        let start = self.backing.as_ptr::<u8>() as usize;
+        let ptr = ptr as usize;
+        ptr >= start && ptr < start + self.backing.len()
+    }
+
+    fn free(&mut self, handle: NativeThunkHandle) {
+        let offset = handle.0 as usize - self.backing.as_ptr::<u8>() as usize;
+        debug_assert!(!self.free.contains(&offset), "double free of thunk");
+
+        // trap if anything jumps into a freed thunk
+        handle.as_slice().fill(0xcc);
+
+        self.free.push(offset);
+        self.live -= 1;
     }
 }
 
@@ -337,11 +386,12 @@ mod tests {
         )
         .expect("emit thunk");
 
+        // reverse order, as in a native frame
         let mut args = [
-            DataValue::Long(0x11223344_aabbccdd),
-            DataValue::Short(-12345),
-            DataValue::Int(7),
             DataValue::Byte(108),
+            DataValue::Int(7),
+            DataValue::Short(-12345),
+            DataValue::Long(0x11223344_aabbccdd),
         ];
         let args = FunctionArgs::from(&mut args[..]);
 
@@ -352,4 +402,20 @@ mod tests {
         );
         assert_eq!(ret, 123);
     }
+
+    #[test]
+    fn native_thunk_reuse() {
+        let mut thunks = NativeThunks::default();
+        let a = thunks.allocate().expect("alloc thunk");
+        let b = thunks.allocate().expect("alloc thunk");
+        let a_ptr = a.0;
+
+        thunks.free(a);
+        let c = thunks.allocate().expect("alloc thunk");
+        assert_eq!(c.0, a_ptr);
+
+        thunks.free(b);
+        thunks.free(c);
+        assert!(thunks.backing.is_empty());
+    }
 }
@@ -0,0 +1,107 @@
+use std::fmt::{Debug, Formatter};
+
+use crate::alloc::VmRef;
+use crate::class::{Class, Method, MethodCode};
+use crate::interpreter::frame::{Frame, FrameInfo};
+
+/// Snapshot of the frame stack taken by `Throwable.fillInStackTrace`, stored in
+/// java/lang/VMThrowable.vmdata
+#[derive(Default)]
+pub struct StackTrace(Box<[StackTraceFrame]>);
+
+pub enum StackTraceFrame {
+    Java {
+        method: VmRef<Method>,
+        /// Offset of the executing instruction
+        pc: usize,
+    },
+    Native {
+        method: VmRef<Method>,
+    },
+}
+
+impl StackTrace {
+    /// Frames are top down along with their stored pc, which is past the executing instruction
+    /// unless the frame is throwing. Leading frames that are filling in the trace or constructing the throwable are skipped, so
+    /// the first frame is where the throwable was created
+    pub(in crate::interpreter) fn capture<'a>(
+        frames: impl Iterator<Item = (&'a Frame, usize)>,
+        throwable: &VmRef<Class>,
+    ) -> Self {
+        let frames = frames
+            .filter_map(|(frame, pc)| match (frame, frame.class_and_method()) {
+                (Frame::Java(frame), _) => Some(StackTraceFrame::Java {
+                    method: frame.method.clone(),
+                    // only callers need to be moved back into the invoke instruction
+                    pc: if frame.pc_is_exact {
+                        pc
+                    } else {
+                        pc.saturating_sub(1)
+                    },
+                }),
+                (Frame::Native(_), FrameInfo::Method(_, method)) => Some(StackTraceFrame::Native {
+                    method: method.clone(),
+                }),
+                // calls into the vm from native code
+                (Frame::Native(_), FrameInfo::Jni(_)) => None,
+            })
+            .skip_while(|frame| {
+                let method = frame.method();
+                let cls = method.class();
+                if cls.name().as_bytes() == b"java/lang/VMThrowable" {
+                    return true;
+                }
+
+                let name = method.name().as_bytes();
+                (name == b"fillInStackTrace" || name == b"<init>") && throwable.is_instance_of(cls)
+            })
+            .collect();
+
+        StackTrace(frames)
+    }
+
+    /// Top down, first is where the throwable was created
+    pub fn frames(&self) -> &[StackTraceFrame] {
+        &self.0
+    }
+}
+
+impl StackTraceFrame {
+    pub fn method(&self) -> &VmRef<Method> {
+        match self {
+            StackTraceFrame::Java { method, .. } | StackTraceFrame::Native { method } => method,
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        matches!(self, StackTraceFrame::Native { .. })
+    }
+
+    /// From the method's LineNumberTable, if present
+    pub fn line_number(&self) -> Option<u16> {
+        match self {
+            StackTraceFrame::Java { method, pc } => match method.code() {
+                MethodCode::Java(code) => code.line_numbers.line_for_pc(*pc),
+                _ => None,
+            },
+            StackTraceFrame::Native { .. } => None,
+        }
+    }
+}
+
+impl Debug for StackTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
+
+impl Debug for StackTraceFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.method())?;
+        match (self, self.line_number()) {
+            (StackTraceFrame::Native { .. }, _) => write!(f, " (native)"),
+            (_, Some(line)) => write!(f, ":{}", line),
+            (StackTraceFrame::Java { pc, .. }, None) => write!(f, " @ {}", pc),
+        }
+    }
+}
@@ -355,7 +355,7 @@ mod jnienv {
     };
     use crate::class::{Class, Object, WhichLoader};
 
-    use crate::error::{Throwables, VmResult};
+    use crate::error::{Throwable, Throwables, VmResult};
     use crate::exec_helper::ArrayType;
     use crate::jni::api::{JniFieldId, JniMethodId};
     use crate::jni::sys::*;
@@ -457,9 +457,8 @@ mod jnienv {
                 vmref_into_raw(cls) as jclass
             }
             Err(err) => {
-                // TODO set exception
                 warn!("FindClass failed: {:?}", err);
-                todo!("set jni exception for {:?}", err);
+                thread.set_exception(err.into());
                 ptr::null_mut()
             }
         }
@@ -505,9 +504,16 @@ mod jnienv {
         todo!("ToReflectedField")
     }
 
-    pub extern "C" fn Throw(env: *mut JNIEnv, arg2: jthrowable) -> jint {
-        trace!("jni::Throw({:?})", arg2);
-        todo!("Throw")
+    pub extern "C" fn Throw(env: *mut JNIEnv, obj: jthrowable) -> jint {
+        trace!("jni::Throw({:?})", obj);
+
+        if is_null_throwing(obj) {
+            return -1;
+        }
+
+        let obj = unsafe { as_vmref::<Object>(obj) };
+        thread::get().set_exception(Throwable::from_object(VmRef::clone(&obj)));
+        0
     }
 
     pub extern "C" fn ThrowNew(
@@ -521,29 +527,51 @@ mod jnienv {
             return -1;
         }
 
-        let (class, msg) = unsafe { (as_vmref::<Class>(cls), CStr::from_ptr(msg)) };
+        let (class, msg) = unsafe { (as_vmref::<Class>(cls), as_string(msg)) };
 
-        todo!("ThrowNew({:?}, {:?})", class.name(), msg)
+        let thread = thread::get();
+        let exc = Object::new_string(msg).and_then(|msg| {
+            thread.exec_helper().instantiate_and_invoke_constructor(
+                VmRef::clone(&class),
+                "(Ljava/lang/String;)V",
+                std::iter::once(DataValue::Reference(msg)),
+            )
+        });
+
+        match exc {
+            Ok(obj) => {
+                thread.set_exception(Throwable::from_object(obj));
+                0
+            }
+            Err(err) => {
+                warn!("ThrowNew({:?}) failed: {:?}", class.name(), err);
+                thread.set_exception(err.into());
+                -1
+            }
+        }
     }
 
     pub extern "C" fn ExceptionOccurred(env: *mut JNIEnv) -> jthrowable {
         trace!("jni::ExceptionOccurred()");
         let t = thread::get();
-        match t.exception() {
+        match t.exception().and_then(|e| e.object.clone()) {
             None => jnull(),
-            Some(e) => vmref_into_raw(e) as jthrowable,
+            Some(obj) => vmref_into_raw(obj) as jthrowable,
         }
     }
 
     pub extern "C" fn ExceptionDescribe(env: *mut JNIEnv) {
         trace!("jni::ExceptionDescribe()");
-        todo!("ExceptionDescribe")
+        // cleared first, as printing runs java code
+        if let Some(exc) = thread::get().take_exception() {
+            exc.print_stack_trace();
+        }
     }
 
     pub extern "C" fn ExceptionClear(env: *mut JNIEnv) {
         trace!("jni::ExceptionClear()");
         let t = thread::get();
-        t.exception().take();
+        t.take_exception();
     }
 
     pub extern "C" fn FatalError(env: *mut JNIEnv, arg2: *const ::std::os::raw::c_char) {
@@ -2978,14 +3006,33 @@ mod jnienv {
         todo!("UnregisterNatives")
     }
 
-    pub extern "C" fn MonitorEnter(env: *mut JNIEnv, arg2: jobject) -> jint {
-        trace!("jni::MonitorEnter({:?})", arg2);
-        todo!("MonitorEnter")
+    pub extern "C" fn MonitorEnter(env: *mut JNIEnv, obj: jobject) -> jint {
+        trace!("jni::MonitorEnter({:?})", obj);
+
+        if is_null_throwing(obj) {
+            return JNI_ERR;
+        }
+
+        let obj = unsafe { as_vmref::<Object>(obj) };
+        obj.monitor().lock();
+        JNI_OK as jint
     }
 
-    pub extern "C" fn MonitorExit(env: *mut JNIEnv, arg2: jobject) -> jint {
-        trace!("jni::MonitorExit({:?})", arg2);
-        todo!("MonitorExit")
+    pub extern "C" fn MonitorExit(env: *mut JNIEnv, obj: jobject) -> jint {
+        trace!("jni::MonitorExit({:?})", obj);
+
+        if is_null_throwing(obj) {
+            return JNI_ERR;
+        }
+
+        let obj = unsafe { as_vmref::<Object>(obj) };
+        match obj.monitor().unlock() {
+            Ok(()) => JNI_OK as jint,
+            Err(err) => {
+                thread::get().set_exception(Throwables::from(err).into());
+                JNI_ERR
+            }
+        }
     }
 
     pub extern "C" fn GetJavaVM(env: *mut JNIEnv, arg2: *mut *mut JavaVM) -> jint {
@@ -3077,7 +3124,12 @@ mod jnienv {
 
     pub extern "C" fn ExceptionCheck(env: *mut JNIEnv) -> jboolean {
         trace!("jni::ExceptionCheck()");
-        todo!("ExceptionCheck")
+        let t = thread::get();
+        if t.exception().is_some() {
+            JNI_TRUE as jboolean
+        } else {
+            JNI_FALSE as jboolean
+        }
     }
 
     pub extern "C" fn NewDirectByteBuffer(
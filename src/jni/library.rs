@@ -69,8 +69,29 @@ impl NativeLibrary {
 
         Ok(Self(lib))
     }
+}
+
+impl Drop for NativeLibrary {
+    fn drop(&mut self) {
+        let on_unload = unsafe {
+            self.0
+                .get::<extern "C" fn(*const sys::JavaVM, *mut ())>(b"JNI_OnUnload\0")
+        };
+
+        if let Ok(func) = on_unload {
+            let func_addr = symbol_addr(func.clone());
 
-    // TODO call JNI_OnUnload in Drop
+            // safety: library is still loaded until the end of this function
+            let func = unsafe { func.into_raw() };
+
+            debug!("running JNI_OnUnload");
+            let thread = thread::get();
+            let interp = thread.interpreter();
+            let frame = NativeFrame::jni_direct("JNI_OnUnload", func_addr);
+
+            interp.execute_native_frame(frame, || func(current_javavm(), ptr::null_mut()));
+        }
+    }
 }
 
 fn symbol_addr<T>(sym: libloading::Symbol<T>) -> usize {
@@ -98,6 +119,25 @@ impl NativeLibraries {
         self.libs.push((owner, name, lib))
     }
 
+    /// Removes libraries whose owning class loader has been freed. They should be dropped after
+    /// releasing the lock on this, as JNI_OnUnload may call back into the VM
+    pub fn take_unloaded(&mut self) -> Vec<NativeLibrary> {
+        let mut unloaded = Vec::new();
+        let mut i = 0;
+        while i < self.libs.len() {
+            match &self.libs[i].0 {
+                Some(owner) if owner.strong_count() == 0 => {
+                    let (_, name, lib) = self.libs.remove(i);
+                    debug!("unloading native library {:?}", name);
+                    unloaded.push(lib);
+                }
+                _ => i += 1,
+            }
+        }
+
+        unloaded
+    }
+
     pub fn resolve_symbol(&self, name: &CStr) -> Option<*const ()> {
         self.libs.iter().find_map(|(_, _, lib)| unsafe {
             lib.0
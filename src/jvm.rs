@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::iter::once;
 use std::sync::Arc;
 
@@ -5,24 +6,30 @@ use clap::{App, AppSettings, Arg};
 use log::*;
 use thiserror::*;
 
-use cafebabe::mutf8::StrExt;
+use cafebabe::mutf8::{mstr, StrExt};
 use cafebabe::MethodAccessFlags;
 
+use crate::alloc::{InternedString, VmRef};
 use crate::bootstrap;
 use crate::class::null;
-use crate::class::{ClassLoader, WhichLoader};
-use crate::classpath::ClassPath;
-use crate::error::ResultExt;
-use crate::interpreter::{Frame, InstructionLookupTable, NativeThunks};
+use crate::class::{ClassLoader, Object, WhichLoader};
+#[cfg(feature = "miri")]
+use crate::classpath::EMBEDDED_ENTRY;
+use crate::classpath::{ClassPath, ClasspathInstall};
+use crate::debug::{ClassEventLog, ClassLogConfig};
+use crate::error::{ResultExt, Throwables, VmResult};
+use crate::interpreter::{Frame, NativeThunks};
 use crate::jit::{JitClient, JitThread};
 use crate::jni::NativeLibraries;
 use crate::properties::SystemProperties;
+use crate::share::{self, ClassSharing, ShareMode};
 use crate::thread::JvmThreadState;
 use crate::types::DataValue;
 use crate::{thread, JvmError, JvmResult};
 use parking_lot::Mutex;
 
 use std::ops::DerefMut;
+use std::path::PathBuf;
 
 pub struct Jvm {
     args: JvmArgsPersist,
@@ -33,17 +40,21 @@ pub struct Jvm {
 /// Each thread shares a reference through an Arc
 pub struct JvmGlobalState {
     classloader: ClassLoader,
-    insn_lookup: InstructionLookupTable,
     jit: JitClient,
     properties: SystemProperties,
     native_libraries: Mutex<NativeLibraries>,
     native_thunks: Mutex<NativeThunks>,
+    /// java/lang/String instances for string literals and String.intern
+    interned_strings: Mutex<HashMap<InternedString, VmRef<Object>>>,
 }
 
 #[derive(Default, Debug)]
 struct JvmArgsPersist {
+    /// Empty when dumping the shared archive
     main: String,
     no_system_classloader: bool,
+    share: ShareMode,
+    shared_archive: PathBuf,
 }
 
 #[derive(Default, Debug)]
@@ -52,6 +63,7 @@ pub struct JvmArgs {
 
     bootclasspath: Arc<ClassPath>,
     userclasspath: Arc<ClassPath>,
+    class_log: Option<ClassLogConfig>,
 
     args: JvmArgsPersist,
 }
@@ -64,24 +76,39 @@ pub enum ArgError {
     #[error("Missing main class")]
     MissingMain,
 
-    #[error("Missing boot classpath")]
+    #[error("Missing boot classpath and no GNU Classpath install found")]
     MissingBoot,
+
+    #[error("Invalid -Xshare mode: {0}")]
+    Share(String),
+
+    #[error("Invalid -Xlog:class option: {0}")]
+    ClassLog(String),
 }
 
 impl Jvm {
     // TODO "catch" any exception during init, and log it properly with stacktrace etc
     pub fn new(args: JvmArgs) -> JvmResult<Self> {
-        let classloader = ClassLoader::new(args.bootclasspath.clone());
+        let sharing = ClassSharing::new(
+            args.args.share,
+            &args.args.shared_archive,
+            &args.bootclasspath,
+        )?;
+        let class_log = match &args.class_log {
+            Some(config) => Some(ClassEventLog::open(config).map_err(JvmError::ClassLog)?),
+            None => None,
+        };
+        let classloader = ClassLoader::new(args.bootclasspath.clone(), sharing, class_log);
         let (jit, jit_client) = JitThread::start();
 
         // create global JVM state
         let global = Arc::new(JvmGlobalState {
             classloader,
-            insn_lookup: InstructionLookupTable::new(),
             jit: jit_client,
             properties: args.properties,
             native_libraries: Mutex::new(NativeLibraries::default()),
             native_thunks: Mutex::new(NativeThunks::default()),
+            interned_strings: Mutex::new(HashMap::new()),
         });
 
         let jvm = Jvm {
@@ -109,6 +136,14 @@ impl Jvm {
     }
 
     pub fn run_main(&mut self) -> JvmResult<()> {
+        let result = self.execute_main();
+
+        // the global state is never dropped
+        thread::get().global().class_loader().finish_class_log();
+        result
+    }
+
+    fn execute_main(&mut self) -> JvmResult<()> {
         let thread = thread::get();
         let class_loader = thread.global().class_loader();
 
@@ -120,6 +155,18 @@ impl Jvm {
             WhichLoader::User(system_loader)
         };
 
+        if self.args.share == ShareMode::Dump {
+            // everything loaded during startup goes in the archive
+            let path = &self.args.shared_archive;
+            let count = class_loader.dump_shared_archive(path)?;
+            info!(
+                "dumped {} classes to shared archive {}",
+                count,
+                path.display()
+            );
+            return Ok(());
+        }
+
         // load main class
         let main_class = class_loader
             .load_class(&self.args.main.to_mstr(), loader)
@@ -169,6 +216,24 @@ impl JvmArgs {
                     .takes_value(true),
             )
             .arg(Arg::with_name("nosystemclassloader").long("XXnosystemclassloader"))
+            .arg(
+                Arg::with_name("share")
+                    .long("Xshare")
+                    .takes_value(true)
+                    .possible_values(["off", "auto", "on", "dump"]),
+            )
+            .arg(Arg::with_name("verboseclass").long("verbose:class"))
+            .arg(
+                Arg::with_name("logclass")
+                    .long("Xlog:class")
+                    .takes_value(true)
+                    .value_name("text|json|dot[:file]"),
+            )
+            .arg(
+                Arg::with_name("sharedarchive")
+                    .long("XXsharedarchivefile")
+                    .takes_value(true),
+            )
             // TODO generic -D arg collection
             .arg(
                 Arg::with_name("librarypath")
@@ -179,14 +244,55 @@ impl JvmArgs {
 
         let mut jvm_args = Self::default();
 
-        jvm_args.args.main = matches
-            .value_of("class")
-            .ok_or(ArgError::MissingMain)?
-            .to_owned();
+        jvm_args.args.share = match matches.value_of("share") {
+            Some(mode) => mode.parse().map_err(ArgError::Share)?,
+            None => ShareMode::default(),
+        };
+        jvm_args.args.shared_archive = matches
+            .value_of("sharedarchive")
+            .map(PathBuf::from)
+            .unwrap_or_else(share::default_archive_path);
+
+        jvm_args.class_log = match matches.value_of("logclass") {
+            Some(config) => Some(config.parse().map_err(ArgError::ClassLog)?),
+            None if matches.is_present("verboseclass") => Some(ClassLogConfig::default()),
+            None => None,
+        };
+
+        jvm_args.args.main = match matches.value_of("class") {
+            Some(main) => main.to_owned(),
+            None if jvm_args.args.share == ShareMode::Dump => String::new(),
+            None => return Err(ArgError::MissingMain),
+        };
         jvm_args.args.no_system_classloader = matches.is_present("nosystemclassloader");
 
-        let bootclasspath =
-            ClassPath::from_colon_separated(matches.value_of("bootcp").unwrap_or(""));
+        // fall back to the embedded class library or a local install
+        let (bootclasspath, natives) = match matches.value_of("bootcp") {
+            // natives of an explicit boot classpath are expected in the current directory
+            Some(bootcp) => (
+                ClassPath::from_colon_separated(bootcp),
+                Some(PathBuf::from(".")),
+            ),
+            None => {
+                let install = ClasspathInstall::discover().ok_or(ArgError::MissingBoot)?;
+                (ClassPath::new(vec![install.classes]), install.natives)
+            }
+        };
+
+        let librarypath = match (matches.value_of("librarypath"), natives) {
+            (Some(path), _) => ClassPath::from_colon_separated(path),
+            (None, Some(natives)) => ClassPath::new(vec![natives]),
+            (None, None) => {
+                // loading the libraries fails, so only their native methods are unavailable
+                warn!(
+                    "no GNU Classpath JNI libraries (lib/classpath) found for boot classpath {}, \
+                    classes that need them will throw UnsatisfiedLinkError. Install them next to \
+                    the executable or pass --XXlibrarypath",
+                    bootclasspath.to_string()
+                );
+                ClassPath::default()
+            }
+        };
         let classpath = ClassPath::from_colon_separated(matches.value_of("cp").unwrap_or(""));
 
         // setup properties
@@ -194,10 +300,9 @@ impl JvmArgs {
         jvm_args
             .properties
             .set_path("sun.boot.class.path", &bootclasspath);
-        jvm_args.properties.set_path(
-            "java.library.path",
-            &ClassPath::from_colon_separated(matches.value_of("librarypath").unwrap_or(".")),
-        );
+        jvm_args
+            .properties
+            .set_path("java.library.path", &librarypath);
 
         jvm_args.bootclasspath = Arc::new(bootclasspath);
         jvm_args.userclasspath = Arc::new(classpath);
@@ -217,11 +322,14 @@ impl JvmArgs {
                 "",
                 "lib/classpath",
             ),
-            bootclasspath: Arc::new(ClassPath::from_colon_separated("share/classpath")),
+            bootclasspath: Arc::new(ClassPath::from_colon_separated(EMBEDDED_ENTRY)),
             userclasspath: Arc::new(ClassPath::default()),
+            class_log: None,
             args: JvmArgsPersist {
                 main: "Nop".to_string(),
                 no_system_classloader: false,
+                share: ShareMode::Off,
+                shared_archive: PathBuf::new(),
             },
         }
     }
@@ -232,10 +340,6 @@ impl JvmGlobalState {
         &self.classloader
     }
 
-    pub(crate) fn insn_lookup(&self) -> &InstructionLookupTable {
-        &self.insn_lookup
-    }
-
     pub(crate) fn jit(&self) -> &JitClient {
         &self.jit
     }
@@ -251,4 +355,28 @@ impl JvmGlobalState {
     pub(crate) fn native_thunks_mut(&self) -> impl DerefMut<Target = NativeThunks> + '_ {
         self.native_thunks.lock()
     }
+
+    /// The single java/lang/String instance with the given contents, instantiated on first use
+    pub(crate) fn intern_string(&self, contents: &mstr) -> VmResult<VmRef<Object>> {
+        let key = InternedString::intern(contents);
+        if let Some(string) = self.interned_strings.lock().get(&key) {
+            return Ok(string.clone());
+        }
+
+        // instantiate outside of the lock, another thread might get there first
+        let string = Object::new_string(contents)?;
+        let mut strings = self.interned_strings.lock();
+        Ok(strings.entry(key).or_insert(string).clone())
+    }
+
+    /// The interned instance equal to the given java/lang/String, which becomes the interned
+    /// instance itself if there isn't one yet (String.intern)
+    pub(crate) fn intern_string_object(&self, string: VmRef<Object>) -> VmResult<VmRef<Object>> {
+        let contents = string
+            .string_value_utf8()
+            .ok_or(Throwables::NullPointerException)?;
+        let key = InternedString::intern(&contents.to_mstr());
+        let mut strings = self.interned_strings.lock();
+        Ok(strings.entry(key).or_insert(string).clone())
+    }
 }
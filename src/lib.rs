@@ -16,9 +16,11 @@ mod interpreter;
 mod jit;
 mod jni;
 mod jvm;
+mod manifest;
 mod monitor;
 mod natives;
 mod properties;
+mod share;
 mod storage;
 mod thread;
 mod types;
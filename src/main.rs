@@ -8,7 +8,14 @@ use log::*;
 fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init_from_env(Env::new().filter_or("JVM_LOG", "DEBUG"));
 
-    let args = JvmArgs::parse(std::env::args().skip(1))?;
+    let args = match JvmArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            // readable message rather than the debug repr returned from main
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
     debug!("args: {:#?}", args);
 
     let mut jvm = Jvm::new(args)?;
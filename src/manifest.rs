@@ -0,0 +1,172 @@
+//! Jar manifest parsing, for package versioning and sealing info
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use log::*;
+
+use crate::classpath::{self, FindClassError};
+
+pub const MANIFEST_PATH: &str = "META-INF/MANIFEST.MF";
+
+/// Attribute names are case insensitive, so are stored lowercase
+#[derive(Debug, Default)]
+pub struct Attributes(HashMap<String, String>);
+
+#[derive(Debug, Default)]
+pub struct Manifest {
+    main: Attributes,
+    /// Per-entry sections by Name, e.g. "java/lang/"
+    entries: HashMap<String, Attributes>,
+}
+
+/// Versioning and sealing info for a package
+#[derive(Debug, Default, Clone)]
+pub struct PackageInfo {
+    pub spec_title: Option<String>,
+    pub spec_version: Option<String>,
+    pub spec_vendor: Option<String>,
+    pub impl_title: Option<String>,
+    pub impl_version: Option<String>,
+    pub impl_vendor: Option<String>,
+    pub sealed: bool,
+}
+
+impl Manifest {
+    /// Manifest of a directory, jar or the embedded classpath entry. None if the manifest doesn't
+    /// exist or can't be read
+    pub fn load_from_entry(entry: &Path) -> Option<Self> {
+        match classpath::read_entry_file(entry, MANIFEST_PATH)? {
+            Ok(bytes) => {
+                trace!("loaded manifest from {}", entry.display());
+                Some(Self::parse(&String::from_utf8_lossy(&bytes)))
+            }
+            Err(FindClassError::NotFound) => None,
+            Err(FindClassError::Io(err)) => {
+                warn!("failed to read manifest from {}: {}", entry.display(), err);
+                None
+            }
+        }
+    }
+
+    /// Lenient, malformed lines are skipped
+    pub fn parse(contents: &str) -> Self {
+        let mut manifest = Manifest::default();
+
+        // join continuation lines first
+        let mut lines: Vec<String> = Vec::new();
+        for line in contents.lines() {
+            match (line.strip_prefix(' '), lines.last_mut()) {
+                (Some(continued), Some(last)) if !last.is_empty() => last.push_str(continued),
+                _ => lines.push(line.to_owned()),
+            }
+        }
+
+        let mut section = Attributes::default();
+        let mut is_main = true;
+        for line in lines.iter().map(String::as_str).chain(std::iter::once("")) {
+            if line.is_empty() {
+                // end of section
+                let attrs = std::mem::take(&mut section);
+                if is_main {
+                    manifest.main = attrs;
+                    is_main = false;
+                } else if let Some(name) = attrs.get("Name") {
+                    manifest.entries.insert(name.to_owned(), attrs);
+                }
+                continue;
+            }
+
+            match line.split_once(':') {
+                Some((key, value)) => {
+                    section
+                        .0
+                        .insert(key.trim().to_ascii_lowercase(), value.trim().to_owned());
+                }
+                None => debug!("skipping malformed manifest line {:?}", line),
+            }
+        }
+
+        manifest
+    }
+
+    pub fn main_attributes(&self) -> &Attributes {
+        &self.main
+    }
+
+    /// Package name is slash separated e.g. java/lang. Per-package attributes take precedence over
+    /// the main section
+    pub fn package_attribute(&self, package: &str, key: &str) -> Option<&str> {
+        self.entries
+            .get(&format!("{}/", package))
+            .and_then(|attrs| attrs.get(key))
+            .or_else(|| self.main.get(key))
+    }
+
+    pub fn package_info(&self, package: &str) -> PackageInfo {
+        let attr = |key| self.package_attribute(package, key).map(str::to_owned);
+        PackageInfo {
+            spec_title: attr("Specification-Title"),
+            spec_version: attr("Specification-Version"),
+            spec_vendor: attr("Specification-Vendor"),
+            impl_title: attr("Implementation-Title"),
+            impl_version: attr("Implementation-Version"),
+            impl_vendor: attr("Implementation-Vendor"),
+            sealed: self
+                .package_attribute(package, "Sealed")
+                .map(|s| s.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl Attributes {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(&key.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "Manifest-Version: 1.0\r
+Implementation-Title: Cool Library\r
+Implementation-Vendor: Cool People Inc. and a very long vendor name that wraps o\r
+ nto the next line\r
+Sealed: true\r
+\r
+Name: com/cool/unsealed/\r
+Sealed: false\r
+Specification-Version: 1.2\r
+\r
+";
+
+    #[test]
+    fn manifest_main_attributes() {
+        let manifest = Manifest::parse(MANIFEST);
+        let main = manifest.main_attributes();
+
+        assert_eq!(main.get("manifest-version"), Some("1.0"));
+        assert_eq!(main.get("Implementation-Title"), Some("Cool Library"));
+        assert_eq!(
+            main.get("Implementation-Vendor"),
+            Some("Cool People Inc. and a very long vendor name that wraps onto the next line")
+        );
+    }
+
+    #[test]
+    fn manifest_package_sections() {
+        let manifest = Manifest::parse(MANIFEST);
+
+        let sealed = manifest.package_info("com/cool/sealed");
+        assert!(sealed.sealed);
+        assert_eq!(sealed.spec_version, None);
+        assert_eq!(sealed.impl_title.as_deref(), Some("Cool Library"));
+
+        let unsealed = manifest.package_info("com/cool/unsealed");
+        assert!(!unsealed.sealed);
+        assert_eq!(unsealed.spec_version.as_deref(), Some("1.2"));
+        assert_eq!(unsealed.impl_title.as_deref(), Some("Cool Library"));
+    }
+}
@@ -1,46 +1,251 @@
-use parking_lot::{Condvar, Mutex, MutexGuard};
+use crate::error::Throwables;
+use log::*;
+use parking_lot::{Condvar, Mutex};
+use std::thread::ThreadId;
+use std::time::Duration;
 
+/// Reentrant monitor owned by at most one thread at a time, as every Java object has
 pub struct Monitor {
-    mutex: Mutex<()>,
-    cvar: Condvar,
+    state: Mutex<MonitorState>,
+    /// Signalled when the owner fully exits
+    released: Condvar,
+    /// Signalled by notify/notifyAll
+    notified: Condvar,
 }
 
-pub struct MonitorGuard<'a> {
-    guard: MutexGuard<'a, ()>,
-    cvar: &'a Condvar,
+#[derive(Default)]
+struct MonitorState {
+    owner: Option<ThreadId>,
+    /// Number of times the owner has entered
+    count: usize,
 }
 
+/// The current thread does not own the monitor
+#[derive(Debug)]
+pub struct NotOwner;
+
+/// Exits the monitor when dropped
+pub struct MonitorGuard<'a>(&'a Monitor);
+
 impl Monitor {
     pub fn new() -> Self {
         Monitor {
-            mutex: Mutex::new(()),
-            cvar: Condvar::new(),
+            state: Mutex::new(MonitorState::default()),
+            released: Condvar::new(),
+            notified: Condvar::new(),
+        }
+    }
+
+    /// Blocks until no other thread owns the monitor, then enters it
+    pub fn lock(&self) {
+        let current = current_thread();
+        let mut state = self.state.lock();
+        if state.owner == Some(current) {
+            state.count += 1;
+            return;
+        }
+
+        while state.owner.is_some() {
+            self.released.wait(&mut state);
         }
+
+        state.owner = Some(current);
+        state.count = 1;
     }
 
+    /// Exits once, releasing the monitor if this was the outermost entry
+    pub fn unlock(&self) -> Result<(), NotOwner> {
+        let mut state = self.state.lock();
+        state.check_owner()?;
+
+        state.count -= 1;
+        if state.count == 0 {
+            state.owner = None;
+            self.released.notify_one();
+        }
+
+        Ok(())
+    }
+
+    /// Enters the monitor until the returned guard is dropped
     pub fn enter(&self) -> MonitorGuard {
-        let guard = self.mutex.lock();
-        MonitorGuard {
-            guard,
-            cvar: &self.cvar,
+        self.lock();
+        MonitorGuard(self)
+    }
+
+    /// Fully releases the monitor and blocks until notified or the timeout elapses, then
+    /// re-enters it as many times as it was held. May wake spuriously, as Java allows
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<(), NotOwner> {
+        let current = current_thread();
+        let mut state = self.state.lock();
+        state.check_owner()?;
+
+        let count = std::mem::take(&mut state.count);
+        state.owner = None;
+        self.released.notify_one();
+
+        match timeout {
+            Some(timeout) => {
+                let _ = self.notified.wait_for(&mut state, timeout);
+            }
+            None => self.notified.wait(&mut state),
+        }
+
+        while state.owner.is_some() {
+            self.released.wait(&mut state);
         }
+
+        state.owner = Some(current);
+        state.count = count;
+        Ok(())
+    }
+
+    pub fn notify_one(&self) -> Result<(), NotOwner> {
+        self.state.lock().check_owner()?;
+        self.notified.notify_one();
+        Ok(())
     }
 
+    pub fn notify_all(&self) -> Result<(), NotOwner> {
+        self.state.lock().check_owner()?;
+        self.notified.notify_all();
+        Ok(())
+    }
+
+    /// Owned by any thread
     pub fn is_locked(&self) -> bool {
-        self.mutex.is_locked()
+        self.state.lock().owner.is_some()
+    }
+
+    /// Owned by the current thread
+    pub fn is_held(&self) -> bool {
+        self.state.lock().owner == Some(current_thread())
+    }
+}
+
+impl MonitorState {
+    fn check_owner(&self) -> Result<(), NotOwner> {
+        if self.owner == Some(current_thread()) {
+            Ok(())
+        } else {
+            Err(NotOwner)
+        }
     }
 }
 
 impl<'a> MonitorGuard<'a> {
     pub fn wait(&mut self) {
-        self.cvar.wait(&mut self.guard)
+        self.0.wait(None).expect("guard owns the monitor")
     }
 
-    pub fn notify_one(&self) {
-        self.cvar.notify_one();
+    pub fn notify_all(&self) {
+        self.0.notify_all().expect("guard owns the monitor")
     }
+}
 
-    pub fn notify_all(&self) {
-        self.cvar.notify_all();
+impl Drop for MonitorGuard<'_> {
+    fn drop(&mut self) {
+        if self.0.unlock().is_err() {
+            error!("monitor guard dropped by a thread that does not own it");
+        }
+    }
+}
+
+impl From<NotOwner> for Throwables {
+    fn from(_: NotOwner) -> Self {
+        Throwables::Other("java/lang/IllegalMonitorStateException")
+    }
+}
+
+fn current_thread() -> ThreadId {
+    std::thread::current().id()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monitor::Monitor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn reentrant() {
+        let monitor = Monitor::new();
+        assert!(monitor.unlock().is_err());
+
+        monitor.lock();
+        monitor.lock();
+        assert!(monitor.is_held());
+
+        monitor.unlock().unwrap();
+        assert!(monitor.is_held());
+        monitor.unlock().unwrap();
+        assert!(!monitor.is_locked());
+        assert!(monitor.unlock().is_err());
+    }
+
+    #[test]
+    fn not_owner() {
+        let monitor = Arc::new(Monitor::new());
+        monitor.lock();
+
+        let other = monitor.clone();
+        std::thread::spawn(move || {
+            assert!(other.is_locked());
+            assert!(!other.is_held());
+            assert!(other.unlock().is_err());
+            assert!(other.notify_all().is_err());
+            assert!(other.wait(None).is_err());
+        })
+        .join()
+        .unwrap();
+
+        monitor.unlock().unwrap();
+        assert!(monitor.notify_one().is_err());
+    }
+
+    #[test]
+    fn wait_notify() {
+        let monitor = Arc::new(Monitor::new());
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let (other, other_counter) = (monitor.clone(), counter.clone());
+        let thread = std::thread::spawn(move || {
+            let _guard = other.enter();
+            other.lock();
+            other_counter.store(1, Ordering::SeqCst);
+
+            while other_counter.load(Ordering::SeqCst) != 2 {
+                other.wait(None).unwrap();
+            }
+
+            // reentered as many times as before waiting
+            other.unlock().unwrap();
+            assert!(other.is_held());
+        });
+
+        // wait for the other thread to wait
+        loop {
+            let _guard = monitor.enter();
+            if counter.load(Ordering::SeqCst) == 1 {
+                counter.store(2, Ordering::SeqCst);
+                monitor.notify_all().unwrap();
+                break;
+            }
+        }
+
+        thread.join().unwrap();
+        assert!(!monitor.is_locked());
+    }
+
+    #[test]
+    fn timed_wait() {
+        let monitor = Monitor::new();
+        let _guard = monitor.enter();
+
+        let start = Instant::now();
+        monitor.wait(Some(Duration::from_millis(20))).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(monitor.is_held());
     }
 }
@@ -184,7 +184,7 @@ pub fn for_name(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable
         .load_class(&mstr::from_utf8(name.as_bytes()), loader)
         .map_err(|e| {
             error!("failed to load class: {:?}", e);
-            Throwables::ClassNotFoundException
+            Throwables::ClassNotFoundException(name.clone())
         })?;
 
     if initialize {
@@ -1,22 +1,97 @@
+use cafebabe::mutf8::StrExt;
+use std::iter::once;
+
 use crate::alloc::VmRef;
-use crate::class::FunctionArgs;
-use crate::error::Throwable;
+use crate::class::{null, FunctionArgs, Object, RuntimePackage, WhichLoader};
+use crate::error::{Throwable, Throwables, VmResult};
+use crate::exec_helper::ArrayType;
+use crate::interpreter::FrameInfo;
 use crate::thread;
 use crate::types::{DataValue, PrimitiveDataType};
 
 /// (Ljava/lang/ClassLoader;Ljava/lang/String;[BIILjava/security/ProtectionDomain;)Ljava/lang/Class;
-pub fn define_class(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmclassloader::define_class")
+pub fn define_class(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (loader, name, data, offset, len, _) = args.destructure::<(
+        VmRef<Object>,
+        VmRef<Object>,
+        VmRef<Object>,
+        i32,
+        i32,
+        VmRef<Object>,
+    )>()?;
+
+    let bytes = {
+        let array = data.array_unchecked();
+        let range = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(offset, len)| array.get(offset..offset.checked_add(len)?))
+            .ok_or(Throwables::Other(
+                "java/lang/ArrayIndexOutOfBoundsException",
+            ))?;
+
+        range
+            .iter()
+            .map(|val| match val {
+                DataValue::Byte(b) => *b as u8,
+                _ => unreachable!("byte array holds {:?}", val),
+            })
+            .collect::<Vec<u8>>()
+    };
+
+    // the name is optional, in which case it is taken from the class file
+    let name = match name.string_value_utf8() {
+        Some(name) => name.replace('.', "/"),
+        None => cafebabe::load_from_buffer(&bytes)
+            .and_then(|class_file| {
+                class_file
+                    .this_class()
+                    .map(|name| name.to_utf8().into_owned())
+            })
+            .map_err(|_| Throwables::ClassFormatError)?,
+    };
+
+    // the caller of ClassLoader.defineClass, past its overloads and those of subclasses
+    let thread = thread::get();
+    let mut caller = None;
+    thread.interpreter().with_frames(|frame| {
+        if let (None, FrameInfo::Method(cls, method)) = (&caller, frame.class_and_method()) {
+            if method.name().as_bytes() != b"defineClass" {
+                caller = Some(cls.clone());
+            }
+        }
+    });
+
+    let class = thread.global().class_loader().define_class(
+        &name.to_mstr(),
+        &bytes,
+        WhichLoader::User(loader),
+        caller.as_ref().map(|cls| cls.name()),
+    )?;
+
+    Ok(Some(DataValue::Reference(class.class_object().clone())))
 }
 
 /// (Ljava/lang/Class;)V
 pub fn resolve_class(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmclassloader::resolve_class")
+    // classes are linked when defined and references are resolved lazily
+    Ok(None)
 }
 
 /// (Ljava/lang/String;Z)Ljava/lang/Class;
-pub fn load_class(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmclassloader::load_class")
+pub fn load_class(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (name, _) = args.destructure::<(String, bool)>()?;
+    let name = name.replace('.', "/");
+
+    // null if the bootstrap loader can't find it, the caller throws ClassNotFoundException
+    let class = thread::get()
+        .global()
+        .class_loader()
+        .load_class(&name.to_mstr(), WhichLoader::Bootstrap)
+        .map(|cls| cls.class_object().clone())
+        .unwrap_or_else(|_| null());
+
+    Ok(Some(DataValue::Reference(class)))
 }
 
 /// (C)Ljava/lang/Class;
@@ -30,6 +105,113 @@ pub fn get_primitive_class(args: FunctionArgs) -> Result<Option<DataValue>, VmRe
 }
 
 /// (Ljava/lang/ClassLoader;Ljava/lang/String;)Ljava/lang/Class;
-pub fn find_loaded_class(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmclassloader::find_loaded_class")
+pub fn find_loaded_class(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (loader, name) = args.destructure::<(VmRef<Object>, String)>()?;
+    let name = name.replace('.', "/");
+    let loader = if loader.is_null() {
+        WhichLoader::Bootstrap
+    } else {
+        WhichLoader::User(loader)
+    };
+
+    let class = thread::get()
+        .global()
+        .class_loader()
+        .find_loaded(&name.to_mstr(), &loader)
+        .map(|cls| cls.class_object().clone())
+        .unwrap_or_else(null);
+
+    Ok(Some(DataValue::Reference(class)))
+}
+
+/// (Ljava/lang/String;)Ljava/lang/Package;
+pub fn get_package(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (name,) = args.destructure::<(String,)>()?;
+    let name = name.replace('.', "/");
+
+    let state = thread::get();
+    let package = state
+        .global()
+        .class_loader()
+        .get_package(&name.to_mstr(), &WhichLoader::Bootstrap);
+
+    let obj = match package {
+        Some(package) => package.package_object(new_package_object)?,
+        None => null(),
+    };
+
+    Ok(Some(DataValue::Reference(obj)))
+}
+
+/// ()[Ljava/lang/Package;
+pub fn get_packages(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let state = thread::get();
+    let class_loader = state.global().class_loader();
+    let packages = class_loader.get_packages(&WhichLoader::Bootstrap);
+    let package_cls =
+        class_loader.load_class("java/lang/Package".as_mstr(), WhichLoader::Bootstrap)?;
+
+    let arr = state.exec_helper().collect_array(
+        ArrayType::Reference(package_cls),
+        packages.into_iter().map(|package| {
+            package
+                .package_object(new_package_object)
+                .map(DataValue::Reference)
+        }),
+    )?;
+
+    Ok(Some(DataValue::Reference(arr)))
+}
+
+/// java/net/URL of the classpath entry sealing the package, or null
+fn sealed_url(package: &RuntimePackage) -> VmResult<DataValue> {
+    let url = match package.seal_base() {
+        Some(url) => thread::get()
+            .exec_helper()
+            .instantiate_and_invoke_constructor(
+                "java/net/URL",
+                "(Ljava/lang/String;)V",
+                once(DataValue::Reference(Object::new_string_utf8(&url)?)),
+            )?,
+        None => null(),
+    };
+
+    Ok(DataValue::Reference(url))
+}
+
+/// Instantiates a java/lang/Package for a bootstrap package
+fn new_package_object(package: &RuntimePackage) -> VmResult<VmRef<Object>> {
+    let state = thread::get();
+    let package_cls = state
+        .global()
+        .class_loader()
+        .load_class("java/lang/Package".as_mstr(), WhichLoader::Bootstrap)?;
+
+    let string = |s: Option<&str>| -> VmResult<DataValue> {
+        let obj = match s {
+            Some(s) => Object::new_string_utf8(s)?,
+            None => null(),
+        };
+        Ok(DataValue::Reference(obj))
+    };
+
+    let info = package.info();
+    let name = package.name().to_utf8().replace('/', ".");
+    let args = [
+        string(Some(&name))?,
+        string(info.spec_title.as_deref())?,
+        string(info.spec_vendor.as_deref())?,
+        string(info.spec_version.as_deref())?,
+        string(info.impl_title.as_deref())?,
+        string(info.impl_vendor.as_deref())?,
+        string(info.impl_version.as_deref())?,
+        sealed_url(package)?,
+        DataValue::Reference(null()), // bootstrap loader
+    ];
+
+    state.exec_helper().instantiate_and_invoke_constructor(
+        package_cls,
+        "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/net/URL;Ljava/lang/ClassLoader;)V",
+        args.into_iter(),
+    )
 }
@@ -2,6 +2,7 @@ use crate::alloc::{vmref_alloc_object, VmRef};
 use crate::class::{FunctionArgs, Object};
 use crate::error::{Throwable, Throwables};
 use crate::types::DataValue;
+use std::time::Duration;
 
 /// (Ljava/lang/Object;)Ljava/lang/Class;
 pub fn get_class(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
@@ -23,16 +24,42 @@ pub fn clone(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>>
 }
 
 /// (Ljava/lang/Object;)V
-pub fn notify(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmobject::notify")
+pub fn notify(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (obj,) = args.destructure::<(VmRef<Object>,)>()?;
+    if obj.is_null() {
+        return Err(Throwables::NullPointerException.into());
+    }
+
+    obj.monitor().notify_one().map_err(Throwables::from)?;
+    Ok(None)
 }
 
 /// (Ljava/lang/Object;)V
-pub fn notify_all(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmobject::notify_all")
+pub fn notify_all(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (obj,) = args.destructure::<(VmRef<Object>,)>()?;
+    if obj.is_null() {
+        return Err(Throwables::NullPointerException.into());
+    }
+
+    obj.monitor().notify_all().map_err(Throwables::from)?;
+    Ok(None)
 }
 
 /// (Ljava/lang/Object;JI)V
-pub fn wait(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmobject::wait")
+pub fn wait(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (obj, millis, nanos) = args.destructure::<(VmRef<Object>, i64, i32)>()?;
+    if obj.is_null() {
+        return Err(Throwables::NullPointerException.into());
+    }
+
+    // arguments were validated by Object.wait, and 0 means forever
+    let timeout = if millis == 0 && nanos == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(millis as u64) + Duration::from_nanos(nanos as u64))
+    };
+
+    log::debug!("waiting on {:?} with timeout {:?}", obj, timeout);
+    obj.monitor().wait(timeout).map_err(Throwables::from)?;
+    Ok(None)
 }
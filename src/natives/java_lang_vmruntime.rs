@@ -28,7 +28,12 @@ pub fn max_memory(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>
 
 /// ()V
 pub fn gc(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmruntime::gc")
+    // no gc yet, but user classes can still be unloaded
+    thread::get()
+        .global()
+        .class_loader()
+        .unload_unreachable_classes();
+    Ok(None)
 }
 
 /// ()V
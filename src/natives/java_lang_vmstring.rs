@@ -0,0 +1,16 @@
+use crate::alloc::VmRef;
+use crate::class::{FunctionArgs, Object};
+use crate::error::{Throwable, Throwables};
+use crate::thread;
+use crate::types::DataValue;
+
+/// (Ljava/lang/String;)Ljava/lang/String;
+pub fn intern(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (string,) = args.destructure::<(VmRef<Object>,)>()?;
+    if string.is_null() {
+        return Err(Throwables::NullPointerException.into());
+    }
+
+    let interned = thread::get().global().intern_string_object(string)?;
+    Ok(Some(DataValue::Reference(interned)))
+}
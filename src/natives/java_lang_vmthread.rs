@@ -56,7 +56,7 @@ pub fn current_thread(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwa
             "thread".as_mstr(),
             &DataType::Reference(Cow::Borrowed("java/lang/Thread".as_mstr())),
         )
-        .ok_or(Throwables::NoSuchFieldError)?;
+        .ok_or_else(|| Throwables::NoSuchFieldError("thread".to_owned()))?;
 
     let thread = thread.into_reference().expect("thread is not reference");
 
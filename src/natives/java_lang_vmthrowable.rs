@@ -1,15 +1,97 @@
 use crate::alloc::VmRef;
-use crate::class::{null, FunctionArgs};
-use crate::error::Throwable;
-use crate::types::DataValue;
+use crate::class::{null, Class, FunctionArgs, Object, WhichLoader};
+use crate::error::{Throwable, Throwables, VmResult};
+use crate::exec_helper::{ArrayType, ExecHelperStandalone};
+use crate::interpreter::StackTraceFrame;
+use crate::thread;
+use crate::types::{DataType, DataValue};
+use cafebabe::mutf8::StrExt;
+use std::borrow::Cow;
+use std::iter::{empty, once};
 
 /// (Ljava/lang/Throwable;)Ljava/lang/VMThrowable;
-pub fn fill_in_stack_trace(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    // TODO implement fillInStackTrace
-    Ok(Some(DataValue::Reference(null())))
+pub fn fill_in_stack_trace(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (throwable,) = args.destructure::<(VmRef<Object>,)>()?;
+    let throwable_class = throwable.class().ok_or(Throwables::NullPointerException)?;
+
+    let thread = thread::get();
+    let trace = thread.interpreter().capture_stack_trace(&throwable_class);
+
+    let helper = thread.exec_helper();
+    let vmthrowable =
+        helper.instantiate_and_invoke_constructor("java/lang/VMThrowable", "()V", empty())?;
+    ExecHelperStandalone.set_instance_field(
+        &vmthrowable,
+        "vmdata",
+        DataValue::VmDataStackTrace(VmRef::new(trace)),
+    )?;
+
+    Ok(Some(DataValue::Reference(vmthrowable)))
 }
 
 /// (Ljava/lang/Throwable;)[Ljava/lang/StackTraceElement;
-pub fn get_stack_trace(_: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
-    todo!("native method java_lang_vmthrowable::get_stack_trace")
+pub fn get_stack_trace(args: FunctionArgs) -> Result<Option<DataValue>, VmRef<Throwable>> {
+    let (this, _) = args.destructure::<(VmRef<Object>, VmRef<Object>)>()?;
+
+    let vmdata = ExecHelperStandalone.get_instance_field(
+        &this,
+        "vmdata",
+        &DataType::Reference(Cow::Borrowed("java/lang/Object".as_mstr())),
+    )?;
+    let trace = match vmdata {
+        DataValue::VmDataStackTrace(trace) => trace,
+        DataValue::Reference(o) if o.is_null() => {
+            // not filled in
+            VmRef::new(Default::default())
+        }
+        val => unreachable!("vmdata is {:?}", val),
+    };
+
+    let thread = thread::get();
+    let element_class = thread.global().class_loader().load_class(
+        "java/lang/StackTraceElement".as_mstr(),
+        WhichLoader::Bootstrap,
+    )?;
+    let helper = thread.exec_helper();
+    let array = helper.collect_array(
+        ArrayType::Reference(element_class.clone()),
+        trace
+            .frames()
+            .iter()
+            .map(|frame| new_stack_trace_element(frame, &element_class)),
+    )?;
+
+    Ok(Some(DataValue::Reference(array)))
+}
+
+fn new_stack_trace_element(
+    frame: &StackTraceFrame,
+    element_class: &VmRef<Class>,
+) -> VmResult<DataValue> {
+    let method = frame.method();
+    let class = method.class();
+
+    let file_name = match class.source_file() {
+        Some(file) => Object::new_string(file)?,
+        None => null(),
+    };
+    let line_number = frame.line_number().map(i32::from).unwrap_or(-1);
+    let class_name = Object::new_string_utf8(&class.name().to_utf8().replace('/', "."))?;
+    let method_name = Object::new_string(method.name())?;
+
+    let args = once(DataValue::Reference(file_name))
+        .chain(once(DataValue::Int(line_number)))
+        .chain(once(DataValue::Reference(class_name)))
+        .chain(once(DataValue::Reference(method_name)))
+        .chain(once(DataValue::Boolean(frame.is_native())));
+
+    let thread = thread::get();
+    thread
+        .exec_helper()
+        .instantiate_and_invoke_constructor(
+            element_class.clone(),
+            "(Ljava/lang/String;ILjava/lang/String;Ljava/lang/String;Z)V",
+            args,
+        )
+        .map(DataValue::Reference)
 }
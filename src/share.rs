@@ -0,0 +1,550 @@
+//! Class data sharing. `-Xshare:dump` records the class files of every boot class loaded during
+//! startup and writes them to a single archive, along with the timestamps of the boot classpath
+//! entries they came from. `-Xshare:on` maps the archive at startup and serves boot classes from it
+//! instead of searching the boot classpath and reading and inflating class files, after validating
+//! that no boot classpath entry has changed since.
+//!
+//! Only class file bytes are archived, not parsed or linked metadata. Classes are still parsed and
+//! linked as usual on load, as linked classes refer to runtime state (interned strings, vtables,
+//! mirrors) that can't be archived. The saving is in finding, reading and inflating class files.
+//!
+//! A directory entry's timestamp only changes when files are added to or removed from its top
+//! level, so classes from directories also record the modification time and size of their own
+//! class file. A class whose file has changed since is loaded from the boot classpath instead.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use log::*;
+use parking_lot::Mutex;
+use thiserror::*;
+
+use crate::classpath::{self, ClassPath};
+
+const MAGIC: &[u8; 8] = b"JVMSHARE";
+const VERSION: u32 = 3;
+
+pub const DEFAULT_ARCHIVE_NAME: &str = "classes.jsa";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ShareMode {
+    #[default]
+    Off,
+    /// Use the archive if it's valid, otherwise silently fall back to the boot classpath
+    Auto,
+    /// Fail if the archive can't be used
+    On,
+    /// Record boot classes and write the archive instead of running main
+    Dump,
+}
+
+#[derive(Debug, Error)]
+pub enum ShareError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Not a shared archive")]
+    Magic,
+
+    #[error("Unsupported archive version {0}")]
+    Version(u32),
+
+    #[error("Archive is truncated or corrupt")]
+    Corrupt,
+
+    #[error("Boot classpath has changed since the archive was dumped")]
+    ClassPathMismatch,
+
+    #[error("{} has been modified since the archive was dumped", .0.display())]
+    Modified(PathBuf),
+
+    #[error("Class sharing is not in dump mode")]
+    NotDumping,
+}
+
+pub enum ClassSharing {
+    Disabled,
+    Mapped(SharedArchive),
+    Dumping(ArchiveRecorder),
+}
+
+/// Boot classes in a mapped archive that has been validated against the current boot classpath
+pub struct SharedArchive {
+    mapping: Mapping,
+    entries: Vec<PathBuf>,
+    classes: HashMap<String, ArchivedClass>,
+}
+
+struct ArchivedClass {
+    /// Index into boot classpath entries
+    entry: usize,
+    /// Range of the class file within the mapping
+    offset: usize,
+    len: usize,
+    /// Only for classes from directory entries, archives are checked as a whole
+    file: Option<FileStamp>,
+}
+
+/// Modification time and size of a class file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Duration,
+    size: u64,
+}
+
+/// Read-only memory mapping of a whole file
+struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+/// Boot classes loaded so far, in load order
+#[derive(Default)]
+pub struct ArchiveRecorder(Mutex<Vec<RecordedClass>>);
+
+struct RecordedClass {
+    name: String,
+    entry: PathBuf,
+    bytes: Box<[u8]>,
+}
+
+impl FromStr for ShareMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "off" => ShareMode::Off,
+            "auto" => ShareMode::Auto,
+            "on" => ShareMode::On,
+            "dump" => ShareMode::Dump,
+            _ => return Err(format!("invalid share mode {:?}", s)),
+        })
+    }
+}
+
+/// Per-user default location
+pub fn default_archive_path() -> PathBuf {
+    let mut path = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+    path.push("jvm");
+    path.push(DEFAULT_ARCHIVE_NAME);
+    path
+}
+
+impl ClassSharing {
+    pub fn new(
+        mode: ShareMode,
+        archive: &Path,
+        bootclasspath: &ClassPath,
+    ) -> Result<Self, ShareError> {
+        Ok(match mode {
+            ShareMode::Off => ClassSharing::Disabled,
+            ShareMode::Dump => ClassSharing::Dumping(ArchiveRecorder::default()),
+            ShareMode::Auto | ShareMode::On => match SharedArchive::open(archive, bootclasspath) {
+                Ok(shared) => {
+                    info!(
+                        "using shared archive {} with {} classes",
+                        archive.display(),
+                        shared.classes.len()
+                    );
+                    ClassSharing::Mapped(shared)
+                }
+                Err(err) if mode == ShareMode::Auto => {
+                    debug!("not using shared archive {}: {}", archive.display(), err);
+                    ClassSharing::Disabled
+                }
+                Err(err) => return Err(err),
+            },
+        })
+    }
+}
+
+impl SharedArchive {
+    pub fn open(path: &Path, bootclasspath: &ClassPath) -> Result<Self, ShareError> {
+        let mapping = Mapping::open(path)?;
+        let bytes = mapping.bytes();
+        let mut reader = Reader(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ShareError::Magic);
+        }
+
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(ShareError::Version(version));
+        }
+
+        // boot classpath must be identical
+        let entry_count = reader.u32()? as usize;
+        if entry_count != bootclasspath.entries().len() {
+            return Err(ShareError::ClassPathMismatch);
+        }
+
+        let mut entries = Vec::with_capacity(entry_count);
+        for expected in bootclasspath.entries() {
+            let entry = PathBuf::from(reader.string()?);
+            if &entry != expected {
+                return Err(ShareError::ClassPathMismatch);
+            }
+
+            // the only check needed for archives, directories are only checked at the top level
+            reader.check_timestamp(&entry)?;
+            entries.push(entry);
+        }
+
+        let class_count = reader.u32()? as usize;
+        let mut classes = HashMap::with_capacity(class_count);
+        for _ in 0..class_count {
+            let name = reader.string()?.to_owned();
+            let entry = reader.u32()? as usize;
+            if entry >= entries.len() {
+                return Err(ShareError::Corrupt);
+            }
+
+            let file = reader.file_stamp()?;
+            let len = reader.u32()? as usize;
+            let offset = bytes.len() - reader.0.len();
+            reader.take(len)?;
+            classes.insert(
+                name,
+                ArchivedClass {
+                    entry,
+                    offset,
+                    len,
+                    file,
+                },
+            );
+        }
+
+        Ok(SharedArchive {
+            mapping,
+            entries,
+            classes,
+        })
+    }
+
+    /// Class bytes and the boot classpath entry they were originally loaded from. None if the
+    /// class isn't archived or its class file has changed since
+    pub fn find(&self, class_name: &str) -> Option<(&[u8], &Path)> {
+        let cls = self.classes.get(class_name)?;
+        let entry = self.entries[cls.entry].as_path();
+        if let Some(recorded) = cls.file {
+            let path = ClassPath::class_file(entry, class_name);
+            if FileStamp::of(&path).ok() != Some(recorded) {
+                debug!(
+                    "{} has been modified since the archive was dumped",
+                    path.display()
+                );
+                return None;
+            }
+        }
+
+        let bytes = &self.mapping.bytes()[cls.offset..cls.offset + cls.len];
+        Some((bytes, entry))
+    }
+}
+
+impl Mapping {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            // can't map an empty file
+            return Ok(Mapping {
+                ptr: NonNull::dangling(),
+                len,
+            });
+        }
+
+        // safety: a fresh private read-only mapping, which stays valid after the file is closed
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(Mapping {
+            ptr: NonNull::new(ptr.cast()).expect("mmap returned null"),
+            len,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // safety: mapping is valid and readable for len bytes until dropped. The archive is
+        // replaced by renaming when dumped rather than written to in place
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // safety: mapped in Mapping::open with this length, and no borrows outlive self
+            unsafe {
+                libc::munmap(self.ptr.as_ptr().cast(), self.len);
+            }
+        }
+    }
+}
+
+// safety: the mapping is immutable
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl ArchiveRecorder {
+    pub fn record(&self, class_name: &str, entry: &Path, bytes: &[u8]) {
+        self.0.lock().push(RecordedClass {
+            name: class_name.to_owned(),
+            entry: entry.to_owned(),
+            bytes: bytes.into(),
+        });
+    }
+
+    /// Returns the number of classes written
+    pub fn write(&self, path: &Path, bootclasspath: &ClassPath) -> Result<usize, ShareError> {
+        let classes = self.0.lock();
+        let entries = bootclasspath.entries();
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        write_u32(&mut out, VERSION);
+
+        write_u32(&mut out, entries.len() as u32);
+        for entry in entries {
+            write_string(&mut out, &entry.to_string_lossy());
+            write_timestamp(&mut out, entry)?;
+        }
+
+        write_u32(&mut out, classes.len() as u32);
+        for cls in classes.iter() {
+            let entry = entries
+                .iter()
+                .position(|e| *e == cls.entry)
+                .expect("recorded class not from boot classpath");
+
+            write_string(&mut out, &cls.name);
+            write_u32(&mut out, entry as u32);
+            if cls.entry.is_dir() {
+                let stamp = FileStamp::of(&ClassPath::class_file(&cls.entry, &cls.name))?;
+                write_u32(&mut out, 1);
+                write_duration(&mut out, stamp.modified);
+                out.extend_from_slice(&stamp.size.to_le_bytes());
+            } else {
+                write_u32(&mut out, 0);
+            }
+            write_u32(&mut out, cls.bytes.len() as u32);
+            out.extend_from_slice(&cls.bytes);
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // write then rename so a concurrently starting vm never reads a partial archive
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, out)?;
+        std::fs::rename(&tmp, path)?;
+
+        Ok(classes.len())
+    }
+}
+
+fn modified(path: &Path) -> Result<Duration, ShareError> {
+    let mtime = classpath::entry_modified(path)?;
+    Ok(mtime.duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Result<Self, ShareError> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata.modified()?;
+        Ok(FileStamp {
+            modified: modified.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            size: metadata.len(),
+        })
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, val: u32) {
+    out.extend_from_slice(&val.to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    write_u32(out, s.len() as u32);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_timestamp(out: &mut Vec<u8>, path: &Path) -> Result<(), ShareError> {
+    write_duration(out, modified(path)?);
+    Ok(())
+}
+
+fn write_duration(out: &mut Vec<u8>, duration: Duration) {
+    out.extend_from_slice(&duration.as_secs().to_le_bytes());
+    write_u32(out, duration.subsec_nanos());
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ShareError> {
+        if self.0.len() < n {
+            return Err(ShareError::Corrupt);
+        }
+
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, ShareError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ShareError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<&'a str, ShareError> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| ShareError::Corrupt)
+    }
+
+    fn duration(&mut self) -> Result<Duration, ShareError> {
+        let secs = self.u64()?;
+        let nanos = self.u32()?;
+        Ok(Duration::new(secs, nanos))
+    }
+
+    fn file_stamp(&mut self) -> Result<Option<FileStamp>, ShareError> {
+        Ok(match self.u32()? {
+            0 => None,
+            1 => Some(FileStamp {
+                modified: self.duration()?,
+                size: self.u64()?,
+            }),
+            _ => return Err(ShareError::Corrupt),
+        })
+    }
+
+    /// Reads a timestamp and checks the file hasn't been modified since
+    fn check_timestamp(&mut self, path: &Path) -> Result<(), ShareError> {
+        let recorded = self.duration()?;
+
+        match modified(path) {
+            Ok(mtime) if mtime == recorded => Ok(()),
+            _ => Err(ShareError::Modified(path.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jvm-share-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("java/lang")).unwrap();
+        dir
+    }
+
+    fn dump(dir: &Path, bootclasspath: &ClassPath) -> PathBuf {
+        let class_file = ClassPath::class_file(dir, "java/lang/Object");
+        std::fs::write(&class_file, b"\xca\xfe\xba\xbe").unwrap();
+
+        let recorder = ArchiveRecorder::default();
+        recorder.record("java/lang/Object", dir, b"\xca\xfe\xba\xbe");
+
+        // outside of the classpath entry, otherwise its timestamp changes
+        let archive = dir.with_extension("jsa");
+        assert_eq!(recorder.write(&archive, bootclasspath).unwrap(), 1);
+        archive
+    }
+
+    #[test]
+    fn shared_archive_round_trip() {
+        let dir = temp_dir("round-trip");
+        let bootclasspath = ClassPath::new(vec![dir.clone()]);
+        let archive = dump(&dir, &bootclasspath);
+
+        let shared = SharedArchive::open(&archive, &bootclasspath).expect("open failed");
+        let (bytes, source) = shared.find("java/lang/Object").expect("class not archived");
+        assert_eq!(bytes, b"\xca\xfe\xba\xbe");
+        assert_eq!(source, dir.as_path());
+        assert!(shared.find("java/lang/String").is_none());
+
+        // different boot classpath
+        let other = ClassPath::new(vec![dir.clone(), dir.join("other")]);
+        assert!(matches!(
+            SharedArchive::open(&archive, &other),
+            Err(ShareError::ClassPathMismatch)
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(&archive);
+    }
+
+    #[test]
+    fn shared_archive_modified_entry() {
+        let dir = temp_dir("modified");
+        let bootclasspath = ClassPath::new(vec![dir.clone()]);
+        let archive = dump(&dir, &bootclasspath);
+
+        // a modified class is loaded from the classpath again, but the rest of the archive is
+        // still used
+        let class_file = ClassPath::class_file(&dir, "java/lang/Object");
+        File::options()
+            .write(true)
+            .open(&class_file)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let shared = SharedArchive::open(&archive, &bootclasspath).expect("open failed");
+        assert!(shared.find("java/lang/Object").is_none());
+
+        // as is one with a different size, even if its timestamp is the same
+        let archive = dump(&dir, &bootclasspath);
+        let modified = std::fs::metadata(&class_file).unwrap().modified().unwrap();
+        std::fs::write(&class_file, b"\xca\xfe\xba\xbe\x00").unwrap();
+        File::options()
+            .write(true)
+            .open(&class_file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let shared = SharedArchive::open(&archive, &bootclasspath).expect("open failed");
+        assert!(shared.find("java/lang/Object").is_none());
+
+        // adding or removing files changes the entry itself
+
+        File::open(&dir)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert!(matches!(
+            SharedArchive::open(&archive, &bootclasspath),
+            Err(ShareError::Modified(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(&archive);
+    }
+}
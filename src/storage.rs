@@ -8,6 +8,12 @@ use std::fmt::Debug;
 #[derive(Debug, Copy, Clone)]
 pub struct FieldId(u32);
 
+/// An instance field resolved in one class that can be accessed in instances of any subclass.
+/// Super class fields are laid out after those of the subclass, so the field is always the same
+/// distance from the end of the storage
+#[derive(Debug, Copy, Clone)]
+pub struct InstanceFieldSlot(u32);
+
 // pub struct FieldStructure {
 //     /// `self.start_indices[fieldid.class] = start offset of this class in storage vec`
 //     start_indices: Vec<u32>,
@@ -117,6 +123,12 @@ impl FieldStorageLayout {
     pub fn get_self_id(&self, field_index: usize) -> Option<FieldId> {
         self.get_id(0, field_index)
     }
+
+    /// Only valid for instance fields, which are all present
+    pub fn instance_slot(&self, id: FieldId) -> InstanceFieldSlot {
+        debug_assert!((id.0 as usize) < self.types.len(), "bad field {:?}", id);
+        InstanceFieldSlot(self.types.len() as u32 - id.0)
+    }
 }
 
 impl FieldStorage {
@@ -146,6 +158,31 @@ impl FieldStorage {
             .unwrap_or(false)
     }
 
+    pub fn try_get_slot(&self, slot: InstanceFieldSlot) -> Option<DataValue> {
+        let guard = self.0.lock();
+        let idx = guard.len().checked_sub(slot.0 as usize)?;
+        guard.get(idx).cloned()
+    }
+
+    pub fn try_set_slot(&self, slot: InstanceFieldSlot, value: DataValue) -> bool {
+        let mut guard = self.0.lock();
+        match guard
+            .len()
+            .checked_sub(slot.0 as usize)
+            .and_then(|idx| guard.get_mut(idx))
+        {
+            Some(val) => {
+                *val = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn for_each_value(&self, f: impl FnMut(&mut DataValue)) {
+        self.0.lock().iter_mut().for_each(f)
+    }
+
     pub fn ensure_get(&self, id: FieldId) -> DataValue {
         self.try_get(id)
             .unwrap_or_else(|| panic!("no such field {:?}", id))
@@ -174,6 +211,42 @@ impl FieldId {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::storage::{FieldDataType, FieldStorageLayoutBuilder};
+    use crate::types::{DataType, DataValue, PrimitiveDataType};
+
+    #[test]
+    fn instance_slot_in_subclass() {
+        let int = || FieldDataType::Present(DataType::Primitive(PrimitiveDataType::Int));
+
+        let mut builder = FieldStorageLayoutBuilder::empty();
+        builder.add_fields_from_class([int(), int()].into_iter());
+        let super_layout = builder.build();
+
+        // own fields first, then the super class
+        let mut builder = FieldStorageLayoutBuilder::empty();
+        builder.add_fields_from_class([int()].into_iter());
+        builder.add_fields_from_class([int(), int()].into_iter());
+        let sub_layout = builder.build();
+
+        let super_id = super_layout.get_self_id(1).unwrap();
+        let slot = super_layout.instance_slot(super_id);
+
+        let fields = sub_layout.new_storage();
+        assert!(fields.try_set_slot(slot, DataValue::Int(5)));
+        let sub_id = sub_layout.get_id(1, 1).unwrap();
+        assert!(matches!(fields.try_get(sub_id), Some(DataValue::Int(5))));
+
+        let fields = super_layout.new_storage();
+        assert!(matches!(fields.try_get_slot(slot), Some(DataValue::Int(0))));
+
+        // too small for the slot
+        let fields = FieldStorageLayoutBuilder::empty().build().new_storage();
+        assert!(fields.try_get_slot(slot).is_none());
+    }
+}
+
 // TODO test this once structure is settled
 /*#[cfg(test)]
 mod tests {
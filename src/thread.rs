@@ -23,7 +23,7 @@ use std::thread::ThreadId;
 pub struct JvmThreadState {
     jvm: Arc<JvmGlobalState>,
     thread_handle: ThreadId,
-    exception: RefCell<Option<VmRef<Throwable /* TODO vmobject */>>>,
+    exception: RefCell<Option<VmRef<Throwable>>>,
     interpreter: RefCell<Interpreter>,
     /// Return value of last call to interpreter.execute_until_return()
     return_value: RefCell<Option<DataValue>>, // TODO really needed?
@@ -230,6 +230,11 @@ impl JvmThreadState {
         self.exception.borrow().clone()
     }
 
+    /// Clears the pending exception, e.g. once it has been caught
+    pub fn take_exception(&self) -> Option<VmRef<Throwable>> {
+        self.exception.borrow_mut().take()
+    }
+
     pub fn thread(&self) -> ThreadId {
         self.thread_handle
     }
@@ -2,6 +2,7 @@ use crate::class::{null, Class};
 
 use crate::alloc::{vmref_eq, VmRef};
 use crate::class::Object;
+use crate::interpreter::{DirectMethodHandle, StackTrace};
 use cafebabe::mutf8::{mstr, StrExt};
 
 use crate::thread;
@@ -48,6 +49,12 @@ pub enum DataValue {
 
     /// java/lang/Class.vmdata
     VmDataClass(VmRef<Class>),
+
+    /// java/lang/VMThrowable.vmdata
+    VmDataStackTrace(VmRef<StackTrace>),
+
+    /// java/lang/invoke/MethodHandle.vmdata
+    VmDataMethodHandle(VmRef<DirectMethodHandle>),
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -164,6 +171,26 @@ impl<'a> DataType<'a> {
         matches!(self, DataType::Reference(_))
     }
 
+    /// As written in Java source, e.g. `int` or `java.lang.String[]`
+    pub fn java_name(&self) -> String {
+        match self {
+            DataType::Primitive(prim) => prim.name().to_owned(),
+            DataType::ReturnAddress => "returnAddress".to_owned(),
+            DataType::Reference(name) => {
+                let dims = name.as_bytes().iter().take_while(|b| **b == b'[').count();
+                if dims == 0 {
+                    return name.to_utf8().replace('/', ".");
+                }
+
+                let elem = mstr::from_mutf8(&name.as_bytes()[dims..]);
+                match DataType::from_descriptor(elem) {
+                    Some(elem) => elem.java_name() + &"[]".repeat(dims),
+                    None => name.to_utf8().replace('/', "."),
+                }
+            }
+        }
+    }
+
     pub fn to_owned(&self) -> DataType<'static> {
         match self {
             DataType::Reference(r) => {
@@ -208,6 +235,9 @@ impl DataValue {
             DataValue::VmDataClass(_) => {
                 DataType::Reference(Cow::Borrowed("java/lang/Class".as_mstr()))
             }
+            DataValue::VmDataStackTrace(_) | DataValue::VmDataMethodHandle(_) => {
+                DataType::Reference(Cow::Borrowed("java/lang/Object".as_mstr()))
+            }
         })
     }
 
@@ -373,26 +403,36 @@ impl DataValue {
         })
     }
 
+    /// Floating point values narrow to types smaller than int via int (JLS 5.1.3), and `as`
+    /// already saturates and maps NaN to 0 as required
     pub fn narrow_primitive_to(&self, target: PrimitiveDataType) -> Option<DataValue> {
         Some(match (target, self) {
             (PrimitiveDataType::Byte, DataValue::Short(val)) => DataValue::from(*val as i8),
             (PrimitiveDataType::Byte, DataValue::Char(val)) => DataValue::from(*val as i8),
             (PrimitiveDataType::Byte, DataValue::Int(val)) => DataValue::from(*val as i8),
             (PrimitiveDataType::Byte, DataValue::Long(val)) => DataValue::from(*val as i8),
-            (PrimitiveDataType::Byte, DataValue::Float(val)) => DataValue::from(*val as i8),
-            (PrimitiveDataType::Byte, DataValue::Double(val)) => DataValue::from(*val as i8),
+            (PrimitiveDataType::Byte, DataValue::Float(val)) => DataValue::from(*val as i32 as i8),
+            (PrimitiveDataType::Byte, DataValue::Double(val)) => DataValue::from(*val as i32 as i8),
 
+            // widening and narrowing primitive conversion (JLS 5.1.4)
+            (PrimitiveDataType::Char, DataValue::Byte(val)) => DataValue::from(*val as u16),
             (PrimitiveDataType::Char, DataValue::Short(val)) => DataValue::from(*val as u16),
             (PrimitiveDataType::Char, DataValue::Int(val)) => DataValue::from(*val as u16),
             (PrimitiveDataType::Char, DataValue::Long(val)) => DataValue::from(*val as u16),
-            (PrimitiveDataType::Char, DataValue::Float(val)) => DataValue::from(*val as u16),
-            (PrimitiveDataType::Char, DataValue::Double(val)) => DataValue::from(*val as u16),
+            (PrimitiveDataType::Char, DataValue::Float(val)) => DataValue::from(*val as i32 as u16),
+            (PrimitiveDataType::Char, DataValue::Double(val)) => {
+                DataValue::from(*val as i32 as u16)
+            }
 
             (PrimitiveDataType::Short, DataValue::Char(val)) => DataValue::from(*val as i16),
             (PrimitiveDataType::Short, DataValue::Int(val)) => DataValue::from(*val as i16),
             (PrimitiveDataType::Short, DataValue::Long(val)) => DataValue::from(*val as i16),
-            (PrimitiveDataType::Short, DataValue::Float(val)) => DataValue::from(*val as i16),
-            (PrimitiveDataType::Short, DataValue::Double(val)) => DataValue::from(*val as i16),
+            (PrimitiveDataType::Short, DataValue::Float(val)) => {
+                DataValue::from(*val as i32 as i16)
+            }
+            (PrimitiveDataType::Short, DataValue::Double(val)) => {
+                DataValue::from(*val as i32 as i16)
+            }
 
             (PrimitiveDataType::Int, DataValue::Long(val)) => DataValue::from(*val as i32),
             (PrimitiveDataType::Int, DataValue::Float(val)) => DataValue::from(*val as i32),
@@ -479,6 +519,13 @@ impl PrimitiveDataType {
         }
     }
 
+    pub fn name(&self) -> &'static str {
+        Self::TYPES
+            .iter()
+            .find_map(|(prim, name)| (prim == self).then_some(*name))
+            .unwrap() // all types are present
+    }
+
     pub fn char(&self) -> char {
         match self {
             PrimitiveDataType::Boolean => 'Z',
@@ -834,6 +881,19 @@ mod tests {
         check_ref("[[Ljava/lang/Object;", "[[Ljava/lang/Object;");
     }
 
+    #[test]
+    fn java_name() {
+        let name = |desc: &'static str| {
+            DataType::from_descriptor(desc.as_mstr())
+                .unwrap()
+                .java_name()
+        };
+        assert_eq!(name("Z"), "boolean");
+        assert_eq!(name("Ljava/lang/String;"), "java.lang.String");
+        assert_eq!(name("[I"), "int[]");
+        assert_eq!(name("[[Ljava/util/List;"), "java.util.List[][]");
+    }
+
     #[test]
     fn array_type() {
         check_array("", None);
@@ -920,10 +980,43 @@ mod tests {
         );
 
         // narrow primitive
-        // TODO actually check values of converted primitives
-        assert!(short.assign_to(&byte.data_type()).is_some());
-        assert!(int.assign_to(&short.data_type()).is_some());
-        assert!(long.assign_to(&int.data_type()).is_some());
+        let narrow = |val: DataValue, prim| val.narrow_primitive_to(prim).unwrap();
+        assert_eq!(narrow(short, PrimitiveDataType::Byte), DataValue::Byte(32));
+        assert_eq!(
+            narrow(int, PrimitiveDataType::Short),
+            DataValue::Short(-31072)
+        );
+        assert_eq!(
+            narrow(long, PrimitiveDataType::Int),
+            DataValue::Int(1705032704)
+        );
+        assert_eq!(
+            narrow(DataValue::Byte(-1), PrimitiveDataType::Char),
+            DataValue::Char(0xffff)
+        );
+        assert_eq!(narrow(double, PrimitiveDataType::Long), DataValue::Long(4));
+        assert_eq!(
+            narrow(DataValue::Double(f64::NAN), PrimitiveDataType::Int),
+            DataValue::Int(0)
+        );
+        assert_eq!(
+            narrow(DataValue::Float(-1e20), PrimitiveDataType::Long),
+            DataValue::Long(i64::MIN)
+        );
+
+        // via int rather than saturating to the narrower type
+        assert_eq!(
+            narrow(DataValue::Float(300.7), PrimitiveDataType::Byte),
+            DataValue::Byte(44)
+        );
+        assert_eq!(
+            narrow(DataValue::Double(-70000.5), PrimitiveDataType::Char),
+            DataValue::Char(61072)
+        );
+        assert_eq!(
+            narrow(DataValue::Double(1e300), PrimitiveDataType::Short),
+            DataValue::Short(-1)
+        );
 
         // int to bool
         assert_eq!(
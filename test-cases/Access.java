@@ -0,0 +1,59 @@
+public class Access {
+    public static int open() {
+        return 1;
+    }
+
+    static int packagePrivate() {
+        return 2;
+    }
+
+    protected static int inherited() {
+        return 3;
+    }
+
+    private static int secret() {
+        return 4;
+    }
+
+    static int packageField = 5;
+
+    static class PackagePrivate {
+    }
+
+    /** Delegates straight to the bootstrap loader, classes are defined by the test */
+    public static class Loader extends ClassLoader {
+        public Loader() {
+            super(null);
+        }
+    }
+
+    /**
+     * Defined by the test with the bootstrap loader and again with a Loader, where it is in a
+     * different runtime package to Access
+     */
+    public static class Caller extends Access {
+        public static int callOpen() {
+            return Access.open();
+        }
+
+        public static int callPackagePrivate() {
+            return Access.packagePrivate();
+        }
+
+        public static int callInherited() {
+            return Access.inherited();
+        }
+
+        public static int callPrivate() {
+            return Access.secret();
+        }
+
+        public static int getPackageField() {
+            return Access.packageField;
+        }
+
+        public static Object newPackagePrivate() {
+            return new PackagePrivate();
+        }
+    }
+}
@@ -0,0 +1,142 @@
+public class Arithmetic {
+    long wide;
+
+    public static long LONG_OPS = longOps();
+    public static int LONG_CMP = longCmp();
+    public static double DOUBLE_OPS = doubleOps();
+    public static int DOUBLE_CMP = doubleCmp();
+    public static long CONVERSIONS = conversions();
+    public static int DIVISION = division();
+    public static int DIVIDE_BY_ZERO = divideByZero();
+    public static int BYTES = bytes();
+    public static long STACK = stack();
+
+    static long longOps() {
+        long a = 1234567890123L;
+        long b = -987654321L;
+        long r = a + b;
+        r = r * 3 - b;
+        r = r / 7;
+        r = r ^ (a % 1000);
+        r = r | 0x100;
+        r = -r;
+        return (r >> 3) + (r >>> 60);
+    }
+
+    static int longCmp() {
+        long a = 5;
+        long b = -3;
+        int x = 0;
+        if (a > b) {
+            x += 1;
+        }
+        if (a < b) {
+            x += 10;
+        }
+        if (a == b) {
+            x += 100;
+        }
+        return x;
+    }
+
+    static double doubleOps() {
+        double a = 3.5;
+        double b = -1.25;
+        double r = a + b;
+        r = r * a - b;
+        r = r / b;
+        r = r % a;
+        return -r;
+    }
+
+    static int doubleCmp() {
+        double a = 1.0;
+        double zero = 0.0;
+        double nan = zero / zero;
+        int x = 0;
+        if (a > zero) {
+            x += 1;
+        }
+        if (nan < a) {
+            x += 10;
+        }
+        if (nan > a) {
+            x += 100;
+        }
+        if (a <= a) {
+            x += 1000;
+        }
+        return x;
+    }
+
+    static long conversions() {
+        double d = 1e20;
+        float f = 2.75f;
+        f = -f;
+        long l = 123456789012L;
+        int i = 300;
+
+        long r = (long) d;
+        r ^= (int) d;
+        r += (long) f;
+        r += (long) ((double) f * 4);
+        r += (byte) i;
+        r += (short) (i * 1000);
+        r += (long) (float) l;
+        r += (long) ((double) l / 3);
+        r += (int) l;
+        r += (long) ((float) (d / 3) / 1e10f);
+        r += (long) ((double) i / 7 * 1000);
+        return r;
+    }
+
+    static int division() {
+        int min = Integer.MIN_VALUE;
+        int minusOne = -1;
+        int a = 17;
+        int b = -5;
+        return min / minusOne + min % minusOne + a / b * 100 + a % b;
+    }
+
+    static int divideByZero() {
+        int zero = 0;
+        long longZero = 0;
+        int count = 0;
+        try {
+            int r = 5 / zero;
+        } catch (ArithmeticException e) {
+            count++;
+        }
+        try {
+            long r = 5L / longZero;
+        } catch (ArithmeticException e) {
+            count++;
+        }
+        try {
+            long r = 5L % longZero;
+        } catch (ArithmeticException e) {
+            count++;
+        }
+        return count;
+    }
+
+    static int bytes() {
+        byte[] bytes = new byte[2];
+        bytes[0] = (byte) 200;
+        bytes[1] = 5;
+        boolean[] flags = new boolean[2];
+        flags[1] = true;
+        return bytes[0] + bytes[1] * 10 + (flags[1] ? 1000 : 0);
+    }
+
+    static long stack() {
+        int[] ints = new int[1];
+        long[] longs = new long[1];
+        Arithmetic obj = new Arithmetic();
+
+        int a = ints[0] = 7;
+        long b = longs[0] = 9L;
+        long c = obj.wide = 11L;
+        return a + b * 10 + c * 100 + ints[0] + longs[0];
+    }
+}
@@ -0,0 +1,17 @@
+public class ArrayTypes {
+    public static int[][][] MULTI = new int[3][4][];
+    public static int MULTI_LENGTHS = MULTI.length * 10 + MULTI[2].length;
+    public static int INNERMOST_NULL = MULTI[1][3] == null ? 1 : 0;
+
+    public static int CLONEABLE = ((Object) MULTI) instanceof Cloneable ? 1 : 0;
+    public static int COVARIANT = ((Object) new String[1][1]) instanceof Object[][] ? 1 : 0;
+    public static int PRIMITIVE_NOT_COVARIANT = ((Object) new int[1]) instanceof Object[] ? 0 : 1;
+
+    public static int CLONE_IS_SHALLOW = cloneIsShallow() ? 1 : 0;
+
+    private static boolean cloneIsShallow() {
+        Object[] original = {new Object(), new Object()};
+        Object[] clone = original.clone();
+        return clone != original && clone.length == 2 && clone[0] == original[0];
+    }
+}
@@ -0,0 +1,21 @@
+public class Benchmark {
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static int collatz(int limit) {
+        int steps = 0;
+        for (int i = 1; i < limit; i++) {
+            long x = i;
+            while (x != 1) {
+                x = (x & 1) == 0 ? x >> 1 : 3 * x + 1;
+                steps++;
+            }
+        }
+        return steps;
+    }
+
+    public static int run() {
+        return fib(25) + collatz(30000);
+    }
+}
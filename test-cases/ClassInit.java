@@ -0,0 +1,20 @@
+public class ClassInit {
+    int field;
+
+    static ClassInit nothing() {
+        return null;
+    }
+
+    public static class NullDeref {
+        public static int VALUE = nothing().field;
+    }
+
+    public static class ExtendsNullDeref extends NullDeref {
+        public static int OTHER = 5;
+    }
+
+    public static class Recursive {
+        public static int VALUE = 10;
+        public static int RECURSIVE = Recursive.VALUE + 1;
+    }
+}
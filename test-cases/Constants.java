@@ -0,0 +1,26 @@
+public class Constants {
+    public interface Iface {
+        int INT = 42;
+        String STRING = "hello";
+    }
+
+    public static final byte BYTE = -5;
+    public static final char CHAR = 'x';
+    public static final boolean BOOL = true;
+    public static final long LONG = 1234567890123L;
+    public static final double DOUBLE = 2.5;
+    public static final String STRING = "hello";
+
+    /** Interning a string built at runtime gives the constant's instance */
+    public static boolean RUNTIME_INTERNED;
+    /** A string that isn't interned yet becomes the interned instance */
+    public static boolean FIRST_INTERNED;
+
+    static {
+        String hello = new StringBuilder("hel").append("lo").toString();
+        RUNTIME_INTERNED = hello != STRING && hello.intern() == STRING;
+
+        String fresh = new StringBuilder("fre").append("sh").toString();
+        FIRST_INTERNED = fresh.intern() == fresh && "fresh" == fresh;
+    }
+}
@@ -0,0 +1,165 @@
+public class Dispatch {
+    static int OVERRIDE;
+    static int INHERITED;
+    static int SUPER_CALL;
+    static int INTERFACE;
+    static int DEFAULT;
+    static int SPECIFIC_DEFAULT;
+    static int CLASS_OVER_DEFAULT;
+    static int ABSTRACT_CLASS;
+    static int OBJECT_METHOD;
+
+    interface Shape {
+        int sides();
+
+        default int corners() {
+            return sides();
+        }
+    }
+
+    interface Round extends Shape {
+        default int corners() {
+            return 0;
+        }
+    }
+
+    static class Base {
+        int value() {
+            return 1;
+        }
+
+        int other() {
+            return 10;
+        }
+    }
+
+    static class Derived extends Base {
+        int value() {
+            return 2 + super.value();
+        }
+    }
+
+    static class Square implements Shape {
+        public int sides() {
+            return 4;
+        }
+    }
+
+    static class Circle implements Round, Shape {
+        public int sides() {
+            return 1;
+        }
+    }
+
+    static class Triangle implements Round {
+        public int sides() {
+            return 3;
+        }
+
+        public int corners() {
+            return 3;
+        }
+    }
+
+    static abstract class Partial implements Shape {
+    }
+
+    static class Complete extends Partial {
+        public int sides() {
+            return 5;
+        }
+    }
+
+    static class Key {
+        public int hashCode() {
+            return 99;
+        }
+    }
+
+    /** Delegates straight to the bootstrap loader, classes are defined by the test */
+    public static class Loader extends ClassLoader {
+        public Loader() {
+            super(null);
+        }
+    }
+
+    /** javac rejects a class inheriting both defaults, so the test generates Dispatch$Conflicted */
+    interface Left {
+        default int pick() {
+            return 1;
+        }
+    }
+
+    interface Right {
+        default int pick() {
+            return 2;
+        }
+    }
+
+    public static class Level1 {
+        int level() {
+            return 1;
+        }
+    }
+
+    public static class Level2 extends Level1 {
+        public int level() {
+            return 2;
+        }
+    }
+
+    /** Replaced by the test with level() package private, which javac rejects as weaker access */
+    public static class Level3 extends Level2 {
+        public int level() {
+            return 3;
+        }
+    }
+
+    /**
+     * Defined by the test with another loader, so it can't override Level3's package private
+     * level(), only Level2's public one and through that Level1's
+     */
+    public static class Level4 extends Level3 {
+        public int level() {
+            return 4;
+        }
+    }
+
+    static {
+        Base derived = new Derived();
+        OVERRIDE = new Base().value() * 100 + derived.value();
+        INHERITED = derived.other();
+        SUPER_CALL = new Derived().value();
+
+        Shape[] shapes = {new Square(), new Circle(), new Triangle(), new Complete()};
+        for (Shape shape : shapes) {
+            INTERFACE = INTERFACE * 10 + shape.sides();
+        }
+
+        DEFAULT = shapes[0].corners();
+        SPECIFIC_DEFAULT = shapes[1].corners() + new Circle().corners();
+        CLASS_OVER_DEFAULT = shapes[2].corners();
+        Partial partial = new Complete();
+        ABSTRACT_CLASS = partial.sides() * 10 + partial.corners();
+
+        Round round = new Circle();
+        Object key = new Key();
+        OBJECT_METHOD = round.hashCode() == System.identityHashCode(round) ? key.hashCode() : -1;
+    }
+
+    static int pick(Left left) {
+        return left.pick();
+    }
+
+    static int level(Level1 level) {
+        return level.level();
+    }
+
+    static int publicLevel(Level2 level) {
+        return level.level();
+    }
+
+    static int narrowedLevel(Level3 level) {
+        return level.level();
+    }
+}
@@ -0,0 +1,185 @@
+public class Exceptions {
+    int field;
+
+    public static int CAUGHT = caught();
+    public static int CAUGHT_BY_SUPERCLASS = caughtBySuperclass();
+    public static int FINALLY = withFinally();
+    public static int FROM_CALLEE = fromCallee();
+    public static int NULL_POINTER = nullPointer();
+    public static int THROW_NULL = throwNull();
+    public static int RETHROWN = rethrown();
+    public static int NESTED = nested();
+    public static String VM_MESSAGE = vmMessage();
+    public static int INIT_CAUSE = initCause();
+
+    static class Custom extends RuntimeException {
+        int value;
+
+        Custom(int value) {
+            this.value = value;
+        }
+    }
+
+    static class Other extends RuntimeException {
+    }
+
+    static class Missing extends RuntimeException {
+    }
+
+    /** Refuses to load Missing, classes are defined by the test */
+    public static class HidingLoader extends ClassLoader {
+        public HidingLoader() {
+            super(null);
+        }
+
+        @Override
+        protected Class<?> loadClass(String name, boolean resolve) throws ClassNotFoundException {
+            if (name.equals("Exceptions$Missing")) {
+                throw new ClassNotFoundException(name);
+            }
+            return super.loadClass(name, resolve);
+        }
+    }
+
+    /** Defined by a HidingLoader, so the catch type of the handler in throwing() can't be resolved */
+    public static class MissingCatchType {
+        public static int RESOLUTION_ERROR = resolutionError();
+
+        static int throwing() {
+            try {
+                throw new IllegalStateException();
+            } catch (Missing e) {
+                return 1;
+            }
+        }
+
+        static int resolutionError() {
+            try {
+                return throwing();
+            } catch (NoClassDefFoundError e) {
+                return 13;
+            } catch (IllegalStateException e) {
+                return 0;
+            }
+        }
+    }
+
+    static class FailingInit {
+        static int VALUE = nothing().field;
+    }
+
+    static int caught() {
+        try {
+            throw new Custom(5);
+        } catch (Custom e) {
+            return e.value;
+        }
+    }
+
+    static int caughtBySuperclass() {
+        try {
+            throw new Custom(6);
+        } catch (Other e) {
+            return 1;
+        } catch (RuntimeException e) {
+            return ((Custom) e).value;
+        }
+    }
+
+    static int withFinally() {
+        int x = 1;
+        try {
+            try {
+                throw new Other();
+            } finally {
+                x += 10;
+            }
+        } catch (Other e) {
+            x += 100;
+        }
+        return x;
+    }
+
+    static void thrower(int depth) {
+        if (depth == 0) {
+            throw new Custom(7);
+        }
+        thrower(depth - 1);
+    }
+
+    static int fromCallee() {
+        try {
+            thrower(3);
+            return 0;
+        } catch (Custom e) {
+            return e.value;
+        }
+    }
+
+    static Exceptions nothing() {
+        return null;
+    }
+
+    static int nullPointer() {
+        try {
+            return nothing().field;
+        } catch (NullPointerException e) {
+            return 8;
+        }
+    }
+
+    static int throwNull() {
+        try {
+            Custom c = null;
+            throw c;
+        } catch (NullPointerException e) {
+            return 9;
+        }
+    }
+
+    static int rethrown() {
+        try {
+            try {
+                throw new Custom(10);
+            } catch (Custom e) {
+                e.value += 1;
+                throw e;
+            }
+        } catch (Custom e) {
+            return e.value;
+        }
+    }
+
+    static int nested() {
+        int x = 0;
+        for (int i = 0; i < 3; i++) {
+            try {
+                if (i == 1) {
+                    throw new Other();
+                }
+                x += 1;
+            } catch (Other e) {
+                x += 10;
+            }
+        }
+        return x;
+    }
+
+    static String vmMessage() {
+        int zero = 0;
+        try {
+            zero = 5 % zero;
+            return null;
+        } catch (ArithmeticException e) {
+            return e.getMessage();
+        }
+    }
+
+    static int initCause() {
+        try {
+            return FailingInit.VALUE;
+        } catch (ExceptionInInitializerError e) {
+            return e.getCause() instanceof NullPointerException ? 1 : 0;
+        }
+    }
+}
@@ -0,0 +1,177 @@
+import java.io.Serializable;
+
+public class Lambdas {
+    interface IntOp {
+        int apply(int a, int b);
+    }
+
+    interface Func<A, B> {
+        B apply(A a);
+    }
+
+    interface Widening {
+        long apply(int x);
+    }
+
+    interface Getter<T> {
+        T get();
+    }
+
+    interface StringGetter extends Getter<String> {
+        String get();
+    }
+
+    interface Wide {
+        double apply(long a, double b);
+    }
+
+    int counter;
+
+    public static int ADD = add();
+    public static int CAPTURE = capture();
+    public static int CAPTURE_THIS = captureThis();
+    public static int STATIC_REF = staticRef();
+    public static int UNBOUND_REF = unboundRef();
+    public static String BOUND_REF = boundRef();
+    public static String CONSTRUCTOR_REF = constructorRef();
+    public static long WIDENING = widening();
+    public static long UNBOXING = unboxing();
+    public static int VOID_RETURN = voidReturn();
+    public static int SERIALIZABLE = serializable();
+    public static String BRIDGE = bridge();
+    public static double WIDE_CAPTURE = wideCapture();
+    public static int CALL_SITE_REUSE = callSiteReuse();
+    public static String CONCAT = concat();
+    public static String CONCAT_CONSTANTS = concatConstants();
+    public static String CONCAT_LOOP = concatLoop();
+
+    static int add() {
+        IntOp add = (a, b) -> a + b;
+        return add.apply(3, 4);
+    }
+
+    static int capture() {
+        int k = 10;
+        IntOp op = (a, b) -> a * b + k;
+        return op.apply(3, 4);
+    }
+
+    int increment() {
+        counter += 1;
+        return counter;
+    }
+
+    static int captureThis() {
+        Lambdas instance = new Lambdas();
+        instance.counter = 5;
+        return instance.addToCounter(100);
+    }
+
+    int addToCounter(int n) {
+        IntOp op = (a, b) -> a + b + counter;
+        return op.apply(n, 1);
+    }
+
+    static int staticRef() {
+        Func<String, Integer> parse = Integer::parseInt;
+        return parse.apply("42");
+    }
+
+    static int unboundRef() {
+        Func<String, Integer> length = String::length;
+        return length.apply("hello");
+    }
+
+    static String boundRef() {
+        Func<String, String> concat = "hello "::concat;
+        return concat.apply("world");
+    }
+
+    static String constructorRef() {
+        Func<String, StringBuilder> make = StringBuilder::new;
+        return make.apply("built").reverse().toString();
+    }
+
+    static long twice(long x) {
+        return x * 2;
+    }
+
+    static long widening() {
+        Widening op = Lambdas::twice;
+        return op.apply(Integer.MAX_VALUE);
+    }
+
+    static long unboxing() {
+        Func<Integer, Long> op = Lambdas::twice;
+        return op.apply(21);
+    }
+
+    static int voidReturn() {
+        Lambdas instance = new Lambdas();
+        Runnable r = instance::increment;
+        r.run();
+        r.run();
+        return instance.counter;
+    }
+
+    static int serializable() {
+        Runnable r = (Runnable & Serializable) () -> {};
+        r.run();
+        return r instanceof Serializable ? 1 : 0;
+    }
+
+    static String bridge() {
+        StringGetter getter = () -> "bridged";
+        Getter<String> erased = getter;
+        return erased.get() + getter.get().length();
+    }
+
+    static double wideCapture() {
+        long l = 1L << 40;
+        double d = 0.5;
+        Wide op = (a, b) -> a + b + l + d;
+        return op.apply(3L, 0.25);
+    }
+
+    static int callSiteReuse() {
+        int total = 0;
+        for (int i = 0; i < 5; i++) {
+            int captured = i;
+            IntOp op = (a, b) -> a + b + captured;
+            total += op.apply(i, 1);
+        }
+        return total;
+    }
+
+    public String toString() {
+        return "Lambdas!";
+    }
+
+    static String concat() {
+        int i = -5;
+        long l = 1L << 33;
+        char c = 'x';
+        boolean z = true;
+        double d = 1.5;
+        float f = 0.25f;
+        byte b = 7;
+        short s = -300;
+        String str = null;
+        Object obj = new Lambdas();
+        return "i=" + i + " l=" + l + " c=" + c + " z=" + z + " d=" + d + " f=" + f + " b=" + b
+            + " s=" + s + " str=" + str + " obj=" + obj;
+    }
+
+    static String concatConstants() {
+        int i = 3;
+        return "tag\u0001" + i + "\u0002end";
+    }
+
+    static String concatLoop() {
+        String s = "";
+        for (int i = 0; i < 4; i++) {
+            s = s + i + ",";
+        }
+        return s;
+    }
+}
@@ -0,0 +1,120 @@
+public class Monitors {
+    public static int REENTRANT = reentrant();
+    public static int NOT_OWNER = notOwner();
+    public static int NULL_LOCK = nullLock();
+    public static int RELEASED = released();
+    public static int STATIC_SYNC = staticSync();
+    public static int TIMED_WAIT = timedWait();
+
+    final Object lock = new Object();
+    int count;
+
+    /** Only the owner of a monitor may notify */
+    static boolean holds(Object obj) {
+        try {
+            obj.notify();
+            return true;
+        } catch (IllegalMonitorStateException e) {
+            return false;
+        }
+    }
+
+    synchronized void outer() {
+        count++;
+        inner();
+        synchronized (this) {
+            synchronized (lock) {
+                count += holds(this) && holds(lock) ? 100 : 0;
+            }
+            count += holds(lock) ? 0 : 1000;
+        }
+    }
+
+    synchronized void inner() {
+        count += 10;
+    }
+
+    synchronized void fail() {
+        throw new IllegalStateException();
+    }
+
+    static synchronized boolean holdsClass() {
+        return holds(Monitors.class);
+    }
+
+    static int reentrant() {
+        Monitors m = new Monitors();
+        m.outer();
+        return m.count + (holds(m) ? 10000 : 0);
+    }
+
+    static int notOwner() {
+        Object obj = new Object();
+        int x = 0;
+        try {
+            obj.notify();
+        } catch (IllegalMonitorStateException e) {
+            x += 1;
+        }
+        try {
+            obj.notifyAll();
+        } catch (IllegalMonitorStateException e) {
+            x += 10;
+        }
+        try {
+            obj.wait();
+        } catch (IllegalMonitorStateException e) {
+            x += 100;
+        } catch (InterruptedException e) {
+            x += 5000;
+        }
+        return x;
+    }
+
+    static int nullLock() {
+        Object obj = null;
+        try {
+            synchronized (obj) {
+                return 5;
+            }
+        } catch (NullPointerException e) {
+            return 1;
+        }
+    }
+
+    static int released() {
+        Monitors m = new Monitors();
+        int x = 0;
+        try {
+            m.fail();
+        } catch (IllegalStateException e) {
+            x += holds(m) ? 0 : 1;
+        }
+        try {
+            synchronized (m.lock) {
+                throw new IllegalStateException();
+            }
+        } catch (IllegalStateException e) {
+            x += holds(m.lock) ? 0 : 10;
+        }
+        return x;
+    }
+
+    static int staticSync() {
+        return (holdsClass() ? 1 : 0) + (holds(Monitors.class) ? 10 : 0);
+    }
+
+    static int timedWait() {
+        Object obj = new Object();
+        synchronized (obj) {
+            synchronized (obj) {
+                try {
+                    obj.wait(5);
+                } catch (InterruptedException e) {
+                    return -1;
+                }
+            }
+            return holds(obj) ? 1 : 0;
+        }
+    }
+}
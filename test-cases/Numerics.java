@@ -0,0 +1,255 @@
+public class Numerics {
+    static final float[] FLOATS = {
+        Float.NaN, Float.POSITIVE_INFINITY, Float.NEGATIVE_INFINITY, 3e9f, -3e9f, 3e19f, -3e19f,
+        2.9f, -2.9f, 0.5f, -0.0f, 300.7f, -70000.5f, Float.MIN_VALUE,
+    };
+
+    static final double[] DOUBLES = {
+        Double.NaN, Double.POSITIVE_INFINITY, Double.NEGATIVE_INFINITY, 3e9, -3e9, 3e19, -3e19,
+        2.9, -2.9, 0.5, -0.0, 300.7, -70000.5, 1e300,
+    };
+
+    static final int[] INTS = {
+        0, 1, -1, 127, 128, -129, 65535, 65536, -32769, 16777217, Integer.MAX_VALUE,
+        Integer.MIN_VALUE,
+    };
+
+    static final long[] LONGS = {
+        0L, -1L, 9007199254740993L, Long.MAX_VALUE, Long.MIN_VALUE, 0x7fffff7fffffffffL,
+        -4294967296L,
+    };
+
+    static final int[] SHIFTS = {0, 1, 31, 32, 33, 63, 64, -1, -33};
+
+    public static int[] F2I = f2i();
+    public static long[] F2L = f2l();
+    public static int[] D2I = d2i();
+    public static long[] D2L = d2l();
+    public static int[] NARROW_FLOAT = narrowFloat();
+    public static int[] NARROW_DOUBLE = narrowDouble();
+    public static int[] NARROW_INT = narrowInt();
+    public static float[] I2F = i2f();
+    public static float[] L2F = l2f();
+    public static double[] L2D = l2d();
+    public static float[] D2F = d2f();
+    public static int[] FCMP = fcmp();
+    public static int[] DCMP = dcmp();
+    public static float[] FREM = frem();
+    public static double[] DREM = drem();
+    public static int[] INT_SHIFTS = intShifts();
+    public static long[] LONG_SHIFTS = longShifts();
+    public static int[] CHARS = chars();
+
+    static int[] f2i() {
+        int[] out = new int[FLOATS.length];
+        for (int i = 0; i < FLOATS.length; i++) {
+            out[i] = (int) FLOATS[i];
+        }
+        return out;
+    }
+
+    static long[] f2l() {
+        long[] out = new long[FLOATS.length];
+        for (int i = 0; i < FLOATS.length; i++) {
+            out[i] = (long) FLOATS[i];
+        }
+        return out;
+    }
+
+    static int[] d2i() {
+        int[] out = new int[DOUBLES.length];
+        for (int i = 0; i < DOUBLES.length; i++) {
+            out[i] = (int) DOUBLES[i];
+        }
+        return out;
+    }
+
+    static long[] d2l() {
+        long[] out = new long[DOUBLES.length];
+        for (int i = 0; i < DOUBLES.length; i++) {
+            out[i] = (long) DOUBLES[i];
+        }
+        return out;
+    }
+
+    /** byte, char and short of each float */
+    static int[] narrowFloat() {
+        int[] out = new int[FLOATS.length * 3];
+        for (int i = 0; i < FLOATS.length; i++) {
+            out[i * 3] = (byte) FLOATS[i];
+            out[i * 3 + 1] = (char) FLOATS[i];
+            out[i * 3 + 2] = (short) FLOATS[i];
+        }
+        return out;
+    }
+
+    /** byte, char and short of each double */
+    static int[] narrowDouble() {
+        int[] out = new int[DOUBLES.length * 3];
+        for (int i = 0; i < DOUBLES.length; i++) {
+            out[i * 3] = (byte) DOUBLES[i];
+            out[i * 3 + 1] = (char) DOUBLES[i];
+            out[i * 3 + 2] = (short) DOUBLES[i];
+        }
+        return out;
+    }
+
+    /** byte, char and short of each int */
+    static int[] narrowInt() {
+        int[] out = new int[INTS.length * 3];
+        for (int i = 0; i < INTS.length; i++) {
+            out[i * 3] = (byte) INTS[i];
+            out[i * 3 + 1] = (char) INTS[i];
+            out[i * 3 + 2] = (short) INTS[i];
+        }
+        return out;
+    }
+
+    static float[] i2f() {
+        float[] out = new float[INTS.length];
+        for (int i = 0; i < INTS.length; i++) {
+            out[i] = INTS[i];
+        }
+        return out;
+    }
+
+    static float[] l2f() {
+        float[] out = new float[LONGS.length];
+        for (int i = 0; i < LONGS.length; i++) {
+            out[i] = LONGS[i];
+        }
+        return out;
+    }
+
+    static double[] l2d() {
+        double[] out = new double[LONGS.length];
+        for (int i = 0; i < LONGS.length; i++) {
+            out[i] = LONGS[i];
+        }
+        return out;
+    }
+
+    static float[] d2f() {
+        float[] out = new float[DOUBLES.length];
+        for (int i = 0; i < DOUBLES.length; i++) {
+            out[i] = (float) DOUBLES[i];
+        }
+        return out;
+    }
+
+    /** Bit set of <, >, ==, <=, >= for each pair, as javac uses both fcmpl and fcmpg */
+    static int compare(float a, float b) {
+        int x = 0;
+        if (a < b) {
+            x |= 1;
+        }
+        if (a > b) {
+            x |= 2;
+        }
+        if (a == b) {
+            x |= 4;
+        }
+        if (a <= b) {
+            x |= 8;
+        }
+        if (a >= b) {
+            x |= 16;
+        }
+        return x;
+    }
+
+    static int compare(double a, double b) {
+        int x = 0;
+        if (a < b) {
+            x |= 1;
+        }
+        if (a > b) {
+            x |= 2;
+        }
+        if (a == b) {
+            x |= 4;
+        }
+        if (a <= b) {
+            x |= 8;
+        }
+        if (a >= b) {
+            x |= 16;
+        }
+        return x;
+    }
+
+    static int[] fcmp() {
+        float[] operands = {Float.NaN, 1.0f, -0.0f, 0.0f, Float.NEGATIVE_INFINITY};
+        int[] out = new int[operands.length * operands.length];
+        for (int i = 0; i < operands.length; i++) {
+            for (int j = 0; j < operands.length; j++) {
+                out[i * operands.length + j] = compare(operands[i], operands[j]);
+            }
+        }
+        return out;
+    }
+
+    static int[] dcmp() {
+        double[] operands = {Double.NaN, 1.0, -0.0, 0.0, Double.NEGATIVE_INFINITY};
+        int[] out = new int[operands.length * operands.length];
+        for (int i = 0; i < operands.length; i++) {
+            for (int j = 0; j < operands.length; j++) {
+                out[i * operands.length + j] = compare(operands[i], operands[j]);
+            }
+        }
+        return out;
+    }
+
+    static float[] frem() {
+        float[] dividends = {5.5f, -5.5f, 5.5f, -5.5f, 1.0f, Float.POSITIVE_INFINITY, 3.0f, -0.0f, 7.0f};
+        float[] divisors = {2.0f, 2.0f, -2.0f, -2.0f, 0.0f, 2.0f, Float.POSITIVE_INFINITY, 1.0f, Float.NaN};
+        float[] out = new float[dividends.length];
+        for (int i = 0; i < dividends.length; i++) {
+            out[i] = dividends[i] % divisors[i];
+        }
+        return out;
+    }
+
+    static double[] drem() {
+        double[] dividends = {5.5, -5.5, 5.5, -5.5, 1.0, Double.POSITIVE_INFINITY, 3.0, -0.0, 1e300};
+        double[] divisors = {2.0, 2.0, -2.0, -2.0, 0.0, 2.0, Double.POSITIVE_INFINITY, 1.0, 0.3};
+        double[] out = new double[dividends.length];
+        for (int i = 0; i < dividends.length; i++) {
+            out[i] = dividends[i] % divisors[i];
+        }
+        return out;
+    }
+
+    /** <<, >> and >>> of -12345 by each shift distance */
+    static int[] intShifts() {
+        int value = -12345;
+        int[] out = new int[SHIFTS.length * 3];
+        for (int i = 0; i < SHIFTS.length; i++) {
+            out[i * 3] = value << SHIFTS[i];
+            out[i * 3 + 1] = value >> SHIFTS[i];
+            out[i * 3 + 2] = value >>> SHIFTS[i];
+        }
+        return out;
+    }
+
+    /** <<, >> and >>> of -123456789012 by each shift distance */
+    static long[] longShifts() {
+        long value = -123456789012L;
+        long[] out = new long[SHIFTS.length * 3];
+        for (int i = 0; i < SHIFTS.length; i++) {
+            out[i * 3] = value << SHIFTS[i];
+            out[i * 3 + 1] = value >> SHIFTS[i];
+            out[i * 3 + 2] = value >>> SHIFTS[i];
+        }
+        return out;
+    }
+
+    static int[] chars() {
+        char[] chars = {(char) -1, (char) 0x8000, 'a'};
+        int minusOne = -1;
+        char c = (char) minusOne;
+        return new int[] {
+            chars[0], chars[1], chars[1] >> 4, c, c + 1, (short) c, (byte) chars[1], chars[2] - chars[0],
+        };
+    }
+}
@@ -0,0 +1,170 @@
+public class SafetyChecks {
+    int field;
+
+    public static String OUT_OF_BOUNDS = outOfBounds();
+    public static String NEGATIVE_INDEX = negativeIndex();
+    public static String NEGATIVE_SIZE = negativeSize();
+    public static String NULL_ARRAY_LOAD = nullArrayLoad();
+    public static String NULL_ARRAY_STORE = nullArrayStore();
+    public static String NULL_ARRAY_LENGTH = nullArrayLength();
+    public static String NULL_FIELD_READ = nullFieldRead();
+    public static String NULL_FIELD_WRITE = nullFieldWrite();
+    public static String NULL_INVOKE = nullInvoke();
+    public static String NULL_THROW = nullThrow();
+    public static String NULL_SYNC = nullSync();
+    public static String ARRAY_STORE = arrayStore();
+    public static String CLASS_CAST = classCast();
+    public static int COVARIANT_STORE = covariantStore();
+    public static int NARROW_ELEMENTS = narrowElements();
+
+    int instance(int x, String[] y) {
+        return x;
+    }
+
+    static SafetyChecks nothing() {
+        return null;
+    }
+
+    static String outOfBounds() {
+        int[] ints = new int[2];
+        try {
+            return Integer.toString(ints[3]);
+        } catch (ArrayIndexOutOfBoundsException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String negativeIndex() {
+        long[] longs = new long[1];
+        try {
+            longs[-1] = 5;
+            return null;
+        } catch (ArrayIndexOutOfBoundsException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String negativeSize() {
+        int size = -4;
+        try {
+            return Integer.toString(new String[size].length);
+        } catch (NegativeArraySizeException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullArrayLoad() {
+        int[] ints = null;
+        try {
+            return Integer.toString(ints[0]);
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullArrayStore() {
+        Object[] objects = null;
+        try {
+            objects[0] = null;
+            return null;
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullArrayLength() {
+        char[] chars = null;
+        try {
+            return Integer.toString(chars.length);
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullFieldRead() {
+        try {
+            return Integer.toString(nothing().field);
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullFieldWrite() {
+        try {
+            nothing().field = 5;
+            return null;
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullInvoke() {
+        try {
+            return Integer.toString(nothing().instance(1, null));
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullThrow() {
+        RuntimeException exc = null;
+        try {
+            throw exc;
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String nullSync() {
+        Object lock = null;
+        try {
+            synchronized (lock) {
+                return null;
+            }
+        } catch (NullPointerException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String arrayStore() {
+        Object[] strings = new String[1];
+        try {
+            strings[0] = new Object();
+            return null;
+        } catch (ArrayStoreException e) {
+            return e.getMessage();
+        }
+    }
+
+    static String classCast() {
+        Object obj = new Object();
+        try {
+            return (String) obj;
+        } catch (ClassCastException e) {
+            return e.getMessage();
+        }
+    }
+
+    static int covariantStore() {
+        Object[] objects = new CharSequence[2];
+        objects[0] = "hello";
+        objects[1] = null;
+        Object[][] nested = new Object[1][];
+        nested[0] = new String[3];
+        return ((CharSequence) objects[0]).length() + nested[0].length * 10;
+    }
+
+    static int narrowElements() {
+        char[] chars = new char[1];
+        short[] shorts = new short[1];
+        byte[] bytes = new byte[1];
+        boolean[] flags = new boolean[1];
+
+        chars[0] = 'a';
+        chars[0]++;
+        shorts[0] = (short) 40000;
+        bytes[0] = (byte) 130;
+        flags[0] = !flags[0];
+        return chars[0] + shorts[0] + bytes[0] * 10 + (flags[0] ? 100000 : 0);
+    }
+}
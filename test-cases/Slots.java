@@ -0,0 +1,51 @@
+public class Slots {
+    long wide;
+    double precise;
+
+    public static long STATIC_ARGS = staticArgs(3, 1234567890123L, 7, 2.5, 11);
+    public static long INSTANCE_ARGS = new Slots().instanceArgs(0.5, 9, -40L);
+    public static long POP2 = pop2();
+    public static long DUP2 = dup2();
+    public static long DUP2_X1 = new Slots().dup2X1(21L);
+    public static double DUP2_X2 = dup2X2(new double[2], 1.75);
+
+    static long staticArgs(int a, long b, int c, double d, int e) {
+        int local = a + c;
+        long sum = b + local;
+        return sum * e + (long) (d * 10) + a;
+    }
+
+    long instanceArgs(double d, int i, long l) {
+        int local = i * 2;
+        this.wide = l;
+        return (long) (d * 100) + local + this.wide + l;
+    }
+
+    static long value() {
+        return 42L;
+    }
+
+    static long pop2() {
+        value();
+        return value() + 1;
+    }
+
+    static long counter;
+
+    static long dup2() {
+        counter = 5;
+        long a = counter++;
+        long b = a = counter;
+        return a * 100 + b + counter;
+    }
+
+    long dup2X1(long v) {
+        long r = this.wide = v;
+        return r + this.wide;
+    }
+
+    static double dup2X2(double[] arr, double v) {
+        double r = arr[1] = v;
+        return r + arr[1] + arr[0];
+    }
+}
@@ -0,0 +1,66 @@
+public class StackTraces {
+    int field;
+
+    public static int THROWN_LINE = thrownLine();
+    public static String CALLER = caller();
+    public static String FILE = file();
+    public static int VM_RAISED_LINE = vmRaisedLine();
+    public static int IN_INITIALISER_LINE = inInitialiserLine();
+    public static int FIRST_ON_LINE = firstOnLine();
+
+    static class Custom extends RuntimeException {
+        Custom() {
+            super();
+        }
+    }
+
+    static class FailingInit {
+        static int VALUE = nothing().field;
+    }
+
+    static Custom create() {
+        return new Custom();
+    }
+
+    static StackTraces nothing() {
+        return null;
+    }
+
+    static int thrownLine() {
+        return create().getStackTrace()[0].getLineNumber();
+    }
+
+    static String caller() {
+        return create().getStackTrace()[1].getMethodName();
+    }
+
+    static String file() {
+        return create().getStackTrace()[0].getFileName();
+    }
+
+    static int vmRaisedLine() {
+        try {
+            return nothing().field;
+        } catch (NullPointerException e) {
+            return e.getStackTrace()[0].getLineNumber();
+        }
+    }
+
+    static int inInitialiserLine() {
+        try {
+            return FailingInit.VALUE;
+        } catch (ExceptionInInitializerError e) {
+            return e.getCause().getStackTrace()[0].getLineNumber();
+        }
+    }
+
+    static int firstOnLine() {
+        StackTraces nothing = nothing();
+        try {
+            return nothing
+                .hashCode();
+        } catch (NullPointerException e) {
+            return e.getStackTrace()[0].getLineNumber();
+        }
+    }
+}
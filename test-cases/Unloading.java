@@ -0,0 +1,23 @@
+public class Unloading {
+    public static int base() {
+        return 41;
+    }
+
+    /** Delegates straight to the bootstrap loader, classes are defined by the test */
+    public static class Loader extends ClassLoader {
+        public Loader() {
+            super(null);
+        }
+    }
+
+    /** Defined by a Loader, referencing itself and a class of the bootstrap loader */
+    public static class Victim {
+        static Victim INSTANCE;
+        static int VALUE;
+
+        static {
+            INSTANCE = new Victim();
+            VALUE = Unloading.base() + 1;
+        }
+    }
+}